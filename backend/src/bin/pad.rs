@@ -0,0 +1,104 @@
+//! コマンドラインからPADを出力するためのツール
+//! 使い方: `pad [--format json|html|svg|mermaid|plantuml|dot] [--options JSON] [FILE]`（FILEを省略した場合は標準入力から読み込みます）
+//! `--options` には `parse_rust_code_with_options` と同じ解析オプションをJSONオブジェクトで指定します。
+//! 入力を解析できない場合（ツリーのルートがErrorノードの場合）は、出力した後に終了コード1で終わります。
+
+use std::io::Read;
+use std::process::ExitCode;
+
+/// 出力形式
+enum Format {
+    Json,
+    Html,
+    Svg,
+    Mermaid,
+    PlantUml,
+    Dot,
+}
+
+const USAGE: &str = "Usage: pad [--format json|html|svg|mermaid|plantuml|dot] [--options JSON] [FILE]";
+
+fn main() -> ExitCode {
+    let mut format = Format::Json;
+    let mut path = None;
+    let mut options = serde_json::Map::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => match args.next().as_deref() {
+                Some("json") => format = Format::Json,
+                Some("html") => format = Format::Html,
+                Some("svg") => format = Format::Svg,
+                Some("mermaid") => format = Format::Mermaid,
+                Some("plantuml") => format = Format::PlantUml,
+                Some("dot") => format = Format::Dot,
+                other => {
+                    eprintln!("Unknown format: {}\n{}", other.unwrap_or(""), USAGE);
+                    return ExitCode::from(2);
+                }
+            },
+            "--options" => {
+                let json = args.next().unwrap_or_default();
+                match serde_json::from_str(&json) {
+                    Ok(serde_json::Value::Object(map)) if backend::options::ParseOptions::from_json(&json).is_ok() => {
+                        options = map
+                    }
+                    _ => {
+                        eprintln!("Invalid options: {}\n{}", json, USAGE);
+                        return ExitCode::from(2);
+                    }
+                }
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => {
+                eprintln!("{}", USAGE);
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    let mut code = String::new();
+    let read = match &path {
+        Some(path) => std::fs::read_to_string(path).map(|text| code = text),
+        None => std::io::stdin().read_to_string(&mut code).map(|_| ()),
+    };
+    if let Err(e) = read {
+        eprintln!("Failed to read input: {}", e);
+        return ExitCode::FAILURE;
+    }
+
+    // 解析できない入力は、図（Errorノード）を出力した上で失敗の終了コードにします
+    let tree = backend::parse_rust_code_with_options(&code, &serde_json::Value::Object(options.clone()).to_string());
+    let failed = serde_json::from_str::<serde_json::Value>(&tree).is_ok_and(|tree| tree["type"] == "error");
+    match format {
+        Format::Json => println!("{}", tree),
+        // HTMLのタイトルには入力のファイル名を使います
+        Format::Html => {
+            options.insert("document".to_string(), true.into());
+            options.insert("source_name".to_string(), path.into());
+            let options = serde_json::Value::Object(options);
+            print!("{}", backend::to_html_with_options(&code, &options.to_string()))
+        }
+        Format::Svg => {
+            let options = serde_json::Value::Object(options);
+            print!("{}", backend::to_svg_with_options(&code, &options.to_string()))
+        }
+        Format::Mermaid => {
+            let options = serde_json::Value::Object(options);
+            print!("{}", backend::to_mermaid_with_options(&code, &options.to_string()))
+        }
+        Format::PlantUml => {
+            let options = serde_json::Value::Object(options);
+            print!("{}", backend::to_plantuml_with_options(&code, &options.to_string()))
+        }
+        Format::Dot => {
+            let options = serde_json::Value::Object(options);
+            print!("{}", backend::to_dot_with_options(&code, &options.to_string()))
+        }
+    }
+    if failed { ExitCode::FAILURE } else { ExitCode::SUCCESS }
+}
@@ -0,0 +1,163 @@
+//! 変換結果のキャッシュ
+//! エディタは変更のない入力でも解析を呼び出すことがあるため、`cache` オプション指定時は
+//! 入力とオプションが直近のいずれかの呼び出しと同じであれば、解析せずに前回のJSON文字列を返します。
+//! キャッシュは最近使った順に `CAPACITY` 件まで保持します（LRU）。
+//! カーソル移動のたびに呼ばれる問い合わせ（`breadcrumb_at`）向けに、直近の1件のツリーも保持します。
+
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use serde::Serialize;
+
+use crate::model::PadNode;
+use crate::options::ParseOptions;
+
+/// 保持する入力の数
+const CAPACITY: usize = 8;
+
+struct Entry {
+    hash: u64,
+    code: String,
+    fingerprint: String,
+    output: String,
+}
+
+/// キャッシュの利用状況
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct CacheStats {
+    /// キャッシュから返した回数
+    pub hits: usize,
+    /// 実際に解析した回数
+    pub parses: usize,
+    /// 保持している入力の数
+    pub entries: usize,
+}
+
+#[derive(Default)]
+struct Cache {
+    /// 最近使ったものほど後ろに並びます
+    entries: Vec<Entry>,
+    stats: CacheStats,
+}
+
+/// 直近に構築したツリー
+struct TreeEntry {
+    hash: u64,
+    code: String,
+    fingerprint: String,
+    tree: Rc<PadNode>,
+}
+
+thread_local! {
+    static CACHE: RefCell<Cache> = RefCell::new(Cache::default());
+    static TREE: RefCell<Option<TreeEntry>> = const { RefCell::new(None) };
+}
+
+/// オプションの違いを区別するための文字列
+fn fingerprint(options: &ParseOptions) -> String {
+    format!("{:?}", options)
+}
+
+fn hash_of(code: &str, fingerprint: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    code.hash(&mut hasher);
+    fingerprint.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// 同じ入力・オプションの結果がキャッシュにあればそれを返し、なければ `render` で作成して保存します
+pub fn get_or_render(code: &str, options: &ParseOptions, render: impl FnOnce() -> String) -> String {
+    let fingerprint = fingerprint(options);
+    let hash = hash_of(code, &fingerprint);
+    let cached = CACHE.with_borrow_mut(|cache| {
+        let index = cache
+            .entries
+            .iter()
+            .position(|entry| entry.hash == hash && entry.code == code && entry.fingerprint == fingerprint)?;
+        let entry = cache.entries.remove(index);
+        let output = entry.output.clone();
+        cache.entries.push(entry);
+        cache.stats.hits += 1;
+        Some(output)
+    });
+    if let Some(output) = cached {
+        return output;
+    }
+    let output = render();
+    CACHE.with_borrow_mut(|cache| {
+        if cache.entries.len() >= CAPACITY {
+            cache.entries.remove(0);
+        }
+        cache.entries.push(Entry { hash, code: code.to_string(), fingerprint, output: output.clone() });
+        cache.stats.parses += 1;
+    });
+    output
+}
+
+/// 直近と同じ入力・オプションであれば保持しているツリーに、そうでなければ `build` で構築したツリーに `f` を適用します
+pub fn with_tree<R>(code: &str, options: &ParseOptions, build: impl FnOnce() -> PadNode, f: impl FnOnce(&PadNode) -> R) -> R {
+    let fingerprint = fingerprint(options);
+    let hash = hash_of(code, &fingerprint);
+    let cached = TREE.with_borrow(|entry| {
+        let entry = entry.as_ref().filter(|e| e.hash == hash && e.code == code && e.fingerprint == fingerprint)?;
+        Some(Rc::clone(&entry.tree))
+    });
+    let tree = match cached {
+        Some(tree) => {
+            CACHE.with_borrow_mut(|cache| cache.stats.hits += 1);
+            tree
+        }
+        None => {
+            let tree = Rc::new(build());
+            CACHE.with_borrow_mut(|cache| cache.stats.parses += 1);
+            TREE.set(Some(TreeEntry { hash, code: code.to_string(), fingerprint, tree: Rc::clone(&tree) }));
+            tree
+        }
+    };
+    f(&tree)
+}
+
+/// キャッシュを空にし、利用状況の集計も0に戻します
+pub fn clear() {
+    CACHE.with_borrow_mut(|cache| *cache = Cache::default());
+    TREE.set(None);
+}
+
+/// キャッシュの利用状況を返します
+pub fn stats() -> CacheStats {
+    CACHE.with_borrow(|cache| CacheStats { entries: cache.entries.len(), ..cache.stats })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn least_recently_used_entries_are_evicted() {
+        clear();
+        let options = ParseOptions::default();
+        for i in 0..CAPACITY {
+            get_or_render(&i.to_string(), &options, || format!("out{}", i));
+        }
+        // 先頭の入力を使い直すと、次に追い出されるのは2番目の入力になります
+        assert_eq!(get_or_render("0", &options, || unreachable!()), "out0");
+        get_or_render("new", &options, || "new".to_string());
+        assert_eq!(stats().entries, CAPACITY);
+        assert_eq!(get_or_render("0", &options, || unreachable!()), "out0");
+        assert_eq!(get_or_render("1", &options, || "again".to_string()), "again");
+        assert_eq!(stats().parses, CAPACITY + 2);
+    }
+
+    #[test]
+    fn the_latest_tree_is_reused() {
+        clear();
+        let options = ParseOptions::default();
+        let count = |tree: &PadNode| tree.children().len();
+        assert_eq!(with_tree("a", &options, || PadNode::sequence(Vec::new()), count), 0);
+        assert_eq!(with_tree("a", &options, || unreachable!(), count), 0);
+        assert_eq!(with_tree("b", &options, || PadNode::sequence(vec![PadNode::command("x")]), count), 1);
+        assert_eq!((stats().hits, stats().parses), (1, 2));
+    }
+}
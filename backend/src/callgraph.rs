@@ -0,0 +1,66 @@
+//! 関数の呼び出しグラフ
+//! 個々の関数のPADと並べて全体の構成を示すため、関数をノード、呼び出しをエッジとするグラフを作ります。
+//! 呼び出しは `ids::link_calls` で同じ入力の関数に対応付けた `call_targets` から集めます。
+
+use serde::Serialize;
+
+use crate::ids;
+use crate::model::{PadNode, Span};
+
+/// `build_call_graph` の結果
+#[derive(Debug, Serialize)]
+pub struct CallGraph {
+    /// 関数（行きがけ順）
+    pub nodes: Vec<CallGraphNode>,
+    /// 呼び出し（呼び出し元の関数ごとに、初めて呼び出した順）
+    pub edges: Vec<CallGraphEdge>,
+}
+
+/// 関数1つ分のノード
+#[derive(Debug, Serialize)]
+pub struct CallGraphNode {
+    /// 関数のBlockのid
+    pub id: String,
+    /// 修飾した関数名（`helper`、`Counter::new` など）
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+}
+
+/// 呼び出し元から呼び出し先へのエッジ
+#[derive(Debug, Serialize)]
+pub struct CallGraphEdge {
+    pub from: String,
+    pub to: String,
+    /// 呼び出し元の関数の中で、呼び出し先を呼び出しているノードの数
+    pub count: usize,
+}
+
+/// `link_calls` を済ませたツリーから呼び出しグラフを作ります
+pub fn build(root: &PadNode) -> CallGraph {
+    let mut graph = CallGraph { nodes: Vec::new(), edges: Vec::new() };
+    collect(root, None, &mut graph);
+    graph
+}
+
+fn collect(node: &PadNode, caller: Option<&str>, graph: &mut CallGraph) {
+    let caller = match ids::function_name(node) {
+        Some(name) => {
+            let id = node.meta().id.clone();
+            graph.nodes.push(CallGraphNode { id: id.clone(), name, span: node.meta().span });
+            Some(id)
+        }
+        None => caller.map(str::to_string),
+    };
+    if let Some(from) = &caller {
+        for to in &node.meta().call_targets {
+            match graph.edges.iter_mut().find(|edge| &edge.from == from && &edge.to == to) {
+                Some(edge) => edge.count += 1,
+                None => graph.edges.push(CallGraphEdge { from: from.clone(), to: to.clone(), count: 1 }),
+            }
+        }
+    }
+    for child in node.children() {
+        collect(child, caller.as_deref(), graph);
+    }
+}
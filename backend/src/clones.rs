@@ -0,0 +1,174 @@
+//! 同じ構造の部分木（コピー＆ペーストされた処理）の検出
+//! 各部分木の構造（ノードの種類とラベル、子の並び）のハッシュ値を求め、同じハッシュ値の部分木を比較してグループにします。
+//! ノードidや位置は比較に含めません。大きい部分木から順に報告し、報告済みの部分木の内側だけにある複製は報告しません。
+
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use proc_macro2::{TokenStream, TokenTree};
+
+use crate::model::{CloneGroup, PadNode};
+use crate::tokens::KEYWORDS;
+
+/// 部分木1つ分の情報（行きがけ順）
+struct Subtree<'a> {
+    node: &'a PadNode,
+    hash: u64,
+    /// 部分木のノード数（行きがけ順で、自身から `size` 個が部分木に含まれます）
+    size: usize,
+}
+
+/// 比較に使うノード自身の内容（種類、ラベル、matchのアームなどの付加情報）
+fn signature(node: &PadNode, ignore_names: bool) -> Vec<String> {
+    let text = |text: &str| if ignore_names { normalize_names(text) } else { text.to_string() };
+    let mut signature = vec![node.kind_name().to_string()];
+    signature.extend(node.display_text().map(text));
+    match node {
+        PadNode::Block { kind, .. } => signature.push(format!("{:?}", kind)),
+        PadNode::Loop { kind, .. } => signature.push(format!("{:?}", kind)),
+        PadNode::If { else_block, .. } => signature.push(else_block.is_some().to_string()),
+        PadNode::Match { arms, .. } => {
+            for arm in arms {
+                signature.push(text(&arm.pattern));
+                signature.push(arm.guard.as_deref().map(text).unwrap_or_default());
+            }
+        }
+        _ => {}
+    }
+    signature
+}
+
+/// ラベルの識別子（キーワード以外）をすべて同じ記号に置き換えます
+/// 字句解析できないラベルはそのまま返します。
+fn normalize_names(text: &str) -> String {
+    fn push(stream: TokenStream, out: &mut Vec<String>) {
+        for tree in stream {
+            match tree {
+                TokenTree::Group(group) => {
+                    let (open, close) = match group.delimiter() {
+                        proc_macro2::Delimiter::Parenthesis => ("(", ")"),
+                        proc_macro2::Delimiter::Brace => ("{", "}"),
+                        proc_macro2::Delimiter::Bracket => ("[", "]"),
+                        proc_macro2::Delimiter::None => ("", ""),
+                    };
+                    out.push(open.to_string());
+                    push(group.stream(), out);
+                    out.push(close.to_string());
+                }
+                TokenTree::Ident(ident) => {
+                    let name = ident.to_string();
+                    out.push(if KEYWORDS.contains(&name.as_str()) { name } else { "$".to_string() });
+                }
+                other => out.push(other.to_string()),
+            }
+        }
+    }
+    let Ok(stream) = text.parse::<TokenStream>() else { return text.to_string() };
+    let mut out = Vec::new();
+    push(stream, &mut out);
+    out.join(" ")
+}
+
+/// 部分木を行きがけ順に集め、ハッシュ値とノード数を返します
+fn summarize<'a>(node: &'a PadNode, ignore_names: bool, subtrees: &mut Vec<Subtree<'a>>) -> (u64, usize) {
+    let index = subtrees.len();
+    subtrees.push(Subtree { node, hash: 0, size: 0 });
+    let mut hasher = DefaultHasher::new();
+    signature(node, ignore_names).hash(&mut hasher);
+    let children = node.children();
+    children.len().hash(&mut hasher);
+    let mut size = 1;
+    for child in children {
+        let (hash, child_size) = summarize(child, ignore_names, subtrees);
+        hash.hash(&mut hasher);
+        size += child_size;
+    }
+    let hash = hasher.finish();
+    subtrees[index].hash = hash;
+    subtrees[index].size = size;
+    (hash, size)
+}
+
+/// 2つの部分木が同じ構造かどうか（ハッシュ値の衝突を除くための比較）
+fn same(a: &PadNode, b: &PadNode, ignore_names: bool) -> bool {
+    let (a_children, b_children) = (a.children(), b.children());
+    signature(a, ignore_names) == signature(b, ignore_names)
+        && a_children.len() == b_children.len()
+        && a_children.iter().zip(&b_children).all(|(a, b)| same(a, b, ignore_names))
+}
+
+/// ノード数が `min_size` 以上の複製を検出し、グループの一覧を返します
+/// 各グループの部分木の根には、`clone_group` にグループの番号を設定します（idを割り当てた後に呼び出してください）。
+pub fn detect_clones(root: &mut PadNode, min_size: usize, ignore_names: bool) -> Vec<CloneGroup> {
+    let mut subtrees = Vec::new();
+    summarize(root, ignore_names, &mut subtrees);
+
+    let mut buckets: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (index, subtree) in subtrees.iter().enumerate() {
+        if subtree.size >= min_size.max(1) {
+            buckets.entry(subtree.hash).or_default().push(index);
+        }
+    }
+    let mut buckets: Vec<Vec<usize>> = buckets.into_values().filter(|bucket| bucket.len() > 1).collect();
+    // 大きい部分木から、同じ大きさの場合は先に現れるものから調べます
+    buckets.sort_by_key(|bucket| (std::cmp::Reverse(subtrees[bucket[0]].size), bucket[0]));
+
+    let mut covered = vec![false; subtrees.len()];
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for bucket in buckets {
+        // ハッシュ値が同じでも構造が異なる部分木は、別のグループに分けます
+        let mut classes: Vec<Vec<usize>> = Vec::new();
+        for index in bucket {
+            match classes.iter_mut().find(|class| same(subtrees[class[0]].node, subtrees[index].node, ignore_names)) {
+                Some(class) => class.push(index),
+                None => classes.push(vec![index]),
+            }
+        }
+        for class in classes {
+            if class.len() < 2 || class.iter().all(|&index| covered[index]) {
+                continue;
+            }
+            for &index in &class {
+                covered[index..index + subtrees[index].size].fill(true);
+            }
+            groups.push(class);
+        }
+    }
+
+    let mut group_of: HashMap<usize, usize> = HashMap::new();
+    let clones = groups
+        .iter()
+        .enumerate()
+        .map(|(number, members)| {
+            group_of.extend(members.iter().map(|&index| (index, number)));
+            CloneGroup {
+                nodes: members.iter().map(|&index| subtrees[index].node.meta().id.clone()).collect(),
+                size: subtrees[members[0]].size,
+            }
+        })
+        .collect();
+    mark(root, &group_of, &mut 0);
+    clones
+}
+
+/// 行きがけ順の番号で指定したノードにグループの番号を設定します
+fn mark(node: &mut PadNode, group_of: &HashMap<usize, usize>, index: &mut usize) {
+    node.meta_mut().clone_group = group_of.get(index).copied();
+    *index += 1;
+    for child in node.children_mut() {
+        mark(child, group_of, index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_normalized_but_keywords_are_kept() {
+        assert_eq!(normalize_names("total += item.price"), "$ + = $ . $");
+        assert_eq!(normalize_names("let mut n = f(x, 1)"), "let mut $ = $ ( $ , 1 )");
+        assert_eq!(normalize_names("a…"), "a…");
+    }
+}
@@ -0,0 +1,44 @@
+//! 深い入れ子の制御構造の折り畳み
+//! `collapse_depth` オプション指定時に、上限の深さにある制御構造（if / ループ / match）の中身を空にします。
+//! 概略表示（`outline`）と同じく、`collapsed: true` と直下の子ノードの数（`child_count`）を付けます。
+//! 深さは関数のBlockごとに0から数えます。
+
+use crate::model::{BlockKind, PadNode};
+
+/// ツリー全体の、上限の深さにある制御構造を畳みます
+pub fn collapse_deep_nodes(node: &mut PadNode, limit: usize) {
+    collapse(node, limit, 0);
+}
+
+fn collapse(node: &mut PadNode, limit: usize, depth: usize) {
+    let depth = match node {
+        PadNode::Block { kind: BlockKind::Function, .. } => 0,
+        PadNode::If { .. } | PadNode::Loop { .. } | PadNode::Match { .. } if depth >= limit => {
+            let count: usize = node.children().into_iter().map(box_count).sum();
+            if count > 0 {
+                for child in node.children_mut() {
+                    let span = child.meta().span;
+                    *child = PadNode::sequence(Vec::new());
+                    child.meta_mut().span = span;
+                }
+                let meta = node.meta_mut();
+                meta.collapsed = true;
+                meta.child_count = Some(count);
+            }
+            return;
+        }
+        PadNode::If { .. } | PadNode::Loop { .. } | PadNode::Match { .. } => depth + 1,
+        _ => depth,
+    };
+    for child in node.children_mut() {
+        collapse(child, limit, depth);
+    }
+}
+
+/// Sequenceをたどって箱の数を数えます
+fn box_count(node: &PadNode) -> usize {
+    match node {
+        PadNode::Sequence { .. } => node.children().into_iter().map(box_count).sum(),
+        _ => 1,
+    }
+}
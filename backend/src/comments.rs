@@ -0,0 +1,340 @@
+//! synはコメントを捨ててしまうため、ソース文字列を直接走査して行コメントを収集します。
+//! 文字列リテラルや文字リテラル、ブロックコメントの中身はコメントとして扱いません。
+
+/// ソース中の1つの行コメント（`// ...`）
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineComment {
+    /// コメントが書かれている行番号（1始まり。proc-macro2のLineColumnと同じ）
+    pub line: usize,
+    /// 同じ行のコメントより前にコードが書かれているか（行末コメントかどうか）
+    pub trailing: bool,
+    /// `//` の後ろのテキスト
+    pub text: String,
+}
+
+/// `// pad:...` 形式のマジックコメント（ディレクティブ）の種類
+#[derive(Debug, Clone, PartialEq)]
+pub enum DirectiveKind {
+    /// `// pad:skip` : 直後（または同じ行）の文を図から除外します
+    Skip,
+    /// `// pad:skip-begin` : 除外範囲の開始
+    SkipBegin,
+    /// `// pad:skip-end` : 除外範囲の終了
+    SkipEnd,
+    /// `// pad:label: <text>` : 直後の文・制御構造のラベル（条件式）を置き換えます
+    Label(String),
+    /// `// pad:group: <name>` : 以降の文を名前付きの合成Blockにまとめます
+    Group(String),
+    /// `// pad:endgroup` : 直近の `pad:group` を閉じます
+    EndGroup,
+}
+
+/// 行番号付きのディレクティブ
+#[derive(Debug, Clone, PartialEq)]
+pub struct Directive {
+    pub line: usize,
+    pub trailing: bool,
+    pub kind: DirectiveKind,
+}
+
+/// ソース全体から抽出したディレクティブの集合
+/// 文のスパン（行範囲）と突き合わせて、各文に適用されるディレクティブを判定します。
+#[derive(Debug, Default)]
+pub struct Directives {
+    directives: Vec<Directive>,
+    /// `pad:skip-begin` 〜 `pad:skip-end` の行範囲（両端のコメント行を含む）
+    skip_regions: Vec<(usize, usize)>,
+    /// ディレクティブ以外の行コメント（`comments` オプションで文に付けるためのもの）
+    comments: Vec<LineComment>,
+}
+
+impl Directives {
+    /// ソースコードを走査してディレクティブを抽出します
+    pub fn scan(code: &str) -> Self {
+        let mut directives = Vec::new();
+        let mut comments = Vec::new();
+        for comment in scan_line_comments(code) {
+            match parse_directive(&comment.text) {
+                Some(kind) => directives.push(Directive { line: comment.line, trailing: comment.trailing, kind }),
+                None => comments.push(comment),
+            }
+        }
+
+        // skip-begin / skip-end の対応付け（閉じられていない範囲はファイル末尾まで）
+        let mut skip_regions = Vec::new();
+        let mut open: Option<usize> = None;
+        for d in &directives {
+            match d.kind {
+                DirectiveKind::SkipBegin if open.is_none() => open = Some(d.line),
+                DirectiveKind::SkipEnd => {
+                    if let Some(begin) = open.take() {
+                        skip_regions.push((begin, d.line));
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let Some(begin) = open {
+            skip_regions.push((begin, usize::MAX));
+        }
+
+        Directives { directives, skip_regions, comments }
+    }
+
+    /// 文に付けるコメントを返します
+    /// 直前の文との間に単独行で書かれたコメントと、文の最終行の行末コメントを、行ごとに改行でつなぎます。
+    /// `///` / `//!` のドキュメントコメントは、先頭の `/` / `!` を除いた本文にします。
+    pub fn comment_for(&self, prev_line: usize, start_line: usize, end_line: usize) -> Option<String> {
+        let lines: Vec<&str> = self
+            .comments
+            .iter()
+            .filter(|c| if c.trailing { c.line == end_line } else { prev_line < c.line && c.line < start_line })
+            .map(|c| c.text.strip_prefix(['/', '!']).unwrap_or(&c.text).trim())
+            .collect();
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+
+    /// 行 `after` と `before` の間（両端を含まない）に単独行で書かれたディレクティブを出現順に返します
+    /// 同じブロック内の文と文の間にあるコメントだけを拾うために使います。
+    pub fn between(&self, after: usize, before: usize) -> impl Iterator<Item = &Directive> {
+        self.directives
+            .iter()
+            .filter(move |d| !d.trailing && after < d.line && d.line < before)
+    }
+
+    /// 文を図から除外すべきかどうかを判定します
+    /// * `prev_line` - 直前の文の終了行（先頭の文の場合はブロックの `{` の行）
+    /// * `start_line` / `end_line` - 対象の文の行範囲
+    pub fn is_skipped(&self, prev_line: usize, start_line: usize, end_line: usize) -> bool {
+        let in_region = self
+            .skip_regions
+            .iter()
+            .any(|&(begin, end)| begin < start_line && end_line < end);
+        if in_region {
+            return true;
+        }
+        self.directives.iter().any(|d| {
+            d.kind == DirectiveKind::Skip
+                && if d.trailing {
+                    // 行末コメント: その行で終わる文に適用
+                    d.line == end_line
+                } else {
+                    // 単独行のコメント: 直前の文との間に書かれていれば直後の文に適用
+                    prev_line < d.line && d.line < start_line
+                }
+        })
+    }
+}
+
+/// コメント本文（`//` の後ろ）をディレクティブとして解釈します
+fn parse_directive(text: &str) -> Option<DirectiveKind> {
+    let body = text.trim().strip_prefix("pad:")?.trim();
+    if let Some(label) = body.strip_prefix("label:") {
+        return Some(DirectiveKind::Label(label.trim().to_string()));
+    }
+    if let Some(name) = body.strip_prefix("group:") {
+        return Some(DirectiveKind::Group(name.trim().to_string()));
+    }
+    match body {
+        "skip" => Some(DirectiveKind::Skip),
+        "skip-begin" => Some(DirectiveKind::SkipBegin),
+        "skip-end" => Some(DirectiveKind::SkipEnd),
+        "endgroup" => Some(DirectiveKind::EndGroup),
+        _ => None,
+    }
+}
+
+/// ソースコード中の行コメントをすべて収集します
+/// 文字列リテラル（raw文字列・バイト文字列を含む）、文字リテラル、ブロックコメントは読み飛ばします。
+pub fn scan_line_comments(code: &str) -> Vec<LineComment> {
+    let chars: Vec<char> = code.chars().collect();
+    let mut comments = Vec::new();
+    let mut line = 1;
+    // 現在の行にコメント以外のコードが現れたか
+    let mut code_on_line = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        match c {
+            '\n' => {
+                line += 1;
+                code_on_line = false;
+                i += 1;
+            }
+            '/' if next == Some('/') => {
+                let start = i + 2;
+                let mut end = start;
+                while end < chars.len() && chars[end] != '\n' {
+                    end += 1;
+                }
+                comments.push(LineComment {
+                    line,
+                    trailing: code_on_line,
+                    text: chars[start..end].iter().collect(),
+                });
+                i = end;
+            }
+            '/' if next == Some('*') => {
+                // ブロックコメント（入れ子に対応）
+                let mut depth = 1;
+                i += 2;
+                while i < chars.len() && depth > 0 {
+                    if chars[i] == '/' && chars.get(i + 1) == Some(&'*') {
+                        depth += 1;
+                        i += 2;
+                    } else if chars[i] == '*' && chars.get(i + 1) == Some(&'/') {
+                        depth -= 1;
+                        i += 2;
+                    } else {
+                        if chars[i] == '\n' {
+                            line += 1;
+                            code_on_line = false;
+                        }
+                        i += 1;
+                    }
+                }
+            }
+            '"' => {
+                code_on_line = true;
+                i = skip_string(&chars, i + 1, &mut line);
+            }
+            'r' | 'b' | 'c' if !prev_is_ident(&chars, i) => {
+                code_on_line = true;
+                match raw_string_hashes(&chars, i) {
+                    Some((hashes, body_start)) => {
+                        i = skip_raw_string(&chars, body_start, hashes, &mut line);
+                    }
+                    None => i += 1,
+                }
+            }
+            '\'' => {
+                code_on_line = true;
+                i = skip_char_literal(&chars, i);
+            }
+            c if c.is_whitespace() => i += 1,
+            _ => {
+                code_on_line = true;
+                i += 1;
+            }
+        }
+    }
+    comments
+}
+
+/// 直前の文字が識別子の一部かどうか（`bar"..."` のような誤認識を避けるため）
+fn prev_is_ident(chars: &[char], i: usize) -> bool {
+    i > 0 && (chars[i - 1].is_alphanumeric() || chars[i - 1] == '_')
+}
+
+/// 通常の文字列リテラルを読み飛ばし、閉じ `"` の次の位置を返します
+fn skip_string(chars: &[char], mut i: usize, line: &mut usize) -> usize {
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '"' => return i + 1,
+            '\n' => {
+                *line += 1;
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+    i
+}
+
+/// `r"`, `r#"`, `br"`, `cr#"` などのraw文字列の開始であれば、`#` の数と本体の開始位置を返します
+/// `b"..."` / `c"..."` のような非rawの接頭辞付き文字列は `usize::MAX` を返し、通常の文字列として扱います
+fn raw_string_hashes(chars: &[char], i: usize) -> Option<(usize, usize)> {
+    let mut j = i;
+    let mut raw = false;
+    if chars[j] == 'b' || chars[j] == 'c' {
+        j += 1;
+    }
+    if chars.get(j) == Some(&'r') {
+        raw = true;
+        j += 1;
+    }
+    let mut hashes = 0;
+    while raw && chars.get(j) == Some(&'#') {
+        hashes += 1;
+        j += 1;
+    }
+    if chars.get(j) == Some(&'"') && (raw || j > i) {
+        Some((if raw { hashes } else { usize::MAX }, j + 1))
+    } else {
+        None
+    }
+}
+
+/// raw文字列（`hashes` 個の `#` で閉じる）を読み飛ばします
+/// `hashes == usize::MAX` の場合はエスケープを持つ通常の文字列として扱います
+fn skip_raw_string(chars: &[char], i: usize, hashes: usize, line: &mut usize) -> usize {
+    if hashes == usize::MAX {
+        return skip_string(chars, i, line);
+    }
+    let mut i = i;
+    while i < chars.len() {
+        if chars[i] == '\n' {
+            *line += 1;
+        }
+        if chars[i] == '"' && (1..=hashes).all(|k| chars.get(i + k) == Some(&'#')) {
+            return i + 1 + hashes;
+        }
+        i += 1;
+    }
+    i
+}
+
+/// 文字リテラル（`'a'`, `'\n'`）を読み飛ばします。ライフタイム（`'a`）の場合は `'` だけを進めます
+fn skip_char_literal(chars: &[char], i: usize) -> usize {
+    match chars.get(i + 1) {
+        Some('\\') => {
+            let mut j = i + 2;
+            while j < chars.len() && chars[j] != '\'' && chars[j] != '\n' {
+                j += 1;
+            }
+            j + 1
+        }
+        Some(_) if chars.get(i + 2) == Some(&'\'') => i + 3,
+        _ => i + 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collects_line_comments_with_trailing_flag() {
+        let code = "// head\nfoo(); // tail\n";
+        let comments = scan_line_comments(code);
+        assert_eq!(comments.len(), 2);
+        assert_eq!((comments[0].line, comments[0].trailing), (1, false));
+        assert_eq!((comments[1].line, comments[1].trailing), (2, true));
+        assert_eq!(comments[1].text, " tail");
+    }
+
+    #[test]
+    fn ignores_comment_markers_inside_literals() {
+        let code = r####"let a = "// pad:skip"; let b = r#"// x"#; let c = '/'; /* // y */"####;
+        assert!(scan_line_comments(code).is_empty());
+    }
+
+    #[test]
+    fn parses_label_and_group_directives() {
+        assert_eq!(parse_directive(" pad:label: validate input"), Some(DirectiveKind::Label("validate input".into())));
+        assert_eq!(parse_directive(" pad:group: setup"), Some(DirectiveKind::Group("setup".into())));
+        assert_eq!(parse_directive(" pad:endgroup"), Some(DirectiveKind::EndGroup));
+        assert_eq!(parse_directive(" pad:unknown"), None);
+    }
+
+    #[test]
+    fn lifetimes_are_not_char_literals() {
+        let code = "fn f<'a>(x: &'a str) {} // ok";
+        let comments = scan_line_comments(code);
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments[0].text, " ok");
+    }
+}
@@ -0,0 +1,115 @@
+//! 定数の条件による実行されない分岐の検出
+//! `if false { ... }` や `while false { ... }`、`if 1 == 2` のように、リテラルだけで値が決まる条件を評価し、
+//! 決して実行されない分岐に印を付けます。変数や定数（`const`）の値は追跡せず、証明できない条件には何も付けません。
+
+use syn::{BinOp, Expr, Lit, UnOp};
+
+use crate::model::{LoopKind, PadNode};
+
+/// リテラルだけから求めた式の値
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Constant {
+    Bool(bool),
+    Int(i128),
+}
+
+/// 条件式の値がリテラルだけで決まる場合に、その真偽を返します
+fn evaluate(condition: &str) -> Option<bool> {
+    let expr = syn::parse_str::<Expr>(condition).ok()?;
+    match eval(&expr)? {
+        Constant::Bool(value) => Some(value),
+        Constant::Int(_) => None,
+    }
+}
+
+fn eval(expr: &Expr) -> Option<Constant> {
+    match expr {
+        Expr::Lit(lit) => match &lit.lit {
+            Lit::Bool(value) => Some(Constant::Bool(value.value)),
+            Lit::Int(value) => value.base10_parse::<i128>().ok().map(Constant::Int),
+            _ => None,
+        },
+        Expr::Paren(paren) => eval(&paren.expr),
+        Expr::Group(group) => eval(&group.expr),
+        Expr::Unary(unary) => match (unary.op, eval(&unary.expr)?) {
+            (UnOp::Not(_), Constant::Bool(value)) => Some(Constant::Bool(!value)),
+            (UnOp::Neg(_), Constant::Int(value)) => Some(Constant::Int(-value)),
+            _ => None,
+        },
+        Expr::Binary(binary) => {
+            let (left, right) = (eval(&binary.left)?, eval(&binary.right)?);
+            let value = match (binary.op, left, right) {
+                (BinOp::Eq(_), l, r) => l == r,
+                (BinOp::Ne(_), l, r) => l != r,
+                (BinOp::Lt(_), Constant::Int(l), Constant::Int(r)) => l < r,
+                (BinOp::Le(_), Constant::Int(l), Constant::Int(r)) => l <= r,
+                (BinOp::Gt(_), Constant::Int(l), Constant::Int(r)) => l > r,
+                (BinOp::Ge(_), Constant::Int(l), Constant::Int(r)) => l >= r,
+                (BinOp::And(_), Constant::Bool(l), Constant::Bool(r)) => l && r,
+                (BinOp::Or(_), Constant::Bool(l), Constant::Bool(r)) => l || r,
+                _ => return None,
+            };
+            // 真偽値と整数の比較は型が合わないため、評価しません
+            (std::mem::discriminant(&left) == std::mem::discriminant(&right)).then_some(Constant::Bool(value))
+        }
+        _ => None,
+    }
+}
+
+/// ツリー全体の定数の条件を評価し、印を付けます
+/// * If: 選ばれない分岐に `dead`、必ず選ばれる分岐に `always_runs`
+/// * `while false`: Loopに `never_runs`、本体に `dead`
+/// * `while true`: Loopに `always_runs`（breakでしか抜けられません）
+pub fn mark_constant_conditions(node: &mut PadNode) {
+    match node {
+        PadNode::If { condition, full_label, then_block, else_block, .. } => {
+            if let Some(value) = evaluate(full_label.as_deref().unwrap_or(condition)) {
+                let (taken, dead) = if value {
+                    (Some(then_block), else_block.as_mut())
+                } else {
+                    (else_block.as_mut(), Some(then_block))
+                };
+                if let Some(taken) = taken {
+                    taken.meta_mut().always_runs = true;
+                }
+                if let Some(dead) = dead {
+                    dead.meta_mut().dead = true;
+                }
+            }
+        }
+        PadNode::Loop { kind: LoopKind::While, condition, full_label, body, meta, .. } => {
+            match evaluate(full_label.as_deref().unwrap_or(condition)) {
+                Some(true) => meta.always_runs = true,
+                Some(false) => {
+                    meta.never_runs = true;
+                    body.meta_mut().dead = true;
+                }
+                None => {}
+            }
+        }
+        _ => {}
+    }
+    for child in node.children_mut() {
+        mark_constant_conditions(child);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_literal_conditions_are_evaluated() {
+        assert_eq!(evaluate("true"), Some(true));
+        assert_eq!(evaluate("!true"), Some(false));
+        assert_eq!(evaluate("1 == 2"), Some(false));
+        assert_eq!(evaluate("(0x10 >= 16) && !false"), Some(true));
+        assert_eq!(evaluate("-1 < 0"), Some(true));
+        assert_eq!(evaluate("x == 2"), None);
+        assert_eq!(evaluate("DEBUG"), None);
+        assert_eq!(evaluate("1"), None);
+        assert_eq!(evaluate("1 + 1 == 2"), None);
+        assert_eq!(evaluate("true == 1"), None);
+        assert_eq!(evaluate("let Some(x) = y"), None);
+    }
+}
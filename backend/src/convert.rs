@@ -0,0 +1,2095 @@
+use crate::comments::{DirectiveKind, Directives};
+use crate::coverage;
+use crate::effects;
+use crate::fallback;
+use crate::macros;
+use crate::model::{Assignment, BlockKind, CommandCategory, ErrorKind, ExitScope, LoopKind, MatchArm, NodeMeta, PadNode, Span};
+use crate::options::ParseOptions;
+use crate::pretty;
+use proc_macro2::TokenTree;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use syn::spanned::Spanned;
+use syn::visit::Visit;
+use syn::{
+    Attribute, Block, Expr, ExprCall, ExprIf, ExprLoop, ExprPath, FnArg, ForeignItem, ImplItem, Item, ItemFn, ItemConst, ItemForeignMod,
+    ItemImpl, ItemMacro, ItemMod, ItemStatic, ItemTrait, ItemType, ItemUse, ReturnType, Signature, Stmt, TraitItem, UseTree,
+};
+
+/// synの構文木をPADノードに変換する変換器
+/// マジックコメント（`// pad:...`）など、変換中に参照する情報を保持します。
+pub struct Converter {
+    options: ParseOptions,
+    directives: Directives,
+    /// `extern { ... }` ブロックで宣言された外部関数の名前
+    foreign_fns: HashSet<String>,
+    /// 同じ入力内で定義された関数（インライン展開用）
+    local_fns: HashMap<String, ItemFn>,
+    /// 展開中の関数名のスタック（先頭は変換中の関数自身）。再帰呼び出しの検出に使います
+    inline_stack: RefCell<Vec<String>>,
+    /// オプションの指定（`include` / `exclude` など）で図から除外したアイテム・メソッドの数
+    filtered: Cell<usize>,
+    /// 同じ入力内で定義されたenumのバリアント（matchの網羅状況の判定用）
+    enums: HashMap<String, Vec<String>>,
+    /// 変換中の関数の変数の型の注釈（型が決まらない変数は None）
+    bindings: RefCell<HashMap<String, Option<String>>>,
+    /// 変換中の式を囲む、本体を展開したクロージャ・asyncブロックの深さ（`return` / `?` で抜ける範囲の判定に使います）
+    closure_depth: Cell<usize>,
+    /// 変換中のブロックの入れ子の深さ（インライン展開した本体を含みます）
+    depth: Cell<usize>,
+}
+
+impl Converter {
+    /// 元のソースコードからマジックコメントを走査し、
+    /// ファイル全体から変換時に参照する宣言（外部関数など）を収集して変換器を作成します
+    pub fn new(code: &str, items: &[Item], options: &ParseOptions) -> Self {
+        let foreign_fns = items
+            .iter()
+            .filter_map(|item| match item {
+                Item::ForeignMod(foreign_mod) => Some(foreign_mod),
+                _ => None,
+            })
+            .flat_map(|foreign_mod| &foreign_mod.items)
+            .filter_map(|item| match item {
+                ForeignItem::Fn(func) => Some(func.sig.ident.to_string()),
+                _ => None,
+            })
+            .collect();
+        let local_fns = items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Fn(func) => Some((func.sig.ident.to_string(), func.clone())),
+                _ => None,
+            })
+            .collect();
+        Converter {
+            options: options.clone(),
+            directives: Directives::scan(code),
+            foreign_fns,
+            local_fns,
+            inline_stack: RefCell::new(Vec::new()),
+            filtered: Cell::new(0),
+            enums: coverage::enum_variants(items),
+            bindings: RefCell::new(HashMap::new()),
+            closure_depth: Cell::new(0),
+            depth: Cell::new(0),
+        }
+    }
+
+    /// `macro_rules!` の定義を `macro_rules! name` というラベルのBlockに変換します
+    /// 子ノードはルールごとのマッチャー（`(...) =>` の部分）で、展開後の本体は解析しません。
+    /// `macro_rules!` 以外のマクロ呼び出しアイテムは対象外のため None を返します。
+    pub fn parse_macro_rules(&self, item: &ItemMacro) -> Option<PadNode> {
+        let name = item.ident.as_ref()?;
+        if !item.mac.path.is_ident("macro_rules") {
+            return None;
+        }
+        let label = format!("macro_rules! {}", name);
+        if self.options.collapse_macros {
+            return Some(PadNode::command(label));
+        }
+
+        // ルールは `(matcher) => {transcriber}` が `;` 区切りで並んだ形をしています
+        let mut rules = Vec::new();
+        let mut expect_matcher = true;
+        for token in item.mac.tokens.clone() {
+            match token {
+                TokenTree::Group(group) if expect_matcher => {
+                    let matcher = group.to_string().replace("$ ", "$").replace(" : ", ":").replace(" ,", ",");
+                    rules.push(PadNode::command(format!("{} =>", matcher)));
+                    expect_matcher = false;
+                }
+                TokenTree::Punct(punct) if punct.as_char() == ';' => expect_matcher = true,
+                _ => {}
+            }
+        }
+        Some(PadNode::block(BlockKind::Macro, label, rules))
+    }
+
+    /// `extern "C" { ... }` ブロックを、ABI名をラベルとするBlockに変換します
+    /// 各子ノードは外部関数・外部staticのシグネチャを示す宣言Commandです。
+    pub fn parse_foreign_mod(&self, foreign_mod: &ItemForeignMod) -> PadNode {
+        let abi = &foreign_mod.abi;
+        let children = foreign_mod
+            .items
+            .iter()
+            .map(|item| {
+                let label = match item {
+                    ForeignItem::Fn(func) => {
+                        let sig = &func.sig;
+                        quote::quote!(#sig).to_string()
+                    }
+                    ForeignItem::Static(item) => {
+                        let (mutability, ident, ty) = (&item.mutability, &item.ident, &item.ty);
+                        quote::quote!(static #mutability #ident : #ty).to_string()
+                    }
+                    other => quote::quote!(#other).to_string(),
+                };
+                PadNode::command(label)
+            })
+            .collect();
+        PadNode::block(BlockKind::Extern, quote::quote!(#abi).to_string(), children)
+    }
+
+    /// ファイルの `use` 宣言を、"imports" というラベルのBlockにまとめます（`imports` オプション指定時のみ）
+    /// 子ノードは宣言ごと（`expand_imports` 指定時はパスごと）のCommandです。`use` 宣言がない場合は None を返します。
+    pub fn parse_imports(&self, items: &[Item]) -> Option<PadNode> {
+        if !self.options.imports {
+            return None;
+        }
+        let children: Vec<PadNode> = items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Use(item_use) => Some(item_use),
+                _ => None,
+            })
+            .flat_map(|item_use| self.use_commands(item_use))
+            .collect();
+        (!children.is_empty()).then(|| PadNode::block(BlockKind::Imports, "imports", children))
+    }
+
+    /// `use` 宣言を宣言Commandにします（`expand_imports` 指定時は、まとめたパスを1つずつのCommandに展開します）
+    fn use_commands(&self, item_use: &ItemUse) -> Vec<PadNode> {
+        let vis = &item_use.vis;
+        let vis = quote::quote!(#vis).to_string();
+        let vis = if vis.is_empty() { vis } else { format!("{} ", compact_tokens(&vis)) };
+        let leading = if item_use.leading_colon.is_some() { "::" } else { "" };
+        let paths = if self.options.expand_imports {
+            expand_use_tree(&item_use.tree, leading.to_string())
+        } else {
+            let tree = &item_use.tree;
+            vec![format!("{}{}", leading, compact_tokens(&quote::quote!(#tree).to_string()).replace("{ ", "{").replace(" }", "}"))]
+        };
+        paths
+            .into_iter()
+            .map(|path| {
+                let node = PadNode::command(format!("{}use {}", vis, path));
+                with_span(set_category(node, CommandCategory::Declaration), item_use)
+            })
+            .collect()
+    }
+
+    /// インラインのモジュール（`mod name { ... }`）を、中のアイテムを子に持つBlockに変換します
+    /// 中身が別のファイルにあるモジュールや、図に表示するアイテムがないモジュールは None を返します。
+    fn parse_module(&self, item_mod: &ItemMod) -> Option<PadNode> {
+        let (_, items) = item_mod.content.as_ref()?;
+        let children: Vec<PadNode> = items
+            .iter()
+            .filter(|item| self.includes_item(item))
+            .filter_map(|item| self.parse_item(item))
+            .collect();
+        (!children.is_empty()).then(|| PadNode::block(BlockKind::Module, format!("mod {}", item_mod.ident), children))
+    }
+
+    /// 型エイリアスを、名前・ジェネリクス・対象の型を並べた宣言Commandに変換します
+    pub fn parse_type_alias(&self, item_type: &ItemType) -> PadNode {
+        let (ident, generics, ty) = (&item_type.ident, &item_type.generics, &item_type.ty);
+        let label = type_alias_label(&item_type.vis, quote::quote!(#ident #generics), quote::quote!(#ty));
+        set_category(PadNode::command(label), CommandCategory::Declaration)
+    }
+
+    /// 定数を、名前・型・値を並べた宣言Commandに変換します
+    pub fn parse_const(&self, item_const: &ItemConst) -> PadNode {
+        let (vis, ident, generics, ty, expr) = (&item_const.vis, &item_const.ident, &item_const.generics, &item_const.ty, &item_const.expr);
+        let label = compact_tokens(&quote::quote!(#vis const #ident #generics: #ty = #expr).to_string());
+        set_category(PadNode::command(label), CommandCategory::Declaration)
+    }
+
+    /// staticを、名前・型・初期値を並べた宣言Commandに変換します
+    pub fn parse_static(&self, item_static: &ItemStatic) -> PadNode {
+        let (vis, mutability, ident, ty, expr) = (&item_static.vis, &item_static.mutability, &item_static.ident, &item_static.ty, &item_static.expr);
+        let label = compact_tokens(&quote::quote!(#vis static #mutability #ident: #ty = #expr).to_string());
+        set_category(PadNode::command(label), CommandCategory::Declaration)
+    }
+
+    /// matchアームの本体を解析します。ブロックでない式もSequenceで包み、then節などと形を揃えます
+    fn parse_arm_body(&self, body: &Expr) -> PadNode {
+        match self.parse_expr(body) {
+            node @ PadNode::Sequence { .. } => node,
+            mut node => {
+                node.meta_mut().call_paths = called_functions(|finder| finder.visit_expr(body));
+                node.meta_mut().awaits = await_points(|finder| finder.visit_expr(body));
+                let span = node.meta().span;
+                let mut seq = PadNode::sequence(vec![node]);
+                seq.meta_mut().span = span;
+                seq
+            }
+        }
+    }
+
+    /// let-else（`let Some(x) = v else { return; };`）を、条件 `let Some(x) = v` のIfに変換します
+    /// 照合に成功した場合はそのまま後続の文へ進むため、真の側は空です（`lowered_from` は "let_else"）。
+    fn parse_let_else(&self, local: &syn::Local, local_init: &syn::LocalInit, diverge: &Expr) -> PadNode {
+        let cond = Expr::Let(syn::ExprLet {
+            attrs: Vec::new(),
+            let_token: local.let_token,
+            pat: Box::new(local.pat.clone()),
+            eq_token: local_init.eq_token,
+            expr: local_init.expr.clone(),
+        });
+        let components = let_chain_components(&cond, self.options.pretty_labels);
+        let mut node = PadNode::if_node(components.join(" && "), PadNode::sequence(Vec::new()), Some(self.parse_arm_body(diverge)));
+        if let PadNode::If { components: c, lowered_from, .. } = &mut node {
+            *c = components;
+            *lowered_from = Some("let_else".to_string());
+        }
+        self.mark_panics(node, &[&local_init.expr, diverge])
+    }
+
+    /// `loop { ... }` を解析します
+    /// 本体の最後の文が `if cond { break }`（else節なし・値なしのbreakのみ）の場合は、
+    /// 後判定ループ（do_while）とみなし、末尾のifを本体から取り除いて継続条件を `!cond` とします。
+    fn parse_loop(&self, expr_loop: &ExprLoop) -> PadNode {
+        let block = &expr_loop.body;
+        let open_line = block.brace_token.span.open().start().line;
+        let close_line = block.brace_token.span.close().start().line;
+        let loop_label = expr_loop.label.as_ref().map(|label| &label.name);
+
+        let tail_break = match block.stmts.split_last() {
+            // このループへのcontinueは終了判定を飛ばすため、後判定ループとはみなしません
+            Some((Stmt::Expr(Expr::If(expr_if), _), rest)) if self.options.detect_do_while => {
+                (is_break_if(expr_if, loop_label) && !continues_loop(block, loop_label)).then_some((expr_if, rest))
+            }
+            _ => None,
+        };
+        let mut node = match tail_break {
+            Some((expr_if, rest)) => {
+                let cond = &expr_if.cond;
+                let raw = quote::quote!(#cond).to_string();
+                let body = self.parse_stmts(rest, open_line, close_line);
+                let mut node = PadNode::loop_node(LoopKind::DoWhile, negate_condition(cond), body);
+                if let PadNode::Loop { break_condition, .. } = &mut node {
+                    *break_condition = Some(raw);
+                }
+                node
+            }
+            None => PadNode::loop_node(LoopKind::Loop, "loop", self.parse_block(block)),
+        };
+        if let PadNode::Loop { results, loop_label: l, modifies, .. } = &mut node {
+            *results = break_values(block, loop_label);
+            *l = loop_label.map(|label| label.to_string());
+            *modifies = effects::modified_variables(None, None, block);
+        }
+        node
+    }
+
+    /// ローカル関数の呼び出しを、呼び出し先の本体を含むBlock（ラベルは呼び出し式）に展開します
+    /// 展開の対象外（ローカル関数でない、深さの上限に達した）の場合は None を返します。
+    /// 展開中の関数を再び呼び出している場合は、展開せず `recursive` を付けたCommandを返します。
+    fn inline_call(&self, call: &ExprCall, label: &str) -> Option<PadNode> {
+        let Expr::Path(ExprPath { path, qself: None, .. }) = &*call.func else {
+            return None;
+        };
+        let name = path.get_ident()?.to_string();
+        let callee = self.local_fns.get(&name)?;
+        // スタックの先頭は変換中の関数自身なので、展開の深さは (長さ - 1) です
+        let depth = self.inline_stack.borrow().len().saturating_sub(1);
+        if self.inline_stack.borrow().contains(&name) {
+            let mut node = PadNode::command(label);
+            if let PadNode::Command { recursive, .. } = &mut node {
+                *recursive = true;
+            }
+            return Some(node);
+        }
+        if depth >= self.options.inline_depth as usize {
+            return None;
+        }
+        self.inline_stack.borrow_mut().push(name);
+        let body = self.parse_fn_body(&callee.sig, &callee.block);
+        self.inline_stack.borrow_mut().pop();
+        Some(PadNode::block(BlockKind::Call, label, vec![body]))
+    }
+
+    /// `thread::spawn(|| ...)` / `tokio::spawn(async { ... })` のような、クロージャやasyncブロックを渡す
+    /// `spawn` の呼び出しを、並行して実行される本体を含むBlockに変換します
+    /// `binding` は `let handle = ...` のように結果を受け取る変数で、ラベルに残します。
+    fn spawn_block(&self, expr: &Expr, binding: Option<String>) -> Option<PadNode> {
+        let Expr::Call(call) = expr else { return None };
+        let Expr::Path(ExprPath { path, .. }) = &*call.func else { return None };
+        if path.segments.last()?.ident != "spawn" || call.args.len() != 1 {
+            return None;
+        }
+        let body = match &call.args[0] {
+            Expr::Closure(closure) => self.in_closure(|| self.parse_arm_body(&closure.body)),
+            Expr::Async(expr_async) => self.in_closure(|| self.parse_block(&expr_async.block)),
+            _ => return None,
+        };
+        let callee = compact_tokens(&quote::quote!(#path).to_string());
+        let label = match binding {
+            Some(binding) => format!("let {} = {}", binding, callee),
+            None => callee,
+        };
+        Some(PadNode::block(BlockKind::Concurrent, label, vec![body]))
+    }
+
+    /// クロージャを、引数の一覧（`binding` がある場合は `let f = |x|`）をラベルとし、本体を展開したBlockに変換します
+    fn closure_block(&self, closure: &syn::ExprClosure, binding: Option<String>) -> PadNode {
+        let label = match binding {
+            Some(binding) => format!("let {} = {}", binding, closure_label(closure)),
+            None => closure_label(closure),
+        };
+        PadNode::block(BlockKind::Closure, label, vec![self.in_closure(|| self.parse_arm_body(&closure.body))])
+    }
+
+    /// `expand_closures` オプション指定時に、`v.iter().map(|x| { ... })` のようにブロックを本体とするクロージャを
+    /// 引数に取る式を、式全体をラベルとし、各クロージャのBlockを子に持つ呼び出しのBlockに変換します
+    /// 本体が単一の式のクロージャ（`|x| x * 2`）はラベルのままで十分なため、展開しません。
+    fn closure_args_block(&self, expr: &Expr, label: &str) -> Option<PadNode> {
+        if !self.options.expand_closures {
+            return None;
+        }
+        let closures: Vec<PadNode> =
+            block_closures(expr).into_iter().map(|closure| with_span(self.closure_block(closure, None), closure)).collect();
+        (!closures.is_empty()).then(|| PadNode::block(BlockKind::Call, label, closures))
+    }
+
+    /// 式がインライン展開の対象となる関数呼び出しであれば取り出します
+    fn inlinable_call<'e>(&self, expr: &'e Expr) -> Option<&'e ExprCall> {
+        match expr {
+            Expr::Call(call) if self.options.inline_depth > 0 => Some(call),
+            _ => None,
+        }
+    }
+
+    /// 式の中に外部関数の呼び出しが含まれているかを判定します
+    fn calls_foreign(&self, expr: &Expr) -> bool {
+        struct Finder<'a> {
+            names: &'a HashSet<String>,
+            found: bool,
+        }
+        impl<'ast> Visit<'ast> for Finder<'_> {
+            fn visit_expr_call(&mut self, call: &'ast ExprCall) {
+                if let Expr::Path(path) = &*call.func
+                    && let Some(last) = path.path.segments.last()
+                    && self.names.contains(&last.ident.to_string())
+                {
+                    self.found = true;
+                }
+                syn::visit::visit_expr_call(self, call);
+            }
+        }
+        if self.foreign_fns.is_empty() {
+            return false;
+        }
+        let mut finder = Finder { names: &self.foreign_fns, found: false };
+        finder.visit_expr(expr);
+        finder.found
+    }
+
+    /// 式が長い構造体リテラル・呼び出し（`multiline_args` を超えるフィールド・引数）の場合に、
+    /// フィールド・引数を1行に1つずつ並べたラベルを返します
+    fn multiline_label(&self, expr: &Expr) -> Option<String> {
+        let max = self.options.multiline_args?;
+        let long = match expr {
+            Expr::Struct(lit) => lit.fields.len() > max,
+            Expr::Call(call) => call.args.len() > max,
+            _ => false,
+        };
+        long.then(|| layout_expr(expr, max, 0))
+    }
+
+    /// 式に含まれるパニックしうる箇所を探し、ノードに `may_panic` と理由を付けます
+    /// 入れ子になった式の中の呼び出しも対象ですが、式の中で定義されたアイテムは対象外です。
+    fn mark_panics(&self, mut node: PadNode, exprs: &[&Expr]) -> PadNode {
+        struct Finder {
+            index: bool,
+            reasons: Vec<String>,
+        }
+        impl<'ast> Visit<'ast> for Finder {
+            fn visit_expr_method_call(&mut self, call: &'ast syn::ExprMethodCall) {
+                let name = call.method.to_string();
+                // 引数・メソッドチェーンの内側の箇所を先に記録し、出現順に並べます
+                syn::visit::visit_expr_method_call(self, call);
+                if matches!(name.as_str(), "unwrap" | "expect" | "unwrap_unchecked") {
+                    self.reasons.push(name);
+                }
+            }
+            fn visit_expr_index(&mut self, index: &'ast syn::ExprIndex) {
+                syn::visit::visit_expr_index(self, index);
+                if self.index {
+                    self.reasons.push("index".to_string());
+                }
+            }
+            fn visit_item(&mut self, _: &'ast Item) {}
+        }
+        let mut finder = Finder { index: self.options.panic_on_index, reasons: Vec::new() };
+        for expr in exprs {
+            finder.visit_expr(expr);
+        }
+        if !finder.reasons.is_empty() {
+            let meta = node.meta_mut();
+            meta.may_panic = true;
+            meta.panic_reasons = finder.reasons;
+        }
+        node
+    }
+
+    /// Commandノードを作成し、外部関数の呼び出しを含む場合は `foreign` を、
+    /// 標準ライブラリの呼び出しを含む場合はドキュメントのURLを付けます
+    fn command_for(&self, label: String, expr: Option<&Expr>) -> PadNode {
+        let mut node = PadNode::command(label);
+        if let (PadNode::Command { foreign, scope, meta, .. }, Some(expr)) = (&mut node, expr) {
+            *foreign = self.calls_foreign(expr);
+            *scope = exits_early(expr).then(|| self.exit_scope());
+            meta.doc_url = doc_url_of(expr);
+        }
+        node
+    }
+
+    /// 式の表示文字列（`pretty_labels` の場合はprettypleaseで整形します）
+    fn expr_text(&self, expr: &Expr) -> String {
+        if self.options.pretty_labels { pretty::expr(expr) } else { quote::quote!(#expr).to_string() }
+    }
+
+    /// パターンの表示文字列（`pretty_labels` の場合はprettypleaseで整形します）
+    fn pat_text(&self, pat: &syn::Pat) -> String {
+        if self.options.pretty_labels { pretty::pat(pat) } else { quote::quote!(#pat).to_string() }
+    }
+
+    /// 本体を展開するクロージャ・asyncブロックの中として変換します
+    fn in_closure<T>(&self, parse: impl FnOnce() -> T) -> T {
+        self.closure_depth.set(self.closure_depth.get() + 1);
+        let result = parse();
+        self.closure_depth.set(self.closure_depth.get() - 1);
+        result
+    }
+
+    /// 変換中の位置の `return` / `?` で抜ける範囲
+    fn exit_scope(&self) -> ExitScope {
+        if self.closure_depth.get() > 0 { ExitScope::Closure } else { ExitScope::Function }
+    }
+
+    /// アイテム（関数定義、implブロックなど）をPADノードに変換します
+    /// 図に表示しない種類のアイテムの場合は None を返します。
+    pub fn parse_item(&self, item: &Item) -> Option<PadNode> {
+        self.parse_item_inner(item).map(|node| {
+            let node = self.apply_docs(with_span(node, item), item_attrs(item));
+            with_span(self.apply_cfg(node, item_attrs(item)), item)
+        })
+    }
+
+    /// `#[cfg(...)]` が付いている場合に、ノードに条件を注記します
+    /// `cfg_as_branches` オプションが有効な場合は、条件 `cfg(...)` のIf（else節なし）でノードを包みます。
+    fn apply_cfg(&self, mut node: PadNode, attrs: &[Attribute]) -> PadNode {
+        let Some(predicate) = cfg_predicate(attrs) else { return node };
+        if self.options.cfg_as_branches {
+            let span = node.meta().span;
+            let mut then_block = PadNode::sequence(vec![node]);
+            then_block.meta_mut().span = span;
+            PadNode::if_node(format!("cfg({})", predicate), then_block, None)
+        } else {
+            node.meta_mut().cfg = Some(predicate);
+            node
+        }
+    }
+
+    /// `comments` オプション指定時に、ドキュメントコメント（`#[doc = "..."]`）をノードの `comment` に付けます
+    fn apply_docs(&self, mut node: PadNode, attrs: &[Attribute]) -> PadNode {
+        if self.options.comments
+            && let Some(doc) = doc_comment(attrs)
+        {
+            node.meta_mut().comment = Some(doc);
+        }
+        node
+    }
+
+    /// アイテムが図に表示されるか（`parse_item` が Some を返すか）を、変換せずに判定します
+    pub fn draws_item(&self, item: &Item) -> bool {
+        match item {
+            Item::Fn(_) | Item::ForeignMod(_) => true,
+            Item::Impl(item_impl) => {
+                let mut methods = item_impl
+                    .items
+                    .iter()
+                    .filter_map(|item| match item {
+                        ImplItem::Fn(method) => Some(method),
+                        _ => None,
+                    })
+                    .peekable();
+                // メソッドのないimpl（`impl Send for S {}` などのマーカー）は、表示する関連型がある場合だけ表示します
+                let declares = self.options.declarations && item_impl.items.iter().any(|item| matches!(item, ImplItem::Type(_)));
+                methods.peek().is_none() && declares
+                    || methods.any(|method| {
+                        self.options.allows_name(&method.sig.ident.to_string())
+                            && (item_impl.trait_.is_some() || self.allows_visibility(&method.vis))
+                    })
+            }
+            Item::Trait(item_trait) => {
+                // 既定の実装を持つメソッドのないトレイトは、表示する関連型がある場合だけ表示します
+                let mut methods = default_methods(item_trait).peekable();
+                let declares = self.options.declarations && item_trait.items.iter().any(|item| matches!(item, TraitItem::Type(_)));
+                methods.peek().is_none() && declares || methods.any(|(method, _)| self.options.allows_name(&method.sig.ident.to_string()))
+            }
+            Item::Macro(item_macro) => item_macro.ident.is_some() && item_macro.mac.path.is_ident("macro_rules"),
+            Item::Type(_) | Item::Const(_) | Item::Static(_) => self.options.declarations,
+            Item::Mod(item_mod) => {
+                // 中のアイテムがすべて関数名のパターンなどで除外されるモジュールは表示しません
+                item_mod.content.as_ref().is_some_and(|(_, items)| items.iter().any(|item| self.is_included(item) && self.draws_item(item)))
+            }
+            _ => false,
+        }
+    }
+
+    fn parse_item_inner(&self, item: &Item) -> Option<PadNode> {
+        match item {
+            // 関数定義を見つけたらPADノードに変換
+            Item::Fn(func) => Some(self.parse_function(func)),
+            // extern ブロック（FFI宣言）は宣言の一覧として表示
+            Item::ForeignMod(foreign_mod) => Some(self.parse_foreign_mod(foreign_mod)),
+            // implブロックはメソッドをまとめたBlockとして表示
+            // （関数名のパターンの指定で、すべてのメソッドが除外された場合と、中身のないマーカーのimplは表示しません）
+            Item::Impl(item_impl) => Some(self.parse_impl(item_impl)).filter(|node| {
+                let has_methods = item_impl.items.iter().any(|item| matches!(item, ImplItem::Fn(_)));
+                let children = node.children();
+                !children.is_empty() && (!has_methods || children.iter().any(|child| matches!(child, PadNode::Block { .. })))
+            }),
+            // トレイトの定義は、既定の実装を持つメソッドと関連型をまとめたBlockとして表示
+            // （既定の実装を持つメソッドがすべて除外された場合と、表示するものがない場合は表示しません）
+            Item::Trait(item_trait) => Some(self.parse_trait(item_trait)).filter(|node| {
+                let has_methods = default_methods(item_trait).next().is_some();
+                let children = node.children();
+                !children.is_empty() && (!has_methods || children.iter().any(|child| matches!(child, PadNode::Block { .. })))
+            }),
+            // macro_rules! の定義はルールの一覧として表示
+            Item::Macro(item_macro) => self.parse_macro_rules(item_macro),
+            // 型エイリアスは宣言のCommandとして表示
+            Item::Type(item_type) if self.options.declarations => Some(self.parse_type_alias(item_type)),
+            // 定数・staticも宣言のCommandとして表示
+            Item::Const(item_const) if self.options.declarations => Some(self.parse_const(item_const)),
+            Item::Static(item_static) if self.options.declarations => Some(self.parse_static(item_static)),
+            // インラインのモジュールは、中のアイテムをまとめたBlockとして表示
+            // （`group_by` が `module` でない場合は、トップレベルの並べ替えの際に中のアイテムを展開します）
+            Item::Mod(item_mod) => self.parse_module(item_mod),
+            _ => None,
+        }
+    }
+
+    /// 関数定義（ItemFn）を解析し、PADのBlockノードを作成します
+    pub fn parse_function(&self, func: &ItemFn) -> PadNode {
+        let name = func.sig.ident.to_string();
+        // 関数本体のブロックを解析（自身への再帰呼び出しを展開しないよう、展開中のスタックに積んでおきます）
+        let outer = self.inline_stack.replace(vec![name.clone()]);
+        let body = self.parse_fn_body(&func.sig, &func.block);
+        self.inline_stack.replace(outer);
+        let children = self.interface(&func.sig, &func.block, body);
+        let mut node = PadNode::block(BlockKind::Function, format!("fn {}()", name), children); // ラベルとして関数名を使用
+        if let PadNode::Block { is_test, .. } = &mut node {
+            *is_test = is_test_fn(func);
+        }
+        set_qualifiers(&mut node, &func.sig);
+        set_signature(&mut node, &func.vis, &func.sig);
+        node
+    }
+
+    /// `skip_tests` / `only_tests` オプションに従い、トップレベルのアイテムを図に含めるかどうかを判定します
+    /// `only_tests` の場合はテスト関数以外のアイテム（implブロックなど）もすべて除外します。
+    /// モジュールは除外せず、中のアイテムを `parse_module` で同じように判定します。
+    /// 関数の場合は `include` / `exclude` の関数名のパターンも適用し、除外したアイテムは数えておきます。
+    pub fn includes_item(&self, item: &Item) -> bool {
+        let included = self.is_included(item);
+        if !included {
+            self.filtered.set(self.filtered.get() + 1);
+        }
+        included
+    }
+
+    /// `includes_item` の判定だけを行います（除外したアイテムを数えません）
+    fn is_included(&self, item: &Item) -> bool {
+        let is_test = matches!(item, Item::Fn(func) if is_test_fn(func));
+        (if self.options.only_tests { is_test || matches!(item, Item::Mod(_)) } else { !(self.options.skip_tests && is_test) })
+            && match item {
+                Item::Fn(func) => self.options.allows_name(&func.sig.ident.to_string()) && self.allows_visibility(&func.vis),
+                _ => true,
+            }
+    }
+
+    /// `include_private` オプションが false の場合、`pub` の付いていない関数・メソッドを除外します
+    fn allows_visibility(&self, vis: &syn::Visibility) -> bool {
+        self.options.include_private || !matches!(vis, syn::Visibility::Inherited)
+    }
+
+    /// オプションの指定で図から除外したアイテム・メソッドの数
+    pub fn filtered_count(&self) -> usize {
+        self.filtered.get()
+    }
+
+    /// implブロックを解析し、各メソッドのBlockを子に持つBlockを作成します
+    /// メソッドのラベルは同名のメソッドを区別できるよう型名で修飾します。
+    /// * 固有実装: `fn Counter::next(&mut self)`
+    /// * トレイト実装: `fn <Counter as Iterator>::next(&mut self)`
+    pub fn parse_impl(&self, item_impl: &ItemImpl) -> PadNode {
+        let self_ty = &item_impl.self_ty;
+        let self_type = compact_tokens(&quote::quote!(#self_ty).to_string());
+        let trait_name = item_impl.trait_.as_ref().map(|(bang, path, _)| {
+            compact_tokens(&quote::quote!(#bang #path).to_string())
+        });
+        let qualifier = match &trait_name {
+            Some(trait_name) => format!("<{} as {}>", self_type, trait_name),
+            None => self_type.clone(),
+        };
+
+        let mut methods = Vec::new();
+        for impl_item in &item_impl.items {
+            // 関連型（`type Item = u32;`）は、メソッドと同じ並びに宣言のCommandとして置きます
+            if let ImplItem::Type(item_type) = impl_item
+                && self.options.declarations
+            {
+                let (ident, generics, ty) = (&item_type.ident, &item_type.generics, &item_type.ty);
+                let label = type_alias_label(&item_type.vis, quote::quote!(#ident #generics), quote::quote!(#ty));
+                let node = set_category(PadNode::command(label), CommandCategory::Declaration);
+                methods.push(with_span(self.apply_cfg(with_span(node, item_type), &item_type.attrs), item_type));
+            }
+            if let ImplItem::Fn(method) = impl_item {
+                // トレイト実装のメソッドは可視性を持たないため、`include_private` の対象は固有実装のメソッドだけです
+                let visible = trait_name.is_some() || self.allows_visibility(&method.vis);
+                if !self.options.allows_name(&method.sig.ident.to_string()) || !visible {
+                    self.filtered.set(self.filtered.get() + 1);
+                    continue;
+                }
+                let label = format!("fn {}::{}", qualifier, signature_params(&method.sig));
+                let body = self.parse_fn_body(&method.sig, &method.block);
+                let children = self.interface(&method.sig, &method.block, body);
+                let mut node = with_span(PadNode::block(BlockKind::Function, label, children), method);
+                set_qualifiers(&mut node, &method.sig);
+                set_signature(&mut node, &method.vis, &method.sig);
+                let node = self.apply_docs(node, &method.attrs);
+                let mut node = with_span(self.apply_cfg(node, &method.attrs), method);
+                set_impl_target(&mut node, &trait_name, &self_type);
+                methods.push(node);
+            }
+        }
+
+        let generics = &item_impl.generics;
+        let generics = compact_tokens(&quote::quote!(#generics).to_string());
+        let label = match &trait_name {
+            Some(trait_name) => format!("impl{} {} for {}", generics, trait_name, self_type),
+            None => format!("impl{} {}", generics, self_type),
+        };
+        let mut node = PadNode::block(BlockKind::Impl, label, methods);
+        set_impl_target(&mut node, &trait_name, &self_type);
+        node
+    }
+
+    /// トレイトの定義を解析し、既定の実装を持つメソッドのBlockを子に持つBlockを作成します
+    /// メソッドのラベルはトレイト名で修飾します（`fn Greet::hello(&self)`）。本体のないメソッドは表示しません。
+    pub fn parse_trait(&self, item_trait: &ItemTrait) -> PadNode {
+        let trait_name = Some(item_trait.ident.to_string());
+        let mut methods = Vec::new();
+        // 関連型（`type Item;`）は、implブロックの関連型と同じく宣言のCommandとして先に置きます
+        for trait_item in &item_trait.items {
+            if let TraitItem::Type(item_type) = trait_item
+                && self.options.declarations
+            {
+                let (ident, generics, colon, bounds) = (&item_type.ident, &item_type.generics, &item_type.colon_token, &item_type.bounds);
+                let name = quote::quote!(#ident #generics #colon #bounds);
+                let label = match &item_type.default {
+                    Some((_, ty)) => type_alias_label(&syn::Visibility::Inherited, name, quote::quote!(#ty)),
+                    None => compact_tokens(&quote::quote!(type #name).to_string()),
+                };
+                let node = set_category(PadNode::command(label), CommandCategory::Declaration);
+                methods.push(with_span(self.apply_cfg(with_span(node, item_type), &item_type.attrs), item_type));
+            }
+        }
+        for (method, block) in default_methods(item_trait) {
+            if !self.options.allows_name(&method.sig.ident.to_string()) {
+                self.filtered.set(self.filtered.get() + 1);
+                continue;
+            }
+            let label = format!("fn {}::{}", item_trait.ident, signature_params(&method.sig));
+            let body = self.parse_fn_body(&method.sig, block);
+            let children = self.interface(&method.sig, block, body);
+            let mut node = with_span(PadNode::block(BlockKind::Function, label, children), method);
+            set_qualifiers(&mut node, &method.sig);
+            set_signature(&mut node, &syn::Visibility::Inherited, &method.sig);
+            let node = self.apply_docs(node, &method.attrs);
+            let mut node = with_span(self.apply_cfg(node, &method.attrs), method);
+            if let PadNode::Block { trait_name: t, .. } = &mut node {
+                t.clone_from(&trait_name);
+            }
+            methods.push(node);
+        }
+
+        let (ident, generics) = (&item_trait.ident, &item_trait.generics);
+        let label = compact_tokens(&quote::quote!(trait #ident #generics).to_string());
+        let mut node = PadNode::block(BlockKind::Trait, label, methods);
+        if let PadNode::Block { trait_name: t, .. } = &mut node {
+            *t = trait_name;
+        }
+        node
+    }
+
+    /// 関数の本体を解析します。本体の中の変数の型の注釈は、解析中だけ参照できるようにします
+    fn parse_fn_body(&self, sig: &Signature, block: &Block) -> PadNode {
+        let outer = self.bindings.replace(coverage::typed_bindings(sig, block));
+        // クロージャの中で定義された関数の本体は、関数の中として扱います
+        let depth = self.closure_depth.replace(0);
+        let body = self.parse_block(block);
+        self.closure_depth.set(depth);
+        self.bindings.replace(outer);
+        body
+    }
+
+    /// 関数のBlockの子ノードの並びを作成します
+    /// 本体の中の関数の出口（`return` と末尾の式）に `returns` の印を付けます。
+    /// `interface_nodes` オプション指定時は、本体の前に引数の一覧（"inputs"）、後に戻り値の型（"output"）のBlockを置きます。
+    /// 引数・戻り値の型がない場合、そのBlockは置きません。
+    fn interface(&self, sig: &Signature, block: &Block, mut body: PadNode) -> Vec<PadNode> {
+        mark_returns(&mut body);
+        let output = match &sig.output {
+            ReturnType::Type(_, ty) => Some(compact_tokens(&quote::quote!(#ty).to_string())),
+            ReturnType::Default => None,
+        };
+        if output.is_some() && matches!(block.stmts.last(), Some(Stmt::Expr(_, None))) {
+            mark_tail(&mut body);
+        }
+        if !self.options.interface_nodes {
+            return vec![body];
+        }
+
+        let inputs: Vec<PadNode> = sig
+            .inputs
+            .iter()
+            .map(|arg| {
+                let label = match arg {
+                    FnArg::Receiver(receiver) => compact_tokens(&quote::quote!(#receiver).to_string()),
+                    FnArg::Typed(pat_type) => {
+                        let (pat, ty) = (&pat_type.pat, &pat_type.ty);
+                        compact_tokens(&format!("{}: {}", quote::quote!(#pat), quote::quote!(#ty)))
+                    }
+                };
+                let mut node = set_category(with_span(PadNode::command(label), arg), CommandCategory::Declaration);
+                if let PadNode::Command { receiver, .. } = &mut node {
+                    *receiver = matches!(arg, FnArg::Receiver(_));
+                }
+                node
+            })
+            .collect();
+        let mut children = Vec::new();
+        if !inputs.is_empty() {
+            children.push(PadNode::block(BlockKind::Interface, "inputs", inputs));
+        }
+        children.push(body);
+        if let Some(output) = output {
+            let ty = set_category(with_span(PadNode::command(output), &sig.output), CommandCategory::Declaration);
+            children.push(with_span(PadNode::block(BlockKind::Interface, "output", vec![ty]), &sig.output));
+        }
+        children
+    }
+
+    /// コードブロック（{}で囲まれた部分）を解析し、Sequenceノードを作成します
+    /// 入れ子が `max_depth` を超える場合（インライン展開で深くなった場合など）は、その位置のErrorノードにします。
+    pub fn parse_block(&self, block: &Block) -> PadNode {
+        let open = block.brace_token.span.open().start();
+        if self.depth.get() >= self.options.max_depth {
+            let message = format!("Nesting is deeper than {} levels; the rest was not converted", self.options.max_depth);
+            let error = PadNode::Error {
+                message,
+                line: Some(open.line),
+                column: Some(open.column + 1),
+                frame: None,
+                byte_offset: None,
+                kind: Some(ErrorKind::Nesting),
+                meta: NodeMeta::default(),
+            };
+            return with_span(error, block);
+        }
+        let close_line = block.brace_token.span.close().start().line;
+        self.depth.set(self.depth.get() + 1);
+        let node = self.parse_stmts(&block.stmts, open.line, close_line);
+        self.depth.set(self.depth.get() - 1);
+        with_span(node, block)
+    }
+
+    /// 文の並びを解析し、Sequenceノードを作成します
+    /// `open_line` / `close_line` は並びを囲む `{` と `}` の行で、マジックコメントの適用範囲の判定に使います。
+    pub fn parse_stmts(&self, stmts: &[Stmt], open_line: usize, close_line: usize) -> PadNode {
+        // `// pad:group:` 〜 `// pad:endgroup` の入れ子を管理するスタック
+        let mut groups = GroupStack::default();
+        let mut skipped = 0;
+        // 直前の文の終了行（`// pad:skip` などの適用先を判定するために使用）
+        let mut prev_line = open_line;
+        // ブロック内の各ステートメント（文）を順に解析
+        for stmt in stmts {
+            let span = stmt.span();
+            let (start_line, end_line) = (span.start().line, span.end().line);
+            let mut label = None;
+            for directive in self.directives.between(prev_line, start_line) {
+                match &directive.kind {
+                    DirectiveKind::Group(name) => groups.open(name),
+                    DirectiveKind::EndGroup => groups.close(),
+                    DirectiveKind::Label(text) => label = Some(text),
+                    _ => {}
+                }
+            }
+            if self.directives.is_skipped(prev_line, start_line, end_line) {
+                skipped += 1;
+            } else {
+                for check in self.try_checks(stmt) {
+                    groups.push(check);
+                }
+                let mut node = self.parse_stmt(stmt);
+                if let Some(text) = label {
+                    node.override_label(text);
+                }
+                node.meta_mut().call_paths = called_functions(|finder| finder.visit_stmt(stmt));
+                node.meta_mut().awaits = await_points(|finder| finder.visit_stmt(stmt));
+                if self.options.comments
+                    && let Some(comment) = self.directives.comment_for(prev_line, start_line, end_line)
+                {
+                    node.meta_mut().comment = Some(comment);
+                }
+                groups.push(node);
+            }
+            prev_line = end_line;
+        }
+        // 最後の文と `}` の間にある `pad:endgroup` も同じブロックのものとして扱います
+        for directive in self.directives.between(prev_line, close_line) {
+            if directive.kind == DirectiveKind::EndGroup {
+                groups.close();
+            }
+        }
+        let mut node = PadNode::sequence(groups.finish());
+        if let PadNode::Sequence { skipped: count, .. } = &mut node {
+            *count = (skipped > 0).then_some(skipped);
+        }
+        node
+    }
+
+    /// `desugar_try` オプション指定時に、文の中の `?` 演算子ごとに、失敗した場合に早期に戻るIfを作成します
+    /// Ifは評価の順（内側の `?` が先）に並べ、文の前に置きます。ブロック・クロージャ・whileの中の `?` は、
+    /// その中の文として扱うため除きます。
+    fn try_checks(&self, stmt: &Stmt) -> Vec<PadNode> {
+        if !self.options.desugar_try {
+            return Vec::new();
+        }
+        try_operators(stmt)
+            .into_iter()
+            .map(|expr_try| {
+                let operand = self.expr_text(&expr_try.expr);
+                let exit = PadNode::Return {
+                    label: "return Err / None".to_string(),
+                    value: None,
+                    scope: self.exit_scope(),
+                    meta: NodeMeta::default(),
+                };
+                let then_block = PadNode::sequence(vec![with_span(exit, expr_try)]);
+                let mut node = PadNode::if_node(format!("{} is Err / None", operand), then_block, None);
+                if let PadNode::If { full_label, lowered_from, .. } = &mut node {
+                    *full_label = Some(self.expr_text(&Expr::Try(expr_try.clone())));
+                    *lowered_from = Some("try".to_string());
+                }
+                with_span(node, expr_try)
+            })
+            .collect()
+    }
+
+    /// `expand_macros` オプション指定時に、展開の表にあるマクロを展開した式として変換します
+    /// Ifになった場合は、元のマクロ呼び出しを `full_label` に、マクロ名を `lowered_from` に入れます。
+    fn expand_macro(&self, mac: &syn::Macro) -> Option<PadNode> {
+        if !self.options.expand_macros {
+            return None;
+        }
+        let (expanded, name) = macros::expand(mac)?;
+        let mut node = self.parse_expr(&expanded);
+        if let PadNode::If { full_label, lowered_from, .. } = &mut node {
+            *full_label = Some(quote::quote!(#mac).to_string());
+            *lowered_from = Some(name);
+        }
+        Some(node)
+    }
+
+    /// 個々のステートメント（文）を解析し、適切なPADノードに変換します
+    fn parse_stmt(&self, stmt: &Stmt) -> PadNode {
+        let node = with_span(self.parse_stmt_inner(stmt), stmt);
+        // アイテムの属性は parse_item で適用済みです
+        let attrs: &[Attribute] = match stmt {
+            Stmt::Local(local) => &local.attrs,
+            Stmt::Macro(mac) => &mac.attrs,
+            Stmt::Expr(expr, _) => expr_attrs(expr),
+            Stmt::Item(_) => &[],
+        };
+        with_span(self.apply_cfg(node, attrs), stmt)
+    }
+
+    fn parse_stmt_inner(&self, stmt: &Stmt) -> PadNode {
+        match stmt {
+            Stmt::Local(local) => {
+                 // ローカル変数定義（let x = ...;）
+                 // quote!マクロを使って元のソースコード表現に戻し、Commandノードとします
+                 let parsed = if self.options.pretty_labels { pretty::stmt(stmt) } else { quote::quote!(#local).to_string() };
+                 let init = local.init.as_ref().map(|init| &*init.expr);
+                 // `let Some(x) = v else { ... };` はパターンの照合を条件とし、else節を偽の側に置いたIfにします
+                 if let Some(local_init) = &local.init
+                     && let Some((_, diverge)) = &local_init.diverge
+                 {
+                     return self.parse_let_else(local, local_init, diverge);
+                 }
+                 // `let x = loop { ... break v; };` はループの値を受け取る変数をLoopノードに記録します
+                 if let Some(Expr::Loop(expr_loop)) = init {
+                     let mut node = self.parse_loop(expr_loop);
+                     if let PadNode::Loop { binding, .. } = &mut node {
+                         let pat = &local.pat;
+                         *binding = Some(quote::quote!(#pat).to_string());
+                     }
+                     return node;
+                 }
+                 // `expand_closures` オプション指定時は、`let f = |x| { ... };` を変数名をラベルに残したクロージャのBlockにします
+                 if let Some(Expr::Closure(closure)) = init
+                     && self.options.expand_closures
+                     && !contains_yield(&closure.body)
+                 {
+                     let pat = &local.pat;
+                     return self.closure_block(closure, Some(quote::quote!(#pat).to_string()));
+                 }
+                 // `let handle = thread::spawn(...)` は変数名をラベルに残した並行処理のBlockにします
+                 if let Some(init) = init {
+                     let pat = &local.pat;
+                     if let Some(node) = self.spawn_block(init, Some(quote::quote!(#pat).to_string())) {
+                         return node;
+                     }
+                 }
+                 if let Some(node) = init.and_then(|init| self.closure_args_block(init, &parsed)) {
+                     return node;
+                 }
+                 let parsed = match init.and_then(|init| self.multiline_label(init)) {
+                     Some(value) => {
+                         let pat = &local.pat;
+                         format!("let {} = {};", quote::quote!(#pat), value)
+                     }
+                     None => parsed,
+                 };
+                 // `let y = helper(x);` の呼び出しもインライン展開の対象にします
+                 let node = init
+                     .and_then(|init| self.inlinable_call(init))
+                     .and_then(|call| self.inline_call(call, &parsed))
+                     .unwrap_or_else(|| self.command_for(parsed, init));
+                 let node = set_category(node, CommandCategory::Declaration);
+                 self.mark_panics(node, &init.into_iter().collect::<Vec<_>>())
+            }
+            // `imports` オプション指定時は、関数内の `use` 宣言をその位置に表示します
+            // （展開して複数のパスになる場合は "imports" のBlockにまとめます）
+            Stmt::Item(Item::Use(item_use)) if self.options.imports => {
+                let mut commands = self.use_commands(item_use);
+                if commands.len() == 1 {
+                    commands.remove(0)
+                } else {
+                    PadNode::block(BlockKind::Imports, "imports", commands)
+                }
+            }
+            // 関数内で定義された関数などのアイテムは、トップレベルと同様に変換します
+            Stmt::Item(item) => self.parse_item(item).unwrap_or_else(|| {
+                let mut node = PadNode::command("Inner item not supported");
+                if let PadNode::Command { fallback, fallback_kind, .. } = &mut node {
+                    *fallback = true;
+                    *fallback_kind = Some(fallback::item_kind(item));
+                }
+                node
+            }),
+            Stmt::Expr(expr, _semi) => {
+                 // 式（if, while, 関数呼び出しなど）
+                 self.parse_expr(expr)
+            },
+            Stmt::Macro(mac) if let Some(node) = self.expand_macro(&mac.mac) => node,
+            Stmt::Macro(mac) => {
+                 // マクロ呼び出し（println!など）
+                 let parsed = if self.options.pretty_labels { pretty::stmt(stmt) } else { quote::quote!(#mac).to_string() };
+                set_category(PadNode::command(parsed), macro_category(&mac.mac))
+            }
+        }
+    }
+
+    /// 式（Expr）を解析し、制御構造（If, While, For）や単純なコマンドに分類します
+    fn parse_expr(&self, expr: &Expr) -> PadNode {
+        with_span(self.parse_expr_inner(expr), expr)
+    }
+
+    fn parse_expr_inner(&self, expr: &Expr) -> PadNode {
+        match expr {
+            Expr::If(expr_if) => {
+                 // if文の解析
+                 let cond = &expr_if.cond;
+
+                 // Then節（真の場合）
+                 let then_node = self.parse_block(&expr_if.then_branch);
+
+                 // Else節（偽の場合）
+                 let else_node = expr_if
+                     .else_branch
+                     .as_ref()
+                     .map(|(_, else_branch)| {
+                         let mut node = self.parse_expr(else_branch);
+                         // else if の条件の呼び出しは、内側のIfのものとします
+                         if let Expr::If(inner) = &**else_branch {
+                             node.meta_mut().call_paths = called_functions(|finder| finder.visit_expr_if(inner));
+                             node.meta_mut().awaits = await_points(|finder| finder.visit_expr_if(inner));
+                         }
+                         node
+                     });
+
+                 // let-chainの場合は各条件を整形して並べ、それ以外はquote!の出力を調整します（ドットの前後のスペース除去など）
+                 let components = let_chain_components(cond, self.options.pretty_labels);
+                 let condition = match components.is_empty() {
+                     true if self.options.pretty_labels => pretty::expr(cond),
+                     true => condition_text(cond),
+                     false => components.join(" && "),
+                 };
+                 let mut node = PadNode::if_node(condition, then_node, else_node);
+                 if let PadNode::If { components: c, compile_time, cfg_predicate, runtime_condition, .. } = &mut node {
+                     *c = components;
+                     if let Some((predicate, runtime)) = cfg_condition(cond) {
+                         *compile_time = true;
+                         *cfg_predicate = Some(predicate);
+                         *runtime_condition = runtime;
+                     }
+                 }
+                 self.mark_panics(node, &[cond])
+            }
+            Expr::While(expr_while) => {
+                // while文の解析
+                let cond = &expr_while.cond;
+                let components = let_chain_components(cond, self.options.pretty_labels);
+                let cond_str = if components.is_empty() { self.expr_text(cond) } else { components.join(" && ") };
+                let body = self.parse_block(&expr_while.body);
+                let mut node = PadNode::loop_node(LoopKind::While, cond_str, body);
+                if let PadNode::Loop { components: c, loop_label, modifies, .. } = &mut node {
+                    *c = components;
+                    *loop_label = expr_while.label.as_ref().map(|label| label.name.to_string());
+                    *modifies = effects::modified_variables(Some(cond), None, &expr_while.body);
+                }
+                self.mark_panics(node, &[cond])
+            }
+            Expr::ForLoop(expr_for) => {
+                 // forループの解析
+                 let (pat, iter) = (&expr_for.pat, &expr_for.expr);
+                 let variables = effects::pattern_bindings(pat);
+                 let modified = effects::modified_variables(None, Some(pat), &expr_for.body);
+                 let pat = self.pat_text(pat); // パターン（例: i）
+                 let expr = self.expr_text(iter); // 反復対象（例: 0..10）
+                 let body = self.parse_block(&expr_for.body);
+                 let mut node = PadNode::loop_node(LoopKind::For, format!("for {} in {}", pat, expr), body);
+                 if let PadNode::Loop { loop_label, modifies, loop_variables, .. } = &mut node {
+                     *loop_label = expr_for.label.as_ref().map(|label| label.name.to_string());
+                     *modifies = modified;
+                     *loop_variables = variables;
+                 }
+                 self.mark_panics(node, &[iter])
+            }
+            Expr::Loop(expr_loop) => self.parse_loop(expr_loop),
+            Expr::Match(expr_match) => {
+                // match式の解析（各アームの本体を再帰的に解析します）
+                let scrutinee = &expr_match.expr;
+                let arms = expr_match
+                    .arms
+                    .iter()
+                    .map(|arm| {
+                        MatchArm {
+                            pattern: self.pat_text(&arm.pat),
+                            guard: arm.guard.as_ref().map(|(_, guard)| self.expr_text(guard)),
+                            body: self.parse_arm_body(&arm.body),
+                        }
+                    })
+                    .collect();
+                // 互換モードでは、各アームを順に判定する入れ子のIfにします
+                if self.options.match_as_if {
+                    let scrutinee = self.expr_text(scrutinee);
+                    let wildcards: Vec<bool> = expr_match.arms.iter().map(|arm| arm.guard.is_none() && is_catch_all(&arm.pat)).collect();
+                    return self.mark_panics(lower_match(&scrutinee, arms, &wildcards), &[&expr_match.expr]);
+                }
+                let coverage = coverage::coverage(expr_match, &self.enums, &self.bindings.borrow());
+                let (enum_type, covered, uncovered_via_wildcard) = match coverage {
+                    Some(c) => (Some(c.enum_type), c.covered, c.uncovered_via_wildcard),
+                    None => (None, Vec::new(), Vec::new()),
+                };
+                let node = PadNode::Match {
+                    scrutinee: self.expr_text(scrutinee),
+                    arms,
+                    enum_type,
+                    covered,
+                    uncovered_via_wildcard,
+                    meta: NodeMeta::default(),
+                };
+                self.mark_panics(node, &[scrutinee])
+            }
+            Expr::Break(brk) => {
+                let target = brk.label.as_ref().map(|label| label.to_string());
+                let value = brk.expr.as_ref().map(|value| self.expr_text(value));
+                let label = ["break".to_string()]
+                    .into_iter()
+                    .chain(target.clone())
+                    .chain(value.clone())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                PadNode::Break { label, target, value, target_id: None, meta: NodeMeta::default() }
+            }
+            Expr::Continue(cont) => {
+                let target = cont.label.as_ref().map(|label| label.to_string());
+                let label = match &target {
+                    Some(target) => format!("continue {}", target),
+                    None => "continue".to_string(),
+                };
+                PadNode::Continue { label, target, target_id: None, meta: NodeMeta::default() }
+            }
+            Expr::Block(expr_block) => match &expr_block.label {
+                // ラベル付きブロック（`'done: { ... break 'done; ... }`）はラベル名のBlockとして表します
+                Some(label) => {
+                    PadNode::block(BlockKind::Labeled, label.name.to_string(), vec![self.parse_block(&expr_block.block)])
+                }
+                // 内側のブロック（スコープ作成など）
+                None => self.parse_block(&expr_block.block),
+            },
+            // `return` は関数（展開したクロージャの中では、そのクロージャ）の出口を表すReturnノードにします
+            Expr::Return(expr_return) => {
+                let value = expr_return.expr.as_ref().map(|value| self.expr_text(value));
+                let label = match &value {
+                    Some(value) => format!("return {}", value),
+                    None => "return".to_string(),
+                };
+                let node = PadNode::Return { label, value, scope: self.exit_scope(), meta: NodeMeta::default() };
+                self.mark_panics(node, &[expr])
+            }
+            Expr::Yield(expr_yield) => {
+                let value = expr_yield.expr.as_ref().map(|value| self.expr_text(value));
+                let label = match &value {
+                    Some(value) => format!("yield {}", value),
+                    None => "yield".to_string(),
+                };
+                PadNode::Yield { label, value, meta: NodeMeta::default() }
+            }
+            // unsafeブロックは安全性の確認が必要な範囲として目立たせるため、`unsafe` というラベルのBlockとして本体を展開します
+            Expr::Unsafe(expr_unsafe) => {
+                let mut body = self.parse_block(&expr_unsafe.block);
+                mark_unsafe(&mut body);
+                let mut node = PadNode::block(BlockKind::Unsafe, "unsafe", vec![body]);
+                mark_unsafe(&mut node);
+                node
+            }
+            // asyncブロックは `async` / `async move` というラベルのBlockとして本体を展開します
+            Expr::Async(expr_async) => {
+                let label = if expr_async.capture.is_some() { "async move" } else { "async" };
+                PadNode::block(BlockKind::Async, label, vec![self.in_closure(|| self.parse_block(&expr_async.block))])
+            }
+            // `yield` を含むクロージャ（コルーチン）はジェネレーターのBlockとして本体を展開します
+            Expr::Closure(closure) if contains_yield(&closure.body) => {
+                PadNode::block(BlockKind::Generator, closure_label(closure), vec![self.in_closure(|| self.parse_arm_body(&closure.body))])
+            }
+            // `expand_closures` オプション指定時は、単独の式のクロージャも本体を展開したBlockにします
+            Expr::Closure(closure) if self.options.expand_closures => self.closure_block(closure, None),
+            // `gen { ... }` ブロックは、対応していないsynでは Verbatim のトークン列になるため、トークンから判定します
+            Expr::Verbatim(tokens) if let Some((label, block)) = gen_block(tokens) => {
+                PadNode::block(BlockKind::Generator, label, vec![self.parse_block(&block)])
+            }
+            Expr::Call(_) if let Some(node) = self.spawn_block(expr, None) => node,
+            Expr::Macro(expr_macro) if let Some(node) = self.expand_macro(&expr_macro.mac) => node,
+            _ => {
+                // その他の式は単純なコマンドとして扱う（関数呼び出し、代入など）
+                let label = self.multiline_label(expr).unwrap_or_else(|| self.expr_text(expr));
+                if let Some(node) = self.closure_args_block(expr, &label) {
+                    return node;
+                }
+                let mut node = self
+                    .inlinable_call(expr)
+                    .and_then(|call| self.inline_call(call, &label))
+                    .unwrap_or_else(|| self.command_for(label, Some(expr)));
+                if let PadNode::Command { assignment, category, fallback, fallback_kind, .. } = &mut node {
+                    *assignment = assignment_of(expr);
+                    *category = if assignment.is_some() { CommandCategory::Assignment } else { expr_category(expr) };
+                    *fallback_kind = fallback::expr_kind(expr);
+                    *fallback = fallback_kind.is_some();
+                }
+                self.mark_panics(node, &[expr])
+            }
+        }
+    }
+}
+
+/// ブロック内の `pad:group` の入れ子を管理します
+/// グループはブロックの境界をまたげないため、ブロックの終わりで閉じられていないグループは無効とし、
+/// その中身は外側の子リストへそのまま戻します。
+#[derive(Default)]
+struct GroupStack {
+    root: Vec<PadNode>,
+    open: Vec<(String, Vec<PadNode>)>,
+}
+
+impl GroupStack {
+    fn open(&mut self, name: &str) {
+        self.open.push((name.to_string(), Vec::new()));
+    }
+
+    /// 直近のグループを閉じて合成Blockにします（対応する `pad:group` がなければ無視）
+    fn close(&mut self) {
+        if let Some((name, children)) = self.open.pop() {
+            // グループの範囲は最初の子の開始から最後の子の終了までとします
+            let span = match (children.first().and_then(|c| c.meta().span), children.last().and_then(|c| c.meta().span)) {
+                (Some(first), Some(last)) => Some(Span { end_line: last.end_line, end_col: last.end_col, ..first }),
+                _ => None,
+            };
+            let mut node = PadNode::block(BlockKind::Group, name, children);
+            node.meta_mut().span = span;
+            self.push(node);
+        }
+    }
+
+    fn push(&mut self, node: PadNode) {
+        match self.open.last_mut() {
+            Some((_, children)) => children.push(node),
+            None => self.root.push(node),
+        }
+    }
+
+    fn finish(mut self) -> Vec<PadNode> {
+        while let Some((_, children)) = self.open.pop() {
+            match self.open.last_mut() {
+                Some((_, parent)) => parent.extend(children),
+                None => self.root.extend(children),
+            }
+        }
+        self.root
+    }
+}
+
+/// `if cond { break }` の形（else節なし、then節が値なしのbreakだけ）かどうかを判定します
+/// ラベル付きbreakは、対象がこのループ自身のラベルの場合に限り該当とみなします。
+fn is_break_if(expr_if: &ExprIf, loop_label: Option<&syn::Lifetime>) -> bool {
+    if expr_if.else_branch.is_some() {
+        return false;
+    }
+    match expr_if.then_branch.stmts.as_slice() {
+        [Stmt::Expr(Expr::Break(brk), _)] => {
+            brk.expr.is_none()
+                && match &brk.label {
+                    None => true,
+                    Some(label) => Some(label) == loop_label,
+                }
+        }
+        _ => false,
+    }
+}
+
+/// ループ本体に、そのループを対象とする `continue` があるかどうかを判定します
+/// 入れ子のループ内のラベルなしcontinueや、クロージャ・asyncブロック内のcontinueは対象外です。
+fn continues_loop(block: &Block, loop_label: Option<&syn::Lifetime>) -> bool {
+    struct Finder<'a> {
+        loop_label: Option<&'a syn::Lifetime>,
+        nested: usize,
+        found: bool,
+    }
+    impl<'ast> Visit<'ast> for Finder<'_> {
+        fn visit_expr_continue(&mut self, cont: &'ast syn::ExprContinue) {
+            self.found |= match &cont.label {
+                Some(label) => Some(label) == self.loop_label,
+                None => self.nested == 0,
+            };
+        }
+        fn visit_expr_loop(&mut self, e: &'ast ExprLoop) {
+            self.nested += 1;
+            syn::visit::visit_expr_loop(self, e);
+            self.nested -= 1;
+        }
+        fn visit_expr_while(&mut self, e: &'ast syn::ExprWhile) {
+            self.nested += 1;
+            syn::visit::visit_expr_while(self, e);
+            self.nested -= 1;
+        }
+        fn visit_expr_for_loop(&mut self, e: &'ast syn::ExprForLoop) {
+            self.nested += 1;
+            syn::visit::visit_expr_for_loop(self, e);
+            self.nested -= 1;
+        }
+        fn visit_expr_closure(&mut self, _: &'ast syn::ExprClosure) {}
+        fn visit_expr_async(&mut self, _: &'ast syn::ExprAsync) {}
+        fn visit_item(&mut self, _: &'ast Item) {}
+    }
+    let mut finder = Finder { loop_label, nested: 0, found: false };
+    finder.visit_block(block);
+    finder.found
+}
+
+/// ループ本体から、そのループを対象とする `break value` の値の式を出現順に集めます
+/// 入れ子のループ内のラベルなしbreakや、クロージャ・asyncブロック内のbreakは対象外です。
+fn break_values(block: &Block, loop_label: Option<&syn::Lifetime>) -> Vec<String> {
+    struct Collector<'a> {
+        loop_label: Option<&'a syn::Lifetime>,
+        nested: usize,
+        values: Vec<String>,
+    }
+    impl<'ast> Visit<'ast> for Collector<'_> {
+        fn visit_expr_break(&mut self, brk: &'ast syn::ExprBreak) {
+            let targets_this = match &brk.label {
+                Some(label) => Some(label) == self.loop_label,
+                None => self.nested == 0,
+            };
+            if targets_this && let Some(value) = &brk.expr {
+                self.values.push(quote::quote!(#value).to_string());
+            }
+            syn::visit::visit_expr_break(self, brk);
+        }
+        fn visit_expr_loop(&mut self, e: &'ast ExprLoop) {
+            self.nested += 1;
+            syn::visit::visit_expr_loop(self, e);
+            self.nested -= 1;
+        }
+        fn visit_expr_while(&mut self, e: &'ast syn::ExprWhile) {
+            self.nested += 1;
+            syn::visit::visit_expr_while(self, e);
+            self.nested -= 1;
+        }
+        fn visit_expr_for_loop(&mut self, e: &'ast syn::ExprForLoop) {
+            self.nested += 1;
+            syn::visit::visit_expr_for_loop(self, e);
+            self.nested -= 1;
+        }
+        fn visit_expr_closure(&mut self, _: &'ast syn::ExprClosure) {}
+        fn visit_expr_async(&mut self, _: &'ast syn::ExprAsync) {}
+        fn visit_item(&mut self, _: &'ast Item) {}
+    }
+    let mut collector = Collector { loop_label, nested: 0, values: Vec::new() };
+    collector.visit_block(block);
+    collector.values
+}
+
+/// 条件式を否定した文字列を作ります（`!x` は `x` に、単純な式は `!x`、それ以外は `!(...)`）
+fn negate_condition(cond: &Expr) -> String {
+    match cond {
+        Expr::Unary(syn::ExprUnary { op: syn::UnOp::Not(_), expr, .. }) => quote::quote!(#expr).to_string(),
+        Expr::Path(_) | Expr::Call(_) | Expr::MethodCall(_) | Expr::Field(_) | Expr::Paren(_) | Expr::Lit(_) => {
+            format!("! {}", quote::quote!(#cond))
+        }
+        _ => format!("! ({})", quote::quote!(#cond)),
+    }
+}
+
+/// let-chain（`let` を含む `&&` の連鎖）の条件を、`&&` で区切った各条件の表示文字列に分解します（`pretty` の場合はprettypleaseで整形します）
+/// `let` を含まない条件の場合は空のVecを返します。
+fn let_chain_components(cond: &Expr, pretty: bool) -> Vec<String> {
+    fn flatten<'a>(expr: &'a Expr, parts: &mut Vec<&'a Expr>) {
+        match expr {
+            Expr::Binary(syn::ExprBinary { left, op: syn::BinOp::And(_), right, .. }) => {
+                flatten(left, parts);
+                flatten(right, parts);
+            }
+            _ => parts.push(expr),
+        }
+    }
+    let mut parts = Vec::new();
+    flatten(cond, &mut parts);
+    if !parts.iter().any(|part| matches!(part, Expr::Let(_))) {
+        return Vec::new();
+    }
+    parts
+        .into_iter()
+        .map(|part| match part {
+            Expr::Let(expr_let) if pretty => format!("let {} = {}", pretty::pat(&expr_let.pat), pretty::expr(&expr_let.expr)),
+            _ if pretty => pretty::expr(part),
+            Expr::Let(expr_let) => {
+                let (pat, expr) = (&expr_let.pat, &expr_let.expr);
+                format!("let {} = {}", compact_tokens(&quote::quote!(#pat).to_string()), condition_text(expr))
+            }
+            other => condition_text(other),
+        })
+        .collect()
+}
+
+/// `_` や変数への束縛など、何にでも一致するアームのパターンかどうか（定数かもしれない大文字で始まる名前は含めません）
+fn is_catch_all(pat: &syn::Pat) -> bool {
+    match pat {
+        syn::Pat::Wild(_) => true,
+        syn::Pat::Ident(ident) => ident.subpat.is_none() && !ident.ident.to_string().starts_with(char::is_uppercase),
+        _ => false,
+    }
+}
+
+/// matchのアームの並びを、先頭のアームから順に判定する入れ子のIfにします（`match_as_if` オプション）
+/// 条件は `let パターン = 照合対象`（ガードがある場合は `&& ガード`）で、残りのアームはelse節に入れます。
+/// 何にでも一致するアームは、それ以降のアームに到達しないため最後のelse節の本体にします。
+fn lower_match(scrutinee: &str, arms: Vec<MatchArm>, wildcards: &[bool]) -> PadNode {
+    let mut lowered: Option<PadNode> = None;
+    let last = wildcards.iter().position(|&wildcard| wildcard).unwrap_or(arms.len().saturating_sub(1));
+    for (index, arm) in arms.into_iter().enumerate().take(last + 1).rev() {
+        let body = match arm.body {
+            body @ PadNode::Sequence { .. } => body,
+            body => PadNode::sequence(vec![body]),
+        };
+        if index == last && wildcards.get(index) == Some(&true) {
+            lowered = Some(body);
+            continue;
+        }
+        let mut condition = format!("let {} = {}", arm.pattern, scrutinee);
+        if let Some(guard) = &arm.guard {
+            condition = format!("{} && {}", condition, guard);
+        }
+        let mut node = PadNode::if_node(condition, body, lowered.take());
+        if let PadNode::If { lowered_from, .. } = &mut node {
+            *lowered_from = Some("match".to_string());
+        }
+        lowered = Some(node);
+    }
+    lowered.unwrap_or_else(|| PadNode::sequence(Vec::new()))
+}
+
+/// `cfg!(...)` だけの条件、または `cfg!(...)` を含む `&&` の連鎖の条件を、cfgの条件と実行時の残りの条件に分けます
+/// `cfg!` を含まない条件や、`||` などで組み合わせた条件の場合は None を返します。
+fn cfg_condition(cond: &Expr) -> Option<(String, Option<String>)> {
+    fn flatten<'a>(expr: &'a Expr, parts: &mut Vec<&'a Expr>) {
+        match expr {
+            Expr::Binary(syn::ExprBinary { left, op: syn::BinOp::And(_), right, .. }) => {
+                flatten(left, parts);
+                flatten(right, parts);
+            }
+            Expr::Paren(paren) => flatten(&paren.expr, parts),
+            _ => parts.push(expr),
+        }
+    }
+    let mut parts = Vec::new();
+    flatten(cond, &mut parts);
+    let (mut predicates, mut runtime) = (Vec::new(), Vec::new());
+    for part in parts {
+        match part {
+            Expr::Macro(expr_macro) if expr_macro.mac.path.is_ident("cfg") => {
+                predicates.push(compact_tokens(&expr_macro.mac.tokens.to_string()));
+            }
+            other => runtime.push(condition_text(other)),
+        }
+    }
+    let predicate = match predicates.len() {
+        0 => return None,
+        1 => predicates.remove(0),
+        _ => format!("all({})", predicates.join(", ")),
+    };
+    Some((predicate, (!runtime.is_empty()).then(|| runtime.join(" && "))))
+}
+
+/// `#[cfg(...)]` 属性の条件を取り出します（複数ある場合は `all(...)` にまとめます）
+/// `cfg_attr` は対象外です。
+fn cfg_predicate(attrs: &[Attribute]) -> Option<String> {
+    let mut predicates: Vec<String> = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("cfg"))
+        .filter_map(|attr| match &attr.meta {
+            syn::Meta::List(list) => Some(compact_tokens(&list.tokens.to_string())),
+            _ => None,
+        })
+        .collect();
+    match predicates.len() {
+        0 => None,
+        1 => predicates.pop(),
+        _ => Some(format!("all({})", predicates.join(", "))),
+    }
+}
+
+/// `///` / `#[doc = "..."]` のドキュメントコメントを、行ごとに改行でつないだ本文
+fn doc_comment(attrs: &[Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                value: Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(text), .. }),
+                ..
+            }) => Some(text.value().trim().to_string()),
+            _ => None,
+        })
+        .collect();
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+/// アイテムに付いた属性
+fn item_attrs(item: &Item) -> &[Attribute] {
+    match item {
+        Item::Const(item) => &item.attrs,
+        Item::Enum(item) => &item.attrs,
+        Item::ExternCrate(item) => &item.attrs,
+        Item::Fn(item) => &item.attrs,
+        Item::ForeignMod(item) => &item.attrs,
+        Item::Impl(item) => &item.attrs,
+        Item::Macro(item) => &item.attrs,
+        Item::Mod(item) => &item.attrs,
+        Item::Static(item) => &item.attrs,
+        Item::Struct(item) => &item.attrs,
+        Item::Trait(item) => &item.attrs,
+        Item::TraitAlias(item) => &item.attrs,
+        Item::Type(item) => &item.attrs,
+        Item::Union(item) => &item.attrs,
+        Item::Use(item) => &item.attrs,
+        _ => &[],
+    }
+}
+
+/// 文として書かれた式に付いた属性（制御構造や呼び出しなど、属性を書ける主な式が対象です）
+fn expr_attrs(expr: &Expr) -> &[Attribute] {
+    match expr {
+        Expr::Assign(expr) => &expr.attrs,
+        Expr::Async(expr) => &expr.attrs,
+        Expr::Await(expr) => &expr.attrs,
+        Expr::Binary(expr) => &expr.attrs,
+        Expr::Block(expr) => &expr.attrs,
+        Expr::Break(expr) => &expr.attrs,
+        Expr::Call(expr) => &expr.attrs,
+        Expr::Continue(expr) => &expr.attrs,
+        Expr::ForLoop(expr) => &expr.attrs,
+        Expr::If(expr) => &expr.attrs,
+        Expr::Loop(expr) => &expr.attrs,
+        Expr::Macro(expr) => &expr.attrs,
+        Expr::Match(expr) => &expr.attrs,
+        Expr::MethodCall(expr) => &expr.attrs,
+        Expr::Return(expr) => &expr.attrs,
+        Expr::Try(expr) => &expr.attrs,
+        Expr::Unsafe(expr) => &expr.attrs,
+        Expr::While(expr) => &expr.attrs,
+        _ => &[],
+    }
+}
+
+/// 式に含まれる呼び出しのうち、ドキュメントのURLを特定できる最初のもの（行きがけ順）のURLを返します
+#[cfg(feature = "std-docs")]
+fn doc_url_of(expr: &Expr) -> Option<String> {
+    #[derive(Default)]
+    struct Finder {
+        url: Option<String>,
+    }
+    impl<'ast> Visit<'ast> for Finder {
+        fn visit_expr_call(&mut self, call: &'ast ExprCall) {
+            if self.url.is_none()
+                && let Expr::Path(path) = &*call.func
+            {
+                let segments: Vec<String> = path.path.segments.iter().map(|s| s.ident.to_string()).collect();
+                self.url = crate::std_docs::lookup_path(&segments);
+            }
+            syn::visit::visit_expr_call(self, call);
+        }
+        fn visit_expr_method_call(&mut self, call: &'ast syn::ExprMethodCall) {
+            if self.url.is_none() {
+                self.url = crate::std_docs::lookup_method(&call.method.to_string());
+            }
+            syn::visit::visit_expr_method_call(self, call);
+        }
+        fn visit_item(&mut self, _: &'ast Item) {}
+    }
+    let mut finder = Finder::default();
+    finder.visit_expr(expr);
+    finder.url
+}
+
+#[cfg(not(feature = "std-docs"))]
+fn doc_url_of(_: &Expr) -> Option<String> {
+    None
+}
+
+/// Commandノードに処理の種類を設定します（Command以外のノードはそのまま返します）
+fn set_category(mut node: PadNode, value: CommandCategory) -> PadNode {
+    if let PadNode::Command { category, .. } = &mut node {
+        *category = value;
+    }
+    node
+}
+
+/// 入出力とみなすマクロ
+const IO_MACROS: &[&str] = &["print", "println", "eprint", "eprintln", "write", "writeln", "dbg"];
+/// 終了とみなすマクロ
+const EXIT_MACROS: &[&str] = &["panic", "unreachable", "todo", "unimplemented"];
+/// 入出力とみなすパスの先頭（`fs::write`、`io::stdin` など）
+const IO_MODULES: &[&str] = &["fs", "io", "File", "OpenOptions"];
+/// 入出力とみなすメソッド
+const IO_METHODS: &[&str] = &["read_line", "read_to_string", "read_to_end", "write_all", "flush"];
+
+/// マクロ呼び出しの処理の種類
+fn macro_category(mac: &syn::Macro) -> CommandCategory {
+    let name = mac.path.segments.last().map(|s| s.ident.to_string()).unwrap_or_default();
+    if IO_MACROS.contains(&name.as_str()) {
+        CommandCategory::Io
+    } else if EXIT_MACROS.contains(&name.as_str()) {
+        CommandCategory::Exit
+    } else {
+        CommandCategory::Other
+    }
+}
+
+/// 式の形から処理の種類を判定します（`?`、`.await`、括弧は外して判定します）
+fn expr_category(expr: &Expr) -> CommandCategory {
+    match expr {
+        Expr::Try(inner) => expr_category(&inner.expr),
+        Expr::Await(inner) => expr_category(&inner.base),
+        Expr::Paren(inner) => expr_category(&inner.expr),
+        Expr::Return(_) => CommandCategory::Exit,
+        Expr::Macro(mac) => macro_category(&mac.mac),
+        Expr::Call(call) => match &*call.func {
+            Expr::Path(path) => {
+                let segments: Vec<String> = path.path.segments.iter().map(|s| s.ident.to_string()).collect();
+                let owner = segments.iter().rev().nth(1).map(String::as_str);
+                match (owner, segments.last().map(String::as_str)) {
+                    (Some("process"), Some("exit" | "abort")) => CommandCategory::Exit,
+                    (Some(owner), _) if IO_MODULES.contains(&owner) => CommandCategory::Io,
+                    _ => CommandCategory::Call,
+                }
+            }
+            _ => CommandCategory::Call,
+        },
+        Expr::MethodCall(call) if IO_METHODS.contains(&call.method.to_string().as_str()) => CommandCategory::Io,
+        Expr::MethodCall(call) => match expr_category(&call.receiver) {
+            // `io::stdin().read_line(...)` のような入出力のメソッドチェーンは入出力とします
+            CommandCategory::Io => CommandCategory::Io,
+            _ => CommandCategory::Call,
+        },
+        Expr::Binary(binary) => {
+            use syn::BinOp::*;
+            match binary.op {
+                Add(_) | Sub(_) | Mul(_) | Div(_) | Rem(_) | BitXor(_) | BitAnd(_) | BitOr(_) | Shl(_) | Shr(_) => {
+                    CommandCategory::Arithmetic
+                }
+                _ => CommandCategory::Other,
+            }
+        }
+        Expr::Unary(syn::ExprUnary { op: syn::UnOp::Neg(_), .. }) => CommandCategory::Arithmetic,
+        _ => CommandCategory::Other,
+    }
+}
+
+/// 式が `return` か、`?` を含むかどうか（入れ子のクロージャ・asyncブロック・アイテムの中は対象外です）
+fn exits_early(expr: &Expr) -> bool {
+    struct Finder {
+        found: bool,
+    }
+    impl<'ast> Visit<'ast> for Finder {
+        fn visit_expr_return(&mut self, _: &'ast syn::ExprReturn) {
+            self.found = true;
+        }
+        fn visit_expr_try(&mut self, e: &'ast syn::ExprTry) {
+            self.found = true;
+            syn::visit::visit_expr_try(self, e);
+        }
+        fn visit_expr_closure(&mut self, _: &'ast syn::ExprClosure) {}
+        fn visit_expr_async(&mut self, _: &'ast syn::ExprAsync) {}
+        fn visit_item(&mut self, _: &'ast Item) {}
+    }
+    let mut finder = Finder { found: false };
+    finder.visit_expr(expr);
+    finder.found
+}
+
+/// 式に含まれる、ブロックを本体とするクロージャ（入れ子のクロージャ・asyncブロック・アイテムの中は対象外です）
+fn block_closures(expr: &Expr) -> Vec<&syn::ExprClosure> {
+    struct Finder<'ast> {
+        closures: Vec<&'ast syn::ExprClosure>,
+    }
+    impl<'ast> Visit<'ast> for Finder<'ast> {
+        fn visit_expr_closure(&mut self, closure: &'ast syn::ExprClosure) {
+            if matches!(&*closure.body, Expr::Block(_)) && !contains_yield(&closure.body) {
+                self.closures.push(closure);
+            }
+        }
+        fn visit_expr_async(&mut self, _: &'ast syn::ExprAsync) {}
+        fn visit_item(&mut self, _: &'ast Item) {}
+    }
+    let mut finder = Finder { closures: Vec::new() };
+    finder.visit_expr(expr);
+    finder.closures
+}
+
+/// 文の中の `?` 演算子を評価の順に集めます
+/// 入れ子のブロック・クロージャ・asyncブロック・アイテム・matchのアーム・whileの中は対象外です。
+fn try_operators(stmt: &Stmt) -> Vec<&syn::ExprTry> {
+    struct Finder<'ast> {
+        found: Vec<&'ast syn::ExprTry>,
+    }
+    impl<'ast> Visit<'ast> for Finder<'ast> {
+        fn visit_expr_try(&mut self, e: &'ast syn::ExprTry) {
+            syn::visit::visit_expr_try(self, e);
+            self.found.push(e);
+        }
+        fn visit_block(&mut self, _: &'ast Block) {}
+        fn visit_arm(&mut self, _: &'ast syn::Arm) {}
+        fn visit_expr_while(&mut self, _: &'ast syn::ExprWhile) {}
+        fn visit_expr_closure(&mut self, _: &'ast syn::ExprClosure) {}
+        fn visit_expr_async(&mut self, _: &'ast syn::ExprAsync) {}
+        fn visit_item(&mut self, _: &'ast Item) {}
+    }
+    let mut finder = Finder { found: Vec::new() };
+    finder.visit_stmt(stmt);
+    finder.found
+}
+
+/// 呼び出している関数のパス（`helper`、`Counter::new`）を出現順に重複なく集めます
+/// `self.tick()` のような `self` のメソッド呼び出しは `Self::tick`、それ以外のメソッド呼び出しは `.tick` とします。
+/// 呼び出し先が同じ入力で定義されているかは、idを割り当てた後の `ids::link_calls` で判定します。
+/// 入れ子のブロック・クロージャ・asyncブロック・アイテム・matchのアーム・else節の中は、それぞれのノードのものとして対象外です。
+fn called_functions(visit: impl FnOnce(&mut CallFinder)) -> Vec<String> {
+    let mut finder = CallFinder { found: Vec::new() };
+    visit(&mut finder);
+    finder.found
+}
+
+struct CallFinder {
+    found: Vec<String>,
+}
+
+impl CallFinder {
+    fn push(&mut self, path: String) {
+        if !self.found.contains(&path) {
+            self.found.push(path);
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for CallFinder {
+    fn visit_expr_call(&mut self, e: &'ast ExprCall) {
+        if let Expr::Path(ExprPath { path, qself: None, .. }) = &*e.func {
+            self.push(compact_tokens(&quote::quote!(#path).to_string()));
+        }
+        syn::visit::visit_expr_call(self, e);
+    }
+    fn visit_expr_method_call(&mut self, e: &'ast syn::ExprMethodCall) {
+        match &*e.receiver {
+            Expr::Path(ExprPath { path, .. }) if path.is_ident("self") => self.push(format!("Self::{}", e.method)),
+            _ => self.push(format!(".{}", e.method)),
+        }
+        syn::visit::visit_expr_method_call(self, e);
+    }
+    fn visit_expr_if(&mut self, e: &'ast syn::ExprIf) {
+        self.visit_expr(&e.cond);
+    }
+    fn visit_block(&mut self, _: &'ast Block) {}
+    fn visit_arm(&mut self, _: &'ast syn::Arm) {}
+    fn visit_expr_closure(&mut self, _: &'ast syn::ExprClosure) {}
+    fn visit_expr_async(&mut self, _: &'ast syn::ExprAsync) {}
+    fn visit_item(&mut self, _: &'ast Item) {}
+}
+
+/// `.await` の数を数えます（対象外の範囲は `called_functions` と同じです）。ない場合は None を返します
+fn await_points(visit: impl FnOnce(&mut AwaitFinder)) -> Option<usize> {
+    let mut finder = AwaitFinder { count: 0 };
+    visit(&mut finder);
+    (finder.count > 0).then_some(finder.count)
+}
+
+struct AwaitFinder {
+    count: usize,
+}
+
+impl<'ast> Visit<'ast> for AwaitFinder {
+    fn visit_expr_await(&mut self, e: &'ast syn::ExprAwait) {
+        self.count += 1;
+        syn::visit::visit_expr_await(self, e);
+    }
+    fn visit_expr_if(&mut self, e: &'ast syn::ExprIf) {
+        self.visit_expr(&e.cond);
+    }
+    fn visit_block(&mut self, _: &'ast Block) {}
+    fn visit_arm(&mut self, _: &'ast syn::Arm) {}
+    fn visit_expr_closure(&mut self, _: &'ast syn::ExprClosure) {}
+    fn visit_expr_async(&mut self, _: &'ast syn::ExprAsync) {}
+    fn visit_item(&mut self, _: &'ast Item) {}
+}
+
+/// 式に `yield` が含まれるかどうか（入れ子のクロージャ・asyncブロック・アイテムの中は対象外です）
+fn contains_yield(expr: &Expr) -> bool {
+    struct Finder {
+        found: bool,
+    }
+    impl<'ast> Visit<'ast> for Finder {
+        fn visit_expr_yield(&mut self, _: &'ast syn::ExprYield) {
+            self.found = true;
+        }
+        fn visit_expr_closure(&mut self, _: &'ast syn::ExprClosure) {}
+        fn visit_expr_async(&mut self, _: &'ast syn::ExprAsync) {}
+        fn visit_item(&mut self, _: &'ast Item) {}
+    }
+    let mut finder = Finder { found: false };
+    finder.visit_expr(expr);
+    finder.found
+}
+
+/// `gen { ... }` / `gen move { ... }` のトークン列から、ラベルと本体のブロックを取り出します
+fn gen_block(tokens: &proc_macro2::TokenStream) -> Option<(&'static str, Block)> {
+    let tokens: Vec<TokenTree> = tokens.clone().into_iter().collect();
+    let (label, body) = match tokens.as_slice() {
+        [TokenTree::Ident(keyword), body] if keyword == "gen" => ("gen", body),
+        [TokenTree::Ident(keyword), TokenTree::Ident(capture), body] if keyword == "gen" && capture == "move" => {
+            ("gen move", body)
+        }
+        _ => return None,
+    };
+    match body {
+        TokenTree::Group(group) if group.delimiter() == proc_macro2::Delimiter::Brace => {
+            syn::parse2::<Block>(TokenTree::Group(group.clone()).into()).ok().map(|block| (label, block))
+        }
+        _ => None,
+    }
+}
+
+/// テスト関数かどうか（属性のパスの最後の要素が `test` のもの。`#[test]`, `#[tokio::test]` など）
+fn is_test_fn(func: &ItemFn) -> bool {
+    func.attrs.iter().any(|attr| attr.path().segments.last().is_some_and(|segment| segment.ident == "test"))
+}
+
+/// 代入式（`x = v`）・複合代入式（`x += v` など）を代入先・演算子・値に分解します
+fn assignment_of(expr: &Expr) -> Option<Assignment> {
+    let (left, op, right) = match expr {
+        Expr::Assign(assign) => (&*assign.left, "=".to_string(), &*assign.right),
+        Expr::Binary(binary) => {
+            use syn::BinOp::*;
+            match binary.op {
+                AddAssign(_) | SubAssign(_) | MulAssign(_) | DivAssign(_) | RemAssign(_) | BitXorAssign(_)
+                | BitAndAssign(_) | BitOrAssign(_) | ShlAssign(_) | ShrAssign(_) => {
+                    let op = &binary.op;
+                    (&*binary.left, quote::quote!(#op).to_string(), &*binary.right)
+                }
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    Some(Assignment { target: place_text(left), op, value: condition_text(right) })
+}
+
+/// 代入先（フィールド・添字アクセスの連鎖）の表示文字列
+fn place_text(expr: &Expr) -> String {
+    condition_text(expr).replace(" [", "[").replace("[ ", "[").replace(" ]", "]")
+}
+
+/// 条件式の表示文字列（quote!の出力からドットの前後のスペースを除きます）
+fn condition_text(expr: &Expr) -> String {
+    quote::quote!(#expr).to_string().replace(" . ", ".")
+}
+
+/// 構造体リテラル・呼び出しを、フィールド・引数が `max` を超える場合は1行に1つずつ並べて整形します
+/// 入れ子の構造体リテラル・呼び出しも同じ規則で整形し、深さに応じて字下げします。
+fn layout_expr(expr: &Expr, max: usize, indent: usize) -> String {
+    let pad = " ".repeat(indent + 4);
+    match expr {
+        Expr::Struct(lit) if lit.fields.len() > max => {
+            let path = &lit.path;
+            let mut text = format!("{} {{\n", compact_tokens(&quote::quote!(#path).to_string()));
+            for field in &lit.fields {
+                let member = &field.member;
+                let member = quote::quote!(#member).to_string();
+                match field.colon_token {
+                    Some(_) => text += &format!("{}{}: {},\n", pad, member, layout_expr(&field.expr, max, indent + 4)),
+                    None => text += &format!("{}{},\n", pad, member),
+                }
+            }
+            if let Some(rest) = &lit.rest {
+                text += &format!("{}..{}\n", pad, condition_text(rest));
+            }
+            text + &" ".repeat(indent) + "}"
+        }
+        Expr::Call(call) if call.args.len() > max => {
+            let func = &call.func;
+            let mut text = format!("{}(\n", compact_tokens(&quote::quote!(#func).to_string()));
+            for arg in &call.args {
+                text += &format!("{}{},\n", pad, layout_expr(arg, max, indent + 4));
+            }
+            text + &" ".repeat(indent) + ")"
+        }
+        _ => condition_text(expr),
+    }
+}
+
+/// 関数の本体の中のReturnノードに、戻り値を返すノードの印を付けます
+/// クロージャ・asyncブロックの中の `return`（`scope` が `closure`）と、内側の関数・インライン展開した呼び出しの中の
+/// `return` は、別の関数からの戻りのため除きます。
+fn mark_returns(node: &mut PadNode) {
+    match node {
+        PadNode::Return { scope: ExitScope::Function, meta, .. } => meta.returns = true,
+        PadNode::Block { kind: BlockKind::Function | BlockKind::Call, .. } => {}
+        _ => node.children_mut().into_iter().for_each(mark_returns),
+    }
+}
+
+/// 関数の末尾の式に、戻り値を返すノードの印を付けます
+/// 末尾の式が if / match の場合は、各分岐の最後のノードに印を付けます。
+fn mark_tail(node: &mut PadNode) {
+    match node {
+        PadNode::Sequence { children, .. } | PadNode::Block { kind: BlockKind::Labeled | BlockKind::Group, children, .. } => {
+            if let Some(last) = children.last_mut() {
+                mark_tail(last);
+            }
+        }
+        PadNode::If { .. } | PadNode::Match { .. } => node.children_mut().into_iter().for_each(mark_tail),
+        PadNode::Command { category: CommandCategory::Exit, .. } | PadNode::Return { .. } | PadNode::Error { .. } => {}
+        _ => node.meta_mut().returns = true,
+    }
+}
+
+/// 構文要素のソース上の位置を取得します
+pub fn span_of(spanned: &impl Spanned) -> Span {
+    let span = spanned.span();
+    let (start, end) = (span.start(), span.end());
+    Span { start_line: start.line, start_col: start.column, end_line: end.line, end_col: end.column }
+}
+
+/// ノードにまだ位置が設定されていなければ、構文要素の位置を設定します
+/// 内側で設定された位置（文の中の式など、より具体的な位置）は上書きしません。
+fn with_span(mut node: PadNode, spanned: &impl Spanned) -> PadNode {
+    let meta = node.meta_mut();
+    if meta.span.is_none() {
+        meta.span = Some(span_of(spanned));
+    }
+    node
+}
+
+/// `async fn` の関数のBlockに `is_async` を、`unsafe fn` の関数のBlockとその本体に `is_unsafe` を付けます
+fn set_qualifiers(node: &mut PadNode, sig: &syn::Signature) {
+    if let PadNode::Block { is_async, is_unsafe, children, .. } = node {
+        *is_async = sig.asyncness.is_some();
+        *is_unsafe = sig.unsafety.is_some();
+        if *is_unsafe {
+            children.iter_mut().for_each(mark_unsafe);
+        }
+    }
+}
+
+/// 関数のBlockに、可視性を含むシグネチャ（`signature`）を付けます
+fn set_signature(node: &mut PadNode, vis: &syn::Visibility, sig: &syn::Signature) {
+    if let PadNode::Block { signature, .. } = node {
+        *signature = Some(compact_tokens(&quote::quote!(#vis #sig).to_string()));
+    }
+}
+
+/// `unsafe` の範囲のBlock・Sequenceに `is_unsafe` を付けます
+fn mark_unsafe(node: &mut PadNode) {
+    if let PadNode::Block { is_unsafe, .. } | PadNode::Sequence { is_unsafe, .. } = node {
+        *is_unsafe = true;
+    }
+}
+
+/// implブロック由来のBlockに実装対象の型とトレイト名を記録します
+fn set_impl_target(node: &mut PadNode, trait_name: &Option<String>, self_type: &str) {
+    if let PadNode::Block { trait_name: t, self_type: s, .. } = node {
+        *t = trait_name.clone();
+        *s = Some(self_type.to_string());
+    }
+}
+
+/// トレイトのメソッドのうち、既定の実装を持つものとその本体
+pub fn default_methods(item_trait: &ItemTrait) -> impl Iterator<Item = (&syn::TraitItemFn, &Block)> {
+    item_trait.items.iter().filter_map(|item| match item {
+        TraitItem::Fn(method) => Some((method, method.default.as_ref()?)),
+        _ => None,
+    })
+}
+
+/// 同じ型に対する固有実装のBlockを、最初に現れたBlockにまとめます（`group_impls_by_type` オプション）
+/// メソッドの順序は元のままで、各メソッドの `impl_span` に元のimplブロックの位置を記録します。
+/// `#[cfg(...)]` の条件が異なるimplブロックは、条件ごとに別々にまとめます。
+pub fn group_impls_by_type(nodes: Vec<PadNode>) -> Vec<PadNode> {
+    let mut grouped: Vec<PadNode> = Vec::new();
+    let mut first: HashMap<(String, Option<String>), usize> = HashMap::new();
+    for mut node in nodes {
+        let key = match &node {
+            PadNode::Block { kind: BlockKind::Impl, trait_name: None, self_type: Some(self_type), meta, .. } => {
+                (self_type.clone(), meta.cfg.clone())
+            }
+            _ => {
+                grouped.push(node);
+                continue;
+            }
+        };
+        let Some(&index) = first.get(&key) else {
+            first.insert(key, grouped.len());
+            grouped.push(node);
+            continue;
+        };
+        let span = node.meta().span;
+        let target = &mut grouped[index];
+        let target_span = target.meta().span;
+        let (PadNode::Block { children: methods, .. }, PadNode::Block { children, .. }) = (target, &mut node) else {
+            unreachable!()
+        };
+        set_impl_span(methods, target_span);
+        set_impl_span(children, span);
+        methods.append(children);
+    }
+    grouped
+}
+
+/// まだ元のimplブロックの位置が記録されていないメソッドに、位置を記録します
+fn set_impl_span(methods: &mut [PadNode], span: Option<Span>) {
+    for method in methods {
+        if let PadNode::Block { impl_span, .. } = method
+            && impl_span.is_none()
+        {
+            *impl_span = span;
+        }
+    }
+}
+
+/// シグネチャを `name(&mut self, x: i32)` の形式で表します
+fn signature_params(sig: &Signature) -> String {
+    let params: Vec<String> = sig
+        .inputs
+        .iter()
+        .map(|arg| compact_tokens(&quote::quote!(#arg).to_string()))
+        .collect();
+    format!("{}({})", sig.ident, params.join(", "))
+}
+
+/// クロージャの引数の一覧のラベル（`|x, y|`。`move` の場合は `move |x|`）
+fn closure_label(closure: &syn::ExprClosure) -> String {
+    let inputs = &closure.inputs;
+    let label = format!("|{}|", compact_tokens(&quote::quote!(#inputs).to_string()));
+    if closure.capture.is_some() { format!("move {}", label) } else { label }
+}
+
+/// 型エイリアス・関連型の宣言のラベル（`pub type Result<T> = std::result::Result<T, MyError>`）
+fn type_alias_label(vis: &syn::Visibility, name: proc_macro2::TokenStream, ty: proc_macro2::TokenStream) -> String {
+    let label = compact_tokens(&quote::quote!(#vis type #name = #ty).to_string());
+    label.replace(" <", "<").replace(" =(", " = (")
+}
+
+/// `use` のツリーを、まとめられたパス（`{a, b::c}`）を展開した個々のパスの一覧にします
+/// `prefix` はそこまでのパス（`std::io::` など）で、グループ内の `self` はそのパス自身を表します。
+fn expand_use_tree(tree: &UseTree, prefix: String) -> Vec<String> {
+    match tree {
+        UseTree::Path(path) => expand_use_tree(&path.tree, format!("{}{}::", prefix, path.ident)),
+        UseTree::Name(name) if name.ident == "self" => vec![prefix.trim_end_matches("::").to_string()],
+        UseTree::Name(name) => vec![format!("{}{}", prefix, name.ident)],
+        UseTree::Rename(rename) => vec![format!("{}{} as {}", prefix, rename.ident, rename.rename)],
+        UseTree::Glob(_) => vec![format!("{}*", prefix)],
+        UseTree::Group(group) => group.items.iter().flat_map(|tree| expand_use_tree(tree, prefix.clone())).collect(),
+    }
+}
+
+/// 型やパスに対するquote!の出力（`Bar < T >` や `& mut self`）を詰めて読みやすくします
+pub fn compact_tokens(text: &str) -> String {
+    text.replace(" :: ", "::")
+        .replace(":: ", "::")
+        .replace(" : ", ": ")
+        .replace(" < ", "<")
+        .replace("< ", "<")
+        .replace(" >", ">")
+        .replace(" ,", ",")
+        .replace("& ", "&")
+        .replace(" (", "(")
+        .replace("( ", "(")
+        .replace(" )", ")")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn let_chain_is_split_into_components() {
+        let cond: Expr = syn::parse_str("let Some(a) = x && let Ok(b) = fetch(a)").unwrap();
+        assert_eq!(let_chain_components(&cond, false), vec!["let Some(a) = x", "let Ok(b) = fetch (a)"]);
+        let plain: Expr = syn::parse_str("a && b").unwrap();
+        assert!(let_chain_components(&plain, false).is_empty());
+    }
+
+    #[test]
+    fn gen_blocks_are_detected_from_tokens() {
+        let tokens: proc_macro2::TokenStream = "gen move { yield 1; }".parse().unwrap();
+        let (label, block) = gen_block(&tokens).unwrap();
+        assert_eq!(label, "gen move");
+        assert_eq!(block.stmts.len(), 1);
+        assert!(gen_block(&"gen (1)".parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn compact_tokens_tightens_generics_and_references() {
+        assert_eq!(compact_tokens("Bar < T >"), "Bar<T>");
+        assert_eq!(compact_tokens("& mut self"), "&mut self");
+        assert_eq!(compact_tokens("HashMap < String , Vec < u8 > >"), "HashMap<String, Vec<u8>>");
+        assert_eq!(compact_tokens("x : & 'a std :: path :: Path"), "x: &'a std::path::Path");
+    }
+}
@@ -0,0 +1,236 @@
+//! 入力の中で定義されたenumに対するmatchの網羅状況
+//! 照合対象の型が構文だけから決まる場合（`let x: MyEnum` の注釈、引数の型、`MyEnum::A` のようなパターン）に、
+//! 各アームが明示的に扱うバリアントと、`_` などのワイルドカードで扱われるバリアントを求めます。
+//! 型が決まらない場合や、解釈できないパターンを含む場合は何も返しません。
+
+use std::collections::HashMap;
+
+use syn::visit::Visit;
+use syn::{Block, Expr, ExprMatch, FnArg, Item, Pat, Signature, Type};
+
+/// matchの網羅状況
+#[derive(Debug, PartialEq)]
+pub struct Coverage {
+    /// 照合対象のenumの名前
+    pub enum_type: String,
+    /// アームで明示的に扱われるバリアント（宣言順）
+    pub covered: Vec<String>,
+    /// 明示的には扱われず、ワイルドカードのアームで扱われるバリアント（宣言順）
+    pub uncovered_via_wildcard: Vec<String>,
+}
+
+/// トップレベルで定義されたenumの名前と、そのバリアントの名前（宣言順）
+pub fn enum_variants(items: &[Item]) -> HashMap<String, Vec<String>> {
+    items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Enum(item_enum) => Some((
+                item_enum.ident.to_string(),
+                item_enum.variants.iter().map(|variant| variant.ident.to_string()).collect(),
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+/// 関数の中の変数の型の注釈を集めます
+/// 同じ名前の変数が型の注釈なし、または異なる型で宣言されている場合は、型を決められないため None にします。
+pub fn typed_bindings(sig: &Signature, block: &Block) -> HashMap<String, Option<String>> {
+    let mut bindings = Bindings::default();
+    for input in &sig.inputs {
+        if let FnArg::Typed(pat_type) = input {
+            bindings.visit_pat_type(pat_type);
+        }
+    }
+    bindings.visit_block(block);
+    bindings.types
+}
+
+#[derive(Default)]
+struct Bindings {
+    types: HashMap<String, Option<String>>,
+}
+
+impl Bindings {
+    fn record(&mut self, name: String, ty: Option<String>) {
+        self.types.entry(name).and_modify(|known| if *known != ty { *known = None }).or_insert(ty);
+    }
+}
+
+impl<'ast> Visit<'ast> for Bindings {
+    fn visit_pat_type(&mut self, pat_type: &'ast syn::PatType) {
+        match &*pat_type.pat {
+            Pat::Ident(ident) if ident.subpat.is_none() => self.record(ident.ident.to_string(), type_name(&pat_type.ty)),
+            pat => self.visit_pat(pat),
+        }
+    }
+
+    fn visit_pat_ident(&mut self, pat: &'ast syn::PatIdent) {
+        self.record(pat.ident.to_string(), None);
+        syn::visit::visit_pat_ident(self, pat);
+    }
+
+    fn visit_item(&mut self, _: &'ast Item) {}
+}
+
+/// 型の注釈が単純な名前（`MyEnum`、`&MyEnum`）の場合に、その名前を返します
+fn type_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Reference(reference) => type_name(&reference.elem),
+        Type::Paren(paren) => type_name(&paren.elem),
+        Type::Path(path) if path.qself.is_none() => {
+            let ident = path.path.get_ident()?;
+            Some(ident.to_string())
+        }
+        _ => None,
+    }
+}
+
+/// matchの網羅状況を求めます（照合対象のenumが決まらない場合は None）
+pub fn coverage(
+    expr_match: &ExprMatch,
+    enums: &HashMap<String, Vec<String>>,
+    bindings: &HashMap<String, Option<String>>,
+) -> Option<Coverage> {
+    let from_binding = scrutinee_name(&expr_match.expr)
+        .and_then(|name| bindings.get(&name).cloned().flatten())
+        .filter(|ty| enums.contains_key(ty));
+    let mut named = Vec::new();
+    for arm in &expr_match.arms {
+        pattern_enums(&arm.pat, enums, &mut named);
+    }
+    let enum_type = match (from_binding, named.as_slice()) {
+        (Some(ty), []) => ty,
+        (Some(ty), [name]) if *name == ty => ty,
+        (None, [name]) => name.clone(),
+        _ => return None,
+    };
+    let variants = &enums[&enum_type];
+
+    let mut full = Vec::new();
+    let mut wildcard = false;
+    for arm in &expr_match.arms {
+        let mut cover = Cover::default();
+        cover.pattern(&arm.pat, &enum_type, variants)?;
+        // ガード付きのアームは、そのバリアント（またはワイルドカード）のすべてを扱うとは限りません
+        if arm.guard.is_none() {
+            full.extend(cover.full);
+            wildcard |= cover.wildcard;
+        }
+    }
+    let covered: Vec<String> = variants.iter().filter(|variant| full.contains(variant)).cloned().collect();
+    let uncovered_via_wildcard = if wildcard {
+        variants.iter().filter(|variant| !full.contains(variant)).cloned().collect()
+    } else {
+        Vec::new()
+    };
+    Some(Coverage { enum_type, covered, uncovered_via_wildcard })
+}
+
+/// 照合対象が変数（`x`、`&x`、`*x`）の場合の変数名
+fn scrutinee_name(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Path(path) if path.qself.is_none() => path.path.get_ident().map(|ident| ident.to_string()),
+        Expr::Reference(reference) => scrutinee_name(&reference.expr),
+        Expr::Unary(unary) if matches!(unary.op, syn::UnOp::Deref(_)) => scrutinee_name(&unary.expr),
+        Expr::Paren(paren) => scrutinee_name(&paren.expr),
+        _ => None,
+    }
+}
+
+/// パターンに `MyEnum::A` の形で現れる、入力の中で定義されたenumの名前を集めます
+fn pattern_enums(pat: &Pat, enums: &HashMap<String, Vec<String>>, named: &mut Vec<String>) {
+    let path = match pat {
+        Pat::Path(pat) => Some(&pat.path),
+        Pat::TupleStruct(pat) => Some(&pat.path),
+        Pat::Struct(pat) => Some(&pat.path),
+        _ => None,
+    };
+    if let Some(path) = path
+        && let [owner, _] = path.segments.iter().collect::<Vec<_>>().as_slice()
+        && enums.contains_key(&owner.ident.to_string())
+        && !named.contains(&owner.ident.to_string())
+    {
+        named.push(owner.ident.to_string());
+    }
+    match pat {
+        Pat::Or(or) => or.cases.iter().for_each(|case| pattern_enums(case, enums, named)),
+        Pat::Ident(ident) => {
+            if let Some((_, subpat)) = &ident.subpat {
+                pattern_enums(subpat, enums, named);
+            }
+        }
+        Pat::Reference(reference) => pattern_enums(&reference.pat, enums, named),
+        Pat::Paren(paren) => pattern_enums(&paren.pat, enums, named),
+        _ => {}
+    }
+}
+
+/// アーム1つのパターンが扱うバリアント
+#[derive(Default)]
+struct Cover {
+    /// すべての値を扱うバリアント（フィールドのパターンが何にでも一致するもの）
+    full: Vec<String>,
+    /// `_` や変数への束縛など、何にでも一致するパターンを含むか
+    wildcard: bool,
+}
+
+impl Cover {
+    /// パターンを調べます。解釈できないパターンの場合は None を返します
+    fn pattern(&mut self, pat: &Pat, enum_type: &str, variants: &[String]) -> Option<()> {
+        let (path, fields): (&syn::Path, Vec<&Pat>) = match pat {
+            Pat::Wild(_) => {
+                self.wildcard = true;
+                return Some(());
+            }
+            Pat::Ident(ident) => {
+                if let Some((_, subpat)) = &ident.subpat {
+                    return self.pattern(subpat, enum_type, variants);
+                }
+                // `use MyEnum::*;` で取り込んだバリアントかもしれない名前は判断しません
+                if variants.contains(&ident.ident.to_string()) {
+                    return None;
+                }
+                self.wildcard = true;
+                return Some(());
+            }
+            Pat::Or(or) => {
+                for case in &or.cases {
+                    self.pattern(case, enum_type, variants)?;
+                }
+                return Some(());
+            }
+            Pat::Reference(reference) => return self.pattern(&reference.pat, enum_type, variants),
+            Pat::Paren(paren) => return self.pattern(&paren.pat, enum_type, variants),
+            Pat::Path(pat) => (&pat.path, Vec::new()),
+            Pat::TupleStruct(pat) => (&pat.path, pat.elems.iter().collect()),
+            Pat::Struct(pat) => (&pat.path, pat.fields.iter().map(|field| &*field.pat).collect()),
+            _ => return None,
+        };
+        let [owner, variant] = path.segments.iter().collect::<Vec<_>>()[..] else { return None };
+        let variant = variant.ident.to_string();
+        if owner.ident != enum_type || !variants.contains(&variant) {
+            return None;
+        }
+        if fields.iter().all(|field| irrefutable(field)) {
+            self.full.push(variant);
+        }
+        Some(())
+    }
+}
+
+/// 何にでも一致するパターンかどうか（定数かもしれない大文字で始まる名前は含めません）
+fn irrefutable(pat: &Pat) -> bool {
+    match pat {
+        Pat::Wild(_) | Pat::Rest(_) => true,
+        Pat::Ident(ident) => match &ident.subpat {
+            Some((_, subpat)) => irrefutable(subpat),
+            None => !ident.ident.to_string().starts_with(char::is_uppercase),
+        },
+        Pat::Tuple(tuple) => tuple.elems.iter().all(irrefutable),
+        Pat::Reference(reference) => irrefutable(&reference.pat),
+        Pat::Paren(paren) => irrefutable(&paren.pat),
+        Pat::Type(pat_type) => irrefutable(&pat_type.pat),
+        _ => false,
+    }
+}
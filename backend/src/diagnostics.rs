@@ -0,0 +1,283 @@
+//! 構文エラーの報告
+//! synのエラーを、位置（行・桁）と該当箇所の抜粋（コードフレーム）を持つErrorノードに変換します。
+
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
+
+use crate::model::{Diagnostic, ErrorKind, PadNode};
+use crate::width::char_width;
+
+/// コードフレームの各行に表示するソースの最大の桁数
+const MAX_FRAME_WIDTH: usize = 80;
+/// タブを展開する桁数
+const TAB_WIDTH: usize = 4;
+
+/// synの構文エラーをErrorノードに変換します
+/// `line` と `column` はどちらも1始まり（桁は文字数）です。
+pub fn parse_error(code: &str, error: &syn::Error) -> PadNode {
+    error_node(diagnostic(code, error))
+}
+
+/// 構文エラー1件分の情報をErrorノードにします
+pub fn error_node(diagnostic: Diagnostic) -> PadNode {
+    PadNode::Error {
+        message: diagnostic.message,
+        line: Some(diagnostic.line),
+        column: Some(diagnostic.column),
+        frame: diagnostic.frame,
+        byte_offset: None,
+        kind: Some(diagnostic.kind),
+        meta: Default::default(),
+    }
+}
+
+/// synのエラー（複数のエラーをまとめたものの場合は先頭）を、位置とコードフレーム付きの情報にします
+fn diagnostic(code: &str, error: &syn::Error) -> Diagnostic {
+    let start = error.span().start();
+    Diagnostic {
+        message: format!("Parse error: {}", error),
+        line: start.line,
+        column: start.column + 1,
+        kind: ErrorKind::Syntax,
+        frame: code_frame(code, start.line, start.column),
+    }
+}
+
+/// synのエラーに含まれるすべてのエラーを、それぞれ位置付きの情報にします
+pub fn diagnostics(code: &str, error: syn::Error) -> Vec<Diagnostic> {
+    error.into_iter().map(|error| diagnostic(code, &error)).collect()
+}
+
+/// UTF-8として不正なバイト列の入力を、最初の不正なバイトの位置を持つErrorノードにします
+/// 行・桁はその直前までの（正しい）部分から求め、コードフレームでは不正なバイトを U+FFFD で表示します。
+pub fn invalid_utf8(bytes: &[u8], error: std::str::Utf8Error) -> PadNode {
+    let offset = error.valid_up_to();
+    let valid = std::str::from_utf8(&bytes[..offset]).unwrap_or_default();
+    let line = valid.matches('\n').count() + 1;
+    let column = valid.rsplit('\n').next().unwrap_or_default().chars().count();
+    PadNode::Error {
+        message: format!("Invalid UTF-8 at byte offset {}", offset),
+        line: Some(line),
+        column: Some(column + 1),
+        frame: code_frame(&String::from_utf8_lossy(bytes), line, column),
+        byte_offset: Some(offset),
+        kind: Some(ErrorKind::Encoding),
+        meta: Default::default(),
+    }
+}
+
+/// 括弧の入れ子と `else if` の連鎖の深さが `max_depth` を超える場合に、超えた位置の情報を返します
+/// 構文解析・変換はどちらも入れ子の深さだけ再帰するため、スタックを使い切る前にここで打ち切ります。
+/// 字句解析は入れ子の深さによらず再帰しないため、構文解析の前に調べられます。`else if` の連鎖は `;` か括弧の終わりまで数えます。
+pub fn nesting_too_deep(code: &str, max_depth: usize) -> Option<Diagnostic> {
+    let tokens: TokenStream = code.parse().ok()?;
+    // 各階層の残りのトークンと、その階層で続いている `else if` の数
+    let mut stack = vec![(tokens.into_iter(), 0usize)];
+    let mut after_else = false;
+    while let Some((rest, _)) = stack.last_mut() {
+        let Some(token) = rest.next() else {
+            stack.pop();
+            after_else = false;
+            continue;
+        };
+        let span = match token {
+            TokenTree::Group(group) => {
+                stack.push((group.stream().into_iter(), 0));
+                after_else = false;
+                group.span_open()
+            }
+            TokenTree::Ident(ident) => {
+                let chained = after_else && ident == "if";
+                after_else = ident == "else";
+                if !chained {
+                    continue;
+                }
+                if let Some((_, chain)) = stack.last_mut() {
+                    *chain += 1;
+                }
+                ident.span()
+            }
+            TokenTree::Punct(punct) => {
+                if punct.as_char() == ';'
+                    && let Some((_, chain)) = stack.last_mut()
+                {
+                    *chain = 0;
+                }
+                after_else = false;
+                continue;
+            }
+            TokenTree::Literal(_) => {
+                after_else = false;
+                continue;
+            }
+        };
+        let depth = stack.len() - 1 + stack.iter().map(|(_, chain)| chain).sum::<usize>();
+        if depth > max_depth {
+            let start = span.start();
+            return Some(Diagnostic {
+                message: format!("Nesting is deeper than {} levels; the code was not converted", max_depth),
+                line: start.line,
+                column: start.column + 1,
+                kind: ErrorKind::Nesting,
+                frame: code_frame(code, start.line, start.column),
+            });
+        }
+    }
+    None
+}
+
+/// ファイル全体を字句解析し、トップレベルのアイテムごとのトークン列に分けます
+/// アイテムは、トップレベルの `;` か、`=` を含まないアイテムの `{ ... }` で終わるものとします
+/// （`const X: T = T { .. };` のような初期化式の波括弧では区切りません）。
+/// 括弧の対応が取れていないなど、字句解析ができない場合は None を返します。
+pub fn split_items(code: &str) -> Option<Vec<TokenStream>> {
+    let tokens: TokenStream = code.parse().ok()?;
+    let mut items = Vec::new();
+    let mut current: Vec<TokenTree> = Vec::new();
+    let mut has_eq = false;
+    for token in tokens {
+        let ends = match &token {
+            TokenTree::Punct(punct) if punct.as_char() == ';' => true,
+            TokenTree::Punct(punct) => {
+                has_eq |= punct.as_char() == '=';
+                false
+            }
+            TokenTree::Group(group) => group.delimiter() == Delimiter::Brace && !has_eq,
+            _ => false,
+        };
+        current.push(token);
+        // 内部属性（`#![allow(...)]`）はアイテムではないため読み飛ばします
+        if let [TokenTree::Punct(hash), TokenTree::Punct(bang), TokenTree::Group(group)] = current.as_slice()
+            && hash.as_char() == '#'
+            && bang.as_char() == '!'
+            && group.delimiter() == Delimiter::Bracket
+        {
+            current.clear();
+            continue;
+        }
+        if ends {
+            items.push(current.drain(..).collect());
+            has_eq = false;
+        }
+    }
+    if !current.is_empty() {
+        items.push(current.into_iter().collect());
+    }
+    Some(items)
+}
+
+/// エラー位置の行と前後1行ずつを、行番号と桁の位置を示す `^` 付きで抜き出します
+/// ```text
+///   1 | fn main() {
+/// > 2 |     let x = 1
+///     |              ^
+///   3 | }
+/// ```
+/// 位置（行は1始まり、桁は0始まりの文字数）がソースの範囲外の場合は None を返します。
+pub fn code_frame(code: &str, line: usize, column: usize) -> Option<String> {
+    let lines: Vec<&str> = code.split('\n').map(|l| l.strip_suffix('\r').unwrap_or(l)).collect();
+    let target = lines.get(line.checked_sub(1)?)?;
+    let first = line.saturating_sub(1).max(1);
+    let last = (line + 1).min(lines.len());
+    let number_width = last.to_string().len();
+    // 長い行はエラー位置が見えるように表示する範囲をずらし、すべての行で同じ範囲を切り出します
+    let caret = expanded_width(target, column);
+    let start = caret.saturating_sub(MAX_FRAME_WIDTH * 3 / 4);
+    let mut frame = String::new();
+    for number in first..=last {
+        let marker = if number == line { '>' } else { ' ' };
+        let text = clip(lines[number - 1], start);
+        frame += &format!("{} {:>width$} | {}\n", marker, number, text, width = number_width);
+        if number == line {
+            let offset = if start > 0 { 1 } else { 0 };
+            frame += &format!("  {:>width$} | {}^\n", "", " ".repeat(caret - start + offset), width = number_width);
+        }
+    }
+    Some(frame.trim_end_matches('\n').to_string())
+}
+
+/// 行の先頭から `chars` 文字の表示幅（タブは次のタブ位置まで、全角文字は2桁）
+fn expanded_width(line: &str, chars: usize) -> usize {
+    line.chars().take(chars).fold(0, |width, c| width + display_char_width(c, width))
+}
+
+fn display_char_width(c: char, at: usize) -> usize {
+    if c == '\t' { TAB_WIDTH - at % TAB_WIDTH } else { char_width(c) }
+}
+
+/// 行をタブを展開した上で、表示幅で `start` 桁目から `MAX_FRAME_WIDTH` 桁分だけ切り出します
+/// 切り取った側には `…` を付けます。
+fn clip(line: &str, start: usize) -> String {
+    let mut text = String::new();
+    let mut width = 0;
+    let mut clipped = false;
+    for c in line.chars() {
+        let w = display_char_width(c, width);
+        if width >= start {
+            if width + w > start + MAX_FRAME_WIDTH {
+                clipped = true;
+                break;
+            }
+            if c == '\t' {
+                text += &" ".repeat(w);
+            } else {
+                text.push(c);
+            }
+        }
+        width += w;
+    }
+    let mut text = text.trim_end().to_string();
+    if start > 0 && !line.is_empty() {
+        text.insert(0, '…');
+    }
+    if clipped {
+        text.push('…');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_on_the_first_line() {
+        let frame = code_frame("fn main( {\n}\n", 1, 8).unwrap();
+        assert_eq!(frame, "> 1 | fn main( {\n    |         ^\n  2 | }");
+    }
+
+    #[test]
+    fn frame_in_the_middle_positions_caret_after_tabs_and_wide_chars() {
+        let code = "fn main() {\n\tlet 値 = 1 2;\n}\n";
+        let frame = code_frame(code, 2, 11).unwrap();
+        assert_eq!(frame, "  1 | fn main() {\n> 2 |     let 値 = 1 2;\n    |                ^\n  3 | }");
+    }
+
+    #[test]
+    fn frame_on_the_last_line_without_trailing_newline() {
+        let code = "fn a() {}\nfn b() {}\nfn c(";
+        let frame = code_frame(code, 3, 5).unwrap();
+        assert_eq!(frame, "  2 | fn b() {}\n> 3 | fn c(\n    |      ^");
+        assert!(code_frame(code, 9, 0).is_none());
+    }
+
+    #[test]
+    fn long_lines_are_clipped_around_the_caret() {
+        let line = format!("let x = {};", "a + ".repeat(40));
+        let frame = code_frame(&line, 1, line.chars().count() - 1).unwrap();
+        let shown = frame.lines().next().unwrap();
+        assert!(shown.starts_with("> 1 | …"));
+        assert!(shown.ends_with(';'));
+        let caret = frame.lines().nth(1).unwrap();
+        assert_eq!(caret.chars().count(), shown.chars().count());
+    }
+
+    #[test]
+    fn items_are_split_at_top_level_boundaries() {
+        let code = "#![allow(dead_code)]\nuse a::b;\n#[derive(Debug)]\nstruct S { x: i32 }\nconst C: S = S { x: 1 };\nfn f() { g(); }\nmacro_rules! m { () => {} }";
+        let items: Vec<String> = split_items(code).unwrap().iter().map(|t| t.to_string()).collect();
+        assert_eq!(items.len(), 5);
+        assert!(items[1].starts_with("# [derive (Debug)] struct S"));
+        assert!(items[2].ends_with("} ;"));
+        assert!(split_items("fn f( {").is_none());
+    }
+}
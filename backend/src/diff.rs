@@ -0,0 +1,248 @@
+//! 2つの版のツリーの差分
+//! リファクタリングで関数の構造がどう変わったかを示すため、変更前と変更後のツリーを突き合わせ、
+//! 変更後のツリーに各ノードの状態（`diff`）を付けます。削除されたノードは変更前の位置に残します。
+//! 子ノードの並びのうち、関数・implブロックなど名前を持つアイテムは、並び順によらず同じ名前（ラベル）のもの同士を対応付けます。
+//! 残りのノードは、種別と表示するテキストが一致するノードを最長共通部分列で対応付けます。
+//! 対応しなかったノードのうち、同じ位置にある同じ種別のノード同士は変更（`modified`）とみなし、中身も突き合わせます。
+//! 変更後の版のノードはidをそのまま使い、削除されたノードのidには `removed-` を付けて重複を避けます。
+
+use std::collections::HashMap;
+
+use crate::model::{BlockKind, DiffStatus, MatchArm, PadNode};
+
+/// 変更前のツリーと変更後のツリーを突き合わせ、差分の状態を付けた変更後のツリーを返します
+/// どちらのツリーもidを割り当てた後に呼び出してください。
+pub fn diff_trees(old: PadNode, new: PadNode) -> PadNode {
+    if same_kind(&old, &new) { diff_node(old, new) } else { added(new) }
+}
+
+/// ノードの対応付けに使う値（種別と表示するテキスト）
+fn key(node: &PadNode) -> (&'static str, String) {
+    (node.kind_name(), node.display_text().unwrap_or_default().to_string())
+}
+
+/// 名前で対応付けるアイテムのBlockの名前（ラベル）
+fn item_name(node: &PadNode) -> Option<&str> {
+    match node {
+        PadNode::Block {
+            kind:
+                BlockKind::Function
+                | BlockKind::Impl
+                | BlockKind::Trait
+                | BlockKind::Module
+                | BlockKind::Extern
+                | BlockKind::Macro
+                | BlockKind::File,
+            label,
+            ..
+        } => Some(label),
+        _ => None,
+    }
+}
+
+fn same_kind(old: &PadNode, new: &PadNode) -> bool {
+    old.kind_name() == new.kind_name()
+}
+
+/// 部分木のすべてのノードに状態を付けます
+fn mark(node: &mut PadNode, status: DiffStatus) {
+    node.meta_mut().diff = Some(status);
+    for child in node.children_mut() {
+        mark(child, status);
+    }
+}
+
+fn added(mut node: PadNode) -> PadNode {
+    mark(&mut node, DiffStatus::Added);
+    node
+}
+
+fn removed(mut node: PadNode) -> PadNode {
+    mark(&mut node, DiffStatus::Removed);
+    retag(&mut node);
+    node
+}
+
+/// 削除されたノードのidと、そのidへの参照に `removed-` を付けます
+fn retag(node: &mut PadNode) {
+    if let PadNode::Break { target_id: Some(id), .. } | PadNode::Continue { target_id: Some(id), .. } = node {
+        *id = format!("removed-{}", id);
+    }
+    let meta = node.meta_mut();
+    meta.id = format!("removed-{}", meta.id);
+    for id in meta.returned_by.iter_mut().chain(&mut meta.call_targets) {
+        *id = format!("removed-{}", id);
+    }
+    for child in node.children_mut() {
+        retag(child);
+    }
+}
+
+/// 同じ種別の2つのノードを突き合わせます
+fn diff_node(mut old: PadNode, mut new: PadNode) -> PadNode {
+    let same_text = key(&old) == key(&new);
+    match (&mut old, &mut new) {
+        (
+            PadNode::Sequence { children: old_children, .. }
+            | PadNode::Document { children: old_children, .. }
+            | PadNode::Block { children: old_children, .. },
+            PadNode::Sequence { children, .. } | PadNode::Document { children, .. } | PadNode::Block { children, .. },
+        ) => {
+            *children = diff_children(std::mem::take(old_children), std::mem::take(children));
+        }
+        (PadNode::If { then_block: old_then, else_block: old_else, .. }, PadNode::If { then_block, else_block, .. }) => {
+            **then_block = diff_node(take_node(old_then), take_node(then_block));
+            *else_block = match (old_else.take(), else_block.take()) {
+                (Some(old_else), Some(new_else)) => Some(Box::new(diff_node(*old_else, *new_else))),
+                (None, Some(new_else)) => Some(Box::new(added(*new_else))),
+                (Some(old_else), None) => Some(Box::new(removed(*old_else))),
+                (None, None) => None,
+            };
+        }
+        (PadNode::Loop { body: old_body, .. }, PadNode::Loop { body, .. }) => {
+            **body = diff_node(take_node(old_body), take_node(body));
+        }
+        (PadNode::Match { arms: old_arms, .. }, PadNode::Match { arms, .. }) => {
+            *arms = diff_list(
+                std::mem::take(old_arms),
+                std::mem::take(arms),
+                |arm| (arm.pattern.clone(), arm.guard.clone()),
+                |_, _| true,
+                |old, new| MatchArm { body: diff_node(old.body, new.body), ..new },
+                |old| MatchArm { body: removed(old.body), ..old },
+                |new| MatchArm { body: added(new.body), ..new },
+            );
+        }
+        _ => {}
+    }
+    let unchanged = same_text && new.children().iter().all(|child| child.meta().diff == Some(DiffStatus::Unchanged));
+    new.meta_mut().diff = Some(if unchanged { DiffStatus::Unchanged } else { DiffStatus::Modified });
+    new
+}
+
+/// 箱の中のノードを取り出し、空のSequenceを残します
+fn take_node(node: &mut PadNode) -> PadNode {
+    std::mem::replace(node, PadNode::sequence(Vec::new()))
+}
+
+/// 子ノードの並びを突き合わせます
+/// どちらの版にも1つずつある名前のアイテムは名前で対応付け、変更後の位置に置きます。
+/// 残りのノードは `diff_list` で突き合わせ、変更後の並びの中の元の位置に戻します（削除されたノードはその直前に置きます）。
+fn diff_children(old: Vec<PadNode>, new: Vec<PadNode>) -> Vec<PadNode> {
+    let mut counts: HashMap<String, (usize, usize)> = HashMap::new();
+    for name in old.iter().filter_map(item_name) {
+        counts.entry(name.to_string()).or_default().0 += 1;
+    }
+    for name in new.iter().filter_map(item_name) {
+        counts.entry(name.to_string()).or_default().1 += 1;
+    }
+    let paired = |node: &PadNode| item_name(node).is_some_and(|name| counts[name] == (1, 1));
+
+    let mut named_old = HashMap::new();
+    let mut rest_old = Vec::new();
+    for node in old {
+        if paired(&node) {
+            named_old.insert(item_name(&node).unwrap_or_default().to_string(), node);
+        } else {
+            rest_old.push(node);
+        }
+    }
+    let order: Vec<bool> = new.iter().map(paired).collect();
+    let (named_new, rest_new): (Vec<PadNode>, Vec<PadNode>) = new.into_iter().partition(paired);
+    let mut named_new = named_new.into_iter();
+    let mut rest = diff_list(rest_old, rest_new, key, same_kind, diff_node, removed, added).into_iter().peekable();
+
+    let mut result = Vec::new();
+    for is_named in order {
+        if is_named {
+            let Some(node) = named_new.next() else { break };
+            let old = named_old.remove(item_name(&node).unwrap_or_default());
+            result.push(match old {
+                Some(old) => diff_node(old, node),
+                None => added(node),
+            });
+        } else {
+            while let Some(node) = rest.next_if(|node| node.meta().diff == Some(DiffStatus::Removed)) {
+                result.push(node);
+            }
+            result.extend(rest.next());
+        }
+    }
+    result.extend(rest);
+    result
+}
+
+/// 子ノード（またはmatchのアーム）の並びを突き合わせます
+/// 対応付けられなかった区間では、同じ位置の要素同士を（`similar` の場合に）`pair` で突き合わせ、残りを削除・追加とします。
+fn diff_list<T, K: PartialEq>(
+    old: Vec<T>,
+    new: Vec<T>,
+    key: impl Fn(&T) -> K,
+    similar: impl Fn(&T, &T) -> bool,
+    pair: impl Fn(T, T) -> T,
+    removed: impl Fn(T) -> T,
+    added: impl Fn(T) -> T,
+) -> Vec<T> {
+    let matches = common_subsequence(&old, &new, &key);
+    let mut result = Vec::new();
+    let mut old = old.into_iter().enumerate().peekable();
+    let mut new = new.into_iter().enumerate().peekable();
+    for (old_index, new_index) in matches.into_iter().chain(std::iter::once((usize::MAX, usize::MAX))) {
+        let mut pending_old = Vec::new();
+        while let Some((_, o)) = old.next_if(|(index, _)| *index < old_index) {
+            pending_old.push(o);
+        }
+        let mut pending_new = Vec::new();
+        while let Some((_, n)) = new.next_if(|(index, _)| *index < new_index) {
+            pending_new.push(n);
+        }
+        let mut pending_old = pending_old.into_iter();
+        let mut pending_new = pending_new.into_iter();
+        loop {
+            match (pending_old.next(), pending_new.next()) {
+                (Some(o), Some(n)) if similar(&o, &n) => result.push(pair(o, n)),
+                (Some(o), Some(n)) => {
+                    result.push(removed(o));
+                    result.push(added(n));
+                }
+                (Some(o), None) => result.push(removed(o)),
+                (None, Some(n)) => result.push(added(n)),
+                (None, None) => break,
+            }
+        }
+        if let (Some((_, o)), Some((_, n))) = (old.next(), new.next()) {
+            result.push(pair(o, n));
+        }
+    }
+    result
+}
+
+/// キーが一致する要素の最長共通部分列を、(変更前の位置, 変更後の位置) の列で返します
+fn common_subsequence<T, K: PartialEq>(old: &[T], new: &[T], key: &impl Fn(&T) -> K) -> Vec<(usize, usize)> {
+    let (n, m) = (old.len(), new.len());
+    // lengths[i][j]: old[i..] と new[j..] の最長共通部分列の長さ
+    let mut lengths = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i][j] = if key(&old[i]) == key(&new[j]) {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut pairs = Vec::new();
+    while i < n && j < m {
+        if key(&old[i]) == key(&new[j]) {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
@@ -0,0 +1,85 @@
+//! 文書（Document）ルートの作成
+//! `document` オプション指定時に、ルートのSequenceを、ファイル名や作成日時などの情報を持つDocumentノードに置き換えます。
+
+use crate::model::PadNode;
+use crate::options::ParseOptions;
+
+/// 出力を作成したプログラムの名前
+const GENERATED_BY: &str = "PAD.js backend";
+
+/// ルートのSequenceをDocumentノードに置き換えます（ルートがErrorの場合などはそのまま返します）
+/// タイトルは `title` オプション、`source_name` のファイル名部分、"PAD" の順に決めます。
+pub fn into_document(root: PadNode, options: &ParseOptions) -> PadNode {
+    let PadNode::Sequence { children, meta, info, .. } = root else { return root };
+    let title = options
+        .title
+        .clone()
+        .or_else(|| {
+            let name = options.source_name.as_deref()?;
+            Some(name.rsplit(['/', '\\']).next().unwrap_or(name).to_string())
+        })
+        .unwrap_or_else(|| "PAD".to_string());
+    PadNode::Document {
+        title,
+        source_name: options.source_name.clone(),
+        generated_by: GENERATED_BY.to_string(),
+        backend_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: (!options.deterministic).then(now_iso8601),
+        children,
+        meta,
+        info,
+    }
+}
+
+/// 現在時刻（UTC）をISO 8601形式（`2024-05-01T12:34:56Z`）で返します
+fn now_iso8601() -> String {
+    format_unix_seconds(unix_seconds())
+}
+
+/// wasmでは `profile::now_ms` がUNIX時刻（`Date.now()`）のミリ秒を返します
+#[cfg(target_arch = "wasm32")]
+fn unix_seconds() -> u64 {
+    (crate::profile::now_ms() / 1000.0) as u64
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn unix_seconds() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// UNIX時刻（秒）をISO 8601形式の文字列にします
+fn format_unix_seconds(seconds: u64) -> String {
+    let days = (seconds / 86400) as i64;
+    let rest = seconds % 86400;
+    // 1970-01-01 からの日数を年月日に変換します（Howard Hinnant の civil_from_days）
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unix_seconds_are_formatted_as_utc() {
+        assert_eq!(format_unix_seconds(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_unix_seconds(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_unix_seconds(1_714_566_896), "2024-05-01T12:34:56Z");
+    }
+}
@@ -0,0 +1,108 @@
+//! Graphviz（DOT）への書き出し
+//! `dot` コマンドでオフラインに描画できるよう、ノードを頂点、順次・分岐・反復のつながりを有向辺にします。
+//! Blockはクラスタ（`subgraph cluster_...`）として書き出し、条件はひし形、反復は六角形の頂点にします。
+
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use crate::graphml::{Edge, collect_edges};
+use crate::model::PadNode;
+use crate::visit::{VisitContext, Visitor, walk};
+
+/// DOTの引用符で囲んだ文字列として書き出せるよう、特別な意味を持つ文字をエスケープします
+fn quote(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// 頂点とクラスタを書き出すビジター
+struct NodeWriter {
+    out: String,
+    indent: usize,
+}
+
+impl NodeWriter {
+    fn line(&mut self, text: &str) {
+        let _ = writeln!(self.out, "{:width$}{}", "", text, width = self.indent * 2);
+    }
+}
+
+impl Visitor for NodeWriter {
+    fn enter(&mut self, node: &PadNode, _ctx: &VisitContext) -> bool {
+        let id = &node.meta().id;
+        let label = quote(node.display_text().unwrap_or_default());
+        let shape = match node {
+            PadNode::Sequence { .. } | PadNode::Document { .. } => return true,
+            PadNode::Block { .. } => {
+                self.line(&format!("subgraph {} {{", quote(&format!("cluster_{}", id))));
+                self.indent += 1;
+                self.line(&format!("label={};", label));
+                return true;
+            }
+            PadNode::If { .. } | PadNode::Match { .. } => "diamond",
+            PadNode::Loop { .. } => "hexagon",
+            _ => "box",
+        };
+        self.line(&format!("{} [label={}, shape={}];", quote(id), label, shape));
+        true
+    }
+
+    fn leave(&mut self, node: &PadNode, _ctx: &VisitContext) {
+        if let PadNode::Block { .. } = node {
+            self.indent -= 1;
+            self.line("}");
+        }
+    }
+}
+
+/// Blockのidを集めます（クラスタは辺の端点にできないため、Blockにつながる辺は書き出しません）
+fn clusters<'a>(node: &'a PadNode, out: &mut HashSet<&'a str>) {
+    if let PadNode::Block { .. } = node {
+        out.insert(&node.meta().id);
+    }
+    for child in node.children() {
+        clusters(child, out);
+    }
+}
+
+/// 辺1本分の行（ループの末尾から先頭へ戻る辺は破線にします）
+fn edge_line(edge: &Edge) -> String {
+    let mut attrs = Vec::new();
+    match (edge.kind, edge.branch, &edge.pattern) {
+        ("back", ..) => attrs.push("style=dashed".to_string()),
+        (_, Some(branch), _) => attrs.push(format!("label={}", quote(if branch { "true" } else { "false" }))),
+        (_, _, Some(pattern)) => attrs.push(format!("label={}", quote(pattern))),
+        _ => {}
+    }
+    let attrs = if attrs.is_empty() { String::new() } else { format!(" [{}]", attrs.join(", ")) };
+    format!("{} -> {}{};", quote(edge.source), quote(edge.target), attrs)
+}
+
+/// ツリー全体をDOTの有向グラフ（`digraph pad`）にします
+pub fn to_dot(root: &PadNode) -> String {
+    let mut writer = NodeWriter { out: String::new(), indent: 1 };
+    walk(root, &mut writer);
+    let mut blocks = HashSet::new();
+    clusters(root, &mut blocks);
+    let mut edges = Vec::new();
+    collect_edges(root, &mut edges);
+
+    let mut out = String::from("digraph pad {\n  node [fontname=\"monospace\"];\n");
+    out.push_str(&writer.out);
+    let edges = edges.iter().filter(|edge| !blocks.contains(edge.source) && !blocks.contains(edge.target));
+    for edge in edges {
+        let _ = writeln!(out, "  {}", edge_line(edge));
+    }
+    out.push_str("}\n");
+    out
+}
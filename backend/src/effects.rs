@@ -0,0 +1,208 @@
+//! ループが変更する変数の集計
+//! ループ本体（whileの場合は条件も）の代入・複合代入・`&mut` での借用・変更を伴うメソッド呼び出しを構文から集め、
+//! 各反復で変わる変数の一覧にします。ループの中で宣言された変数は含めません。
+//! 同じ名前の宣言が隠すのは、宣言より後の、同じスコープか内側のスコープでの変更だけです。
+//! 型の情報は使わないため、メソッドが変更を伴うかどうかは標準ライブラリのよく使うメソッド名で判断します。
+
+use syn::visit::Visit;
+use syn::{BinOp, Expr, Member, Pat};
+
+/// レシーバを変更する標準ライブラリのメソッド（`Vec`、`String`、`HashMap`、`Iterator`、`Option` など）
+const MUTATING_METHODS: &[&str] = &[
+    "append", "clear", "dedup", "drain", "entry", "extend", "fill", "get_mut", "insert", "iter_mut", "next", "pop",
+    "pop_back", "pop_front", "push", "push_back", "push_front", "push_str", "remove", "replace", "resize", "retain",
+    "reverse", "sort", "sort_by", "sort_by_key", "sort_unstable", "split_off", "swap", "swap_remove", "take", "truncate",
+];
+
+/// 変更と宣言を集めるビジター
+struct Effects {
+    /// 変更された変数（最初に変更された順、重複なし）
+    modified: Vec<String>,
+    /// ループの中のスコープごとの、その位置までに宣言された変数（最後が最も内側）
+    scopes: Vec<Vec<String>>,
+}
+
+impl Default for Effects {
+    fn default() -> Self {
+        Effects { modified: Vec::new(), scopes: vec![Vec::new()] }
+    }
+}
+
+impl Effects {
+    fn modify(&mut self, place: &Expr) {
+        let Some(name) = place_name(place) else { return };
+        let variable = name.split('.').next().unwrap_or(&name);
+        if !self.scopes.iter().any(|scope| scope.iter().any(|declared| declared == variable)) && !self.modified.contains(&name) {
+            self.modified.push(name);
+        }
+    }
+
+    /// 内側のスコープの中で `visit` を呼び出します
+    fn scoped(&mut self, visit: impl FnOnce(&mut Self)) {
+        self.scopes.push(Vec::new());
+        visit(self);
+        self.scopes.pop();
+    }
+}
+
+impl<'ast> Visit<'ast> for Effects {
+    fn visit_pat_ident(&mut self, pat: &'ast syn::PatIdent) {
+        self.scopes.last_mut().unwrap().push(pat.ident.to_string());
+        syn::visit::visit_pat_ident(self, pat);
+    }
+
+    fn visit_block(&mut self, block: &'ast syn::Block) {
+        self.scoped(|effects| syn::visit::visit_block(effects, block));
+    }
+
+    // `let` の束縛は初期化式の後から有効です
+    fn visit_local(&mut self, local: &'ast syn::Local) {
+        if let Some(init) = &local.init {
+            self.visit_expr(&init.expr);
+            if let Some((_, diverge)) = &init.diverge {
+                self.visit_expr(diverge);
+            }
+        }
+        self.visit_pat(&local.pat);
+    }
+
+    fn visit_expr_closure(&mut self, closure: &'ast syn::ExprClosure) {
+        self.scoped(|effects| syn::visit::visit_expr_closure(effects, closure));
+    }
+
+    fn visit_arm(&mut self, arm: &'ast syn::Arm) {
+        self.scoped(|effects| syn::visit::visit_arm(effects, arm));
+    }
+
+    // `if let` の束縛はthen節の中だけで有効です
+    fn visit_expr_if(&mut self, expr: &'ast syn::ExprIf) {
+        self.scoped(|effects| {
+            effects.visit_expr(&expr.cond);
+            effects.visit_block(&expr.then_branch);
+        });
+        if let Some((_, else_branch)) = &expr.else_branch {
+            self.visit_expr(else_branch);
+        }
+    }
+
+    fn visit_expr_while(&mut self, expr: &'ast syn::ExprWhile) {
+        self.scoped(|effects| syn::visit::visit_expr_while(effects, expr));
+    }
+
+    fn visit_expr_for_loop(&mut self, expr: &'ast syn::ExprForLoop) {
+        self.visit_expr(&expr.expr);
+        self.scoped(|effects| {
+            effects.visit_pat(&expr.pat);
+            effects.visit_block(&expr.body);
+        });
+    }
+
+    fn visit_expr_assign(&mut self, expr: &'ast syn::ExprAssign) {
+        self.modify(&expr.left);
+        syn::visit::visit_expr_assign(self, expr);
+    }
+
+    fn visit_expr_binary(&mut self, expr: &'ast syn::ExprBinary) {
+        if is_compound_assign(&expr.op) {
+            self.modify(&expr.left);
+        }
+        syn::visit::visit_expr_binary(self, expr);
+    }
+
+    fn visit_expr_reference(&mut self, expr: &'ast syn::ExprReference) {
+        if expr.mutability.is_some() {
+            self.modify(&expr.expr);
+        }
+        syn::visit::visit_expr_reference(self, expr);
+    }
+
+    fn visit_expr_method_call(&mut self, expr: &'ast syn::ExprMethodCall) {
+        if MUTATING_METHODS.contains(&expr.method.to_string().as_str()) {
+            self.modify(&expr.receiver);
+        }
+        syn::visit::visit_expr_method_call(self, expr);
+    }
+
+    // ループの中で定義された関数などのアイテムは、ループの処理ではないため調べません
+    fn visit_item(&mut self, _: &'ast syn::Item) {}
+}
+
+/// 複合代入演算子（`+=` など）かどうか
+fn is_compound_assign(op: &BinOp) -> bool {
+    matches!(
+        op,
+        BinOp::AddAssign(_)
+            | BinOp::SubAssign(_)
+            | BinOp::MulAssign(_)
+            | BinOp::DivAssign(_)
+            | BinOp::RemAssign(_)
+            | BinOp::BitXorAssign(_)
+            | BinOp::BitAndAssign(_)
+            | BinOp::BitOrAssign(_)
+            | BinOp::ShlAssign(_)
+            | BinOp::ShrAssign(_)
+    )
+}
+
+/// 変更される場所の変数名（`v[i]` は `v`、`*p` は `p`、`self.items.len` は `self.items`）
+/// 呼び出しの結果など、変数に結び付けられない場所の場合は None を返します。
+fn place_name(place: &Expr) -> Option<String> {
+    match place {
+        Expr::Path(path) => path.path.get_ident().map(|ident| ident.to_string()),
+        Expr::Field(field) => match (&*field.base, &field.member) {
+            (Expr::Path(base), Member::Named(member)) if base.path.is_ident("self") => Some(format!("self.{}", member)),
+            (base, _) => place_name(base),
+        },
+        Expr::Index(index) => place_name(&index.expr),
+        Expr::Paren(paren) => place_name(&paren.expr),
+        Expr::Unary(unary) if matches!(unary.op, syn::UnOp::Deref(_)) => place_name(&unary.expr),
+        _ => None,
+    }
+}
+
+/// パターンが束縛する変数名（出現順）
+pub fn pattern_bindings(pat: &Pat) -> Vec<String> {
+    let mut effects = Effects::default();
+    effects.visit_pat(pat);
+    effects.scopes.pop().unwrap_or_default()
+}
+
+/// ループの条件（whileの場合）と本体が変更する変数を返します（ループの中・パターンで宣言された変数を除きます）
+pub fn modified_variables(cond: Option<&Expr>, pat: Option<&Pat>, body: &syn::Block) -> Vec<String> {
+    let mut effects = Effects::default();
+    if let Some(pat) = pat {
+        effects.visit_pat(pat);
+    }
+    if let Some(cond) = cond {
+        effects.visit_expr(cond);
+    }
+    effects.visit_block(body);
+    effects.modified
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modified(code: &str) -> Vec<String> {
+        let block: syn::Block = syn::parse_str(code).unwrap();
+        modified_variables(None, None, &block)
+    }
+
+    #[test]
+    fn writes_are_reduced_to_variable_names() {
+        assert_eq!(modified("{ v[i] = 0; *p += 1; self.items.count -= 1; }"), ["v", "p", "self.items"]);
+        assert_eq!(modified("{ out.push(x); fill(&mut buf); let n = v.len(); }"), ["out", "buf"]);
+        assert_eq!(modified("{ let mut t = 0; t += 1; f().x = 1; }"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn shadowing_hides_only_later_writes_in_its_scope() {
+        assert_eq!(modified("{ total += x; if x > 3 { let total = 1; } }"), ["total"]);
+        assert_eq!(modified("{ sum += y; let sum = 0; }"), ["sum"]);
+        assert_eq!(modified("{ if a { let mut n = 0; n += 1; } n += 2; }"), ["n"]);
+        assert_eq!(modified("{ v.iter().for_each(|v| drop(v)); v.push(1); }"), ["v"]);
+        assert_eq!(modified("{ let mut n = n; n += 1; }"), Vec::<String>::new());
+        assert_eq!(modified("{ let c = { c += 1; c }; }"), ["c"]);
+    }
+}
@@ -0,0 +1,91 @@
+//! 解析結果のエンベロープ（`{ok, tree, errors}`）
+//! エラーをErrorノードのメッセージから読み取らなくて済むよう、成否と、位置・種類を持つエラーの一覧を分けて返します。
+
+use serde::Serialize;
+
+use crate::model::{ErrorKind, PadNode};
+
+/// エラー1件分（位置はどちらも1始まり。位置のないエラーでは省略します）
+#[derive(Debug, Serialize)]
+pub struct EnvelopeError {
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+    pub kind: ErrorKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frame: Option<String>,
+}
+
+/// 解析結果
+#[derive(Debug, Serialize)]
+pub struct Envelope {
+    /// エラーが1件もない場合に true
+    pub ok: bool,
+    /// 図のツリー（入力全体を解析できなかった場合は省略します。`recover` で一部を図にした場合は付きます）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tree: Option<PadNode>,
+    pub errors: Vec<EnvelopeError>,
+}
+
+impl Envelope {
+    /// 解析オプションのJSONが不正な場合の結果
+    pub fn invalid_options(message: String) -> Self {
+        let error = EnvelopeError { message, line: None, column: None, kind: ErrorKind::Options, frame: None };
+        Envelope { ok: false, tree: None, errors: vec![error] }
+    }
+
+    /// 解析結果のツリーから、エラーの一覧を取り出します
+    /// ルートがErrorノードの場合はそれだけを、そうでない場合はルートの `errors` と、
+    /// そこに含まれないツリーの中のErrorノード（入れ子が深すぎて変換を打ち切った箇所など）を位置の順に並べます。
+    pub fn from_tree(tree: PadNode) -> Self {
+        if let PadNode::Error { .. } = tree {
+            let errors = error_of(&tree).into_iter().collect();
+            return Envelope { ok: false, tree: None, errors };
+        }
+        let mut errors: Vec<EnvelopeError> = match &tree {
+            PadNode::Sequence { info: Some(info), .. } | PadNode::Document { info: Some(info), .. } => info
+                .errors
+                .iter()
+                .map(|diagnostic| EnvelopeError {
+                    message: diagnostic.message.clone(),
+                    line: Some(diagnostic.line),
+                    column: Some(diagnostic.column),
+                    kind: diagnostic.kind,
+                    frame: diagnostic.frame.clone(),
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let recorded = !errors.is_empty();
+        collect(&tree, recorded, &mut errors);
+        errors.sort_by_key(|error| (error.line, error.column));
+        Envelope { ok: errors.is_empty(), tree: Some(tree), errors }
+    }
+}
+
+fn error_of(node: &PadNode) -> Option<EnvelopeError> {
+    match node {
+        PadNode::Error { message, line, column, frame, kind, .. } => Some(EnvelopeError {
+            message: message.clone(),
+            line: *line,
+            column: *column,
+            kind: kind.unwrap_or_default(),
+            frame: frame.clone(),
+        }),
+        _ => None,
+    }
+}
+
+/// ツリーの中のErrorノードを集めます（構文エラーがルートの `errors` に記録済みの場合、構文エラーのノードは除きます）
+fn collect(node: &PadNode, recorded: bool, errors: &mut Vec<EnvelopeError>) {
+    if let Some(error) = error_of(node)
+        && !(recorded && error.kind == ErrorKind::Syntax)
+    {
+        errors.push(error);
+    }
+    for child in node.children() {
+        collect(child, recorded, errors);
+    }
+}
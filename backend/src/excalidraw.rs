@@ -0,0 +1,160 @@
+//! Excalidrawのシーン（`.excalidraw`）への書き出し
+//! 図をホワイトボード上で編集できるよう、SVGと同じ配置の図形を、ラベルの文字列と矢印でつないだシーンにします。
+//! 要素のidはノードのidから作るため、同じ入力からは常に同じシーンになります。
+
+use serde_json::{Value, json};
+
+use crate::layout::{BOX_HEIGHT, CHAR_WIDTH, Connector, ConnectorKind, Layout, PADDING_X, Shape, ShapeKind, layout};
+use crate::model::PadNode;
+use crate::width::display_width;
+
+/// ラベルの文字の大きさ（px）
+const FONT_SIZE: f64 = 13.0;
+/// 行の高さ（文字の大きさに対する比）
+const LINE_HEIGHT: f64 = 1.25;
+/// 等幅フォント（Cascadia）を表すExcalidrawのフォント番号
+const FONT_FAMILY: u8 = 3;
+
+/// ツリー全体を1つのExcalidrawシーン（JSON文字列）にします
+pub fn to_excalidraw(root: &PadNode) -> String {
+    let layout = layout(root);
+    let mut elements = Vec::new();
+    for (index, shape) in layout.shapes.iter().enumerate() {
+        let text_id = format!("{}-label", shape.id);
+        if shape.kind == ShapeKind::Title {
+            elements.push(text(&text_id, shape, None));
+            continue;
+        }
+        let mut bound = vec![json!({ "id": text_id, "type": "text" })];
+        bound.extend(
+            layout
+                .connectors
+                .iter()
+                .enumerate()
+                .filter(|(_, c)| c.from == Some(index) || c.to == Some(index))
+                .map(|(i, c)| json!({ "id": arrow_id(&layout, i, c), "type": "arrow" })),
+        );
+        elements.push(container(shape, bound));
+        elements.push(text(&text_id, shape, Some(&shape.id)));
+    }
+    for (index, connector) in layout.connectors.iter().enumerate() {
+        elements.push(arrow(&layout, index, connector));
+    }
+    json!({
+        "type": "excalidraw",
+        "version": 2,
+        "source": "https://excalidraw.com",
+        "elements": elements,
+        "appState": { "gridSize": null, "viewBackgroundColor": "#ffffff" },
+        "files": {},
+    })
+    .to_string()
+}
+
+/// 線のid（両端の図形のidから作ります。終点の図形がない線は、線の番号で区別します）
+fn arrow_id(layout: &Layout, index: usize, connector: &Connector) -> String {
+    let kind = match connector.kind {
+        ConnectorKind::Nest => "nest",
+        ConnectorKind::Sequence => "seq",
+    };
+    let end = |shape: Option<usize>| shape.map(|i| layout.shapes[i].id.clone());
+    match (end(connector.from), end(connector.to)) {
+        (Some(from), Some(to)) => format!("{}-{}-{}", kind, from, to),
+        (from, _) => format!("{}-{}-{}", kind, from.unwrap_or_default(), index),
+    }
+}
+
+/// idから求めた乱数の種（Excalidrawは手描き風の線の揺らぎに使います）
+fn seed(id: &str) -> u32 {
+    // FNV-1a
+    id.bytes().fold(0x811c9dc5u32, |hash, b| (hash ^ b as u32).wrapping_mul(0x01000193)) & 0x7fff_ffff
+}
+
+/// すべての要素に共通する属性
+fn base(id: &str, kind: &str, x: usize, y: usize, width: f64, height: f64) -> Value {
+    json!({
+        "id": id,
+        "type": kind,
+        "x": x,
+        "y": y,
+        "width": width,
+        "height": height,
+        "angle": 0,
+        "strokeColor": "#1e1e1e",
+        "backgroundColor": "transparent",
+        "fillStyle": "solid",
+        "strokeWidth": 1,
+        "strokeStyle": "solid",
+        "roughness": 0,
+        "opacity": 100,
+        "groupIds": [],
+        "frameId": null,
+        "roundness": null,
+        "seed": seed(id),
+        "version": 1,
+        "versionNonce": seed(&format!("{}#nonce", id)),
+        "isDeleted": false,
+        "boundElements": null,
+        "updated": 1,
+        "link": null,
+        "locked": false,
+    })
+}
+
+/// ラベルを収める図形（選択は菱形、それ以外は長方形）
+fn container(shape: &Shape, bound: Vec<Value>) -> Value {
+    let kind = if shape.kind == ShapeKind::Selection { "diamond" } else { "rectangle" };
+    let mut element = base(&shape.id, kind, shape.x, shape.y, shape.width as f64, shape.height as f64);
+    element["boundElements"] = bound.into();
+    if shape.class == "block" {
+        element["roundness"] = json!({ "type": 3 });
+    }
+    if shape.class == "error" {
+        element["backgroundColor"] = "#ffdddd".into();
+    }
+    element
+}
+
+/// ラベルの文字列（`container` を指定した場合はその図形に結び付けます）
+fn text(id: &str, shape: &Shape, container: Option<&str>) -> Value {
+    let width = (display_width(&shape.label) * CHAR_WIDTH) as f64;
+    let height = FONT_SIZE * LINE_HEIGHT;
+    let y = shape.y + ((BOX_HEIGHT as f64 - height) / 2.0) as usize;
+    let mut element = base(id, "text", shape.x + PADDING_X, y, width, height);
+    let extra = json!({
+        "text": shape.label,
+        "originalText": shape.label,
+        "fontSize": FONT_SIZE,
+        "fontFamily": FONT_FAMILY,
+        "textAlign": if container.is_some() { "center" } else { "left" },
+        "verticalAlign": "middle",
+        "containerId": container,
+        "lineHeight": LINE_HEIGHT,
+        "autoResize": true,
+    });
+    if let (Value::Object(element), Value::Object(extra)) = (&mut element, extra) {
+        element.extend(extra);
+    }
+    element
+}
+
+/// 図形の間の矢印（順次処理の縦線は矢じりを付けません）
+fn arrow(layout: &Layout, index: usize, connector: &Connector) -> Value {
+    let (dx, dy) = (connector.x2 as f64 - connector.x1 as f64, connector.y2 as f64 - connector.y1 as f64);
+    let id = arrow_id(layout, index, connector);
+    let mut element = base(&id, "arrow", connector.x1, connector.y1, dx.abs(), dy.abs());
+    let binding = |shape: Option<usize>| shape.map(|i| json!({ "elementId": layout.shapes[i].id, "focus": 0, "gap": 1 }));
+    let extra = json!({
+        "points": [[0, 0], [dx, dy]],
+        "lastCommittedPoint": null,
+        "startBinding": binding(connector.from),
+        "endBinding": binding(connector.to),
+        "startArrowhead": null,
+        "endArrowhead": if connector.kind == ConnectorKind::Nest { Some("arrow") } else { None },
+        "elbowed": false,
+    });
+    if let (Value::Object(element), Value::Object(extra)) = (&mut element, extra) {
+        element.extend(extra);
+    }
+    element
+}
@@ -0,0 +1,94 @@
+//! 構造を展開せずにCommandとして表示した構文の集計
+//! 変換器が専用のノードを持たない式（`try` ブロックやクロージャなど）と、関数の中のアイテム（構造体の定義など）は、
+//! ソースのテキストをそのままラベルにしたCommandになります。そのCommandに `fallback` の印を付け、synの種類ごとに数えます。
+//! 関数呼び出しや代入など、もともと1つの処理として表示する式は対象外です。
+
+use std::collections::BTreeMap;
+
+use syn::{Expr, Item};
+
+use crate::model::{FallbackEntry, PadNode};
+
+/// 種類ごとに記録する位置の例の最大数
+const MAX_EXAMPLES: usize = 3;
+
+/// Commandとして表示すると構造が失われる式の場合、そのsynの種類の名前（`Expr::Closure` など）を返します
+/// 制御構造など、`parse_expr` が専用のノードに変換する式はここに来ないため、まとめて `Expr::Other` とします。
+pub fn expr_kind(expr: &Expr) -> Option<&'static str> {
+    let kind = match expr {
+        Expr::Array(_)
+        | Expr::Assign(_)
+        | Expr::Await(_)
+        | Expr::Binary(_)
+        | Expr::Call(_)
+        | Expr::Cast(_)
+        | Expr::Field(_)
+        | Expr::Index(_)
+        | Expr::Lit(_)
+        | Expr::Macro(_)
+        | Expr::MethodCall(_)
+        | Expr::Paren(_)
+        | Expr::Path(_)
+        | Expr::Range(_)
+        | Expr::RawAddr(_)
+        | Expr::Reference(_)
+        | Expr::Repeat(_)
+        | Expr::Return(_)
+        | Expr::Struct(_)
+        | Expr::Try(_)
+        | Expr::Tuple(_)
+        | Expr::Unary(_) => return None,
+        Expr::Closure(_) => "Expr::Closure",
+        Expr::Const(_) => "Expr::Const",
+        Expr::Group(_) => "Expr::Group",
+        Expr::Infer(_) => "Expr::Infer",
+        Expr::Let(_) => "Expr::Let",
+        Expr::TryBlock(_) => "Expr::TryBlock",
+        Expr::Verbatim(_) => "Expr::Verbatim",
+        _ => "Expr::Other",
+    };
+    Some(kind)
+}
+
+/// アイテムのsynの種類の名前（`Item::Struct` など）
+pub fn item_kind(item: &Item) -> &'static str {
+    match item {
+        Item::Const(_) => "Item::Const",
+        Item::Enum(_) => "Item::Enum",
+        Item::ExternCrate(_) => "Item::ExternCrate",
+        Item::Fn(_) => "Item::Fn",
+        Item::ForeignMod(_) => "Item::ForeignMod",
+        Item::Impl(_) => "Item::Impl",
+        Item::Macro(_) => "Item::Macro",
+        Item::Mod(_) => "Item::Mod",
+        Item::Static(_) => "Item::Static",
+        Item::Struct(_) => "Item::Struct",
+        Item::Trait(_) => "Item::Trait",
+        Item::TraitAlias(_) => "Item::TraitAlias",
+        Item::Type(_) => "Item::Type",
+        Item::Union(_) => "Item::Union",
+        Item::Use(_) => "Item::Use",
+        Item::Verbatim(_) => "Item::Verbatim",
+        _ => "Item::Other",
+    }
+}
+
+/// ツリーの中の `fallback` のCommandを、synの種類ごとの件数と位置の例（出現順）にまとめます
+pub fn report(root: &PadNode) -> BTreeMap<String, FallbackEntry> {
+    let mut report = BTreeMap::new();
+    collect(root, &mut report);
+    report
+}
+
+fn collect(node: &PadNode, report: &mut BTreeMap<String, FallbackEntry>) {
+    if let PadNode::Command { fallback_kind: Some(kind), meta, .. } = node {
+        let entry: &mut FallbackEntry = report.entry(kind.to_string()).or_default();
+        entry.count += 1;
+        if entry.examples.len() < MAX_EXAMPLES {
+            entry.examples.extend(meta.span);
+        }
+    }
+    for child in node.children() {
+        collect(child, report);
+    }
+}
@@ -0,0 +1,163 @@
+//! 平坦なグラフ（ノードとエッジの一覧）への変換
+//! D3のフォースレイアウトやCytoscape.jsなど、入れ子のツリーではなくグラフを受け取る描画ライブラリ向けの形式です。
+//! 各ノードは親のidと、子ノード以外の属性（`attrs`）を持ち、エッジの種類と順序からツリーを組み立て直せます。
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::model::PadNode;
+
+/// 平坦化したノード
+#[derive(Debug, Serialize)]
+pub struct GraphNode {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    /// 箱に表示するテキスト（Sequenceの場合は null）
+    pub label: Option<String>,
+    /// 親ノードのid（ルートの場合は null）
+    pub parent: Option<String>,
+    /// 子ノードを除いた、ツリーのJSONでのノードの属性（`type` と `id` を除きます）
+    /// matchの `arms` は、各アームの `body` を除いて残します。
+    pub attrs: Map<String, Value>,
+}
+
+/// エッジ
+#[derive(Debug, Serialize)]
+pub struct GraphEdge {
+    pub source: String,
+    pub target: String,
+    /// `child`（Sequence・Block・文書の子、matchのアームの本体）、`then`、`else`、`body`（ループ本体）、
+    /// `seq`（同じ並びの中で次に実行される兄弟ノード）のいずれか
+    pub kind: &'static str,
+    /// `child` の場合、親の中での順番（matchの場合はアームの番号）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<usize>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Graph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+/// ツリーのJSONのうち、子ノードを持つキー
+const CHILD_KEYS: &[&str] = &["children", "then_block", "else_block", "body"];
+
+/// ツリーを平坦なグラフに変換します（ノードは行きがけ順）
+/// ノードのidで親子をつなぐため、idを割り当てた後のツリーを渡してください。
+pub fn to_graph(root: &PadNode) -> Graph {
+    let mut graph = Graph::default();
+    let value = serde_json::to_value(root).unwrap();
+    flatten(root, value, None, &mut graph);
+    graph
+}
+
+fn flatten(node: &PadNode, value: Value, parent: Option<&str>, graph: &mut Graph) {
+    let Value::Object(mut attrs) = value else { return };
+    attrs.remove("type");
+    attrs.remove("id");
+    // 子ノードのJSONを、`PadNode::children` と同じ順に取り出します
+    let mut child_values: Vec<Value> = Vec::new();
+    for key in CHILD_KEYS {
+        match attrs.remove(*key) {
+            Some(Value::Array(children)) => child_values.extend(children),
+            Some(Value::Null) | None => {}
+            Some(child) => child_values.push(child),
+        }
+    }
+    if let Some(Value::Array(arms)) = attrs.get_mut("arms") {
+        for arm in arms {
+            if let Some(body) = arm.as_object_mut().and_then(|arm| arm.remove("body")) {
+                child_values.push(body);
+            }
+        }
+    }
+
+    let id = node.meta().id.clone();
+    graph.nodes.push(GraphNode {
+        id: id.clone(),
+        kind: node.kind_name(),
+        label: node.display_text().map(str::to_string),
+        parent: parent.map(str::to_string),
+        attrs,
+    });
+    let children = node.children();
+    for (index, (child, value)) in children.iter().zip(child_values).enumerate() {
+        let (kind, index) = match node {
+            PadNode::If { .. } if index == 0 => ("then", None),
+            PadNode::If { .. } => ("else", None),
+            PadNode::Loop { .. } => ("body", None),
+            _ => ("child", Some(index)),
+        };
+        graph.edges.push(GraphEdge { source: id.clone(), target: child.meta().id.clone(), kind, index });
+        flatten(child, value, Some(&id), graph);
+    }
+    if let PadNode::Sequence { .. } | PadNode::Document { .. } | PadNode::Block { .. } = node {
+        for pair in children.windows(2) {
+            let (source, target) = (pair[0].meta().id.clone(), pair[1].meta().id.clone());
+            graph.edges.push(GraphEdge { source, target, kind: "seq", index: None });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// グラフのJSONからツリーのJSONを組み立て直します
+    fn rebuild(graph: &Value) -> Value {
+        let nodes: HashMap<&str, &Value> =
+            graph["nodes"].as_array().unwrap().iter().map(|node| (node["id"].as_str().unwrap(), node)).collect();
+        let edges = graph["edges"].as_array().unwrap();
+        let root = graph["nodes"].as_array().unwrap().iter().find(|node| node["parent"].is_null()).unwrap();
+        build(root["id"].as_str().unwrap(), &nodes, edges)
+    }
+
+    fn build(id: &str, nodes: &HashMap<&str, &Value>, edges: &[Value]) -> Value {
+        let node = nodes[id];
+        let mut object = node["attrs"].as_object().unwrap().clone();
+        object.insert("type".into(), node["type"].clone());
+        object.insert("id".into(), id.into());
+        let outgoing = edges.iter().filter(|edge| edge["source"] == id && edge["kind"] != "seq");
+        let mut children: Vec<(u64, Value)> = Vec::new();
+        if node["type"] == "if" {
+            object.insert("else_block".into(), Value::Null);
+        }
+        for edge in outgoing {
+            let child = build(edge["target"].as_str().unwrap(), nodes, edges);
+            match edge["kind"].as_str().unwrap() {
+                "then" => object.insert("then_block".into(), child),
+                "else" => object.insert("else_block".into(), child),
+                "body" => object.insert("body".into(), child),
+                _ => {
+                    children.push((edge["index"].as_u64().unwrap(), child));
+                    None
+                }
+            };
+        }
+        children.sort_by_key(|(index, _)| *index);
+        if node["type"] == "match" {
+            for (index, body) in children {
+                object["arms"][index as usize]["body"] = body;
+            }
+        } else if matches!(node["type"].as_str(), Some("sequence" | "document" | "block")) {
+            object.insert("children".into(), children.into_iter().map(|(_, child)| child).collect());
+        }
+        Value::Object(object)
+    }
+
+    #[test]
+    fn graph_round_trips_to_the_same_tree() {
+        let code = "fn main() {\n    let mut n = 3;\n    while n > 0 {\n        if n % 2 == 0 { even(n); } else { odd(n); }\n        n -= 1;\n    }\n    match n { 0 => zero(), _ if n > 1 => many(), _ => {} }\n}";
+        let tree = crate::build_tree(code, &crate::options::ParseOptions::default());
+        let graph = serde_json::to_value(to_graph(&tree)).unwrap();
+        assert_eq!(graph["nodes"].as_array().unwrap().len(), 18);
+        let kinds: Vec<&str> = graph["edges"].as_array().unwrap().iter().map(|edge| edge["kind"].as_str().unwrap()).collect();
+        for kind in ["seq", "then", "else", "body", "child"] {
+            assert!(kinds.contains(&kind), "{}", kind);
+        }
+        assert_eq!(rebuild(&graph), serde_json::to_value(&tree).unwrap());
+    }
+}
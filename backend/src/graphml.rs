@@ -0,0 +1,195 @@
+//! GraphMLへの書き出し
+//! yEdやNetworkXでプログラムの構造を解析できるよう、ノードを頂点、順次・分岐・反復のつながりを有向辺にします。
+//! Blockは入れ子のグラフとして書き出し、関数などの階層を保ちます（Sequenceは頂点にせず、順次の辺で表します）。
+
+use std::fmt::Write;
+
+use crate::model::{BlockKind, PadNode};
+use crate::svg::escape_xml;
+use crate::visit::{VisitContext, Visitor, walk};
+
+/// 宣言する属性のキー（id, 対象, 型）
+const KEYS: &[(&str, &str, &str)] = &[
+    ("label", "node", "string"),
+    ("type", "node", "string"),
+    ("span", "node", "string"),
+    ("function", "node", "string"),
+    ("kind", "edge", "string"),
+    ("branch", "edge", "boolean"),
+    ("pattern", "edge", "string"),
+];
+
+/// 頂点になるノードかどうか（Sequenceと文書は順次のつながりとして表します）
+fn is_vertex(node: &PadNode) -> bool {
+    !matches!(node, PadNode::Sequence { .. } | PadNode::Document { .. })
+}
+
+/// 頂点と入れ子のグラフを書き出すビジター
+struct NodeWriter {
+    out: String,
+    indent: usize,
+}
+
+impl NodeWriter {
+    fn line(&mut self, text: &str) {
+        let _ = writeln!(self.out, "{:width$}{}", "", text, width = self.indent * 2);
+    }
+
+    fn data(&mut self, key: &str, value: &str) {
+        self.line(&format!("<data key=\"{}\">{}</data>", key, escape_xml(value)));
+    }
+}
+
+impl Visitor for NodeWriter {
+    fn enter(&mut self, node: &PadNode, ctx: &VisitContext) -> bool {
+        if !is_vertex(node) {
+            return true;
+        }
+        let id = &node.meta().id;
+        self.line(&format!("<node id=\"{}\">", escape_xml(id)));
+        self.indent += 1;
+        self.data("label", node.display_text().unwrap_or_default());
+        self.data("type", node.kind_name());
+        if let Some(span) = node.meta().span {
+            let span = format!("{}:{}-{}:{}", span.start_line, span.start_col, span.end_line, span.end_col);
+            self.data("span", &span);
+        }
+        let function = match node {
+            PadNode::Block { kind: BlockKind::Function, label, .. } => Some(label.as_str()),
+            _ => ctx.function,
+        };
+        if let Some(function) = function {
+            self.data("function", function);
+        }
+        if let PadNode::Block { .. } = node {
+            self.line(&format!("<graph id=\"{}:\" edgedefault=\"directed\">", escape_xml(id)));
+            self.indent += 1;
+        }
+        true
+    }
+
+    fn leave(&mut self, node: &PadNode, _ctx: &VisitContext) {
+        if !is_vertex(node) {
+            return;
+        }
+        if let PadNode::Block { .. } = node {
+            self.indent -= 1;
+            self.line("</graph>");
+        }
+        self.indent -= 1;
+        self.line("</node>");
+    }
+}
+
+/// 有向辺1本分
+pub struct Edge<'a> {
+    pub source: &'a str,
+    pub target: &'a str,
+    /// 辺の種類（`seq`、`child`、`branch`、`body`、`back`、`arm`）
+    pub kind: &'static str,
+    /// 分岐の辺の場合、条件が真の側か偽の側か
+    pub branch: Option<bool>,
+    /// matchのアームの辺の場合、そのパターン
+    pub pattern: Option<String>,
+}
+
+/// 処理の並びの先頭の頂点（Sequenceの場合は最初の子の先頭）
+fn entry(node: &PadNode) -> Option<&PadNode> {
+    match node {
+        PadNode::Sequence { children, .. } | PadNode::Document { children, .. } => children.iter().find_map(entry),
+        other => Some(other),
+    }
+}
+
+/// 処理の並びの末尾の頂点（Sequenceの場合は最後の子の末尾）
+fn exit(node: &PadNode) -> Option<&PadNode> {
+    match node {
+        PadNode::Sequence { children, .. } | PadNode::Document { children, .. } => children.iter().rev().find_map(exit),
+        other => Some(other),
+    }
+}
+
+/// ツリー全体の辺を集めます
+pub fn collect_edges<'a>(node: &'a PadNode, edges: &mut Vec<Edge<'a>>) {
+    let id = node.meta().id.as_str();
+    let mut edge = |target: Option<&'a PadNode>, kind, branch, pattern| {
+        if let Some(target) = target {
+            edges.push(Edge { source: id, target: &target.meta().id, kind, branch, pattern });
+        }
+    };
+    match node {
+        PadNode::Sequence { children, .. } | PadNode::Document { children, .. } => {
+            let entries: Vec<&PadNode> = children.iter().filter_map(entry).collect();
+            for pair in entries.windows(2) {
+                edges.push(Edge { source: &pair[0].meta().id, target: &pair[1].meta().id, kind: "seq", branch: None, pattern: None });
+            }
+        }
+        PadNode::Block { children, .. } => {
+            for child in children {
+                edge(entry(child), "child", None, None);
+            }
+        }
+        PadNode::If { then_block, else_block, .. } => {
+            edge(entry(then_block), "branch", Some(true), None);
+            if let Some(else_block) = else_block {
+                edge(entry(else_block), "branch", Some(false), None);
+            }
+        }
+        PadNode::Loop { body, .. } => {
+            edge(entry(body), "body", None, None);
+            // 本体の末尾からループの先頭へ戻る辺
+            if let Some(last) = exit(body) {
+                edges.push(Edge { source: &last.meta().id, target: id, kind: "back", branch: None, pattern: None });
+            }
+        }
+        PadNode::Match { arms, .. } => {
+            for arm in arms {
+                let pattern = match &arm.guard {
+                    Some(guard) => format!("{} if {}", arm.pattern, guard),
+                    None => arm.pattern.clone(),
+                };
+                edge(entry(&arm.body), "arm", None, Some(pattern));
+            }
+        }
+        _ => {}
+    }
+    for child in node.children() {
+        collect_edges(child, edges);
+    }
+}
+
+/// ツリー全体をGraphMLの文書にします
+pub fn to_graphml(root: &PadNode) -> String {
+    let mut writer = NodeWriter { out: String::new(), indent: 2 };
+    walk(root, &mut writer);
+    let mut edges = Vec::new();
+    collect_edges(root, &mut edges);
+
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:schemaLocation=\"http://graphml.graphdrawing.org/xmlns http://graphml.graphdrawing.org/xmlns/1.0/graphml.xsd\">\n",
+    );
+    for (id, target, kind) in KEYS {
+        let _ = writeln!(out, "  <key id=\"{id}\" for=\"{target}\" attr.name=\"{id}\" attr.type=\"{kind}\"/>");
+    }
+    out.push_str("  <graph id=\"G\" edgedefault=\"directed\">\n");
+    out.push_str(&writer.out);
+    for (number, edge) in edges.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "    <edge id=\"e{}\" source=\"{}\" target=\"{}\">",
+            number,
+            escape_xml(edge.source),
+            escape_xml(edge.target)
+        );
+        let _ = writeln!(out, "      <data key=\"kind\">{}</data>", edge.kind);
+        if let Some(branch) = edge.branch {
+            let _ = writeln!(out, "      <data key=\"branch\">{}</data>", branch);
+        }
+        if let Some(pattern) = &edge.pattern {
+            let _ = writeln!(out, "      <data key=\"pattern\">{}</data>", escape_xml(pattern));
+        }
+        out.push_str("    </edge>\n");
+    }
+    out.push_str("  </graph>\n</graphml>\n");
+    out
+}
@@ -0,0 +1,64 @@
+//! 単体で開けるHTMLへの書き出し
+//! アプリもRustも持っていない相手に図を共有するため、SVGとソースコードを1つのHTMLファイルに埋め込みます。
+//! 外部のスクリプトやスタイルシートは参照せず、オフラインで開けます。
+
+use std::fmt::Write;
+
+use crate::model::{BlockKind, PadNode};
+use crate::svg::{escape_xml, render_svg};
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+details { margin-bottom: 1em; }
+summary { cursor: pointer; font-family: monospace; font-weight: bold; }
+svg.pad { display: block; margin: 0.5em 0; }
+pre { background: #f6f8fa; padding: 1em; overflow: auto; }
+.error { color: #b00; }";
+
+/// 図として個別に描画する単位（関数ごと）を集めます
+/// implブロックやexternブロックは、その中のメソッド・宣言をそれぞれ1つの単位として扱います。
+/// モジュールのBlockは、その中のアイテムを同じ規則で単位に分けます。
+pub fn sections(root: &PadNode) -> Vec<&PadNode> {
+    let items: Vec<&PadNode> = match root {
+        PadNode::Sequence { children, .. } | PadNode::Document { children, .. } => children.iter().collect(),
+        other => vec![other],
+    };
+    items.into_iter().flat_map(units).collect()
+}
+
+fn units(item: &PadNode) -> Vec<&PadNode> {
+    match item {
+        PadNode::Block { kind: BlockKind::Impl | BlockKind::Trait, children, .. } => children.iter().collect(),
+        PadNode::Block { kind: BlockKind::Module, children, .. } => children.iter().flat_map(units).collect(),
+        other => vec![other],
+    }
+}
+
+/// 解析結果のツリーと元のソースコードから、HTML文書を生成します
+/// ルートがDocumentの場合は、そのタイトルを文書のタイトルと見出しにします。
+pub fn to_html(root: &PadNode, source: &str) -> String {
+    let mut body = String::new();
+    let title = match root {
+        PadNode::Document { title, .. } => {
+            let _ = writeln!(body, "<h1>{}</h1>", escape_xml(title));
+            title.as_str()
+        }
+        _ => "PAD",
+    };
+    if let PadNode::Error { message, .. } = root {
+        let _ = writeln!(body, "<p class=\"error\">{}</p>", escape_xml(message));
+    } else {
+        for section in sections(root) {
+            let title = section.display_text().unwrap_or("(sequence)");
+            let _ = writeln!(body, "<details open>\n<summary>{}</summary>", escape_xml(title));
+            body.push_str(&render_svg(section));
+            body.push_str("</details>\n");
+        }
+    }
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"ja\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}\n</style>\n</head>\n<body>\n{}<details>\n<summary>ソースコード</summary>\n<pre><code>{}</code></pre>\n</details>\n</body>\n</html>\n",
+        escape_xml(title),
+        STYLE,
+        body,
+        escape_xml(source)
+    )
+}
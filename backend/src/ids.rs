@@ -0,0 +1,383 @@
+//! ノードidの割り当て
+//! 警告やフロントエンドの選択状態からノードを参照できるよう、ツリー内で一意なidを付けます。
+
+use std::collections::HashMap;
+
+use crate::model::{BlockKind, PadNode};
+
+/// ツリーの全ノードに行きがけ順の連番id（`n0`, `n1`, ...）を割り当てます
+/// 同じツリーに対しては常に同じidになります。
+pub fn assign_ids(root: &mut PadNode) {
+    assign_ids_from(root, 0);
+}
+
+/// `n{first}` から順にidを割り当て、次に使う番号を返します
+/// ルートのSequenceを作らずにアイテムごとに変換する場合に、ツリー全体で割り当てた場合と同じidにするためのものです。
+pub fn assign_ids_from(root: &mut PadNode, first: usize) -> usize {
+    let mut next = first;
+    assign(root, &mut next);
+    next
+}
+
+fn assign(node: &mut PadNode, next: &mut usize) {
+    node.meta_mut().id = format!("n{}", next);
+    *next += 1;
+    for child in node.children_mut() {
+        assign(child, next);
+    }
+}
+
+/// ツリーの全ノードに、再解析しても変わりにくいid（`s` + 16桁の16進数）を割り当てます
+/// idは、ノードを囲むBlockのラベルの列・ノードの種別・表示するテキスト・最も内側のBlockからの子インデックスの列のハッシュです。
+/// ソース上の位置は含めないため、関数の外の行を追加・削除しても、関数の中のノードのidは変わりません。
+/// トップレベルのノードと、関数・implブロックなどのアイテムのBlockは、子インデックスを含めず種別とラベル（名前）だけで決めるため、
+/// 前に別のアイテムを追加してもidは変わりません。
+/// 同じ値になったノードには、2つ目から `-2`、`-3` ... を付けて区別します。
+pub fn assign_stable_ids(root: &mut PadNode) {
+    stable(root, &mut Vec::new(), &mut Vec::new(), &mut HashMap::new());
+}
+
+fn stable(node: &mut PadNode, scope: &mut Vec<String>, path: &mut Vec<usize>, seen: &mut HashMap<String, usize>) {
+    let is_item = matches!(
+        node,
+        PadNode::Block {
+            kind: BlockKind::Function
+                | BlockKind::Impl
+                | BlockKind::Trait
+                | BlockKind::Extern
+                | BlockKind::Macro
+                | BlockKind::Module
+                | BlockKind::File
+                | BlockKind::Imports,
+            ..
+        }
+    );
+    let relative = if is_item || scope.is_empty() && path.len() <= 1 {
+        String::new()
+    } else {
+        path.iter().map(|index| index.to_string()).collect::<Vec<_>>().join(".")
+    };
+    let text = node.display_text().unwrap_or_default();
+    let key = format!("{}\u{0}{}\u{0}{}\u{0}{}", scope.join("\u{0}"), node.kind_name(), text, relative);
+    let id = format!("s{:016x}", fnv1a(&key));
+    let count = seen.entry(id.clone()).or_insert(0);
+    *count += 1;
+    node.meta_mut().id = if *count == 1 { id } else { format!("{}-{}", id, count) };
+
+    let block = match node {
+        PadNode::Block { label, .. } => Some(label.clone()),
+        _ => None,
+    };
+    let outer = block.is_some().then(|| std::mem::take(path));
+    scope.extend(block);
+    for (index, child) in node.children_mut().into_iter().enumerate() {
+        path.push(index);
+        stable(child, scope, path, seen);
+        path.pop();
+    }
+    if let Some(outer) = outer {
+        scope.pop();
+        *path = outer;
+    }
+}
+
+/// 64ビットのFNV-1aハッシュ（Rustのバージョンや実行環境によらず同じ値になります）
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3))
+}
+
+/// break / continue の脱出先となりうる構造
+enum JumpScope {
+    /// ループ（ラベルがあればそのラベル）
+    Loop { label: Option<String>, id: String },
+    /// ラベル付きブロック
+    Labeled { label: String, id: String },
+    /// 関数の境界（内側のbreakは外側の構造を対象にできません）
+    Function,
+}
+
+/// break / continue に脱出先（ループまたはラベル付きブロック）のノードidを設定します
+/// ラベルなしのbreak / continue は最も内側のループ、ラベル付きのものは同じラベルを持つ最も内側の構造が対象です。
+/// idを割り当てた後に呼び出してください。
+pub fn link_jump_targets(root: &mut PadNode) {
+    link(root, &mut Vec::new());
+}
+
+fn resolve(scopes: &[JumpScope], target: Option<&str>, allow_block: bool) -> Option<String> {
+    for scope in scopes.iter().rev() {
+        match (scope, target) {
+            (JumpScope::Function, _) => return None,
+            (JumpScope::Loop { id, .. }, None) => return Some(id.clone()),
+            (JumpScope::Loop { label: Some(label), id }, Some(target)) if label == target => return Some(id.clone()),
+            (JumpScope::Labeled { label, id }, Some(target)) if allow_block && label == target => {
+                return Some(id.clone());
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn link(node: &mut PadNode, scopes: &mut Vec<JumpScope>) {
+    match node {
+        PadNode::Break { target, target_id, .. } => *target_id = resolve(scopes, target.as_deref(), true),
+        PadNode::Continue { target, target_id, .. } => *target_id = resolve(scopes, target.as_deref(), false),
+        _ => {}
+    }
+    let scope = match node {
+        PadNode::Loop { loop_label, meta, .. } => Some(JumpScope::Loop { label: loop_label.clone(), id: meta.id.clone() }),
+        PadNode::Block { kind: BlockKind::Labeled, label, meta, .. } => {
+            Some(JumpScope::Labeled { label: label.clone(), id: meta.id.clone() })
+        }
+        // クロージャ・asyncブロックを本体とするBlockも、内側のbreakが外へ出られない境界です
+        PadNode::Block {
+            kind: BlockKind::Function | BlockKind::Call | BlockKind::Async | BlockKind::Generator | BlockKind::Closure | BlockKind::Concurrent,
+            ..
+        } => Some(JumpScope::Function),
+        _ => None,
+    };
+    let pushed = scope.is_some();
+    scopes.extend(scope);
+    for child in node.children_mut() {
+        link(child, scopes);
+    }
+    if pushed {
+        scopes.pop();
+    }
+}
+
+/// 関数の出力（"output"）のBlockに、その関数の戻り値を返すノード（`returns` が true）のidを設定します
+/// `interface_nodes` オプションで作られた関数だけが対象です。idを割り当てた後に呼び出してください。
+pub fn link_returns(node: &mut PadNode) {
+    if let PadNode::Block { kind: BlockKind::Function, children, .. } = node
+        && let Some(PadNode::Block { kind: BlockKind::Interface, label, .. }) = children.last()
+        && label == "output"
+    {
+        let mut ids = Vec::new();
+        for child in children.iter() {
+            returning_ids(child, &mut ids);
+        }
+        if let Some(output) = children.last_mut() {
+            output.meta_mut().returned_by = ids;
+        }
+    }
+    for child in node.children_mut() {
+        link_returns(child);
+    }
+}
+
+/// 戻り値を返すノードのidを行きがけ順に集めます（内側の関数・クロージャなどの中は除きます）
+fn returning_ids(node: &PadNode, ids: &mut Vec<String>) {
+    if node.meta().returns {
+        ids.push(node.meta().id.clone());
+    }
+    if let PadNode::Block {
+        kind: BlockKind::Function | BlockKind::Call | BlockKind::Async | BlockKind::Generator | BlockKind::Closure | BlockKind::Concurrent,
+        ..
+    } = node
+    {
+        return;
+    }
+    for child in node.children() {
+        returning_ids(child, ids);
+    }
+}
+
+/// 関数を呼び出しているノードの `calls` に、呼び出している関数のうち同じ入力で定義されたものを、`call_targets` にその関数のBlockのidを設定します
+/// 関数名は、関数は `helper`、メソッドは `Counter::new` のように型名で修飾します（トレイトの既定のメソッドはトレイト名で修飾します）。
+/// 型名はジェネリクスの引数とパスを除いた名前（`impl<T> a::Wrap<T>` は `Wrap`）にします。
+/// `Self::` はそのメソッドのimplの型に読み替え、`util::helper` のようなモジュールのパスは最後の名前で関数を探します。
+/// `self` 以外に対するメソッド呼び出し（`.next`）は、その名前のメソッドが1つだけの場合にそのメソッドとみなします。
+/// idを割り当てた後に呼び出してください。
+pub fn link_calls(root: &mut PadNode) {
+    let mut functions = HashMap::new();
+    collect_functions(root, &mut functions);
+    let mut methods: HashMap<&str, Vec<&str>> = HashMap::new();
+    for name in functions.keys() {
+        if let Some((_, method)) = name.rsplit_once("::") {
+            methods.entry(method).or_default().push(name);
+        }
+    }
+    let methods = methods.into_iter().filter(|(_, names)| names.len() == 1).map(|(method, names)| (method, names[0])).collect();
+    resolve_calls(root, &functions, &methods, None);
+}
+
+/// 関数のBlockの修飾した名前を返します（関数のBlockでない場合は None）
+pub fn function_name(node: &PadNode) -> Option<String> {
+    let PadNode::Block { kind: BlockKind::Function, label, full_label, trait_name, self_type, .. } = node else {
+        return None;
+    };
+    let signature = full_label.as_deref().unwrap_or(label).strip_prefix("fn ")?;
+    let path = signature.split('(').next()?;
+    let name = path.rsplit("::").next()?.trim();
+    Some(match (self_type, trait_name) {
+        (Some(owner), _) | (None, Some(owner)) => format!("{}::{}", type_name(owner), name),
+        (None, None) => name.to_string(),
+    })
+}
+
+/// 型からジェネリクスの引数（`<...>`）と前のパスを除いた名前（`a::Wrap<T>` は `Wrap`）
+pub fn type_name(ty: &str) -> String {
+    let path = strip_generics(ty);
+    path.rsplit("::").next().unwrap_or(&path).trim().to_string()
+}
+
+/// パスからジェネリクスの引数（`<...>`）を除きます（`a::Wrap<T>::get` は `a::Wrap::get`）
+pub fn strip_generics(path: &str) -> String {
+    let mut depth = 0usize;
+    path.chars()
+        .filter(|&c| {
+            match c {
+                '<' => depth += 1,
+                '>' => depth = depth.saturating_sub(1),
+                _ => return depth == 0,
+            }
+            false
+        })
+        .collect()
+}
+
+fn collect_functions(node: &PadNode, functions: &mut HashMap<String, String>) {
+    if let Some(name) = function_name(node) {
+        functions.entry(name).or_insert_with(|| node.meta().id.clone());
+    }
+    for child in node.children() {
+        collect_functions(child, functions);
+    }
+}
+
+fn resolve_calls(node: &mut PadNode, functions: &HashMap<String, String>, methods: &HashMap<&str, &str>, self_type: Option<&str>) {
+    let owner = match node {
+        PadNode::Block { kind: BlockKind::Function, self_type: Some(owner), .. } => Some(type_name(owner)),
+        PadNode::Block { kind: BlockKind::Function, .. } => None,
+        _ => self_type.map(str::to_string),
+    };
+    let meta = node.meta_mut();
+    let mut calls = Vec::new();
+    let mut targets = Vec::new();
+    for call in meta.call_paths.clone() {
+        let name = match (call.strip_prefix("Self::"), call.strip_prefix('.'), &owner) {
+            (Some(method), _, Some(owner)) => format!("{}::{}", owner, method),
+            (_, Some(method), _) => methods.get(method).map_or(call.clone(), |name| name.to_string()),
+            _ => call,
+        };
+        let found = functions.get_key_value(&name).or_else(|| {
+            // 先頭が小文字のパス（`util::helper`、`crate::helper`）はモジュールとみなします
+            let (module, last) = name.rsplit_once("::")?;
+            module.starts_with(|c: char| c.is_lowercase()).then(|| functions.get_key_value(last))?
+        });
+        if let Some((name, id)) = found
+            && !calls.contains(name)
+        {
+            calls.push(name.clone());
+            targets.push(id.clone());
+        }
+    }
+    meta.calls = calls;
+    meta.call_targets = targets;
+    for child in node.children_mut() {
+        resolve_calls(child, functions, methods, owner.as_deref());
+    }
+}
+
+/// 各ノードに階層的な手順番号（`1`、`1.1`、`1.2`、`2` ...）を付けます
+/// 子ノードは親の番号に続けて1から順に番号を付けます。Sequenceは番号を持たず、階層も増やしません。
+/// ifの then / else 節やmatchの各アームの子は、分岐をまたいで通し番号になります。
+pub fn assign_steps(root: &mut PadNode) {
+    number(root, "");
+}
+
+fn number(node: &mut PadNode, prefix: &str) {
+    let mut steps = Vec::new();
+    flatten_sequences(node, &mut steps);
+    for (i, child) in steps.into_iter().enumerate() {
+        let step = if prefix.is_empty() { (i + 1).to_string() } else { format!("{}.{}", prefix, i + 1) };
+        number(child, &step);
+        child.meta_mut().step = Some(step);
+    }
+}
+
+/// 子ノードを、Sequenceを展開しながら集めます
+fn flatten_sequences<'a>(node: &'a mut PadNode, out: &mut Vec<&'a mut PadNode>) {
+    for child in node.children_mut() {
+        if matches!(child, PadNode::Sequence { .. }) {
+            flatten_sequences(child, out);
+        } else {
+            out.push(child);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_follow_pre_order() {
+        let mut root = PadNode::sequence(vec![
+            PadNode::if_node("x", PadNode::command("a"), Some(PadNode::command("b"))),
+            PadNode::command("c"),
+        ]);
+        assign_ids(&mut root);
+        let json = serde_json::to_value(&root).unwrap();
+        assert_eq!(json["id"], "n0");
+        assert_eq!(json["children"][0]["id"], "n1");
+        assert_eq!(json["children"][0]["then_block"]["id"], "n2");
+        assert_eq!(json["children"][0]["else_block"]["id"], "n3");
+        assert_eq!(json["children"][1]["id"], "n4");
+    }
+
+    #[test]
+    fn stable_ids_do_not_depend_on_sibling_functions() {
+        let main = || {
+            let body = PadNode::sequence(vec![PadNode::command("a"), PadNode::command("a")]);
+            PadNode::block(BlockKind::Function, "fn main()", vec![body])
+        };
+        let mut before = PadNode::sequence(vec![main()]);
+        let mut after = PadNode::sequence(vec![PadNode::block(BlockKind::Function, "fn added()", Vec::new()), main()]);
+        assign_stable_ids(&mut before);
+        assign_stable_ids(&mut after);
+        let main_ids = |root: &PadNode| -> Vec<String> {
+            let main = *root.children().last().unwrap();
+            let commands = main.children()[0].children().into_iter().map(|node| node.meta().id.clone());
+            std::iter::once(main.meta().id.clone()).chain(commands).collect()
+        };
+        let ids = main_ids(&before);
+        assert_eq!(ids, main_ids(&after));
+        let ids = &ids[1..];
+        // 同じテキストの兄弟も位置で区別されます
+        assert!(ids[0].starts_with('s') && ids[0] != ids[1]);
+    }
+
+    #[test]
+    fn steps_are_hierarchical_and_skip_sequences() {
+        let code = "fn main() {\n    init();\n    if ok {\n        a();\n        while busy { wait(); }\n    } else {\n        b();\n    }\n    done();\n}\nfn helper() { x(); }";
+        let mut root = crate::build_tree(code, &crate::options::ParseOptions::default());
+        assign_steps(&mut root);
+        let mut steps = Vec::new();
+        fn collect(node: &PadNode, out: &mut Vec<(String, String)>) {
+            if let Some(step) = &node.meta().step {
+                out.push((step.clone(), node.display_text().unwrap_or_default().to_string()));
+            }
+            for child in node.children() {
+                collect(child, out);
+            }
+        }
+        collect(&root, &mut steps);
+        let expected = [
+            ("1", "fn main()"),
+            ("1.1", "init ()"),
+            ("1.2", "ok"),
+            ("1.2.1", "a ()"),
+            ("1.2.2", "busy"),
+            ("1.2.2.1", "wait ()"),
+            ("1.2.3", "b ()"),
+            ("1.3", "done ()"),
+            ("2", "fn helper()"),
+            ("2.1", "x ()"),
+        ];
+        let expected: Vec<(String, String)> = expected.iter().map(|(a, b)| (a.to_string(), b.to_string())).collect();
+        assert_eq!(steps, expected);
+        assert!(root.meta().step.is_none());
+    }
+}
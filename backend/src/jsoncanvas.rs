@@ -0,0 +1,123 @@
+//! JSON Canvas（`.canvas`）への書き出し
+//! Obsidianなどのノートの中で図に書き込みができるよう、箱をテキストのノード、つながりをエッジにします。
+//! 関数ごとに配置し、それぞれを "group" ノードで囲んで縦に並べます。
+
+use serde::Serialize;
+
+use crate::html::sections;
+use crate::layout::{ConnectorKind, Layout, ROW, Shape, layout};
+use crate::model::PadNode;
+
+/// JSON Canvas の文書
+#[derive(Debug, Serialize)]
+struct Canvas {
+    nodes: Vec<CanvasNode>,
+    edges: Vec<CanvasEdge>,
+}
+
+/// ノード（座標は整数のpx）
+#[derive(Debug, Serialize)]
+struct CanvasNode {
+    id: String,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    x: i64,
+    y: i64,
+    width: i64,
+    height: i64,
+    /// "text" ノードの内容（Markdown）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    /// "group" ノードの見出し
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    /// プリセットの色（"1" は赤）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    color: Option<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CanvasEdge {
+    id: String,
+    from_node: String,
+    from_side: &'static str,
+    to_node: String,
+    to_side: &'static str,
+}
+
+/// ツリー全体をJSON Canvasの文書（JSON文字列）にします
+/// 入れ子の処理へのエッジは箱の右辺から子の左辺へ、順次処理のエッジは下辺から次の処理の上辺へ向かいます。
+pub fn to_jsoncanvas(root: &PadNode) -> String {
+    let mut canvas = Canvas { nodes: Vec::new(), edges: Vec::new() };
+    let mut top = 0;
+    for (number, section) in sections(root).into_iter().enumerate() {
+        let layout = layout(section);
+        // グループは中身より先に置き、背面に描かれるようにします
+        let id = &section.meta().id;
+        canvas.nodes.push(CanvasNode {
+            id: if id.is_empty() { format!("group{}", number) } else { format!("{}-group", id) },
+            kind: "group",
+            x: 0,
+            y: top,
+            width: layout.width as i64,
+            height: layout.height as i64,
+            text: None,
+            label: section.display_text().map(str::to_string),
+            color: None,
+        });
+        canvas.nodes.extend(layout.shapes.iter().map(|shape| text_node(shape, top)));
+        canvas.edges.extend(edges(&layout));
+        top += (layout.height + ROW) as i64;
+    }
+    serde_json::to_string_pretty(&canvas).unwrap()
+}
+
+fn text_node(shape: &Shape, top: i64) -> CanvasNode {
+    CanvasNode {
+        id: shape.id.clone(),
+        kind: "text",
+        x: shape.x as i64,
+        y: top + shape.y as i64,
+        width: shape.width as i64,
+        height: shape.height as i64,
+        text: Some(escape_markdown(&shape.label)),
+        label: None,
+        color: (shape.class == "error").then_some("1"),
+    }
+}
+
+fn edges(layout: &Layout) -> Vec<CanvasEdge> {
+    let id = |index: usize| layout.shapes[index].id.clone();
+    let edge = |from: usize, from_side, to: usize, to_side| CanvasEdge {
+        id: format!("{}-{}", id(from), id(to)),
+        from_node: id(from),
+        from_side,
+        to_node: id(to),
+        to_side,
+    };
+    let mut edges = Vec::new();
+    for connector in &layout.connectors {
+        match (connector.kind, connector.from, connector.to) {
+            (ConnectorKind::Nest, Some(from), Some(to)) => edges.push(edge(from, "right", to, "left")),
+            (ConnectorKind::Sequence, _, _) => {
+                edges.extend(connector.members.windows(2).map(|pair| edge(pair[0], "bottom", pair[1], "top")));
+            }
+            _ => {}
+        }
+    }
+    edges
+}
+
+/// ラベルがMarkdownとして解釈されないよう、記号をエスケープします
+/// `Vec<T>` がHTMLのタグとして、`*p` が強調として表示されるのを防ぎます。
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '|' | '~') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
@@ -0,0 +1,283 @@
+//! PAD図の配置
+//! 標準的なPADの配置に従い、順次処理は縦に並べ、入れ子になった処理は右の列に配置します。
+//! SVGのほか、Excalidrawなどの図形エディタ向けの書き出しも、ここで求めた位置をそのまま使います。
+
+use crate::model::{LoopKind, PadNode};
+use crate::width::display_width;
+
+/// 1桁あたりの幅（px）
+pub const CHAR_WIDTH: usize = 8;
+/// 箱の左右の余白（px）
+pub const PADDING_X: usize = 8;
+/// 箱の最小の幅（px）
+const MIN_BOX_WIDTH: usize = 40;
+/// 箱の高さ（px）
+pub const BOX_HEIGHT: usize = 28;
+/// 縦に並ぶ箱の間隔（px）
+pub const ROW_GAP: usize = 8;
+/// 入れ子の列の間隔（px）
+pub const COLUMN_GAP: usize = 24;
+/// 図の周囲の余白（px）
+pub const MARGIN: usize = 10;
+/// 1行分の高さ
+pub const ROW: usize = BOX_HEIGHT + ROW_GAP;
+
+/// ラベルを表示する箱の幅
+pub fn box_width(text: &str) -> usize {
+    (display_width(text) * CHAR_WIDTH + PADDING_X * 2).max(MIN_BOX_WIDTH)
+}
+
+/// 図形の形
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShapeKind {
+    /// 長方形の箱（処理・関数など）
+    Box,
+    /// 反復の箱（縦の二重線を持つ長方形。後判定ループは線が右側）
+    Loop { do_while: bool },
+    /// 選択（if / match）の箱。右辺に切り欠きがあり、すべての分岐の高さにわたります
+    Selection,
+    /// 枠を持たない文字列（文書のタイトル）
+    Title,
+}
+
+/// 配置済みの図形1つ分（座標は図の左上を原点とするpx）
+#[derive(Debug, Clone)]
+pub struct Shape {
+    /// 元のノードのid（matchのアームの箱は `{matchのid}-arm{番号}`、文書のタイトルは `{id}-title`）
+    pub id: String,
+    pub kind: ShapeKind,
+    /// 描画用の分類（`block`、`loop`、`if`、`arm`、`error`、またはノード種別名）
+    pub class: &'static str,
+    pub label: String,
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// 図形の間の線の種類
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConnectorKind {
+    /// 箱から右の列の入れ子の処理へ向かう横線
+    Nest,
+    /// 順次処理の先頭から末尾までの左端をつなぐ縦線
+    Sequence,
+}
+
+/// 配置済みの線1本分
+/// `from` と `to` は両端の図形の `Layout::shapes` での位置です（端に図形がない場合は None）。
+#[derive(Debug, Clone)]
+pub struct Connector {
+    pub kind: ConnectorKind,
+    pub from: Option<usize>,
+    pub to: Option<usize>,
+    /// 順次処理の縦線の場合、各要素の先頭の図形の位置（上から順）
+    pub members: Vec<usize>,
+    pub x1: usize,
+    pub y1: usize,
+    pub x2: usize,
+    pub y2: usize,
+}
+
+/// 配置の結果
+#[derive(Debug, Default)]
+pub struct Layout {
+    /// 図形（行きがけ順）
+    pub shapes: Vec<Shape>,
+    pub connectors: Vec<Connector>,
+    /// 図全体の大きさ（周囲の余白を含みます）
+    pub width: usize,
+    pub height: usize,
+}
+
+/// ノード（通常は関数1つ分）を配置します
+pub fn layout(node: &PadNode) -> Layout {
+    let mut layouter = Layouter { layout: Layout::default(), max_x: 0 };
+    let height = layouter.node(node, MARGIN, MARGIN) - ROW_GAP;
+    let mut layout = layouter.layout;
+    layout.width = layouter.max_x + MARGIN;
+    layout.height = height + MARGIN * 2;
+    layout
+}
+
+/// 配置中の状態（配置済みの図形と、図の右端）
+struct Layouter {
+    layout: Layout,
+    max_x: usize,
+}
+
+impl Layouter {
+    /// ノードを (x, y) を左上として配置し、使用した高さを返します
+    fn node(&mut self, node: &PadNode, x: usize, y: usize) -> usize {
+        let id = &node.meta().id;
+        match node {
+            PadNode::Sequence { children, .. } => self.sequence(children.iter(), x, y),
+            // 文書はタイトルを先頭に置き、その下に中身を並べます
+            PadNode::Document { title, children, .. } => {
+                self.shape(format!("{}-title", id), ShapeKind::Title, "title", title, x - PADDING_X, y);
+                ROW + self.sequence(children.iter(), x, y + ROW)
+            }
+            PadNode::Block { label, children, .. } => {
+                let index = self.shape(id.clone(), ShapeKind::Box, "block", label, x, y);
+                self.nested(index, y, |l, cx| l.sequence(children.iter(), cx, y))
+            }
+            PadNode::Loop { kind, condition, body, .. } => {
+                let label = if condition.is_empty() { "loop" } else { condition.as_str() };
+                let shape = ShapeKind::Loop { do_while: *kind == LoopKind::DoWhile };
+                let index = self.shape(id.clone(), shape, "loop", label, x, y);
+                self.nested(index, y, |l, cx| l.node(body, cx, y))
+            }
+            PadNode::If { condition, then_block, else_block, .. } => {
+                let branches: Vec<(Option<&str>, &PadNode)> =
+                    std::iter::once((None, &**then_block)).chain(else_block.as_deref().map(|e| (None, e))).collect();
+                self.selection(id, x, y, condition, &branches)
+            }
+            PadNode::Match { scrutinee, arms, .. } => {
+                let labels: Vec<String> = arms
+                    .iter()
+                    .map(|arm| match &arm.guard {
+                        Some(guard) => format!("{} if {}", arm.pattern, guard),
+                        None => arm.pattern.clone(),
+                    })
+                    .collect();
+                let branches: Vec<(Option<&str>, &PadNode)> =
+                    labels.iter().zip(arms).map(|(label, arm)| (Some(label.as_str()), &arm.body)).collect();
+                self.selection(id, x, y, scrutinee, &branches)
+            }
+            PadNode::Command { label, .. }
+            | PadNode::Break { label, .. }
+            | PadNode::Continue { label, .. }
+            | PadNode::Yield { label, .. }
+            | PadNode::Return { label, .. } => {
+                self.shape(id.clone(), ShapeKind::Box, node.kind_name(), label, x, y);
+                ROW
+            }
+            PadNode::Error { message, .. } => {
+                self.shape(id.clone(), ShapeKind::Box, "error", message, x, y);
+                ROW
+            }
+        }
+    }
+
+    /// 順次処理を縦に並べ、左端を縦線でつなぎます
+    fn sequence<'a>(&mut self, children: impl Iterator<Item = &'a PadNode>, x: usize, y: usize) -> usize {
+        let mut height = 0;
+        let mut last_top = y;
+        let mut members = Vec::new();
+        for child in children {
+            last_top = y + height;
+            let start = self.layout.shapes.len();
+            height += self.node(child, x, last_top);
+            if self.layout.shapes.len() > start {
+                members.push(start);
+            }
+        }
+        if last_top > y {
+            self.layout.connectors.push(Connector {
+                kind: ConnectorKind::Sequence,
+                from: members.first().copied(),
+                to: members.last().copied(),
+                members,
+                x1: x,
+                y1: y,
+                x2: x,
+                y2: last_top + BOX_HEIGHT,
+            });
+        }
+        height.max(ROW)
+    }
+
+    /// 図形 `from` の右側に入れ子の処理を配置し、箱とつなぎます
+    fn nested(&mut self, from: usize, y: usize, f: impl FnOnce(&mut Self, usize) -> usize) -> usize {
+        let shape = &self.layout.shapes[from];
+        let right = shape.x + shape.width;
+        let child_x = right + COLUMN_GAP;
+        self.connect(from, right, child_x, y, |l| f(l, child_x)).max(ROW)
+    }
+
+    /// 選択（if / match）の箱を置き、各分岐を右側に上から順に配置します
+    /// 分岐にラベル（matchのパターン）がある場合は、分岐の先頭にその箱を置きます。
+    fn selection(&mut self, id: &str, x: usize, y: usize, label: &str, branches: &[(Option<&str>, &PadNode)]) -> usize {
+        // 箱の高さは分岐を配置するまでわからないため、先に置いてから設定します
+        let index = self.shape(id.to_string(), ShapeKind::Selection, "if", label, x, y);
+        let w = self.layout.shapes[index].width;
+        let child_x = x + w + COLUMN_GAP;
+        let mut height = 0;
+        for (number, (branch_label, body)) in branches.iter().enumerate() {
+            let top = y + height;
+            height += self.connect(index, x + w, child_x, top, |l| match branch_label {
+                Some(branch_label) => {
+                    let arm = l.shape(format!("{}-arm{}", id, number), ShapeKind::Box, "arm", branch_label, child_x, top);
+                    l.nested(arm, top, |l, cx| l.node(body, cx, top))
+                }
+                None => l.node(body, child_x, top),
+            });
+        }
+        let height = height.max(ROW);
+        self.layout.shapes[index].height = height - ROW_GAP;
+        height
+    }
+
+    /// 図形 `from` の右辺から `child_x` への横線を引き、`f` で配置した処理の先頭の図形につなぎます
+    fn connect(&mut self, from: usize, right: usize, child_x: usize, y: usize, f: impl FnOnce(&mut Self) -> usize) -> usize {
+        let connector = self.layout.connectors.len();
+        let start = self.layout.shapes.len();
+        self.layout.connectors.push(Connector {
+            kind: ConnectorKind::Nest,
+            from: Some(from),
+            to: None,
+            members: Vec::new(),
+            x1: right,
+            y1: y + BOX_HEIGHT / 2,
+            x2: child_x,
+            y2: y + BOX_HEIGHT / 2,
+        });
+        let height = f(self);
+        if self.layout.shapes.len() > start {
+            self.layout.connectors[connector].to = Some(start);
+        }
+        height
+    }
+
+    /// ラベル付きの図形を置き、その位置を返します
+    fn shape(&mut self, id: String, kind: ShapeKind, class: &'static str, label: &str, x: usize, y: usize) -> usize {
+        let width = box_width(label);
+        self.max_x = self.max_x.max(if kind == ShapeKind::Title { x + PADDING_X } else { x } + width);
+        self.layout.shapes.push(Shape { id, kind, class, label: label.to_string(), x, y, width, height: BOX_HEIGHT });
+        self.layout.shapes.len() - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::BlockKind;
+
+    #[test]
+    fn connectors_link_parent_boxes_to_their_first_child() {
+        let mut root = PadNode::block(
+            BlockKind::Function,
+            "fn main()",
+            vec![PadNode::sequence(vec![
+                PadNode::command("a"),
+                PadNode::if_node("x", PadNode::sequence(vec![PadNode::command("b")]), None),
+            ])],
+        );
+        crate::ids::assign_ids(&mut root);
+        let layout = layout(&root);
+        let ids: Vec<&str> = layout.shapes.iter().map(|shape| shape.id.as_str()).collect();
+        assert_eq!(ids, ["n0", "n2", "n3", "n5"]);
+        let links: Vec<(ConnectorKind, Option<usize>, Option<usize>)> =
+            layout.connectors.iter().map(|c| (c.kind, c.from, c.to)).collect();
+        assert_eq!(
+            links,
+            [
+                (ConnectorKind::Nest, Some(0), Some(1)),
+                (ConnectorKind::Nest, Some(2), Some(3)),
+                (ConnectorKind::Sequence, Some(1), Some(2)),
+            ]
+        );
+        assert_eq!(layout.shapes[2].kind, ShapeKind::Selection);
+        assert_eq!(layout.height, ROW * 2 - ROW_GAP + MARGIN * 2);
+    }
+}
@@ -1,7 +1,66 @@
-mod model;
-use model::PadNode;
+mod cache;
+mod callgraph;
+mod clones;
+mod collapse;
+mod comments;
+mod constant;
+mod convert;
+mod coverage;
+mod diagnostics;
+mod diff;
+mod document;
+mod dot;
+mod effects;
+mod envelope;
+mod excalidraw;
+mod fallback;
+mod graph;
+mod graphml;
+mod html;
+mod ids;
+mod jsoncanvas;
+mod layout;
+mod lint;
+mod listing;
+mod macros;
+mod markdown;
+mod measure;
+mod merge;
+mod mermaid;
+mod metrics;
+mod naming;
+pub mod model;
+pub mod options;
+mod order;
+mod outline;
+mod plantuml;
+mod profile;
+mod project;
+mod preprocess;
+mod pretty;
+mod pseudocode;
+mod query;
+mod regex;
+mod skeleton;
+mod sourcemap;
+mod stats;
+#[cfg(feature = "std-docs")]
+mod std_docs;
+mod subtree;
+mod svg;
+mod tikz;
+mod tokens;
+mod truncate;
+pub mod visit;
+mod v2;
+mod validate;
+mod width;
+use convert::{Converter, group_impls_by_type};
+use model::{BlockKind, DocumentInfo, PadNode};
+use options::{FieldNaming, IdScheme, ParseMode, ParseOptions};
 use wasm_bindgen::prelude::*;
-use syn::{parse_str, Block, Expr, Item, ItemFn, Stmt};
+use syn::parse::Parser;
+use syn::{parse_str, Block, File, Item, Stmt};
 
 #[wasm_bindgen]
 pub fn add(left: u64, right: u64) -> u64 {
@@ -12,143 +71,3202 @@ pub fn add(left: u64, right: u64) -> u64 {
 /// wasm-bindgenを通してJavaScriptから呼び出されます。
 #[wasm_bindgen]
 pub fn parse_rust_code(code: &str) -> String {
-    // synクレートを使用して文字列としてのコードをRustの構文木（File）にパースしようと試みます
-    match parse_str::<File>(code) {
-        Ok(file) => {
-            let mut nodes = Vec::new();
-            // ファイル内のアイテム（関数など）を走査
-            for item in file.items {
-                if let Item::Fn(func) = item {
-                   // 関数定義を見つけたらPADノードに変換
-                   nodes.push(parse_function(func));
-                }
-            }
-            if nodes.is_empty() {
-                 serde_json::to_string(&PadNode::Error{ message: "No function found".to_string() }).unwrap()
-            } else {
-                 // 複数の関数がある場合も想定し、全体をSequenceとして返します
-                 // これにより、フロントエンドは複数の関数ブロックを順に描画できます
-                 serde_json::to_string(&PadNode::Sequence { children: nodes }).unwrap_or_else(|e| {
-                    format!("{{\"type\": \"error\", \"message\": \"Serialization error: {}\"}}", e)
-                 })
-            }
+    convert_code(code, &ParseOptions::default())
+}
+
+/// インデントで構造を表す擬似コードを、Rustの場合と同じ形式のPAD表示用のJSON文字列に変換します
+/// 書き方は `pseudocode` モジュールを参照してください。解析できない場合は、問題のある行の位置を持つErrorノードを返します。
+#[wasm_bindgen]
+pub fn parse_pseudocode(text: &str) -> String {
+    let mut tree = pseudocode::parse(text);
+    if let PadNode::Error { .. } = tree {
+        return to_json(&tree);
+    }
+    ids::assign_ids(&mut tree);
+    let warnings = lint::lint(&tree, &ParseOptions::default().lint);
+    if let PadNode::Sequence { info, .. } = &mut tree {
+        info.get_or_insert_default().warnings = warnings;
+    }
+    to_json(&tree)
+}
+
+/// Markdownに埋め込まれた `rust` のコードブロックをそれぞれ変換し、`{line, title, tree}` の配列をJSON文字列で返します
+/// `line` は開始のフェンスの行、`title` は直前の見出しです。Rust以外のコードブロックは無視します。
+/// `fn main` のない例も図にできるよう、ファイルとして解析できない場合はスクリプトモードで解析し直します。
+/// 解析できないコードブロックは `tree` がErrorノードになり、残りのコードブロックの変換は続けます。
+#[wasm_bindgen]
+pub fn parse_markdown(text: &str) -> String {
+    let diagrams: Vec<markdown::FenceDiagram> = markdown::rust_fences(text)
+        .into_iter()
+        .map(|fence| markdown::FenceDiagram { line: fence.line, title: fence.title, tree: build_fence_tree(&fence.code) })
+        .collect();
+    serde_json::to_string(&diagrams).unwrap()
+}
+
+fn build_fence_tree(code: &str) -> PadNode {
+    let tree = build_tree(code, &ParseOptions::default());
+    if !matches!(tree, PadNode::Error { .. }) {
+        return tree;
+    }
+    let script = build_tree(code, &ParseOptions { mode: ParseMode::Script, ..ParseOptions::default() });
+    if matches!(script, PadNode::Error { .. }) { tree } else { script }
+}
+
+/// 解析オプション（JSON文字列）を指定してRustコードをPAD表示用のJSON文字列に変換します
+/// オプションのJSONが不正な場合はErrorノードを返します。
+#[wasm_bindgen]
+pub fn parse_rust_code_with_options(code: &str, options_json: &str) -> String {
+    match ParseOptions::from_json(options_json) {
+        Ok(options) => convert_code(code, &options),
+        Err(message) => to_json(&PadNode::error(message)),
+    }
+}
+
+/// 複数ファイルのRustコード（`{ "src/lib.rs": "...", ... }` のJSON文字列）を、1つのPAD表示用のJSON文字列に変換します
+/// `mod foo;` の宣言を `foo.rs` / `foo/mod.rs`（`#[path]` があればそのパス）のファイルに対応付け、
+/// ファイルごとのBlock（`kind: "file"`、`label` はパス、`module_path` はモジュールのパス）をモジュールの親子の順に入れ子にします。
+/// ファイルをまたぐ関数の呼び出しも `calls` で対応付けます。JSONが不正な場合はErrorノードを返します。
+#[wasm_bindgen]
+pub fn parse_rust_project(files_json: &str) -> String {
+    parse_rust_project_with_options(files_json, "")
+}
+
+/// 解析オプション（JSON文字列）を指定して、複数ファイルのRustコードを変換します
+#[wasm_bindgen]
+pub fn parse_rust_project_with_options(files_json: &str, options_json: &str) -> String {
+    let options = match ParseOptions::from_json(options_json) {
+        Ok(options) => options,
+        Err(message) => return to_json(&PadNode::error(message)),
+    };
+    match project::parse_files(files_json) {
+        Ok(files) => render_value(serde_json::to_value(build_project_tree(&files, &options)).unwrap(), &options),
+        Err(message) => to_json(&PadNode::error(message)),
+    }
+}
+
+/// ファイルごとに変換したツリーをまとめ、ツリー全体でidの割り当てと参照の対応付けをやり直します
+fn build_project_tree(files: &std::collections::BTreeMap<String, String>, options: &ParseOptions) -> PadNode {
+    let mut tree = project::build(files, options);
+    match options.id_scheme {
+        IdScheme::Sequential => ids::assign_ids(&mut tree),
+        IdScheme::Stable => ids::assign_stable_ids(&mut tree),
+    }
+    ids::link_jump_targets(&mut tree);
+    ids::link_returns(&mut tree);
+    ids::link_calls(&mut tree);
+    let info = model::DocumentInfo {
+        warnings: lint::lint(&tree, &options.lint),
+        fallback_report: fallback::report(&tree),
+        ..model::DocumentInfo::default()
+    };
+    if let PadNode::Sequence { info: slot, .. } = &mut tree {
+        *slot = Some(Box::new(info));
+    }
+    tree
+}
+
+/// Rustコードのうち、名前で指定した1つの関数のBlockだけをPAD表示用のJSON文字列で返します
+/// 関数は `main`、メソッドは `MyStruct::method`（トレイトの既定のメソッドは `MyTrait::method`）のように指定します。
+/// ジェネリクスの引数は省略でき、`net::send` のようにモジュールのパスで修飾することもできます（`crate::` から始まる場合は完全なパスで比べます）。
+/// ノードidはファイル全体を変換した場合と同じです。該当する関数がない場合は `kind` が `not_found`、
+/// 複数の関数が該当する場合は `kind` が `ambiguous` で候補を並べたErrorノードを返します。
+#[wasm_bindgen]
+pub fn parse_function_by_name(code: &str, fn_name: &str) -> String {
+    parse_function_by_name_with_options(code, fn_name, "")
+}
+
+/// 解析オプション（JSON文字列）を指定して、名前で指定した1つの関数を変換します
+#[wasm_bindgen]
+pub fn parse_function_by_name_with_options(code: &str, fn_name: &str, options_json: &str) -> String {
+    let options = match ParseOptions::from_json(options_json) {
+        Ok(options) => options,
+        Err(message) => return to_json(&PadNode::error(message)),
+    };
+    let tree = build_tree(code, &options);
+    if let PadNode::Error { .. } = tree {
+        return to_json(&tree);
+    }
+    let mut functions = Vec::new();
+    collect_functions(&tree, "", &mut functions);
+    let query: String = ids::strip_generics(fn_name).split_whitespace().collect();
+    let matches: Vec<&(String, &PadNode)> = match query.strip_prefix("crate::") {
+        Some(path) => functions.iter().filter(|(name, _)| name == path).collect(),
+        None => functions.iter().filter(|(name, _)| name == &query || name.ends_with(&format!("::{}", query))).collect(),
+    };
+    match matches.as_slice() {
+        [(_, function)] => render_value(serde_json::to_value(function).unwrap(), &options),
+        [] => to_json(&PadNode::error_of(model::ErrorKind::NotFound, format!("Function not found: {}", fn_name))),
+        candidates => {
+            let candidates: Vec<String> = candidates
+                .iter()
+                .map(|(name, function)| match &function.meta().span {
+                    Some(span) => format!("{} (line {})", name, span.start_line),
+                    None => name.clone(),
+                })
+                .collect();
+            let message = format!("Function name is ambiguous: {} matches {}", fn_name, candidates.join(", "));
+            to_json(&PadNode::error_of(model::ErrorKind::Ambiguous, message))
         }
-        Err(e) => {
-             let msg = format!("Parse error: {}", e);
-             serde_json::to_string(&PadNode::Error{ message: msg }).unwrap()
+    }
+}
+
+/// 関数のBlockを、モジュールのパスで修飾した名前（`net::Client::send`、ジェネリクスの引数を除きます）と一緒に集めます
+/// モジュールのパスは、`group_by: "module"` のモジュールのBlockと、解いたモジュールの中のBlockの `module_path` から求めます。
+fn collect_functions<'a>(node: &'a PadNode, module: &str, functions: &mut Vec<(String, &'a PadNode)>) {
+    let join = |inner: &str| if module.is_empty() { inner.to_string() } else { format!("{}::{}", module, inner) };
+    let module = match node {
+        PadNode::Block { kind: BlockKind::Module, label, .. } => join(label.strip_prefix("mod ").unwrap_or(label)),
+        PadNode::Block { kind: BlockKind::File, module_path: Some(path), .. } => {
+            path.strip_prefix("crate").unwrap_or(path).trim_start_matches("::").to_string()
         }
+        PadNode::Block { module_path: Some(path), .. } => join(path),
+        _ => module.to_string(),
+    };
+    if let Some(name) = ids::function_name(node) {
+        let name = if module.is_empty() { name } else { format!("{}::{}", module, name) };
+        functions.push((name, node));
+    }
+    for child in node.children() {
+        collect_functions(child, &module, functions);
+    }
+}
+
+/// Rustコードを解析し、PADノードのツリーを返します
+/// wasm-bindgenを通さずにRustから直接使うためのもので、ビルドスクリプトやCIでの利用を想定しています。
+/// JSON文字列を作らないため、`field_naming` と `cache` のオプションは使いません。
+pub fn parse(code: &str, options: &ParseOptions) -> PadNode {
+    build_tree(code, options)
+}
+
+/// PADノードのツリーを、PlantUMLのアクティビティ図のテキストに変換します
+/// `parse` で得たツリー（またはその一部）を、Rustから直接書き出すためのものです。
+pub fn render_plantuml(tree: &PadNode) -> String {
+    plantuml::to_plantuml(tree)
+}
+
+/// UTF-8のバイト列（JavaScriptの `Uint8Array`）で受け取ったRustコードをPAD表示用のJSON文字列に変換します
+/// 大きな入力を文字列に変換して渡す際のコピーを避けるためのものです。先頭のBOMは取り除きます。
+/// UTF-8として不正な場合は、最初の不正なバイトの位置（`byte_offset`）と行・桁を持つErrorノードを返します。
+#[wasm_bindgen]
+pub fn parse_rust_code_bytes(code: &[u8]) -> String {
+    convert_bytes(code, &ParseOptions::default())
+}
+
+/// 解析オプション（JSON文字列）を指定して、バイト列で受け取ったRustコードを変換します
+/// `lossy_utf8` オプションを指定すると、不正なバイト列を U+FFFD に置き換えて解析を続けます。
+#[wasm_bindgen]
+pub fn parse_rust_code_bytes_with_options(code: &[u8], options_json: &str) -> String {
+    match ParseOptions::from_json(options_json) {
+        Ok(options) => convert_bytes(code, &options),
+        Err(message) => to_json(&PadNode::error(message)),
     }
 }
 
-use syn::File;
+fn convert_bytes(bytes: &[u8], options: &ParseOptions) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(code) => convert_code(code, options),
+        Err(_) if options.lossy_utf8 => convert_code(&String::from_utf8_lossy(bytes), options),
+        Err(e) => to_json(&diagnostics::invalid_utf8(bytes, e)),
+    }
+}
 
-/// 関数定義（ItemFn）を解析し、PADのBlockノードを作成します
-fn parse_function(func: ItemFn) -> PadNode {
-    let name = func.sig.ident.to_string();
-    // 関数本体のブロックを解析
-    let body = parse_block(*func.block);
-    PadNode::Block {
-        label: format!("fn {}()", name), // ラベルとして関数名を使用
-        children: vec![body],
+/// Rustコードをスキーマv2の形式（Ifの `then` / `else` とLoopの `body` が子ノードの配列）のJSON文字列に変換します
+/// オプションは `parse_rust_code_with_options` と同じです。v1の出力は変わりません。
+#[wasm_bindgen]
+pub fn parse_rust_code_v2(code: &str, options_json: &str) -> String {
+    match ParseOptions::from_json(options_json) {
+        Ok(options) => {
+            let mut value = serde_json::to_value(build_tree(code, &options)).unwrap();
+            v2::to_v2(&mut value);
+            render_value(value, &options)
+        }
+        Err(message) => to_json(&PadNode::error(message)),
     }
 }
 
-/// コードブロック（{}で囲まれた部分）を解析し、Sequenceノードを作成します
-fn parse_block(block: Block) -> PadNode {
-    let mut children = Vec::new();
-    // ブロック内の各ステートメント（文）を順に解析
-    for stmt in block.stmts {
-        children.push(parse_stmt(stmt));
+/// Rustコードを解析し、成否とエラーの一覧を分けたエンベロープ `{ok, tree, errors}` のJSON文字列を返します
+/// 各エラーは `message`・`line`・`column`（1始まり）と `kind`（`syntax` / `nesting` / `encoding` / `options` / `empty`）を持ちます。
+/// 入力全体を解析できなかった場合は `tree` を省略します。オプションは `parse_rust_code_with_options` と同じです。
+#[wasm_bindgen]
+pub fn parse_rust_code_result(code: &str, options_json: &str) -> String {
+    match ParseOptions::from_json(options_json) {
+        Ok(options) => {
+            let envelope = envelope::Envelope::from_tree(build_tree(code, &options));
+            render_value(serde_json::to_value(envelope).unwrap(), &options)
+        }
+        Err(message) => serde_json::to_string(&envelope::Envelope::invalid_options(message)).unwrap(),
     }
-    PadNode::Sequence { children }
 }
 
-/// 個々のステートメント（文）を解析し、適切なPADノードに変換します
-fn parse_stmt(stmt: Stmt) -> PadNode {
-    match stmt {
-        Stmt::Local(local) => {
-             // ローカル変数定義（let x = ...;）
-             // quote!マクロを使って元のソースコード表現に戻し、Commandノードとします
-             let parsed = quote::quote!(#local).to_string();
-             PadNode::Command { label: parsed }
+/// スキーマv2形式のJSON文字列をv1形式に戻します（保存済みのv2文書を従来の描画処理に渡す場合など）
+/// JSONとして読めない場合はErrorノードを返します。
+#[wasm_bindgen]
+pub fn pad_v2_to_v1(json: &str) -> String {
+    match serde_json::from_str::<serde_json::Value>(json) {
+        Ok(mut value) => {
+            v2::to_v1(&mut value);
+            value.to_string()
         }
-        Stmt::Item(_item) => PadNode::Command { label: "Inner item not supported".to_string() },
-        Stmt::Expr(expr, _semi) => {
-             // 式（if, while, 関数呼び出しなど）
-             parse_expr(expr)
+        Err(e) => to_json(&PadNode::error(format!("Invalid JSON: {}", e))),
+    }
+}
+
+/// フロントエンドで作成されたPADツリー（v1形式のJSON文字列）を検証し、結果をJSON文字列で返します
+/// 結果は `{valid, errors, warnings}` の形式で、各問題はJSONパス（`path`）と説明（`message`）を持ちます。
+#[wasm_bindgen]
+pub fn validate_pad_json(json: &str) -> String {
+    serde_json::to_string(&validate::validate(json)).unwrap()
+}
+
+/// PADツリー（v1形式のJSON文字列）から、Rustのコードの骨組みを作成します
+/// 関数・分岐・反復・matchの構造をRustの構文で書き出し、処理は `todo!("ラベル")` にします。
+/// 図をフロントエンドで編集した後、コードに戻す場合に使います。JSONとして読み込めない場合はErrorノードのJSONを返します。
+#[wasm_bindgen]
+pub fn pad_to_rust(json: &str) -> String {
+    match serde_json::from_str::<PadNode>(json) {
+        Ok(tree) => skeleton::to_rust(&tree),
+        Err(e) => to_json(&PadNode::error(format!("Invalid JSON: {}", e))),
+    }
+}
+
+/// 複数のPADツリーを1つの文書（ルートのSequence）に結合し、`{document, remapped}` をJSON文字列で返します
+/// 各要素はJSON文字列、または `{ tree, title }`（`title` を指定するとその名前のグループで包みます）です。
+/// 重複したノードidは付け替えられ、その対応が `remapped` に入ります。
+#[wasm_bindgen]
+pub fn merge_pads(jsons: JsValue) -> String {
+    merge_pads_json(&json_stringify(&jsons))
+}
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = JSON, js_name = stringify)]
+    fn json_stringify(value: &JsValue) -> String;
+}
+
+/// `merge_pads` の本体（入力は文字列化したJSON配列）
+fn merge_pads_json(json: &str) -> String {
+    match merge::parse_inputs(json).and_then(merge::merge) {
+        Ok(result) => serde_json::to_string(&result).unwrap(),
+        Err(message) => to_json(&PadNode::error(message)),
+    }
+}
+
+/// 大きなファイルを、トップレベルのアイテムごとに変換しながら返します
+/// 構文解析は一度だけ行い、アイテムを1つ変換するたびに `on_item(index, total, item_json)` を呼び出します。
+/// 構文エラーのあるアイテムは、その位置のErrorノードとして同じコールバックに渡します。
+/// 戻り値は `{total, errors, warnings, filtered}` の集計のJSON文字列で、ノードidはファイル全体を変換した場合と同じです。
+/// コールバックが例外を投げた場合は、そこで変換を打ち切ってその例外を投げます。
+#[wasm_bindgen]
+pub fn parse_rust_code_streaming(code: &str, on_item: &JsFunction) -> Result<String, JsValue> {
+    stream_items(code, &ParseOptions::default(), |index, total, json| {
+        on_item.call3(&JsValue::NULL, &JsValue::from(index as u32), &JsValue::from(total as u32), &JsValue::from(json))?;
+        Ok(())
+    })
+}
+
+#[wasm_bindgen]
+extern "C" {
+    /// JavaScriptの関数（呼び出しに必要なメソッドだけを宣言しています）
+    #[wasm_bindgen(js_name = Function)]
+    pub type JsFunction;
+
+    #[wasm_bindgen(method, catch, js_name = call)]
+    fn call3(this: &JsFunction, context: &JsValue, a: &JsValue, b: &JsValue, c: &JsValue) -> Result<JsValue, JsValue>;
+}
+
+/// `parse_rust_code_streaming` の本体（コールバックはアイテムの位置・総数・JSON文字列を受け取ります）
+fn stream_items<E>(
+    code: &str,
+    options: &ParseOptions,
+    mut on_item: impl FnMut(usize, usize, String) -> Result<(), E>,
+) -> Result<String, E> {
+    if let Some(diagnostic) = diagnostics::nesting_too_deep(code, options.max_depth) {
+        let mut node = diagnostics::error_node(diagnostic.clone());
+        ids::assign_ids_from(&mut node, 1);
+        on_item(0, 1, to_json(&node))?;
+        return Ok(serde_json::json!({ "total": 1, "errors": [diagnostic], "warnings": [], "filtered": 0 }).to_string());
+    }
+    // ファイル全体を解析できない場合は、アイテムごとに解析し直します（`recover` オプションと同じ分け方です）
+    let parsed: Vec<Result<Item, syn::Error>> = match parse_str::<File>(code) {
+        Ok(file) => file.items.into_iter().map(Ok).collect(),
+        Err(error) => match diagnostics::split_items(code) {
+            Some(chunks) => chunks.into_iter().map(syn::parse2::<Item>).collect(),
+            None => vec![Err(error)],
         },
-        Stmt::Macro(mac) => {
-             // マクロ呼び出し（println!など）
-             let parsed = quote::quote!(#mac).to_string();
-            PadNode::Command { label: parsed }
-        }
-    }
-}
-
-/// 式（Expr）を解析し、制御構造（If, While, For）や単純なコマンドに分類します
-fn parse_expr(expr: Expr) -> PadNode {
-    match expr {
-        Expr::If(expr_if) => {
-             // if文の解析
-             let cond = &expr_if.cond;
-             let cond_str = quote::quote!(#cond).to_string(); 
-             
-             // Then節（真の場合）
-             let then_node = parse_block(expr_if.then_branch);
-             
-             // Else節（偽の場合）
-             let else_node = if let Some((_, else_branch)) = expr_if.else_branch {
-                 Some(Box::new(parse_expr(*else_branch)))
-             } else {
-                 None
-             };
-             
-             PadNode::If {
-                 condition: cond_str.replace(" . ", "."), // quote!の出力調整（ドットの前後のスペース除去など）
-                 then_block: Box::new(then_node),
-                 else_block: else_node,
-             }
-        }
-        Expr::While(expr_while) => {
-            // while文の解析
-            let cond = &expr_while.cond;
-            let cond_str = quote::quote!(#cond).to_string();
-            let body = parse_block(expr_while.body);
-            PadNode::Loop {
-                condition: cond_str,
-                body: Box::new(body),
+    };
+    let items: Vec<Item> = parsed.iter().filter_map(|item| item.as_ref().ok().cloned()).collect();
+    let converter = Converter::new(code, &items, options);
+    let drawn: Vec<&Result<Item, syn::Error>> = parsed
+        .iter()
+        .filter(|item| match item {
+            Ok(item) => converter.includes_item(item) && converter.draws_item(item),
+            Err(_) => true,
+        })
+        .collect();
+    let total = drawn.len();
+    // ルートのSequenceが `n0` になるため、アイテムには `n1` から割り当てます
+    let mut next_id = 1;
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    for (index, item) in drawn.into_iter().enumerate() {
+        let mut node = match item {
+            Ok(item) => converter.parse_item(item).unwrap_or_else(|| PadNode::sequence(Vec::new())),
+            Err(e) => {
+                errors.extend(diagnostics::diagnostics(code, e.clone()));
+                diagnostics::parse_error(code, e)
             }
+        };
+        next_id = ids::assign_ids_from(&mut node, next_id);
+        ids::link_jump_targets(&mut node);
+        ids::link_returns(&mut node);
+        ids::link_calls(&mut node);
+        if options.width_hints {
+            width::apply_width_hints(&mut node);
+        }
+        warnings.extend(lint::lint(&node, &options.lint));
+        on_item(index, total, to_json(&node))?;
+    }
+    Ok(serde_json::json!({
+        "total": total,
+        "errors": errors,
+        "warnings": warnings,
+        "filtered": converter.filtered_count(),
+    })
+    .to_string())
+}
+
+/// ノードidで部分木を取り出し、`{found, node, ancestors}` をJSON文字列で返します
+/// 入力はシリアライズ済みのツリー（JSON）またはRustのソースコードです。ソースコードの場合は解析してから取り出します。
+/// 見つからない場合は `{found: false, error: {code, message}}` を返します。
+#[wasm_bindgen]
+pub fn get_subtree(json_or_code: &str, id: &str) -> String {
+    with_loaded_tree(json_or_code, &ParseOptions::default(), |tree| {
+        serde_json::to_string(&subtree::by_id(tree, id)).unwrap()
+    })
+}
+
+/// `"0/2/1"` のような子ノードの位置の並びで部分木を取り出します（結果の形式は `get_subtree` と同じです）
+#[wasm_bindgen]
+pub fn get_subtree_by_path(json_or_code: &str, path: &str) -> String {
+    with_loaded_tree(json_or_code, &ParseOptions::default(), |tree| {
+        serde_json::to_string(&subtree::by_path(tree, path)).unwrap()
+    })
+}
+
+/// 図の深さ・葉の数・深さごとのノード数と、外接矩形の見積もり（列数・行数・幅）をJSON文字列で返します
+/// 入力はシリアライズ済みのツリー（JSON）またはRustのソースコードです。
+/// 幅はノードの `width_hint` から求めるため、ソースコードの場合は `width_hints` を有効にして解析します。
+/// 解析できない場合は、測らずにErrorノードのJSONを返します。
+#[wasm_bindgen]
+pub fn measure(code_or_json: &str) -> String {
+    let options = ParseOptions { width_hints: true, ..ParseOptions::default() };
+    with_loaded_tree(code_or_json, &options, |tree| match tree {
+        PadNode::Error { .. } => to_json(tree),
+        _ => serde_json::to_string(&measure::measure(tree)).unwrap(),
+    })
+}
+
+/// Rustコードを、ツリー全体を描いた単体のSVG文書に変換します
+/// 配置（箱の大きさ・入れ子の位置）もバックエンドで求めるため、JavaScript側で図形を計算せずにそのまま表示・保存できます。
+#[wasm_bindgen]
+pub fn to_svg(code: &str) -> String {
+    svg::render_svg(&build_tree(code, &ParseOptions::default()))
+}
+
+/// 解析オプション（JSON文字列）を指定して、単体のSVG文書に変換します
+/// オプションのJSONが不正な場合は、Errorノード1つだけの図を返します。
+#[wasm_bindgen]
+pub fn to_svg_with_options(code: &str, options_json: &str) -> String {
+    match ParseOptions::from_json(options_json) {
+        Ok(options) => svg::render_svg(&build_tree(code, &options)),
+        Err(message) => svg::render_svg(&PadNode::error(message)),
+    }
+}
+
+/// Rustコードを、関数ごとのSVG図と元のソースコードを埋め込んだ単体のHTML文書に変換します
+/// 外部のファイルを参照しないため、そのまま保存してオフラインで開けます。
+#[wasm_bindgen]
+pub fn to_html(code: &str) -> String {
+    html::to_html(&build_tree(code, &ParseOptions::default()), code)
+}
+
+/// Rustコードを、Excalidrawで開いて編集できるシーン（`.excalidraw` のJSON文字列）に変換します
+/// 箱の配置はSVGと同じで、ラベルは箱に結び付けた文字列、入れ子・順次のつながりは箱に結び付けた矢印になります。
+#[wasm_bindgen]
+pub fn to_excalidraw(code: &str) -> String {
+    excalidraw::to_excalidraw(&build_tree(code, &ParseOptions::default()))
+}
+
+/// Rustコードを、JSON Canvas（Obsidianなどの `.canvas`）の文書に変換します
+/// 関数ごとに "group" ノードで囲み、箱は配置どおりの位置のテキストノード、つながりはエッジになります。
+#[wasm_bindgen]
+pub fn to_jsoncanvas(code: &str) -> String {
+    jsoncanvas::to_jsoncanvas(&build_tree(code, &ParseOptions::default()))
+}
+
+/// Rustコードを、平坦なグラフ `{nodes, edges}` のJSON文字列に変換します
+/// 各ノードはid・種別・ラベル・親のidを持ち、エッジの種類（`seq`、`then`、`else`、`body`、`child`）と順序からツリーを復元できます。
+#[wasm_bindgen]
+pub fn to_graph_json(code: &str) -> String {
+    serde_json::to_string(&graph::to_graph(&build_tree(code, &ParseOptions::default()))).unwrap()
+}
+
+/// Rustコードを、yEdやNetworkXで読み込めるGraphMLの文書に変換します
+/// ノードは頂点（ラベル・種別・位置・関数名の属性付き）、順次・分岐・反復のつながりは有向辺になり、Blockは入れ子のグラフになります。
+#[wasm_bindgen]
+pub fn to_graphml(code: &str) -> String {
+    graphml::to_graphml(&build_tree(code, &ParseOptions::default()))
+}
+
+/// Rustコードを、Graphvizの `dot` で描画できるDOTの有向グラフに変換します
+/// 関数などのBlockはクラスタ、条件はひし形、反復は六角形の頂点になり、ループの末尾から先頭へ戻る辺は破線になります。
+#[wasm_bindgen]
+pub fn to_dot(code: &str) -> String {
+    dot::to_dot(&build_tree(code, &ParseOptions::default()))
+}
+
+/// 解析オプション（JSON文字列）を指定してDOTの有向グラフに変換します
+/// オプションのJSONが不正な場合は、Errorノード1つだけのグラフを返します。
+#[wasm_bindgen]
+pub fn to_dot_with_options(code: &str, options_json: &str) -> String {
+    match ParseOptions::from_json(options_json) {
+        Ok(options) => dot::to_dot(&build_tree(code, &options)),
+        Err(message) => {
+            let mut tree = PadNode::error(message);
+            ids::assign_ids(&mut tree);
+            dot::to_dot(&tree)
         }
-        Expr::ForLoop(expr_for) => {
-             // forループの解析
-             let pat = quote::quote!(#expr_for.pat).to_string(); // パターン（例: i）
-             let expr = quote::quote!(#expr_for.expr).to_string(); // 反復対象（例: 0..10）
-             let body = parse_block(expr_for.body);
-             PadNode::Loop {
-                 condition: format!("for {} in {}", pat, expr),
-                 body: Box::new(body),
-             }
+    }
+}
+
+/// Rustコードを、Mermaidのフローチャート（`flowchart TD`）に変換します
+/// 関数などのBlockはサブグラフ、分岐はひし形、反復は六角形の頂点になり、ループの末尾から先頭へ戻る辺は点線になります。
+#[wasm_bindgen]
+pub fn to_mermaid(code: &str) -> String {
+    mermaid::to_mermaid(&build_tree(code, &ParseOptions::default()))
+}
+
+/// 解析オプション（JSON文字列）を指定してMermaidのフローチャートに変換します
+/// オプションのJSONが不正な場合は、Errorノード1つだけの図を返します。
+#[wasm_bindgen]
+pub fn to_mermaid_with_options(code: &str, options_json: &str) -> String {
+    match ParseOptions::from_json(options_json) {
+        Ok(options) => mermaid::to_mermaid(&build_tree(code, &options)),
+        Err(message) => {
+            // Mermaidの頂点にはidが必要なため、Errorノードにも割り当てます
+            let mut tree = PadNode::error(message);
+            ids::assign_ids(&mut tree);
+            mermaid::to_mermaid(&tree)
         }
-        Expr::Block(expr_block) => {
-            // 内側のブロック（スコープ作成など）
-            parse_block(expr_block.block)
+    }
+}
+
+/// Rustコードを、PlantUMLのアクティビティ図（`@startuml` 〜 `@enduml`）に変換します
+/// 関数ごとに `start` 〜 `stop` の流れを作り、分岐は `if`、前判定の反復は `while`、後判定の反復は `repeat`、matchは `switch` になります。
+#[wasm_bindgen]
+pub fn to_plantuml(code: &str) -> String {
+    plantuml::to_plantuml(&build_tree(code, &ParseOptions::default()))
+}
+
+/// 解析オプション（JSON文字列）を指定してPlantUMLのアクティビティ図に変換します
+/// オプションのJSONが不正な場合は、Errorノード1つだけの図を返します。
+#[wasm_bindgen]
+pub fn to_plantuml_with_options(code: &str, options_json: &str) -> String {
+    match ParseOptions::from_json(options_json) {
+        Ok(options) => plantuml::to_plantuml(&build_tree(code, &options)),
+        Err(message) => plantuml::to_plantuml(&PadNode::error(message)),
+    }
+}
+
+/// Rustコードを、LaTeXの文書に埋め込める `tikzpicture` に変換します
+/// 箱と線の位置はSVGと同じで、ラベルはLaTeX用にエスケープします。
+#[wasm_bindgen]
+pub fn to_tikz(code: &str) -> String {
+    tikz::to_tikz(&build_tree(code, &ParseOptions::default()), false)
+}
+
+/// 解析オプション（JSON文字列）を指定して `tikzpicture` に変換します
+/// `standalone` を有効にすると、そのままコンパイルできる文書（`standalone` クラス）にします。
+#[wasm_bindgen]
+pub fn to_tikz_with_options(code: &str, options_json: &str) -> String {
+    match ParseOptions::from_json(options_json) {
+        Ok(options) => tikz::to_tikz(&build_tree(code, &options), options.standalone),
+        Err(message) => tikz::to_tikz(&PadNode::error(message), false),
+    }
+}
+
+/// 解析オプション（JSON文字列）を指定してHTML文書に変換します
+/// `document` を有効にすると、`title`（または `source_name`）を文書のタイトルにします。
+#[wasm_bindgen]
+pub fn to_html_with_options(code: &str, options_json: &str) -> String {
+    match ParseOptions::from_json(options_json) {
+        Ok(options) => html::to_html(&build_tree(code, &options), code),
+        Err(message) => html::to_html(&PadNode::error(message), code),
+    }
+}
+
+/// 条件に一致するノードを検索し、`{matches: [{id, type, path, label, span}]}` をJSON文字列で返します
+/// 条件はJSONオブジェクトで `{"type": "loop", "label_contains": "retries", "min_depth": 2}` のように指定します
+/// （ほかに `label_regex`, `max_depth`, `case_sensitive` を指定できます）。
+/// `span` はソースコードを入力した場合にだけ付きます。
+#[wasm_bindgen]
+pub fn find_nodes(code_or_json: &str, query: &str) -> String {
+    let query = match query::parse_query(query) {
+        Ok(query) => query,
+        Err(message) => return to_json(&PadNode::error(message)),
+    };
+    with_loaded_tree(code_or_json, &ParseOptions::default(), |tree| match query::find_nodes(tree, &query) {
+        Ok(matches) => serde_json::json!({ "matches": matches }).to_string(),
+        Err(message) => to_json(&PadNode::error(message)),
+    })
+}
+
+/// ソース上のバイトオフセットを含む最も内側のノードと、その祖先をJSON文字列で返します
+/// 結果は `{id, type, range, ancestors}` の形式で、`range` はバイトとUTF-16の両方の範囲です。
+#[wasm_bindgen]
+pub fn node_at_offset(code: &str, offset: usize) -> String {
+    node_at(code, offset, sourcemap::OffsetUnit::Byte)
+}
+
+/// `node_at_offset` と同じですが、オフセットをUTF-16のコードユニット数（JavaScriptの文字列の添字）で指定します
+#[wasm_bindgen]
+pub fn node_at_utf16_offset(code: &str, offset: usize) -> String {
+    node_at(code, offset, sourcemap::OffsetUnit::Utf16)
+}
+
+fn node_at(code: &str, offset: usize, unit: sourcemap::OffsetUnit) -> String {
+    let tree = build_tree(code, &ParseOptions::default());
+    match sourcemap::node_at_offset(&tree, code, offset, unit) {
+        Ok(node) => serde_json::to_string(&node).unwrap(),
+        Err(message) => to_json(&PadNode::error(message)),
+    }
+}
+
+/// バイトオフセットを囲む構造（関数・ループ・if・match・matchのアーム）を外側から順に並べたJSON配列を返します
+/// エディタのパンくずリスト用です。各要素は `{id, type, kind?, label, range}` で、最も内側の構造が最後になります。
+/// カーソル移動のたびに呼び出せるよう、直前と同じコードであれば解析し直さずにツリーを再利用します。
+#[wasm_bindgen]
+pub fn breadcrumb_at(code: &str, offset: usize) -> String {
+    let options = ParseOptions::default();
+    cache::with_tree(code, &options, || build_tree(code, &options), |tree| {
+        match sourcemap::breadcrumb_at(tree, code, offset) {
+            Ok(path) => serde_json::to_string(&path).unwrap(),
+            Err(message) => to_json(&PadNode::error(message)),
         }
-        _ => {
-            // その他の式は単純なコマンドとして扱う（関数呼び出し、代入など）
-            let label = quote::quote!(#expr).to_string();
-            PadNode::Command { label }
+    })
+}
+
+/// ノードidに対応するソース上の範囲を `{start, end, utf16_start, utf16_end}` のJSON文字列で返します
+#[wasm_bindgen]
+pub fn span_of_node(code: &str, id: &str) -> String {
+    let tree = build_tree(code, &ParseOptions::default());
+    match sourcemap::span_of_node(&tree, code, id) {
+        Ok(range) => serde_json::to_string(&range).unwrap(),
+        Err(message) => to_json(&PadNode::error(message)),
+    }
+}
+
+/// 入力がJSONのツリーならそれを読み込み、それ以外はRustコードとして `options` で解析したツリーに `f` を適用します
+/// JSONとして読み込めない場合はErrorノードを返します。
+fn with_loaded_tree(json_or_code: &str, options: &ParseOptions, f: impl FnOnce(&PadNode) -> String) -> String {
+    if json_or_code.trim_start().starts_with('{') {
+        match serde_json::from_str::<PadNode>(json_or_code) {
+            Ok(tree) => f(&tree),
+            Err(e) => to_json(&PadNode::error(format!("Invalid JSON: {}", e))),
         }
+    } else {
+        f(&build_tree(json_or_code, options))
     }
 }
 
+/// 既定の出力形式（v1）のJSON Schemaを返します
+/// 他の言語のフロントエンドや手で書いた図など、外部で作ったPADのJSONの検証に使えます（`validate_pad_json` と同じ形式です）。
+/// スキーマv2のJSON Schemaから、Ifの `then_block` などを単一のノードに戻して作ります。
+#[wasm_bindgen]
+pub fn pad_schema() -> String {
+    let mut schema: serde_json::Value = serde_json::from_str(PAD_SCHEMA_V2).unwrap();
+    v2::schema_to_v1(&mut schema);
+    serde_json::to_string_pretty(&schema).unwrap()
+}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// スキーマv2のJSON Schemaを返します
+#[wasm_bindgen]
+pub fn pad_schema_v2() -> String {
+    PAD_SCHEMA_V2.to_string()
+}
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
+const PAD_SCHEMA_V2: &str = include_str!("../schema/pad-v2.schema.json");
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_PAD_NODE_V2: &str = PAD_NODE_V2_TYPES;
+
+// wasm以外の対象では型定義は出力されず、テストからだけ参照します
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+const PAD_NODE_V2_TYPES: &str = r#"
+export type PadNodeMeta = { id?: string; span?: { start_line: number; start_col: number; end_line: number; end_col: number }; width_hint?: number; cfg?: string; may_panic?: boolean; panic_reasons?: string[]; doc_url?: string; step?: string; tokens?: { start: number; end: number; kind: 'keyword' | 'ident' | 'literal' | 'string' | 'punct' | 'comment' }[]; dead?: boolean; always_runs?: boolean; never_runs?: boolean; clone_group?: number; returns?: boolean; returned_by?: string[]; collapsed?: boolean; child_count?: number; diff?: 'added' | 'removed' | 'modified' | 'unchanged'; comment?: string; calls?: string[]; call_targets?: string[]; awaits?: number };
+export type PadErrorKind = 'syntax' | 'nesting' | 'encoding' | 'options' | 'empty' | 'not_found' | 'ambiguous';
+export type PadDocumentInfo = { warnings?: { code: string; message: string; node_id: string; span?: PadNodeMeta['span'] }[]; max_width_hint?: number; filtered?: number; total_items?: number; errors?: { message: string; line: number; column: number; kind: PadErrorKind; frame?: string }[]; profile?: { parse_ms: number; convert_ms: number; serialize_ms: number; input_bytes: number; node_count: number }; clones?: { nodes: string[]; size: number }[]; fallback_report?: Record<string, { count: number; examples: NonNullable<PadNodeMeta['span']>[] }> };
+export type PadNodeV2 = PadNodeMeta & (
+  | ({ type: 'sequence'; children: PadNodeV2[]; skipped?: number; is_unsafe?: boolean } & PadDocumentInfo)
+  | ({ type: 'document'; title: string; source_name?: string; generated_by: string; backend_version: string; created_at?: string; children: PadNodeV2[] } & PadDocumentInfo)
+  | { type: 'block'; kind: string; label: string; children: PadNodeV2[]; full_label?: string; trait_name?: string; self_type?: string; signature?: string; is_test?: boolean; is_async?: boolean; is_unsafe?: boolean; impl_span?: PadNodeMeta['span']; metrics?: { cyclomatic: number; max_nesting: number; statements: number; loops: number }; module_path?: string }
+  | { type: 'if'; condition: string; then: PadNodeV2[]; else: PadNodeV2[]; then_skipped?: number; else_skipped?: number; then_dead?: boolean; then_always_runs?: boolean; else_dead?: boolean; else_always_runs?: boolean; full_label?: string; components?: string[]; compile_time?: boolean; cfg_predicate?: string; runtime_condition?: string; lowered_from?: string }
+  | { type: 'loop'; kind: 'while' | 'for' | 'loop' | 'do_while'; condition: string; body: PadNodeV2[]; body_skipped?: number; body_dead?: boolean; full_label?: string; components?: string[]; break_condition?: string; results?: string[]; binding?: string; loop_label?: string; modifies?: string[]; loop_variables?: string[] }
+  | { type: 'break'; label: string; target?: string; target_id?: string; value?: string }
+  | { type: 'continue'; label: string; target?: string; target_id?: string }
+  | { type: 'yield'; label: string; value?: string }
+  | { type: 'return'; label: string; value?: string; scope: 'function' | 'closure' }
+  | { type: 'match'; scrutinee: string; arms: { pattern: string; guard?: string; body: PadNodeV2 }[]; enum_type?: string; covered?: string[]; uncovered_via_wildcard?: string[] }
+  | { type: 'command'; label: string; full_label?: string; foreign?: boolean; recursive?: boolean; receiver?: boolean; scope?: 'function' | 'closure'; fallback?: boolean; assignment?: { target: string; op: string; value: string }; category: 'declaration' | 'assignment' | 'call' | 'io' | 'exit' | 'arithmetic' | 'other' }
+  | { type: 'error'; message: string; line?: number; column?: number; frame?: string; byte_offset?: number; kind?: PadErrorKind });
+"#;
+
+/// Rustコードを解析し、ノード種別ごとの件数（関数ごとの内訳付き）をJSON文字列で返します
+/// 解析できない場合は通常どおりErrorノードを返します。
+#[wasm_bindgen]
+pub fn count_nodes(code: &str) -> String {
+    let tree = build_tree(code, &ParseOptions::default());
+    if let PadNode::Error { .. } = tree {
+        return to_json(&tree);
+    }
+    serde_json::to_string(&stats::count_nodes(&tree)).unwrap()
+}
+
+/// Rustコードを解析し、関数ごとの複雑さの指標（循環的複雑度・入れ子の最大の深さ・文の数・ループの数）を
+/// `{functions: [...]}` のJSON文字列で返します
+/// 解析できない場合は通常どおりErrorノードを返します。
+#[wasm_bindgen]
+pub fn analyze(code: &str) -> String {
+    let tree = build_tree(code, &ParseOptions::default());
+    if let PadNode::Error { .. } = tree {
+        return to_json(&tree);
+    }
+    serde_json::to_string(&metrics::report(&tree)).unwrap()
+}
+
+/// Rustコードを解析し、関数をノード（`{id, name, span}`）、同じ入力で定義された関数の呼び出しをエッジ（`{from, to, count}`）とする
+/// 呼び出しグラフを `{nodes, edges}` のJSON文字列で返します
+/// `self` 以外に対するメソッド呼び出しは、その名前のメソッドが1つだけの場合にそのメソッドへの呼び出しとみなします。
+/// 解析できない場合は通常どおりErrorノードを返します。
+#[wasm_bindgen]
+pub fn build_call_graph(code: &str) -> String {
+    let tree = build_tree(code, &ParseOptions::default());
+    if let PadNode::Error { .. } = tree {
+        return to_json(&tree);
+    }
+    serde_json::to_string(&callgraph::build(&tree)).unwrap()
+}
+
+/// 変更前と変更後のRustコードを比べ、変更後のツリーの各ノードに状態（`diff`: `added` / `removed` / `modified` / `unchanged`）を
+/// 付けたPAD表示用のJSON文字列を返します
+/// 削除されたノードは変更前の位置に残します。どちらかを解析できない場合は、そのErrorノードを返します。
+#[wasm_bindgen]
+pub fn diff_rust_code(old: &str, new: &str) -> String {
+    to_json(&diff_tree(old, new, &ParseOptions::default()))
+}
+
+/// 解析オプション（JSON文字列）を指定して、2つの版のRustコードを比べます
+#[wasm_bindgen]
+pub fn diff_rust_code_with_options(old: &str, new: &str, options_json: &str) -> String {
+    match ParseOptions::from_json(options_json) {
+        Ok(options) => to_json(&diff_tree(old, new, &options)),
+        Err(message) => to_json(&PadNode::error(message)),
+    }
+}
+
+fn diff_tree(old: &str, new: &str, options: &ParseOptions) -> PadNode {
+    let old = build_tree(old, options);
+    if let PadNode::Error { .. } = old {
+        return old;
+    }
+    let new = build_tree(new, options);
+    if let PadNode::Error { .. } = new {
+        return new;
+    }
+    diff::diff_trees(old, new)
+}
+
+/// 図に表示できるトップレベルのアイテム（関数・implブロックなど）の一覧を `{total_items, items}` のJSON文字列で返します
+/// 関数の本体は変換しないため、大きなファイルで `item_offset` / `item_limit` によるページ送りの前に、全体の構成を調べるのに使えます。
+/// 解析できない場合は通常どおりErrorノードを返します。
+#[wasm_bindgen]
+pub fn list_functions(code: &str) -> String {
+    list_functions_with_options(code, "")
+}
+
+/// 解析オプション（`include` / `exclude` などの絞り込み）を指定して、トップレベルのアイテムの一覧を返します
+#[wasm_bindgen]
+pub fn list_functions_with_options(code: &str, options_json: &str) -> String {
+    let options = match ParseOptions::from_json(options_json) {
+        Ok(options) => options,
+        Err(message) => return to_json(&PadNode::error(message)),
+    };
+    let (code, _) = preprocess::preprocess(code);
+    if let Some(diagnostic) = diagnostics::nesting_too_deep(&code, options.max_depth) {
+        return to_json(&diagnostics::error_node(diagnostic));
+    }
+    match parse_str::<File>(&code) {
+        Ok(file) => {
+            let converter = Converter::new(&code, &file.items, &options);
+            serde_json::to_string(&listing::list_items(&converter, &file.items, &options)).unwrap()
+        }
+        Err(e) => to_json(&diagnostics::parse_error(&code, &e)),
+    }
+}
+
+/// 解析結果のキャッシュ（`cache` オプション）を空にします
+#[wasm_bindgen]
+pub fn clear_cache() {
+    cache::clear();
+}
+
+/// 解析結果のキャッシュの利用状況を `{hits, parses, entries}` のJSON文字列で返します
+#[wasm_bindgen]
+pub fn cache_stats() -> String {
+    serde_json::to_string(&cache::stats()).unwrap()
+}
+
+/// 解析済みのツリーを保持し、概略（アウトライン）と、ノードごとの展開を返す文書
+/// 大きなファイルでは、まず `outline` で骨組みだけを描画し、利用者が開いたノードを `expand` で取り出します。
+/// どちらも保持しているツリーから作るため、再解析はせず、ノードidも一致します。
+#[wasm_bindgen]
+pub struct PadDocument {
+    tree: PadNode,
+    options: ParseOptions,
+}
+
+#[wasm_bindgen]
+impl PadDocument {
+    /// コードを解析して文書を作成します（オプションは `parse_rust_code_with_options` と同じです）
+    /// オプションのJSONが不正な場合は、Errorノードだけを持つ文書になります。
+    #[wasm_bindgen(constructor)]
+    pub fn new(code: &str, options_json: &str) -> PadDocument {
+        match ParseOptions::from_json(options_json) {
+            Ok(options) => PadDocument { tree: build_tree(code, &options), options },
+            Err(message) => PadDocument { tree: PadNode::error(message), options: ParseOptions::default() },
+        }
+    }
+
+    /// ツリー全体をJSON文字列で返します
+    pub fn to_json(&self) -> String {
+        render_value(serde_json::to_value(&self.tree).unwrap(), &self.options)
+    }
+
+    /// 深さ `max_depth` までのノードを残したツリーをJSON文字列で返します
+    /// それより深いノードを持つ箱は中身を空にし、`collapsed: true` と子ノードの数 `child_count` を付けます。
+    pub fn outline(&self, max_depth: u8) -> String {
+        render_value(outline::truncated(&self.tree, max_depth as usize), &self.options)
+    }
+
+    /// 折りたたまれたノードをidで取り出し、`{found, node, ancestors}` をJSON文字列で返します
+    /// `node` の中身は `depth` 段まで展開します（さらに深いノードは `outline` と同様に折りたたみます）。
+    /// 見つからない場合は `get_subtree` と同じく `{found: false, error: {code, message}}` を返します。
+    pub fn expand(&self, id: &str, depth: u8) -> String {
+        match subtree::by_id(&self.tree, id) {
+            subtree::SubtreeResult::Found { found, node, ancestors } => {
+                let value = serde_json::json!({
+                    "found": found,
+                    "node": outline::truncated(node, depth as usize),
+                    "ancestors": ancestors,
+                });
+                render_value(value, &self.options)
+            }
+            not_found => serde_json::to_string(&not_found).unwrap(),
+        }
+    }
+}
+
+/// オプションに従ってコードを解析し、JSON文字列に変換します
+/// `cache` オプション指定時は、同じ入力・オプションの前回の結果を再利用します。
+fn convert_code(code: &str, options: &ParseOptions) -> String {
+    if options.cache {
+        return cache::get_or_render(code, options, || render_code(code, options));
+    }
+    render_code(code, options)
+}
+
+fn render_code(code: &str, options: &ParseOptions) -> String {
+    let tree = build_tree(code, options);
+    if options.profile {
+        // シリアライズの時間は出力の作成後にしかわからないため、JSON値に書き込みます
+        let start = profile::now_ms();
+        let mut value = serde_json::to_value(&tree).unwrap();
+        if let Some(profile) = value.get_mut("profile") {
+            profile["serialize_ms"] = profile::elapsed_ms(start).into();
+        }
+        return render_value(value, options);
+    }
+    match options.field_naming {
+        FieldNaming::SnakeCase => to_json(&tree),
+        FieldNaming::CamelCase => render_value(serde_json::to_value(&tree).unwrap(), options),
+    }
+}
+
+/// 変換済みのJSON値を、キーの命名規則を適用して文字列にします
+fn render_value(mut value: serde_json::Value, options: &ParseOptions) -> String {
+    if options.field_naming == FieldNaming::CamelCase {
+        naming::camel_case_keys(&mut value);
+    }
+    value.to_string()
+}
+
+/// オプションに従ってコードを解析し、PADノードのツリーを構築します
+/// 先頭のBOM・shebang・入力全体を囲むコードフェンスは取り除き、そのことを警告に記録します。
+fn build_tree(code: &str, options: &ParseOptions) -> PadNode {
+    let mut phases = profile::Phases::start(options.profile);
+    let (source, stripped) = preprocess::preprocess(code);
+    let mut tree = match diagnostics::nesting_too_deep(&source, options.max_depth) {
+        Some(diagnostic) => diagnostics::error_node(diagnostic),
+        None => match options.mode {
+            ParseMode::File => build_file_tree(&source, options, &mut phases),
+            ParseMode::Script => build_script_tree(&source, options, &mut phases),
+        },
+    };
+    if let PadNode::Sequence { info, .. } = &mut tree {
+        info.get_or_insert_default();
+    }
+    if let Some(limit) = options.collapse_depth {
+        collapse::collapse_deep_nodes(&mut tree, limit);
+    }
+    if let Some(max) = options.max_label_length {
+        truncate::truncate_labels(&mut tree, max, &options.truncation_marker, options.truncation_position);
+    }
+    match options.id_scheme {
+        IdScheme::Sequential => ids::assign_ids(&mut tree),
+        IdScheme::Stable => ids::assign_stable_ids(&mut tree),
+    }
+    ids::link_jump_targets(&mut tree);
+    ids::link_returns(&mut tree);
+    ids::link_calls(&mut tree);
+    constant::mark_constant_conditions(&mut tree);
+    if options.clones {
+        let clones = clones::detect_clones(&mut tree, options.clone_min_size, options.clone_ignore_names);
+        if let PadNode::Sequence { info: Some(info), .. } = &mut tree {
+            info.clones = clones;
+        }
+    }
+    let fallback_report = fallback::report(&tree);
+    if let PadNode::Sequence { info: Some(info), .. } = &mut tree {
+        info.fallback_report = fallback_report;
+    }
+    if options.step_numbers {
+        ids::assign_steps(&mut tree);
+    }
+    if options.width_hints {
+        width::apply_width_hints(&mut tree);
+    }
+    if options.label_tokens {
+        tokens::apply_label_tokens(&mut tree);
+    }
+    if options.metrics {
+        metrics::apply_metrics(&mut tree);
+    }
+    let root_id = tree.meta().id.clone();
+    let warnings = stripped
+        .into_iter()
+        .map(|warning| model::Warning { node_id: root_id.clone(), ..warning })
+        .chain(lint::lint(&tree, &options.lint))
+        .collect();
+    if let PadNode::Sequence { info: Some(info), .. } = &mut tree {
+        info.warnings = warnings;
+    }
+    if options.document {
+        tree = document::into_document(tree, options);
+    }
+    let profile = phases.finish(code, &tree);
+    if let PadNode::Sequence { info: Some(info), .. } | PadNode::Document { info: Some(info), .. } = &mut tree {
+        info.profile = profile;
+    }
+    tree
+}
+
+/// ファイル全体（アイテムの並び）として解析します
+fn build_file_tree(code: &str, options: &ParseOptions, phases: &mut profile::Phases) -> PadNode {
+    // synクレートを使用して文字列としてのコードをRustの構文木（File）にパースしようと試みます
+    match phases.parse(|| parse_str::<File>(code)) {
+        Ok(file) => {
+            // マジックコメント（// pad:skip など）は変換前にソース文字列から収集しておきます
+            let converter = Converter::new(code, &file.items, options);
+            // ファイル内のアイテム（関数など）を走査
+            // ページに分ける場合も総数は図に表示できるアイテムで数え、範囲外のアイテムは変換しません
+            let drawn: Vec<&Item> = file
+                .items
+                .iter()
+                .filter(|item| converter.includes_item(item) && converter.draws_item(item))
+                .collect();
+            let total_items = drawn.len();
+            // `imports` オプション指定時は、use宣言の一覧を先頭に置きます
+            let nodes: Vec<PadNode> = converter
+                .parse_imports(&file.items)
+                .into_iter()
+                .chain(options.page(drawn).into_iter().filter_map(|item| converter.parse_item(item)))
+                .collect();
+            let nodes = if options.group_impls_by_type { group_impls_by_type(nodes) } else { nodes };
+            let nodes = order::arrange(nodes, &file.items, options);
+            if nodes.is_empty() && !(options.paginates() && total_items > 0) {
+                 PadNode::error_of(model::ErrorKind::Empty, "No function found")
+            } else {
+                 // 複数の関数がある場合も想定し、全体をSequenceとして返します
+                 // これにより、フロントエンドは複数の関数ブロックを順に描画できます
+                 let mut root = PadNode::sequence(nodes);
+                 let filtered = converter.filtered_count();
+                 if let PadNode::Sequence { info, .. } = &mut root
+                     && (filtered > 0 || options.paginates())
+                 {
+                     *info = Some(Box::new(DocumentInfo {
+                         filtered: (filtered > 0).then_some(filtered),
+                         total_items: options.paginates().then_some(total_items),
+                         ..DocumentInfo::default()
+                     }));
+                 }
+                 root
+            }
+        }
+        Err(e) if options.recover => build_recovered_tree(code, options, e, phases),
+        Err(e) => diagnostics::parse_error(code, &e),
+    }
+}
+
+/// 構文エラーのあるファイルを、トップレベルのアイテムごとに解析し直して図にします
+/// 解析できないアイテムはその位置のErrorノードにし、すべての構文エラーをルートの `errors` に記録します。
+fn build_recovered_tree(code: &str, options: &ParseOptions, error: syn::Error, phases: &mut profile::Phases) -> PadNode {
+    // 括弧の対応が取れていない場合はアイテムに分けられないため、通常どおり報告します
+    let Some(chunks) = phases.parse(|| diagnostics::split_items(code)) else {
+        return diagnostics::parse_error(code, &error);
+    };
+    let parsed: Vec<Result<Item, syn::Error>> =
+        phases.parse(|| chunks.into_iter().map(syn::parse2::<Item>).collect());
+    let items: Vec<Item> = parsed.iter().filter_map(|item| item.as_ref().ok().cloned()).collect();
+    let converter = Converter::new(code, &items, options);
+    let mut errors = Vec::new();
+    let mut nodes: Vec<PadNode> = converter.parse_imports(&items).into_iter().collect();
+    // 構文エラーはページの範囲外のものも含めて、すべてルートに記録します
+    let drawn: Vec<Result<Item, Vec<model::Diagnostic>>> = parsed
+        .into_iter()
+        .filter_map(|item| match item {
+            Ok(item) => (converter.includes_item(&item) && converter.draws_item(&item)).then_some(Ok(item)),
+            Err(e) => {
+                let found = diagnostics::diagnostics(code, e);
+                errors.extend(found.iter().cloned());
+                Some(Err(found))
+            }
+        })
+        .collect();
+    let total_items = drawn.len();
+    for item in options.page(drawn) {
+        match item {
+            Ok(item) => nodes.extend(converter.parse_item(&item)),
+            Err(found) => nodes.extend(found.first().cloned().map(diagnostics::error_node)),
+        }
+    }
+    errors.sort_by_key(|diagnostic| (diagnostic.line, diagnostic.column));
+    if options.group_impls_by_type {
+        nodes = group_impls_by_type(nodes);
+    }
+    let nodes = order::arrange(nodes, &items, options);
+    let filtered = converter.filtered_count();
+    let mut root = PadNode::sequence(nodes);
+    if let PadNode::Sequence { info, .. } = &mut root {
+        *info = Some(Box::new(DocumentInfo {
+            filtered: (filtered > 0).then_some(filtered),
+            total_items: options.paginates().then_some(total_items),
+            errors,
+            ..DocumentInfo::default()
+        }));
+    }
+    root
+}
+
+/// 関数で囲まれていない文の並び（スクリプト）として解析し、`(script)` というBlockで包みます
+/// 文の間に書かれた関数定義などのアイテムも通常どおり変換されます。
+fn build_script_tree(code: &str, options: &ParseOptions, phases: &mut profile::Phases) -> PadNode {
+    match phases.parse(|| Block::parse_within.parse_str(code)) {
+        Ok(stmts) => {
+            let items: Vec<Item> = stmts
+                .iter()
+                .filter_map(|stmt| match stmt {
+                    Stmt::Item(item) => Some(item.clone()),
+                    _ => None,
+                })
+                .collect();
+            let converter = Converter::new(code, &items, options);
+            let body = converter.parse_stmts(&stmts, 0, usize::MAX);
+            PadNode::sequence(vec![PadNode::block(BlockKind::Script, "(script)", vec![body])])
+        }
+        Err(e) => diagnostics::parse_error(code, &e),
+    }
+}
+
+/// PADノードをJSON文字列にシリアライズします
+fn to_json(node: &PadNode) -> String {
+    serde_json::to_string(node).unwrap_or_else(|e| {
+        format!("{{\"type\": \"error\", \"message\": \"Serialization error: {}\"}}", e)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_works() {
+        let result = add(2, 2);
+        assert_eq!(result, 4);
+    }
+
+    /// parse_rust_codeの出力をJSON値として取得するヘルパー
+    fn parse_json(code: &str) -> serde_json::Value {
+        serde_json::from_str(&parse_rust_code(code)).unwrap()
+    }
+
+    /// 先頭の関数本体（Sequence）を取り出すヘルパー
+    fn first_body(json: &serde_json::Value) -> &serde_json::Value {
+        &json["children"][0]["children"][0]
+    }
+
+    #[test]
+    fn pad_skip_omits_single_statement() {
+        let code = "fn main() {\n    let x = 1;\n    // pad:skip\n    log(x);\n    run(x); // pad:skip\n    done();\n}";
+        let json = parse_json(code);
+        let body = first_body(&json);
+        let labels: Vec<&str> = body["children"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| c["label"].as_str().unwrap())
+            .collect();
+        assert_eq!(labels, vec!["let x = 1 ;", "done ()"]);
+        assert_eq!(body["skipped"], 2);
+    }
+
+    #[test]
+    fn pad_skip_region_omits_statements_between_markers() {
+        let code = "fn main() {\n    a();\n    // pad:skip-begin\n    b();\n    if x { c(); }\n    // pad:skip-end\n    d();\n}";
+        let json = parse_json(code);
+        let body = first_body(&json);
+        assert_eq!(body["children"].as_array().unwrap().len(), 2);
+        assert_eq!(body["children"][1]["label"], "d ()");
+        assert_eq!(body["skipped"], 2);
+    }
+
+    #[test]
+    fn pad_skip_inside_string_literal_is_ignored() {
+        let code = "fn main() {\n    let s = \"// pad:skip\";\n    run(s);\n}";
+        let json = parse_json(code);
+        let body = first_body(&json);
+        assert_eq!(body["children"].as_array().unwrap().len(), 2);
+        assert!(body.get("skipped").is_none());
+    }
+
+    #[test]
+    fn pad_label_overrides_if_condition() {
+        let code = "fn main() {\n    // pad:label: validate input\n    if a.len() > 0 && b.is_ok() {\n        go();\n    }\n}";
+        let json = parse_json(code);
+        let node = &first_body(&json)["children"][0];
+        assert_eq!(node["type"], "if");
+        assert_eq!(node["condition"], "validate input");
+        assert_eq!(node["full_label"], "a.len () > 0 && b.is_ok ()");
+    }
+
+    #[test]
+    fn pad_group_wraps_statements_inside_loop() {
+        let code = "fn main() {\n    for x in xs {\n        // pad:group: prepare\n        a();\n        b();\n        c();\n        // pad:endgroup\n        d();\n    }\n}";
+        let json = parse_json(code);
+        let body = &first_body(&json)["children"][0]["body"];
+        let group = &body["children"][0];
+        assert_eq!(group["type"], "block");
+        assert_eq!(group["label"], "prepare");
+        assert_eq!(group["children"].as_array().unwrap().len(), 3);
+        assert_eq!(body["children"][1]["label"], "d ()");
+    }
+
+    #[test]
+    fn pad_group_does_not_cross_block_boundary() {
+        let code = "fn main() {\n    if x {\n        // pad:group: inner\n        a();\n    }\n    b();\n    // pad:endgroup\n}";
+        let json = parse_json(code);
+        let body = first_body(&json);
+        let then_children = body["children"][0]["then_block"]["children"].as_array().unwrap();
+        assert_eq!(then_children[0]["label"], "a ()");
+        assert_eq!(body["children"][1]["label"], "b ()");
+    }
+
+    #[test]
+    fn extern_block_lists_declarations_and_tags_foreign_calls() {
+        let code = "extern \"C\" {\n    fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;\n    static errno: c_int;\n}\nfn main() {\n    let r = unsafe { ioctl(0, 1) };\n    helper(r);\n}";
+        let json = parse_json(code);
+        let ffi = &json["children"][0];
+        assert_eq!(ffi["type"], "block");
+        assert_eq!(ffi["label"], "extern \"C\"");
+        assert_eq!(ffi["children"][0]["label"], "fn ioctl (fd : c_int , request : c_ulong , ...) -> c_int");
+        assert_eq!(ffi["children"][1]["label"], "static errno : c_int");
+
+        let stmts = &json["children"][1]["children"][0]["children"];
+        assert_eq!(stmts[0]["foreign"], true);
+        assert!(stmts[1].get("foreign").is_none());
+    }
+
+    #[test]
+    fn impl_methods_are_qualified_by_type_and_trait() {
+        let code = "impl Counter {\n    fn next(&mut self) -> u32 { self.n }\n}\nimpl Iterator for Counter {\n    type Item = u32;\n    fn next(&mut self) -> Option<u32> { None }\n}";
+        let json = parse_json(code);
+        let inherent = &json["children"][0];
+        assert_eq!(inherent["label"], "impl Counter");
+        assert_eq!(inherent["children"][0]["label"], "fn Counter::next(&mut self)");
+        assert_eq!(inherent["children"][0]["self_type"], "Counter");
+        assert!(inherent["children"][0].get("trait_name").is_none());
+
+        let trait_impl = &json["children"][1];
+        assert_eq!(trait_impl["label"], "impl Iterator for Counter");
+        assert_eq!(trait_impl["children"][0]["label"], "type Item = u32");
+        let method = &trait_impl["children"][1];
+        assert_eq!(method["label"], "fn <Counter as Iterator>::next(&mut self)");
+        assert_eq!(method["trait_name"], "Iterator");
+        assert_eq!(method["self_type"], "Counter");
+    }
+
+    #[test]
+    fn marker_impls_without_items_are_not_drawn() {
+        let code = "struct S;\nunsafe impl Send for S {}\nimpl Copy for S {}\nimpl Marker for S {\n    type Kind = u8;\n}\nfn main() {}";
+        let json = parse_json(code);
+        let labels: Vec<&str> = json["children"].as_array().unwrap().iter().map(|node| node["label"].as_str().unwrap()).collect();
+        assert_eq!(labels, ["impl Marker for S", "fn main()"]);
+
+        let json = parse_json_with(code, r#"{"declarations": false}"#);
+        assert_eq!(json["children"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn inherent_impls_of_one_type_are_grouped() {
+        let code = "struct Counter;\nimpl Counter {\n    fn new() -> Self { Counter }\n}\nimpl Iterator for Counter {\n    fn next(&mut self) -> Option<u32> { None }\n}\nimpl Counter {\n    fn reset(&mut self) {}\n    fn get(&self) -> u32 { 0 }\n}";
+        let json: serde_json::Value =
+            serde_json::from_str(&parse_rust_code_with_options(code, "{\"group_impls_by_type\": true}")).unwrap();
+        let children = json["children"].as_array().unwrap();
+        assert_eq!(children.len(), 2);
+        let inherent = &children[0];
+        assert_eq!(inherent["label"], "impl Counter");
+        let labels: Vec<&str> = inherent["children"].as_array().unwrap().iter().map(|m| m["label"].as_str().unwrap()).collect();
+        assert_eq!(labels, ["fn Counter::new()", "fn Counter::reset(&mut self)", "fn Counter::get(&self)"]);
+        let lines: Vec<u64> =
+            inherent["children"].as_array().unwrap().iter().map(|m| m["impl_span"]["start_line"].as_u64().unwrap()).collect();
+        assert_eq!(lines, [2, 8, 8]);
+        assert_eq!(children[1]["label"], "impl Iterator for Counter");
+        assert!(children[1]["children"][0].get("impl_span").is_none());
+
+        // 既定ではまとめません
+        assert_eq!(parse_json(code)["children"].as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn functions_are_ordered_and_grouped_by_options() {
+        let code = "use std::fmt;\nfn helper() {}\nimpl Parser {\n    fn parse(&self) { helper(); }\n    fn new() -> Self { Parser }\n}\nfn main() {\n    let p = Parser::new();\n    p.parse();\n    report();\n}\nfn report() {}\nfn unused() {}\nmod util {\n    fn a() {}\n}";
+        fn labels(nodes: &serde_json::Value) -> Vec<String> {
+            nodes
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|node| match node["children"].as_array() {
+                    Some(children) if node["kind"] == "impl" || node["kind"] == "module" => format!(
+                        "{}[{}]",
+                        node["label"].as_str().unwrap(),
+                        labels(&serde_json::Value::Array(children.clone())).join(", ")
+                    ),
+                    _ => node["label"].as_str().unwrap_or(node["kind"].as_str().unwrap()).to_string(),
+                })
+                .collect()
+        }
+        let arrange = |options: &str| {
+            let options = format!("{{\"imports\": true, {}}}", options);
+            let json: serde_json::Value = serde_json::from_str(&parse_rust_code_with_options(code, &options)).unwrap();
+            labels(&json["children"])
+        };
+
+        let source = arrange("\"order\": \"source\"");
+        assert_eq!(source[1..], ["fn helper()", "impl Parser[fn Parser::parse(&self), fn Parser::new()]", "fn main()", "fn report()", "fn unused()", "fn a()"]);
+        assert_eq!(
+            arrange("\"order\": \"name\"")[1..],
+            ["fn a()", "fn helper()", "fn main()", "impl Parser[fn Parser::new(), fn Parser::parse(&self)]", "fn report()", "fn unused()"]
+        );
+        // mainから呼び出される順にたどり、呼び出されない関数は最後に並べます。use宣言は元の位置に残ります
+        let call_order = arrange("\"order\": \"call_order\"");
+        assert_eq!(call_order[0], source[0]);
+        assert_eq!(
+            call_order[1..],
+            ["fn main()", "impl Parser[fn Parser::new(), fn Parser::parse(&self)]", "fn report()", "fn helper()", "fn unused()", "fn a()"]
+        );
+        assert_eq!(
+            arrange("\"order\": \"call_order\", \"group_by\": \"none\"")[1..],
+            ["fn main()", "fn Parser::new()", "fn Parser::parse(&self)", "fn report()", "fn helper()", "fn unused()", "fn a()"]
+        );
+        assert_eq!(arrange("\"group_by\": \"module\"").last().unwrap(), "mod util[fn a()]");
+    }
+
+    #[test]
+    fn inline_modules_are_drawn_recursively() {
+        let code = "mod outer {\n    fn a() {}\n    mod inner {\n        fn b() {}\n    }\n}\n#[cfg(test)]\nmod tests {\n    #[test]\n    fn works() {}\n}\nmod external;";
+        let json = parse_json(code);
+        let labels: Vec<&str> = json["children"].as_array().unwrap().iter().map(|n| n["label"].as_str().unwrap()).collect();
+        assert_eq!(labels, ["fn a()", "fn b()", "fn works()"]);
+        // モジュールの cfg の条件は中の関数に引き継ぎます
+        assert_eq!(json["children"][2]["cfg"], "test");
+        // 解いたモジュールの中のBlockには、モジュールのパスを付けます
+        let paths: Vec<&str> = json["children"].as_array().unwrap().iter().map(|n| n["module_path"].as_str().unwrap()).collect();
+        assert_eq!(paths, ["outer", "outer::inner", "tests"]);
+
+        let json = parse_json_with(code, r#"{"group_by": "module"}"#);
+        let outer = &json["children"][0];
+        assert_eq!((outer["kind"].as_str(), outer["label"].as_str()), (Some("module"), Some("mod outer")));
+        assert_eq!(outer["children"][1]["label"], "mod inner");
+        assert_eq!(outer["children"][1]["children"][0]["label"], "fn b()");
+        assert!(outer["children"][1]["children"][0].get("module_path").is_none());
+
+        let json = parse_json_with(code, r#"{"only_tests": true}"#);
+        assert_eq!(json["children"].as_array().unwrap().len(), 1);
+        assert_eq!(json["children"][0]["label"], "fn works()");
+    }
+
+    #[test]
+    fn top_level_items_are_paginated() {
+        let code: String = (0..10).map(|i| format!("fn f{}() {{ a(); }}\n", i)).collect();
+        let page = |offset: usize| {
+            let options = format!("{{\"item_offset\": {}, \"item_limit\": 4}}", offset);
+            let json: serde_json::Value = serde_json::from_str(&parse_rust_code_with_options(&code, &options)).unwrap();
+            assert_eq!(json["total_items"], 10);
+            json["children"].as_array().unwrap().iter().map(|node| node["label"].as_str().unwrap().to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(page(0), ["fn f0()", "fn f1()", "fn f2()", "fn f3()"]);
+        assert_eq!(page(4), ["fn f4()", "fn f5()", "fn f6()", "fn f7()"]);
+        // 指定しない場合は総数を記録しません
+        assert!(parse_json(&code).get("total_items").is_none());
+
+        let listing: serde_json::Value = serde_json::from_str(&list_functions(&code)).unwrap();
+        assert_eq!(listing["total_items"], 10);
+        assert_eq!(listing["items"][5]["name"], "f5");
+        assert_eq!(listing["items"][5]["index"], 5);
+        assert_eq!(listing["items"][5]["start_line"], 6);
+    }
+
+    #[test]
+    fn fully_filtered_modules_do_not_take_a_page_slot() {
+        let code = "mod helpers {\n    fn h() {}\n}\nfn handle_a() {}\nfn handle_b() {}";
+        let options = r#"{"include": ["handle_*"], "item_offset": 0, "item_limit": 1}"#;
+        let json = parse_json_with(code, options);
+        assert_eq!(json["total_items"], 2);
+        assert_eq!(json["children"][0]["label"], "fn handle_a()");
+        let json = parse_json_with(code, r#"{"include": ["handle_*"], "item_offset": 1, "item_limit": 1}"#);
+        assert_eq!(json["children"][0]["label"], "fn handle_b()");
+        let listing: serde_json::Value = serde_json::from_str(&list_functions_with_options(code, r#"{"include": ["handle_*"]}"#)).unwrap();
+        assert_eq!(listing["total_items"], 2);
+    }
+
+    #[test]
+    fn interface_nodes_list_inputs_and_output() {
+        let code = "impl Store {\n    fn put(&mut self, key: &str, value: u32) -> Result<(), Error> {\n        if key.is_empty() {\n            return Err(Error::Empty);\n        }\n        self.map.insert(key, value);\n        Ok(())\n    }\n}";
+        let json: serde_json::Value =
+            serde_json::from_str(&parse_rust_code_with_options(code, "{\"interface_nodes\": true}")).unwrap();
+        let method = &json["children"][0]["children"][0];
+        let children = method["children"].as_array().unwrap();
+        assert_eq!(children.len(), 3);
+
+        let inputs = &children[0];
+        assert_eq!((inputs["kind"].as_str(), inputs["label"].as_str()), (Some("interface"), Some("inputs")));
+        let params: Vec<&str> = inputs["children"].as_array().unwrap().iter().map(|p| p["label"].as_str().unwrap()).collect();
+        assert_eq!(params, ["&mut self", "key: &str", "value: u32"]);
+        assert_eq!(inputs["children"][0]["receiver"], true);
+        assert!(inputs["children"][1].get("receiver").is_none());
+
+        let output = &children[2];
+        assert_eq!(output["label"], "output");
+        assert_eq!(output["children"][0]["label"], "Result<(), Error>");
+        // 早期returnと末尾の式の両方を参照します
+        let body = &children[1]["children"];
+        let early = &body[0]["then_block"]["children"][0];
+        assert_eq!(early["returns"], true);
+        assert_eq!(body[2]["returns"], true);
+        assert!(body[1].get("returns").is_none());
+        assert_eq!(output["returned_by"], serde_json::json!([early["id"], body[2]["id"]]));
+
+        // 既定では本体だけです
+        assert_eq!(parse_json(code)["children"][0]["children"][0]["children"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn returns_inside_closures_only_exit_the_closure() {
+        let code = "fn run(items: Vec<u32>) -> u32 {\n    let v: Vec<u32> = items.iter().map(|x| { if bad { return 0; } x + 1 }).collect();\n    thread::spawn(move || {\n        if v.is_empty() { return; }\n        let n = load()?;\n    });\n    let total = sum(&v)?;\n    return total;\n}";
+        let json = parse_json(code);
+        let body = &json["children"][0]["children"][0]["children"];
+        // 展開しないクロージャの中の `return` は、その文の早期脱出として扱いません
+        assert!(body[0].get("scope").is_none());
+        let spawned = &body[1]["children"][0]["children"];
+        assert_eq!(spawned[0]["then_block"]["children"][0]["scope"], "closure");
+        assert_eq!(spawned[1]["scope"], "closure");
+        assert_eq!(body[2]["scope"], "function");
+        assert_eq!(body[3]["scope"], "function");
+    }
+
+    #[test]
+    fn cfg_macro_conditions_are_compile_time_switches() {
+        let code = "fn main() {\n    if cfg!(feature = \"metrics\") { record(); }\n    if cfg!(debug_assertions) && verbose { log(); }\n    if cfg2!(feature = \"metrics\") { other(); }\n}";
+        let json = parse_json(code);
+        let body = &json["children"][0]["children"][0]["children"];
+        assert_eq!(body[0]["compile_time"], true);
+        assert_eq!(body[0]["cfg_predicate"], "feature = \"metrics\"");
+        assert!(body[0].get("runtime_condition").is_none());
+
+        assert_eq!(body[1]["compile_time"], true);
+        assert_eq!(body[1]["cfg_predicate"], "debug_assertions");
+        assert_eq!(body[1]["runtime_condition"], "verbose");
+
+        assert!(body[2].get("compile_time").is_none());
+        assert!(body[2].get("cfg_predicate").is_none());
+    }
+
+    #[test]
+    fn unsupported_constructs_are_reported_as_fallbacks() {
+        let code = "fn main() {\n    let p = load();\n    || p.write(1);\n    || p.write(2);\n    struct Local;\n    call(p);\n}";
+        let json = parse_json(code);
+        let body = &json["children"][0]["children"][0]["children"];
+        assert_eq!(body[1]["fallback"], true);
+        assert_eq!(body[3]["fallback"], true);
+        assert!(body[4].get("fallback").is_none());
+
+        let report = &json["fallback_report"];
+        assert_eq!(report["Expr::Closure"]["count"], 2);
+        let lines: Vec<u64> =
+            report["Expr::Closure"]["examples"].as_array().unwrap().iter().map(|span| span["start_line"].as_u64().unwrap()).collect();
+        assert_eq!(lines, [3, 4]);
+        assert_eq!(report["Item::Struct"]["count"], 1);
+        assert_eq!(report.as_object().unwrap().len(), 2);
+
+        assert!(parse_json("fn main() {\n    let x = f(1);\n    if x > 0 { g(x); }\n}").get("fallback_report").is_none());
+    }
+
+    #[test]
+    fn matches_can_be_lowered_to_nested_ifs() {
+        let code = "fn main() {\n    match x {\n        Some(0) => a(),\n        Some(n) if n > 5 => b(n),\n        _ => c(),\n    }\n}";
+        let native = &parse_json(code)["children"][0]["children"][0]["children"][0];
+        assert_eq!(native["type"], "match");
+        let arms = native["arms"].as_array().unwrap();
+        assert_eq!(arms.len(), 3);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&parse_rust_code_with_options(code, "{\"match_as_if\": true}")).unwrap();
+        let first = &json["children"][0]["children"][0]["children"][0];
+        assert_eq!(first["type"], "if");
+        assert_eq!(first["lowered_from"], "match");
+        assert_eq!(first["condition"], format!("let {} = x", arms[0]["pattern"].as_str().unwrap()));
+        assert_eq!(first["then_block"]["children"][0]["label"], arms[0]["body"]["children"][0]["label"]);
+
+        let second = &first["else_block"];
+        assert_eq!(second["lowered_from"], "match");
+        assert_eq!(
+            second["condition"],
+            format!("let {} = x && {}", arms[1]["pattern"].as_str().unwrap(), arms[1]["guard"].as_str().unwrap())
+        );
+        assert_eq!(second["then_block"]["children"][0]["label"], arms[1]["body"]["children"][0]["label"]);
+        // ワイルドカードのアームは最後のelse節になります
+        assert_eq!(second["else_block"]["type"], "sequence");
+        assert_eq!(second["else_block"]["children"][0]["label"], arms[2]["body"]["children"][0]["label"]);
+    }
+
+    #[test]
+    fn deeply_nested_input_is_rejected_before_overflowing_the_stack() {
+        let code = format!("fn main() {{\n{} a(); {}\n}}", "{ ".repeat(5000), "} ".repeat(5000));
+        let json = parse_json(&code);
+        assert_eq!(json["type"], "error");
+        assert_eq!(json["message"], "Nesting is deeper than 48 levels; the code was not converted");
+        assert_eq!((json["line"].as_u64(), json["column"].as_u64()), (Some(2), Some(95)));
+
+        let chain = format!("fn main() {{\n    if a {{}} {} else {{}}\n}}", "else if a {} ".repeat(5000));
+        assert_eq!(parse_json(&chain)["type"], "error");
+
+        // 上限以内の入れ子は通常どおり変換し、上限は変更できます
+        let shallow = format!("fn main() {{ {} a(); {} }}", "{ ".repeat(20), "} ".repeat(20));
+        assert_eq!(parse_json(&shallow)["type"], "sequence");
+        let limited: serde_json::Value =
+            serde_json::from_str(&parse_rust_code_with_options(&shallow, "{\"max_depth\": 10}")).unwrap();
+        assert_eq!(limited["type"], "error");
+    }
+
+    #[test]
+    fn every_node_carries_its_source_span() {
+        let json = parse_json("fn main() {\n    let x = 1;\n    if x > 0 {\n        run(x);\n    }\n}");
+        let function = &json["children"][0];
+        assert_eq!(function["span"]["start_line"], 1);
+        assert_eq!(function["span"]["end_line"], 6);
+        let body = first_body(&json);
+        assert_eq!(body["children"][0]["span"]["start_line"], 2);
+        let if_node = &body["children"][1];
+        assert_eq!(if_node["span"]["start_line"], 3);
+        assert_eq!(if_node["span"]["end_line"], 5);
+        assert_eq!(if_node["then_block"]["children"][0]["span"]["start_line"], 4);
+    }
+
+    #[test]
+    fn native_parse_returns_the_same_tree_as_the_json_api() {
+        let code = "fn main() {\n    helper();\n}\nfn helper() {}";
+        let options = ParseOptions::from_json(r#"{"order": "name"}"#).unwrap();
+        let tree = parse(code, &options);
+        let PadNode::Sequence { children, .. } = &tree else { panic!("expected a sequence") };
+        assert_eq!(children.len(), 2);
+        assert_eq!(serde_json::to_value(&tree).unwrap(), parse_json_with(code, r#"{"order": "name"}"#));
+    }
+
+    #[test]
+    fn trait_default_methods_are_grouped_under_the_trait() {
+        let code = "trait Greet<T> {\n    fn name(&self) -> String;\n    fn hello(&self) {\n        println!(\"{}\", self.name());\n    }\n}\ntrait Marker {\n    fn id(&self) -> u32;\n}";
+        let json = parse_json(code);
+        let children = json["children"].as_array().unwrap();
+        assert_eq!(children.len(), 1);
+        let greet = &children[0];
+        assert_eq!((greet["kind"].as_str(), greet["label"].as_str()), (Some("trait"), Some("trait Greet<T>")));
+        assert_eq!(greet["trait_name"], "Greet");
+        let methods = greet["children"].as_array().unwrap();
+        assert_eq!(methods.len(), 1);
+        assert_eq!(methods[0]["label"], "fn Greet::hello(&self)");
+        assert_eq!(methods[0]["children"][0]["children"][0]["category"], "io");
+
+        // group_by が none の場合はメソッドをトップレベルに並べます
+        let json = parse_json_with(code, r#"{"group_by": "none"}"#);
+        assert_eq!(json["children"][0]["label"], "fn Greet::hello(&self)");
+    }
+
+    #[test]
+    fn result_envelope_separates_errors_from_the_tree() {
+        let result = |code: &str, options: &str| -> serde_json::Value {
+            serde_json::from_str(&parse_rust_code_result(code, options)).unwrap()
+        };
+        let ok = result("fn main() { run(); }", "");
+        assert_eq!(ok["ok"], true);
+        assert_eq!(ok["errors"], serde_json::json!([]));
+        assert_eq!(ok["tree"]["children"][0]["label"], "fn main()");
+
+        let broken = result("fn main() {\n    let x = ;\n}", "");
+        assert_eq!(broken["ok"], false);
+        assert!(broken.get("tree").is_none());
+        let error = &broken["errors"][0];
+        assert_eq!((error["kind"].as_str(), error["line"].as_u64(), error["column"].as_u64()), (Some("syntax"), Some(2), Some(13)));
+
+        // recover の場合は、解析できた部分の図とすべての構文エラーを返します
+        let recovered = result("fn a() { run(); }\nfn b() { x + ; }\nfn c() -> { }", r#"{"recover": true}"#);
+        assert_eq!(recovered["ok"], false);
+        assert_eq!(recovered["tree"]["children"][0]["label"], "fn a()");
+        assert_eq!(recovered["errors"].as_array().unwrap().len(), 2);
+
+        assert_eq!(result("fn main() {}", r#"{"mode": 1}"#)["errors"][0]["kind"], "options");
+        assert_eq!(result("struct A;", "")["errors"][0]["kind"], "empty");
+        assert_eq!(result("fn main() { { { } } }", r#"{"max_depth": 2}"#)["errors"][0]["kind"], "nesting");
+    }
+
+    #[test]
+    fn pretty_labels_format_statements_like_rust() {
+        let code = "fn main() {\n    let n: Vec<u8> = v.iter().map(|i| i * 2).collect();\n    if n.len() > 0 && !done { go(&mut n[0]); }\n    for (i, x) in n.iter().enumerate() { println!(\"{}\", x); }\n    match n.first() { Some(&v) if v > 1 => a(), _ => b() }\n}";
+        let json = parse_json_with(code, r#"{"pretty_labels": true}"#);
+        let body = first_body(&json);
+        assert_eq!(body["children"][0]["label"], "let n: Vec<u8> = v.iter().map(|i| i * 2).collect();");
+        assert_eq!(body["children"][1]["condition"], "n.len() > 0 && !done");
+        assert_eq!(body["children"][1]["then_block"]["children"][0]["label"], "go(&mut n[0])");
+        assert_eq!(body["children"][2]["condition"], "for (i, x) in n.iter().enumerate()");
+        assert_eq!(body["children"][2]["body"]["children"][0]["label"], "println!(\"{}\", x);");
+        let arm = &body["children"][3]["arms"][0];
+        assert_eq!((arm["pattern"].as_str(), arm["guard"].as_str()), (Some("Some(&v)"), Some("v > 1")));
+        // 既定ではquote!の出力のままです
+        assert_eq!(first_body(&parse_json(code))["children"][1]["then_block"]["children"][0]["label"], "go (& mut n [0])");
+    }
+
+    #[test]
+    fn output_shape_options_shorten_collapse_and_filter_the_tree() {
+        let code = "pub fn run() {\n    let f = move |x| { if x > 0 { g(x); } };\n    if a { while b { if c { d(); } } }\n    compute_everything_at_once(first, second);\n}\nfn helper() {}\nstruct S;\nimpl S { fn hidden(&self) {} pub fn shown(&self) {} }";
+        let json = parse_json_with(
+            code,
+            r#"{"max_label_length": 16, "truncation_marker": "...", "collapse_depth": 1, "include_private": false, "expand_closures": true}"#,
+        );
+        assert_eq!(json["filtered"], 2);
+        let functions = json["children"].as_array().unwrap();
+        assert_eq!(functions.len(), 2);
+        let body = first_body(&json);
+        let closure = &body["children"][0];
+        assert_eq!((closure["kind"].as_str(), closure["label"].as_str()), (Some("closure"), Some("let f = move |x|")));
+        // 深さ1の制御構造（ifの中のwhile）は中身を省略します
+        let outer = &body["children"][1];
+        let inner = &outer["then_block"]["children"][0];
+        assert_eq!((inner["collapsed"].as_bool(), inner["child_count"].as_u64()), (Some(true), Some(1)));
+        assert_eq!(inner["body"]["children"].as_array().unwrap().len(), 0);
+        assert!(outer.get("collapsed").is_none());
+        let command = &body["children"][2];
+        assert_eq!(command["label"], "compute_everythi...");
+        assert_eq!(command["full_label"], "compute_everything_at_once (first , second)");
+        assert_eq!(functions[1]["children"][0]["label"], "fn S::shown(&sel...");
+        // 既定ではクロージャは1行のCommandです
+        assert_eq!(first_body(&parse_json(code))["children"][0]["type"], "command");
+    }
+
+    #[test]
+    fn returns_are_explicit_nodes_and_tail_expressions_are_exits() {
+        let code = "fn pick(x: i32) -> i32 {\n    if x < 0 { return -x; }\n    let f = || { return; };\n    x * 2\n}\nfn run() { go(); }";
+        let json = parse_json(code);
+        let body = first_body(&json);
+        let early = &body["children"][0]["then_block"]["children"][0];
+        assert_eq!(early["type"], "return");
+        assert_eq!((early["label"].as_str(), early["value"].as_str()), (Some("return - x"), Some("- x")));
+        assert_eq!((early["scope"].as_str(), early["returns"].as_bool()), (Some("function"), Some(true)));
+        // 末尾の式は暗黙の戻り値として関数の出口になります
+        assert_eq!(body["children"][2]["returns"], true);
+        assert!(body["children"][1].get("returns").is_none());
+        // 戻り値の型がない関数の末尾の式は出口として扱いません
+        assert!(json["children"][1]["children"][0]["children"][0].get("returns").is_none());
+    }
+
+    #[test]
+    fn closures_passed_to_iterators_are_expanded_under_the_call() {
+        let code = "fn main() {\n    let n: Vec<u8> = v.iter().map(|x| { if ok(x) { a(x) } else { 0 } }).filter(|y| y > 1).collect();\n    items.for_each(move |it| { process(it); });\n}";
+        let json = parse_json_with(code, r#"{"expand_closures": true}"#);
+        let body = first_body(&json);
+        let call = &body["children"][0];
+        assert_eq!((call["kind"].as_str(), call["children"].as_array().unwrap().len()), (Some("call"), 1));
+        let closure = &call["children"][0];
+        assert_eq!((closure["kind"].as_str(), closure["label"].as_str()), (Some("closure"), Some("|x|")));
+        assert_eq!(closure["children"][0]["children"][0]["type"], "if");
+        assert_eq!(body["children"][1]["children"][0]["label"], "move |it|");
+        assert_eq!(body["children"][1]["children"][0]["children"][0]["children"][0]["label"], "process (it)");
+    }
+
+    #[test]
+    fn try_operators_become_early_return_branches() {
+        let code = "fn main() -> Result<(), E> {\n    let x = fetch(a)?.parse()?;\n    if check(x)? { go()?; }\n    Ok(())\n}";
+        let json = parse_json_with(code, r#"{"desugar_try": true}"#);
+        let body = first_body(&json)["children"].as_array().unwrap().clone();
+        let kinds: Vec<&str> = body.iter().map(|node| node["type"].as_str().unwrap()).collect();
+        assert_eq!(kinds, vec!["if", "if", "command", "if", "if", "command"]);
+        assert_eq!(body[0]["condition"], "fetch (a) is Err / None");
+        assert_eq!(body[0]["full_label"], "fetch (a) ?");
+        assert_eq!(body[0]["lowered_from"], "try");
+        assert_eq!(body[1]["condition"], "fetch (a) ? . parse () is Err / None");
+        let exit = &body[0]["then_block"]["children"][0];
+        assert_eq!((exit["type"].as_str(), exit["returns"].as_bool()), (Some("return"), Some(true)));
+        // ブロックの中の `?` は、その中の文の前に置きます
+        assert_eq!(body[4]["then_block"]["children"][0]["condition"], "go () is Err / None");
+        assert_eq!(first_body(&parse_json(code))["children"][0]["type"], "command");
+    }
+
+    #[test]
+    fn diff_marks_added_removed_and_modified_nodes() {
+        let old = "fn main() {\n    init();\n    if ready {\n        run();\n    }\n    cleanup();\n}\nfn helper() { x(); }";
+        let new = "fn main() {\n    init();\n    if ready {\n        run_fast();\n    }\n    report();\n}\nfn helper() { x(); }";
+        let json: serde_json::Value = serde_json::from_str(&diff_rust_code(old, new)).unwrap();
+        assert_eq!(json["diff"], "modified");
+        let main = &json["children"][0];
+        assert_eq!(main["diff"], "modified");
+        let body = &main["children"][0]["children"];
+        let statuses: Vec<(&str, &str)> =
+            body.as_array().unwrap().iter().map(|n| (n["type"].as_str().unwrap(), n["diff"].as_str().unwrap())).collect();
+        assert_eq!(statuses, [("command", "unchanged"), ("if", "modified"), ("command", "modified")]);
+        assert_eq!(body[2]["label"], "report ()");
+        assert_eq!(json["children"][1]["diff"], "unchanged");
+
+        // 対応する相手のないノードは削除・追加として残り、削除側のidは重複しません
+        let json: serde_json::Value =
+            serde_json::from_str(&diff_rust_code("fn main() { a(); b(); }", "fn main() { b(); while x { c(); } }")).unwrap();
+        let body = json["children"][0]["children"][0]["children"].as_array().unwrap();
+        let statuses: Vec<&str> = body.iter().map(|n| n["diff"].as_str().unwrap()).collect();
+        assert_eq!(statuses, ["removed", "unchanged", "added"]);
+        assert!(body[0]["id"].as_str().unwrap().starts_with("removed-"));
+        assert_eq!(body[2]["body"]["children"][0]["diff"], "added");
+
+        let error: serde_json::Value = serde_json::from_str(&diff_rust_code("fn main() {", "fn main() {}")).unwrap();
+        assert_eq!(error["type"], "error");
+    }
+
+    #[test]
+    fn diff_pairs_reordered_items_by_name() {
+        let old = "fn a() { x(); }\nfn b() { y(); }\nconst N: u8 = 1;";
+        let new = "fn b() { y(); }\nconst M: u8 = 2;\nfn a() { x(); z(); }\nfn c() {}";
+        let json: serde_json::Value = serde_json::from_str(&diff_rust_code(old, new)).unwrap();
+        let items: Vec<(&str, &str)> = json["children"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|n| (n["label"].as_str().unwrap(), n["diff"].as_str().unwrap()))
+            .collect();
+        assert_eq!(items, [("fn b()", "unchanged"), ("const M: u8 = 2", "modified"), ("fn a()", "modified"), ("fn c()", "added")]);
+        let body = &json["children"][2]["children"][0]["children"];
+        assert_eq!((body[0]["diff"].as_str(), body[1]["diff"].as_str()), (Some("unchanged"), Some("added")));
+
+        // 並べ替えただけの場合は変更なしです
+        let json: serde_json::Value = serde_json::from_str(&diff_rust_code("fn a() { x(); }\nfn b() {}", "fn b() {}\nfn a() { x(); }")).unwrap();
+        assert_eq!(json["diff"], "unchanged");
+    }
+
+    #[test]
+    fn comments_option_attaches_comments_and_docs() {
+        let code = "/// Entry point.\n/// Runs once.\nfn main() {\n    // pad:label: setup\n    // prepare the state\n    init();\n    run(); // main work\n    done();\n}";
+        let json = parse_json_with(code, r#"{"comments": true}"#);
+        let main = &json["children"][0];
+        assert_eq!(main["comment"], "Entry point.\nRuns once.");
+        let body = &main["children"][0]["children"];
+        assert_eq!(body[0]["comment"], "prepare the state");
+        assert_eq!(body[0]["label"], "setup");
+        assert_eq!(body[1]["comment"], "main work");
+        assert!(body[2].get("comment").is_none());
+
+        let json = parse_json(code);
+        assert!(json["children"][0].get("comment").is_none());
+    }
+
+    #[test]
+    fn calls_link_to_functions_defined_in_the_same_input() {
+        let code = "fn main() {\n    let c = Counter::new();\n    if ready() { println!(\"x\"); }\n    helper(std::env::args());\n}\nfn helper<T>(_: T) {}\nfn ready() -> bool { true }\nstruct Counter;\nimpl Counter {\n    fn new() -> Self { Self::reset(); Counter }\n    fn reset() {}\n}";
+        let json = parse_json(code);
+        let id_of = |path: &[usize]| {
+            let mut node = &json;
+            for &i in path {
+                node = &node["children"][i];
+            }
+            node["id"].clone()
+        };
+        let body = &first_body(&json)["children"];
+        assert_eq!(body[0]["calls"], serde_json::json!(["Counter::new"]));
+        assert_eq!(body[0]["call_targets"][0], id_of(&[3, 0]));
+        assert_eq!(body[1]["calls"], serde_json::json!(["ready"]));
+        assert_eq!(body[1]["call_targets"][0], id_of(&[2]));
+        // 同じ入力で定義されていない関数（`std::env::args`）は含めません
+        assert_eq!(body[2]["calls"], serde_json::json!(["helper"]));
+        assert_eq!(body[2]["call_targets"][0], id_of(&[1]));
+        // `Self::` はimplの型に読み替えます
+        let new_body = &json["children"][3]["children"][0]["children"][0]["children"];
+        assert_eq!(new_body[0]["calls"], serde_json::json!(["Counter::reset"]));
+        assert!(new_body[1].get("calls").is_none());
+    }
+
+    #[test]
+    fn call_graph_lists_functions_and_resolved_calls() {
+        let code = "fn main() {\n    let mut c = Counter::new();\n    c.tick();\n    c.tick();\n    v.push(1);\n}\nstruct Counter;\nimpl Counter {\n    fn new() -> Self { Counter }\n    fn tick(&mut self) { self.log(); }\n    fn log(&self) {}\n}";
+        let json: serde_json::Value = serde_json::from_str(&build_call_graph(code)).unwrap();
+        let names: Vec<&str> = json["nodes"].as_array().unwrap().iter().map(|n| n["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["main", "Counter::new", "Counter::tick", "Counter::log"]);
+        let id = |i: usize| json["nodes"][i]["id"].as_str().unwrap();
+        let edges: Vec<(&str, &str, u64)> = json["edges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| (e["from"].as_str().unwrap(), e["to"].as_str().unwrap(), e["count"].as_u64().unwrap()))
+            .collect();
+        // 同じ入力にない `push` はエッジになりません
+        assert_eq!(edges, [(id(0), id(1), 1), (id(0), id(2), 2), (id(2), id(3), 1)]);
+    }
+
+    #[test]
+    fn calls_into_generic_impls_are_linked() {
+        let code = "fn main() {\n    let w = Wrap::new(1);\n}\nstruct Wrap<T>(T);\nimpl<T: Clone> Wrap<T> {\n    fn new(v: T) -> Self { Wrap(v) }\n}";
+        let json = parse_json(code);
+        let body = &first_body(&json)["children"];
+        assert_eq!(body[0]["calls"], serde_json::json!(["Wrap::new"]));
+        assert_eq!(body[0]["call_targets"][0], json["children"][1]["children"][0]["id"]);
+        let graph: serde_json::Value = serde_json::from_str(&build_call_graph(code)).unwrap();
+        let names: Vec<&str> = graph["nodes"].as_array().unwrap().iter().map(|n| n["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["main", "Wrap::new"]);
+        assert_eq!(graph["edges"][0]["from"], graph["nodes"][0]["id"]);
+        assert_eq!(graph["edges"][0]["to"], graph["nodes"][1]["id"]);
+    }
+
+    #[test]
+    fn project_files_are_nested_by_module_path() {
+        let files = serde_json::json!({
+            "src/main.rs": "mod net;\nmod util;\nfn main() { util::helper(); }",
+            "src/util.rs": "pub fn helper() { net::send(); }",
+            "src/net/mod.rs": "mod http;\npub fn send() {}",
+            "src/net/http.rs": "pub fn get() {",
+            "tools/gen.rs": "fn main() {}",
+        });
+        let json: serde_json::Value = serde_json::from_str(&parse_rust_project(&files.to_string())).unwrap();
+        let roots = json["children"].as_array().unwrap();
+        let summary = |node: &serde_json::Value| (node["label"].as_str().unwrap().to_string(), node["module_path"].as_str().unwrap().to_string());
+        assert_eq!(roots.len(), 2);
+        assert_eq!(summary(&roots[0]), ("src/main.rs".to_string(), "crate".to_string()));
+        assert_eq!(summary(&roots[1]), ("tools/gen.rs".to_string(), "crate".to_string()));
+        let main = roots[0]["children"].as_array().unwrap();
+        assert_eq!(main[0]["label"], "fn main()");
+        assert_eq!(summary(&main[1]), ("src/net/mod.rs".to_string(), "crate::net".to_string()));
+        assert_eq!(summary(&main[2]), ("src/util.rs".to_string(), "crate::util".to_string()));
+        let http = &main[1]["children"][1];
+        assert_eq!(summary(http), ("src/net/http.rs".to_string(), "crate::net::http".to_string()));
+        assert_eq!(http["children"][0]["type"], "error");
+        // ファイルをまたぐ呼び出しも対応付けます
+        assert_eq!(main[0]["children"][0]["children"][0]["call_targets"][0], main[2]["children"][0]["id"]);
+
+        let error: serde_json::Value = serde_json::from_str(&parse_rust_project("[]")).unwrap();
+        assert_eq!(error["type"], "error");
+    }
+
+    #[test]
+    fn plantuml_activity_diagram_of_control_flow() {
+        let code = "fn main() {\n    init();\n    if x > 0 { go(); } else { return; }\n    while n > 0 { n -= 1; }\n    loop {\n        step();\n        if done() { break; }\n    }\n    match k { 1 => one(), _ => {} }\n}\nimpl S { fn f(&self) { a(); } }";
+        let expected = "@startuml
+start
+partition \"fn main()\" {
+  :init ();
+  if (x > 0) then (true)
+    :go ();
+  else (false)
+    :return;
+    stop
+  endif
+  while (n > 0)
+    :n -= 1;
+  endwhile
+  repeat
+    :step ();
+  repeat while (! done ())
+  switch (k)
+  case (1)
+    :one ();
+  case (_)
+  endswitch
+}
+stop
+start
+partition \"fn S::f(&self)\" {
+  :a ();
+}
+stop
+@enduml
+";
+        assert_eq!(to_plantuml(code), expected);
+        assert_eq!(render_plantuml(&parse(code, &ParseOptions::default())), expected);
+        assert!(to_plantuml_with_options("fn main() {}", "{\"mode\": 1}").contains("#pink:Invalid options"));
+    }
+
+    #[test]
+    fn function_can_be_selected_by_name() {
+        let code = "fn main() { run(); }\nstruct Counter;\nimpl Counter {\n    fn next(&mut self) { step(); }\n}\nmod util { pub fn helper() {} }";
+        let json: serde_json::Value = serde_json::from_str(&parse_function_by_name(code, "Counter::next")).unwrap();
+        assert_eq!((json["kind"].as_str(), json["label"].as_str()), (Some("function"), Some("fn Counter::next(&mut self)")));
+        // idはファイル全体を変換した場合と同じです
+        let whole = parse_json(code);
+        assert_eq!(json["id"], whole["children"][1]["children"][0]["id"]);
+        let json: serde_json::Value = serde_json::from_str(&parse_function_by_name(code, "helper")).unwrap();
+        assert_eq!(json["label"], "fn helper()");
+
+        let json: serde_json::Value = serde_json::from_str(&parse_function_by_name(code, "Counter::prev")).unwrap();
+        assert_eq!((json["type"].as_str(), json["kind"].as_str()), (Some("error"), Some("not_found")));
+        assert_eq!(json["message"], "Function not found: Counter::prev");
+    }
+
+    #[test]
+    fn function_names_ignore_generics_and_accept_module_paths() {
+        let code = "fn run() {}\nmod a { pub fn run() {} }\nmod b {\n    pub fn run() {}\n    pub struct Wrap<T>(T);\n    impl<T> Wrap<T> { pub fn get(&self) {} }\n}";
+        let select = |name: &str| -> serde_json::Value { serde_json::from_str(&parse_function_by_name(code, name)).unwrap() };
+        assert_eq!(select("Wrap::get")["label"], "fn Wrap<T>::get(&self)");
+        assert_eq!(select("b::Wrap<T>::get")["label"], "fn Wrap<T>::get(&self)");
+        assert_eq!(select("b::run")["span"]["start_line"], 4);
+        assert_eq!(select("crate::run")["span"]["start_line"], 1);
+        // 該当する関数が複数ある場合は候補を並べます
+        let json = select("run");
+        assert_eq!(json["kind"], "ambiguous");
+        assert_eq!(json["message"], "Function name is ambiguous: run matches run (line 1), a::run (line 2), b::run (line 4)");
+        // `group_by: "module"` でモジュールをまとめた場合も同じです
+        let json: serde_json::Value =
+            serde_json::from_str(&parse_function_by_name_with_options(code, "a::run", "{\"group_by\": \"module\"}")).unwrap();
+        assert_eq!(json["span"]["start_line"], 2);
+    }
+
+    #[test]
+    fn async_functions_and_await_points_are_marked() {
+        let code = "async fn fetch() {\n    let a = get(1).await;\n    let b = join(get(2), get(3)).await.ok()?.await;\n    while let Some(x) = rx.recv().await { handle(x); }\n    sync();\n}\nfn plain() {}\nimpl S { async fn run(&self) {} }";
+        let json = parse_json(code);
+        assert_eq!(json["children"][0]["is_async"], true);
+        assert!(json["children"][1].get("is_async").is_none());
+        assert_eq!(json["children"][2]["children"][0]["is_async"], true);
+        let body = &first_body(&json)["children"];
+        let awaits: Vec<Option<u64>> = body.as_array().unwrap().iter().map(|n| n["awaits"].as_u64()).collect();
+        assert_eq!(awaits, [Some(1), Some(2), Some(1), None]);
+        assert!(body[2]["body"]["children"][0].get("awaits").is_none());
+    }
+
+    #[test]
+    fn unsafe_blocks_and_functions_are_marked() {
+        let code = "unsafe fn raw(p: *mut u8) {\n    p.write(0);\n}\nfn main() {\n    let p = alloc();\n    unsafe {\n        raw(p);\n    }\n    free(p);\n}";
+        let json = parse_json(code);
+        assert_eq!(json["children"][0]["is_unsafe"], true);
+        assert_eq!(first_body(&json)["is_unsafe"], true);
+        assert!(json["children"][1].get("is_unsafe").is_none());
+
+        let block = &json["children"][1]["children"][0]["children"][1];
+        assert_eq!(block["kind"], "unsafe");
+        assert_eq!(block["label"], "unsafe");
+        assert_eq!(block["is_unsafe"], true);
+        assert_eq!(block["children"][0]["is_unsafe"], true);
+        assert_eq!(block["children"][0]["children"][0]["label"], "raw (p)");
+        assert!(block.get("fallback").is_none());
+        assert!(json.get("fallback_report").is_none());
+    }
+
+    #[test]
+    fn std_macros_can_be_expanded_into_branches() {
+        let code = "fn check(v: &[u8], c: char) -> bool {\n    assert!(!v.is_empty(), \"empty\");\n    assert_eq!(v[0], 1);\n    println!(\"ok\");\n    matches!(c, 'a'..='z' | '_')\n}";
+        let json = parse_json_with(code, r#"{"expand_macros": true}"#);
+        let body = &first_body(&json)["children"];
+        assert_eq!(body[0]["type"], "if");
+        assert_eq!(body[0]["lowered_from"], "assert");
+        assert_eq!(body[0]["span"]["start_line"], 2);
+        assert_eq!(body[0]["then_block"]["children"][0]["category"], "exit");
+        assert_eq!(body[1]["lowered_from"], "assert_eq");
+        assert_eq!(body[1]["condition"], "v [0] != 1");
+        assert_eq!(body[2]["type"], "command");
+        assert_eq!(body[3]["type"], "match");
+        assert_eq!(body[3]["arms"].as_array().unwrap().len(), 2);
+
+        let plain = parse_json(code);
+        assert!(first_body(&plain)["children"].as_array().unwrap().iter().all(|node| node["type"] == "command"));
+    }
+
+    #[test]
+    fn pad_json_round_trips_to_rust_skeleton() {
+        let code = "impl P {\n    async fn run(&mut self, items: &[u8]) {\n        'outer: for (i, it) in items.iter().enumerate() {\n            while self.x > 0 { self.x -= 1; if i == 3 { break 'outer; } }\n        }\n        loop { step(); if done() { break; } }\n        match self.x { 0 => return, n if n > 5 => { go(n, \"{}\"); } _ => {} }\n        unsafe { raw(); }\n    }\n}";
+        let rust = pad_to_rust(&parse_rust_code(code));
+        assert!(rust.starts_with("impl P {\n    async fn run(&mut self, items: &[u8]) {\n"), "{}", rust);
+        assert!(rust.contains("todo!(\"go (n , \\\"{{}}\\\")\");"), "{}", rust);
+        assert!(rust.contains("if done () {\n                break;\n            }"), "{}", rust);
+        let file = syn::parse_file(&rust).unwrap();
+        let reparsed = parse_json(&rust);
+        let body = &reparsed["children"][0]["children"][0]["children"][0]["children"];
+        let kinds: Vec<&str> = body.as_array().unwrap().iter().map(|n| n["type"].as_str().unwrap()).collect();
+        assert_eq!(kinds, ["loop", "loop", "match", "block"]);
+        assert_eq!(body[1]["kind"], "do_while");
+        assert_eq!(file.items.len(), 1);
+
+        let drawn = r#"{"type": "sequence", "children": [{"type": "if", "condition": "在庫が 1 個以上", "then_block": {"type": "sequence", "children": [{"type": "command", "label": "出荷する"}]}}]}"#;
+        assert_eq!(pad_to_rust(drawn), "fn main() {\n    if todo!(\"在庫が 1 個以上\") {\n        todo!(\"出荷する\");\n    }\n}\n");
+        assert!(pad_to_rust("{").contains("Invalid JSON"));
+    }
+
+    #[test]
+    fn rust_skeleton_keeps_signatures_and_modules() {
+        let code = "fn main() -> Result<(), Box<dyn std::error::Error>> {\n    if std::env::args().count() > 1 {\n        return Err(\"too many arguments\".into());\n    }\n    return Ok(());\n}\nconst LIMIT: u8 = 3;\nmod b {\n    pub struct Counter { pub n: u8 }\n    impl Counter {\n        pub fn bar(&mut self, a: u8) -> u8 {\n            if a > self.n { return a; }\n            self.n += a;\n            self.n\n        }\n    }\n    impl Iterator for Counter {\n        type Item = u8;\n        fn next(&mut self) -> Option<u8> {\n            match self.n { 0 => None, n => Some(n) }\n        }\n    }\n}";
+        let rust = pad_to_rust(&parse_rust_code(code));
+        assert!(rust.starts_with("fn main() -> Result<(), Box<dyn std::error::Error>> {\n"), "{}", rust);
+        assert!(rust.contains("\nconst LIMIT: u8 = 3;\nmod b {\n    impl Counter {\n        pub fn bar(&mut self, a: u8) -> u8 {\n"), "{}", rust);
+        assert!(rust.contains("    impl Iterator for Counter {\n        type Item = u8;\n        fn next(&mut self) -> Option<u8> {\n"), "{}", rust);
+        syn::parse_file(&rust).unwrap();
+
+        // 構造体の宣言は図に含まれないため補ってから、`todo!()` 以外の部分が型検査を通ることを確かめます
+        let source = rust.replace("mod b {\n", "mod b {\n    pub struct Counter { pub n: u8 }\n");
+        let dir = std::env::temp_dir().join(format!("pad-skeleton-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("skeleton.rs"), source).unwrap();
+        let output = std::process::Command::new(std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string()))
+            .args(["--edition", "2024", "--crate-type", "lib", "--emit", "metadata", "-A", "warnings", "--out-dir"])
+            .arg(&dir)
+            .arg(dir.join("skeleton.rs"))
+            .output()
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    }
+
+    #[test]
+    fn constant_conditions_mark_dead_branches() {
+        let code = "fn main() {\n    if true { a(); }\n    if false { b(); } else { c(); }\n    while false { d(); }\n    if 1 == 2 { e(); }\n    if x == 2 { f(); } else { g(); }\n}";
+        let json = parse_json(code);
+        let body = &json["children"][0]["children"][0]["children"];
+        assert_eq!(body[0]["then_block"]["always_runs"], true);
+        assert!(body[0]["then_block"].get("dead").is_none());
+
+        assert_eq!(body[1]["then_block"]["dead"], true);
+        assert_eq!(body[1]["else_block"]["always_runs"], true);
+
+        assert_eq!(body[2]["never_runs"], true);
+        assert_eq!(body[2]["body"]["dead"], true);
+        assert_eq!(body[3]["then_block"]["dead"], true);
+
+        for key in ["dead", "always_runs", "never_runs"] {
+            assert!(body[4].get(key).is_none());
+            assert!(body[4]["then_block"].get(key).is_none());
+            assert!(body[4]["else_block"].get(key).is_none());
+        }
+    }
+
+    #[test]
+    fn copied_blocks_are_reported_as_clones() {
+        let code = "fn main() {\n    if a {\n        x += 1;\n        y = x * 2;\n        log(y);\n        v.push(y);\n    }\n    if b {\n        x += 1;\n        y = x * 2;\n        log(y);\n        v.push(y);\n    }\n    if c {\n        x += 1;\n        y = x * 3;\n        log(y);\n        v.push(y);\n    }\n    if d {\n        z += 1;\n        w = z * 2;\n        log(w);\n        u.push(w);\n    }\n}";
+        let json: serde_json::Value =
+            serde_json::from_str(&parse_rust_code_with_options(code, "{\"clones\": true}")).unwrap();
+        let body = &json["children"][0]["children"][0]["children"];
+        let clones = json["clones"].as_array().unwrap();
+        assert_eq!(clones.len(), 1);
+        assert_eq!(clones[0]["size"], 5);
+        assert_eq!(clones[0]["nodes"], serde_json::json!([body[0]["then_block"]["id"], body[1]["then_block"]["id"]]));
+        assert_eq!(body[0]["then_block"]["clone_group"], 0);
+        assert_eq!(body[1]["then_block"]["clone_group"], 0);
+        // 定数が1つ違うだけの近い複製や、名前だけが異なる複製は、既定では同じとみなしません
+        assert!(body[2]["then_block"].get("clone_group").is_none());
+        assert!(body[3]["then_block"].get("clone_group").is_none());
+
+        let json: serde_json::Value = serde_json::from_str(&parse_rust_code_with_options(
+            code,
+            "{\"clones\": true, \"clone_ignore_names\": true}",
+        ))
+        .unwrap();
+        let body = &json["children"][0]["children"][0]["children"];
+        assert_eq!(json["clones"].as_array().unwrap().len(), 1);
+        // 条件の変数名も無視されるため、if全体が複製になります
+        assert_eq!(json["clones"][0]["nodes"], serde_json::json!([body[0]["id"], body[1]["id"], body[3]["id"]]));
+        assert!(body[2].get("clone_group").is_none());
+    }
+
+    #[test]
+    fn loops_summarize_the_variables_they_modify() {
+        let code = "fn main() {\n    let mut sum = 0;\n    for x in &v { sum += x; }\n    for (i, x) in v.iter().enumerate() { println!(\"{} {}\", i, x); }\n    for row in rows {\n        let mut row_sum = 0;\n        for cell in row { row_sum += cell; }\n        totals.push(row_sum);\n    }\n    while let Some(n) = stack.pop() { seen[n] = true; }\n}";
+        let json = parse_json(code);
+        let body = &json["children"][0]["children"][0]["children"];
+        assert_eq!(body[1]["modifies"], serde_json::json!(["sum"]));
+        assert_eq!(body[1]["loop_variables"], serde_json::json!(["x"]));
+
+        // 読み取るだけのループ
+        assert!(body[2].get("modifies").is_none());
+        assert_eq!(body[2]["loop_variables"], serde_json::json!(["i", "x"]));
+
+        // 入れ子のループはそれぞれ集計し、外側のループの中で宣言された変数は外側にも含めません
+        assert_eq!(body[3]["modifies"], serde_json::json!(["totals"]));
+        let inner = &body[3]["body"]["children"][1];
+        assert_eq!(inner["modifies"], serde_json::json!(["row_sum"]));
+        assert_eq!(inner["loop_variables"], serde_json::json!(["cell"]));
+
+        assert_eq!(body[4]["modifies"], serde_json::json!(["stack", "seen"]));
+        assert!(body[4].get("loop_variables").is_none());
+    }
+
+    #[test]
+    fn matches_on_local_enums_report_variant_coverage() {
+        let code = "enum Light { Red, Yellow, Green(u8) }\nfn step(light: Light) {\n    match light {\n        Light::Red => stop(),\n        Light::Yellow | Light::Green(_) => go(),\n    }\n    let next: Light = light.next();\n    match next {\n        Light::Green(0) => wait(),\n        Light::Red => stop(),\n        _ => go(),\n    }\n    match ordering {\n        Ordering::Less => a(),\n        _ => b(),\n    }\n}";
+        let json = parse_json(code);
+        let body = &json["children"][0]["children"][0]["children"];
+        assert_eq!(body[0]["enum_type"], "Light");
+        assert_eq!(body[0]["covered"], serde_json::json!(["Red", "Yellow", "Green"]));
+        assert!(body[0].get("uncovered_via_wildcard").is_none());
+
+        // 一部の値だけを扱うバリアントは、ワイルドカードで扱われる側に数えます
+        assert_eq!(body[2]["covered"], serde_json::json!(["Red"]));
+        assert_eq!(body[2]["uncovered_via_wildcard"], serde_json::json!(["Yellow", "Green"]));
+
+        // 入力の外で定義されたenumは判定しません
+        for key in ["enum_type", "covered", "uncovered_via_wildcard"] {
+            assert!(body[3].get(key).is_none());
+        }
+    }
+
+    #[test]
+    fn generic_impl_renders_generics_compactly() {
+        let code = "impl<T: Clone> From<Vec<T>> for Bar<T> {\n    fn from(v: Vec<T>) -> Self { todo!() }\n}";
+        let json = parse_json(code);
+        let imp = &json["children"][0];
+        assert_eq!(imp["label"], "impl<T: Clone> From<Vec<T>> for Bar<T>");
+        assert_eq!(imp["children"][0]["label"], "fn <Bar<T> as From<Vec<T>>>::from(v: Vec<T>)");
+    }
+
+    #[test]
+    fn macro_rules_lists_rule_matchers() {
+        let code = "macro_rules! square {\n    ($x:expr) => { $x * $x };\n    ($x:expr, $y:expr) => { $x * $y }\n}";
+        let json = parse_json(code);
+        let mac = &json["children"][0];
+        assert_eq!(mac["label"], "macro_rules! square");
+        let rules = mac["children"].as_array().unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0]["label"], "($x:expr) =>");
+        assert_eq!(rules[1]["label"], "($x:expr, $y:expr) =>");
+    }
+
+    #[test]
+    fn collapse_macros_option_emits_single_declaration() {
+        let code = "macro_rules! square { ($x:expr) => { $x * $x }; }";
+        let json: serde_json::Value =
+            serde_json::from_str(&parse_rust_code_with_options(code, "{\"collapse_macros\": true}")).unwrap();
+        assert_eq!(json["children"][0]["type"], "command");
+        assert_eq!(json["children"][0]["label"], "macro_rules! square");
+    }
+
+    #[test]
+    fn script_mode_wraps_top_level_statements() {
+        let code = "let data = load();\nfn helper(x: i32) -> i32 { x * 2 }\nfor x in data {\n    helper(x);\n}";
+        let json: serde_json::Value =
+            serde_json::from_str(&parse_rust_code_with_options(code, "{\"mode\": \"script\"}")).unwrap();
+        let script = &json["children"][0];
+        assert_eq!(script["label"], "(script)");
+        let stmts = script["children"][0]["children"].as_array().unwrap();
+        assert_eq!(stmts.len(), 3);
+        assert_eq!(stmts[0]["label"], "let data = load () ;");
+        assert_eq!(stmts[1]["type"], "block");
+        assert_eq!(stmts[1]["label"], "fn helper()");
+        assert_eq!(stmts[2]["type"], "loop");
+    }
+
+    #[test]
+    fn file_mode_rejects_top_level_statements() {
+        let json = parse_json("let data = load();");
+        assert_eq!(json["type"], "error");
+    }
+
+    #[test]
+    fn markdown_fences_are_converted_independently() {
+        let text = "# Usage\n\n```rust\nfn main() { if ready() { go(); } }\n```\n\n```python\nprint('x')\n```\n\n## Broken\n\n```rust,no_run\n# let x = 1;\nfn oops( {\n```\n\n```rust\nlet total = sum(1, 2);\n```\n";
+        let json: serde_json::Value = serde_json::from_str(&parse_markdown(text)).unwrap();
+        let entries = json.as_array().unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0]["line"], 3);
+        assert_eq!(entries[0]["title"], "Usage");
+        assert_eq!(entries[0]["tree"]["children"][0]["label"], "fn main()");
+        assert_eq!(entries[1]["line"], 13);
+        assert_eq!(entries[1]["title"], "Broken");
+        assert_eq!(entries[1]["tree"]["type"], "error");
+        assert_eq!(entries[1]["tree"]["line"], 2);
+        // fn main のない例はスクリプトとして解析します
+        assert_eq!(entries[2]["tree"]["children"][0]["kind"], "script");
+    }
+
+    /// オプション付きで解析した結果をJSON値として取得するヘルパー
+    fn parse_json_with(code: &str, options: &str) -> serde_json::Value {
+        serde_json::from_str(&parse_rust_code_with_options(code, options)).unwrap()
+    }
+
+    const INLINE_SAMPLE: &str = "fn main() {\n    helper(1);\n}\nfn helper(x: i32) {\n    let y = inner(x);\n}\nfn inner(x: i32) -> i32 {\n    x + 1\n}";
+
+    #[test]
+    fn inline_depth_one_expands_direct_callee() {
+        let json = parse_json_with(INLINE_SAMPLE, "{\"inline_depth\": 1}");
+        let call = &first_body(&json)["children"][0];
+        assert_eq!(call["type"], "block");
+        assert_eq!(call["label"], "helper (1)");
+        // 深さ1ではhelperの中の呼び出しは展開されません
+        let inner = &call["children"][0]["children"][0];
+        assert_eq!(inner["type"], "command");
+        assert_eq!(inner["label"], "let y = inner (x) ;");
+    }
+
+    #[test]
+    fn inline_depth_two_expands_nested_callee() {
+        let json = parse_json_with(INLINE_SAMPLE, "{\"inline_depth\": 2}");
+        let inner = &first_body(&json)["children"][0]["children"][0]["children"][0];
+        assert_eq!(inner["type"], "block");
+        assert_eq!(inner["label"], "let y = inner (x) ;");
+        assert_eq!(inner["children"][0]["children"][0]["label"], "x + 1");
+    }
+
+    #[test]
+    fn inline_stops_at_recursive_calls() {
+        let code = "fn main() { ping(3); }\nfn ping(n: u32) { pong(n); }\nfn pong(n: u32) { ping(n - 1); }";
+        let json = parse_json_with(code, "{\"inline_depth\": 10}");
+        let ping = &first_body(&json)["children"][0];
+        let pong = &ping["children"][0]["children"][0];
+        assert_eq!(pong["label"], "pong (n)");
+        let again = &pong["children"][0]["children"][0];
+        assert_eq!(again["type"], "command");
+        assert_eq!(again["recursive"], true);
+    }
+
+    #[test]
+    fn type_aliases_are_declarations_in_source_order() {
+        let code = "pub type Result<T> = std::result::Result<T, MyError>;\nfn main() { run(); }\ntype Pair<'a, K> = (&'a K, Vec<K>);";
+        let json = parse_json(code);
+        let labels: Vec<&str> = json["children"].as_array().unwrap().iter().map(|c| c["label"].as_str().unwrap()).collect();
+        assert_eq!(labels, ["pub type Result<T> = std::result::Result<T, MyError>", "fn main()", "type Pair<'a, K> = (&'a K, Vec<K>)"]);
+        assert_eq!(json["children"][0]["category"], "declaration");
+
+        let json = parse_json_with(code, r#"{"declarations": false}"#);
+        assert_eq!(json["children"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn consts_and_statics_are_declarations() {
+        let code = "pub const MAX: usize = 10;\nstatic mut COUNT: u32 = 0;\nfn main() {}";
+        let json = parse_json(code);
+        let labels: Vec<&str> = json["children"].as_array().unwrap().iter().map(|c| c["label"].as_str().unwrap()).collect();
+        assert_eq!(labels, ["pub const MAX: usize = 10", "static mut COUNT: u32 = 0", "fn main()"]);
+        assert_eq!(json["children"][1]["category"], "declaration");
+        let listing: serde_json::Value = serde_json::from_str(&list_functions(code)).unwrap();
+        assert_eq!(listing["items"][0]["kind"], "const");
+        assert_eq!(listing["items"][1]["kind"], "static");
+
+        let json = parse_json_with(code, r#"{"declarations": false}"#);
+        assert_eq!(json["children"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn trait_associated_types_are_declarations() {
+        let code = "trait Store {\n    type Key: Clone;\n    type Value = String;\n    fn get(&self) -> u8 { 0 }\n    fn put(&mut self);\n}\ntrait Kind {\n    type Id;\n}";
+        let json = parse_json(code);
+        let store = &json["children"][0];
+        let labels: Vec<&str> = store["children"].as_array().unwrap().iter().map(|c| c["label"].as_str().unwrap()).collect();
+        assert_eq!(labels, ["type Key: Clone", "type Value = String", "fn Store::get(&self)"]);
+        assert_eq!(store["children"][0]["category"], "declaration");
+        assert_eq!(json["children"][1]["label"], "trait Kind");
+
+        let json = parse_json_with(code, r#"{"declarations": false}"#);
+        assert_eq!(json["children"].as_array().unwrap().len(), 1);
+        assert_eq!(json["children"][0]["children"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn associated_types_are_listed_with_impl_methods() {
+        let code = "impl Iterator for Counter {\n    type Item = Option<u32>;\n    fn next(&mut self) -> Option<u32> { None }\n}";
+        let json = parse_json(code);
+        let children = &json["children"][0]["children"];
+        assert_eq!(children[0]["type"], "command");
+        assert_eq!(children[0]["label"], "type Item = Option<u32>");
+        assert_eq!(children[1]["type"], "block");
+    }
+
+    #[test]
+    fn imports_option_collects_use_statements_first() {
+        let code = "fn main() { run(); }\nuse std::{fs, io::{self, Read}};\npub use crate::model::PadNode as Node;";
+        let json = parse_json_with(code, r#"{"imports": true}"#);
+        let imports = &json["children"][0];
+        assert_eq!(imports["kind"], "imports");
+        assert_eq!(imports["label"], "imports");
+        let labels: Vec<&str> = imports["children"].as_array().unwrap().iter().map(|c| c["label"].as_str().unwrap()).collect();
+        assert_eq!(labels, ["use std::{fs, io::{self, Read}}", "pub use crate::model::PadNode as Node"]);
+        assert_eq!(imports["children"][0]["category"], "declaration");
+        assert_eq!(json["children"][1]["label"], "fn main()");
+
+        let json = parse_json_with(code, r#"{"imports": true, "expand_imports": true}"#);
+        let labels: Vec<&str> =
+            json["children"][0]["children"].as_array().unwrap().iter().map(|c| c["label"].as_str().unwrap()).collect();
+        assert_eq!(labels, ["use std::fs", "use std::io", "use std::io::Read", "pub use crate::model::PadNode as Node"]);
+
+        // 既定では use 宣言は表示しません
+        let json = parse_json(code);
+        assert_eq!(json["children"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn use_inside_function_stays_in_that_function() {
+        let code = "fn main() {\n    use std::io::Write;\n    flush();\n}";
+        let json = parse_json_with(code, r#"{"imports": true}"#);
+        assert_eq!(json["children"].as_array().unwrap().len(), 1);
+        assert_eq!(first_body(&json)["children"][0]["label"], "use std::io::Write");
+    }
+
+    #[test]
+    fn calls_are_not_inlined_by_default() {
+        let json = parse_json(INLINE_SAMPLE);
+        assert_eq!(first_body(&json)["children"][0]["type"], "command");
+    }
+
+    #[test]
+    fn for_loop_condition_shows_pattern_and_iterator() {
+        let json = parse_json("fn main() { for i in 0..10 { work(i); } }");
+        let node = &first_body(&json)["children"][0];
+        assert_eq!(node["kind"], "for");
+        assert_eq!(node["condition"], "for i in 0 .. 10");
+    }
+
+    #[test]
+    fn loop_with_trailing_break_if_becomes_do_while() {
+        let code = "fn main() {\n    loop {\n        step();\n        if !keep_going {\n            break;\n        }\n    }\n}";
+        let json = parse_json(code);
+        let node = &first_body(&json)["children"][0];
+        assert_eq!(node["type"], "loop");
+        assert_eq!(node["kind"], "do_while");
+        assert_eq!(node["condition"], "keep_going");
+        assert_eq!(node["break_condition"], "! keep_going");
+        let body = node["body"]["children"].as_array().unwrap();
+        assert_eq!(body.len(), 1);
+        assert_eq!(body[0]["label"], "step ()");
+    }
+
+    #[test]
+    fn break_if_with_else_is_not_transformed() {
+        let code = "fn main() { loop { step(); if done { break; } else { more(); } } }";
+        let json = parse_json(code);
+        let node = &first_body(&json)["children"][0];
+        assert_eq!(node["kind"], "loop");
+        assert_eq!(node["body"]["children"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn break_with_value_is_not_transformed() {
+        let code = "fn main() { loop { step(); if done { break 42; } } }";
+        let json = parse_json(code);
+        assert_eq!(first_body(&json)["children"][0]["kind"], "loop");
+    }
+
+    #[test]
+    fn loop_with_continue_is_not_transformed() {
+        let code = "fn main() { loop { if skip() { continue; } work(); if done() { break; } } }";
+        assert_eq!(first_body(&parse_json(code))["children"][0]["kind"], "loop");
+        let code = "fn main() { 'outer: loop { for i in 0..3 { if i == 1 { continue 'outer; } } if done() { break 'outer; } } }";
+        assert_eq!(first_body(&parse_json(code))["children"][0]["kind"], "loop");
+        // 内側のループへのcontinueは終了判定を飛ばしません
+        let code = "fn main() { loop { for i in 0..3 { if i == 1 { continue; } } if done() { break; } } }";
+        assert_eq!(first_body(&parse_json(code))["children"][0]["kind"], "do_while");
+    }
+
+    #[test]
+    fn do_while_detection_can_be_disabled() {
+        let code = "fn main() { loop { step(); if done { break; } } }";
+        let json = parse_json_with(code, "{\"detect_do_while\": false}");
+        assert_eq!(first_body(&json)["children"][0]["kind"], "loop");
+    }
+
+    #[test]
+    fn loop_value_is_recorded_with_its_binding() {
+        let code = "fn main() {\n    let answer = loop {\n        let c = next();\n        if ok(c) { break c; }\n    };\n}";
+        let json = parse_json(code);
+        let node = &first_body(&json)["children"][0];
+        assert_eq!(node["type"], "loop");
+        assert_eq!(node["binding"], "answer");
+        assert_eq!(node["results"], serde_json::json!(["c"]));
+        let brk = &node["body"]["children"][1]["then_block"]["children"][0];
+        assert_eq!(brk["type"], "break");
+        assert_eq!(brk["label"], "break c");
+        assert_eq!(brk["value"], "c");
+    }
+
+    #[test]
+    fn loop_with_two_break_values_lists_both() {
+        let code = "fn main() {\n    let r = 'outer: loop {\n        if a { break 'outer 1; }\n        for x in xs { if x { break 'outer 2; } break; }\n    };\n}";
+        let json = parse_json(code);
+        let node = &first_body(&json)["children"][0];
+        assert_eq!(node["results"], serde_json::json!(["1", "2"]));
+        let brk = &node["body"]["children"][0]["then_block"]["children"][0];
+        assert_eq!(brk["target"], "'outer");
+    }
+
+    #[test]
+    fn width_hints_are_emitted_only_when_enabled() {
+        let code = "fn main() {\n    // pad:label: 入力を検証する\n    if ok { run(); }\n}";
+        let json = parse_json(code);
+        assert!(json.get("max_width_hint").is_none());
+        assert!(json["children"][0].get("width_hint").is_none());
+
+        let json = parse_json_with(code, "{\"width_hints\": true}");
+        assert_eq!(json["max_width_hint"], 14);
+        assert_eq!(json["children"][0]["width_hint"], 9);
+        let node = &first_body(&json)["children"][0];
+        assert_eq!(node["width_hint"], 14);
+        assert_eq!(node["then_block"]["children"][0]["width_hint"], 6);
+        assert!(json.get("width_hint").is_none());
+    }
+
+    #[test]
+    fn count_nodes_reports_hand_checked_totals() {
+        let code = "fn main() {\n    let x = 1;\n    if x > 0 {\n        a();\n    } else {\n        b();\n    }\n    for i in 0..3 {\n        if i == 1 { continue; }\n    }\n}\nfn helper() {\n    loop { break; }\n}";
+        let json: serde_json::Value = serde_json::from_str(&count_nodes(code)).unwrap();
+        assert_eq!(json["functions"], 2);
+        assert_eq!(json["counts"]["block"], 2);
+        assert_eq!(json["counts"]["if"], 2);
+        assert_eq!(json["counts"]["loop"], 2);
+        assert_eq!(json["counts"]["command"], 3);
+        assert_eq!(json["counts"]["break"], 1);
+        assert_eq!(json["counts"]["continue"], 1);
+        assert_eq!(json["counts"]["error"], 0);
+        // ルート + main本体 + then/else + for本体 + continue節 + helper本体 + loop本体
+        assert_eq!(json["counts"]["sequence"], 8);
+        assert_eq!(json["total"], 19);
+        let main = &json["per_function"][0];
+        assert_eq!(main["function"], "fn main()");
+        assert_eq!(main["counts"]["if"], 2);
+        assert_eq!(main["counts"]["loop"], 1);
+        assert_eq!(json["per_function"][1]["counts"]["break"], 1);
+    }
+
+    #[test]
+    fn analyze_reports_complexity_per_function() {
+        let code = "fn main() {\n    let x = 1;\n    if x > 0 {\n        a();\n    } else {\n        b();\n    }\n    for i in 0..3 {\n        match i { 0 => c(), 1 => d(), _ => {} }\n    }\n}\nfn helper() {\n    loop { break; }\n}";
+        let json: serde_json::Value = serde_json::from_str(&analyze(code)).unwrap();
+        let main = &json["functions"][0];
+        assert_eq!(main["function"], "fn main()");
+        // 1 + if + for + (3アーム - 1)
+        assert_eq!(main["cyclomatic"], 5);
+        assert_eq!(main["max_nesting"], 2);
+        assert_eq!(main["loops"], 1);
+        // let, if, a(), b(), for, match, c(), d()
+        assert_eq!(main["statements"], 8);
+        assert_eq!(json["functions"][1]["cyclomatic"], 2);
+        // `metrics` オプションでは関数のBlockに同じ指標を付けます
+        let tree = parse_json_with(code, r#"{"metrics": true}"#);
+        assert_eq!(tree["children"][0]["metrics"], serde_json::json!({"cyclomatic": 5, "max_nesting": 2, "statements": 8, "loops": 1}));
+        assert!(parse_json(code)["children"][0].get("metrics").is_none());
+    }
+
+    #[test]
+    fn clean_input_has_empty_warnings() {
+        let json = parse_json("fn main() { if a { b(); } }");
+        assert_eq!(json["warnings"], serde_json::json!([]));
+        assert_eq!(json["id"], "n0");
+    }
+
+    /// 指定したオプションで解析した結果の警告コードの一覧
+    fn warning_codes(code: &str, options: &str) -> Vec<String> {
+        let json = parse_json_with(code, options);
+        json["warnings"].as_array().unwrap().iter().map(|w| w["code"].as_str().unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn deep_nesting_is_reported_once() {
+        let code = "fn main() {\n    if a {\n        while b {\n            if c {\n                if d { x(); }\n            }\n        }\n    }\n}";
+        assert_eq!(warning_codes(code, "{\"lint\": {\"max_nesting\": 2}}"), vec!["deep_nesting"]);
+        let json = parse_json_with(code, "{\"lint\": {\"max_nesting\": 2}}");
+        let warning = &json["warnings"][0];
+        let inner_if = &first_body(&json)["children"][0]["then_block"]["children"][0]["body"]["children"][0];
+        assert_eq!(warning["node_id"], inner_if["id"]);
+        assert_eq!(warning["span"]["start_line"], 4);
+    }
+
+    #[test]
+    fn long_function_is_reported() {
+        let code = "fn main() { a(); b(); c(); d(); }";
+        assert_eq!(warning_codes(code, "{\"lint\": {\"max_statements\": 3}}"), vec!["long_function"]);
+        assert!(warning_codes(code, "{\"lint\": {\"max_statements\": 4}}").is_empty());
+    }
+
+    #[test]
+    fn long_condition_is_reported() {
+        let code = "fn main() { if first_condition && second_condition { go(); } }";
+        assert_eq!(warning_codes(code, "{\"lint\": {\"max_condition_length\": 20}}"), vec!["long_condition"]);
+    }
+
+    #[test]
+    fn match_with_many_arms_is_reported() {
+        let code = "fn main() { match n { 1 => a(), 2 => b(), 3 => c(), _ => d() } }";
+        assert_eq!(warning_codes(code, "{\"lint\": {\"max_match_arms\": 3}}"), vec!["many_match_arms"]);
+        let json = parse_json(code);
+        let node = &first_body(&json)["children"][0];
+        assert_eq!(node["type"], "match");
+        assert_eq!(node["scrutinee"], "n");
+        assert_eq!(node["arms"][3]["pattern"], "_");
+        assert_eq!(node["arms"][0]["body"]["children"][0]["label"], "a ()");
+    }
+
+    #[test]
+    fn camel_case_option_renames_keys_but_not_values() {
+        let code = "fn main() { if a { b(); } else { c(); } loop { d(); if e { break; } } }";
+        let snake = parse_json(code);
+        let camel = parse_json_with(code, "{\"field_naming\": \"camel_case\"}");
+        let snake_if = &first_body(&snake)["children"][0];
+        let camel_if = &first_body(&camel)["children"][0];
+        assert!(snake_if.get("then_block").is_some());
+        assert!(camel_if.get("then_block").is_none());
+        let mut then_block = snake_if["then_block"].clone();
+        naming::camel_case_keys(&mut then_block);
+        assert_eq!(camel_if["thenBlock"], then_block);
+        assert_eq!(camel_if["elseBlock"]["type"], "sequence");
+        let camel_loop = &first_body(&camel)["children"][1];
+        assert_eq!(camel_loop["kind"], "do_while");
+        assert_eq!(camel_loop["breakCondition"], first_body(&snake)["children"][1]["break_condition"]);
+    }
+
+    const V2_SAMPLE: &str = "fn main() {\n    if a {\n        b();\n        // pad:skip\n        log();\n    }\n    while c { d(); }\n    if e { f(); } else { g(); }\n}";
+
+    #[test]
+    fn v2_flattens_if_and_loop_bodies() {
+        let json: serde_json::Value = serde_json::from_str(&parse_rust_code_v2(V2_SAMPLE, "")).unwrap();
+        let body = first_body(&json);
+        let if_node = &body["children"][0];
+        assert_eq!(if_node["then"][0]["label"], "b ()");
+        assert_eq!(if_node["then_skipped"], 1);
+        assert_eq!(if_node["else"], serde_json::json!([]));
+        assert!(if_node.get("then_block").is_none());
+        assert_eq!(body["children"][1]["body"][0]["label"], "d ()");
+        assert_eq!(body["children"][2]["else"][0]["label"], "g ()");
+    }
+
+    /// v1の出力からラッパーSequence（Ifの節やループ本体）のidと位置を取り除きます（v2では保持されないため）
+    fn strip_wrapper_ids(value: &mut serde_json::Value) {
+        if let serde_json::Value::Object(map) = value {
+            for key in ["then_block", "else_block", "body"] {
+                if let Some(serde_json::Value::Object(inner)) = map.get_mut(key)
+                    && inner.get("type") == Some(&"sequence".into())
+                {
+                    inner.remove("id");
+                    inner.remove("span");
+                }
+            }
+            map.values_mut().for_each(strip_wrapper_ids);
+        } else if let serde_json::Value::Array(items) = value {
+            items.iter_mut().for_each(strip_wrapper_ids);
+        }
+    }
+
+    #[test]
+    fn v1_and_v2_round_trip() {
+        let mut v1 = parse_json(V2_SAMPLE);
+        let v2: serde_json::Value = serde_json::from_str(&parse_rust_code_v2(V2_SAMPLE, "")).unwrap();
+
+        let mut converted = v1.clone();
+        v2::to_v2(&mut converted);
+        assert_eq!(converted, v2);
+
+        let back: serde_json::Value = serde_json::from_str(&pad_v2_to_v1(&v2.to_string())).unwrap();
+        strip_wrapper_ids(&mut v1);
+        assert_eq!(back, v1);
+    }
+
+    #[test]
+    fn v2_keeps_dead_branch_flags() {
+        let code = "fn main() {\n    if false { b(); } else { c(); }\n    while false { d(); }\n    if true { e(); } else {}\n}";
+        let mut v1 = parse_json(code);
+        let v2: serde_json::Value = serde_json::from_str(&parse_rust_code_v2(code, "")).unwrap();
+        let body = first_body(&v2);
+        assert_eq!((body["children"][0]["then_dead"].as_bool(), body["children"][0]["else_always_runs"].as_bool()), (Some(true), Some(true)));
+        assert_eq!((body["children"][1]["never_runs"].as_bool(), body["children"][1]["body_dead"].as_bool()), (Some(true), Some(true)));
+        assert_eq!(body["children"][2]["else"], serde_json::json!([]));
+        assert_eq!(body["children"][2]["else_dead"], true);
+
+        let back: serde_json::Value = serde_json::from_str(&pad_v2_to_v1(&v2.to_string())).unwrap();
+        strip_wrapper_ids(&mut v1);
+        assert_eq!(back, v1);
+    }
+
+    #[test]
+    fn v1_output_is_unchanged_by_v2() {
+        assert_eq!(parse_rust_code(V2_SAMPLE), parse_rust_code_with_options(V2_SAMPLE, ""));
+        assert!(parse_rust_code(V2_SAMPLE).contains("\"then_block\""));
+    }
+
+    #[test]
+    fn v2_schema_requires_node_arrays() {
+        let schema: serde_json::Value = serde_json::from_str(&pad_schema_v2()).unwrap();
+        assert_eq!(schema["$defs"]["if"]["properties"]["then"]["$ref"], "#/$defs/nodes");
+        assert_eq!(schema["$defs"]["loop"]["properties"]["body"]["$ref"], "#/$defs/nodes");
+    }
+
+    #[test]
+    fn typescript_typings_declare_every_schema_field() {
+        let schema: serde_json::Value = serde_json::from_str(PAD_SCHEMA_V2).unwrap();
+        for (name, def) in schema["$defs"].as_object().unwrap() {
+            for key in def["properties"].as_object().into_iter().flat_map(|properties| properties.keys()) {
+                assert!(PAD_NODE_V2_TYPES.contains(&format!(" {}", key)), "`{}` of {} is not in the TypeScript typings", key, name);
+            }
+        }
+    }
+
+    #[test]
+    fn v1_schema_declares_every_emitted_field() {
+        let schema: serde_json::Value = serde_json::from_str(&pad_schema()).unwrap();
+        assert_eq!(schema["$defs"]["if"]["properties"]["then_block"]["$ref"], "#/$defs/node");
+        assert_eq!(schema["$defs"]["loop"]["properties"]["body"]["$ref"], "#/$defs/node");
+        assert!(schema["$defs"]["if"]["properties"].get("then").is_none());
+
+        fn check(value: &serde_json::Value, schema: &serde_json::Value) {
+            if let Some(map) = value.as_object() {
+                if let Some(kind) = map.get("type").and_then(|kind| kind.as_str()) {
+                    let def = match kind {
+                        "break" | "continue" | "yield" => "jump",
+                        other => other,
+                    };
+                    for key in map.keys() {
+                        let declared = [&schema["$defs"]["node"]["properties"], &schema["$defs"][def]["properties"]];
+                        assert!(declared.iter().any(|properties| properties.get(key).is_some()), "`{}` of {} is not in the schema", key, kind);
+                    }
+                }
+                map.values().for_each(|child| check(child, schema));
+            } else if let Some(items) = value.as_array() {
+                items.iter().for_each(|child| check(child, schema));
+            }
+        }
+        let code = format!(
+            "{}\n{}\nimpl S {{\n    async unsafe fn run(&self, n: u8) -> Option<u8> {{\n        // step\n        'outer: for i in 0..n {{\n            match i {{ 0 => continue 'outer, _ => break }}\n        }}\n        unsafe {{ raw(); }}\n        assert!(n > 0);\n        let v = self.get(n).await?;\n        Some(v[0])\n    }}\n}}",
+            INLINE_SAMPLE, V2_SAMPLE
+        );
+        let json = parse_rust_code_with_options(&code, r#"{"metrics": true, "comments": true, "expand_macros": true, "panic_on_index": true}"#);
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["type"], "sequence", "{}", json);
+        check(&value, &schema);
+    }
+
+    #[test]
+    fn output_deserializes_back_to_the_same_json() {
+        let json = parse_rust_code(INLINE_SAMPLE);
+        let tree: PadNode = serde_json::from_str(&json).unwrap();
+        assert_eq!(to_json(&tree), json);
+    }
+
+    #[test]
+    fn merge_pads_accepts_serialized_trees() {
+        let a = parse_rust_code("fn a() { x(); }");
+        let input = serde_json::json!([a, {"tree": a, "title": "again"}]).to_string();
+        let json: serde_json::Value = serde_json::from_str(&merge_pads_json(&input)).unwrap();
+        assert_eq!(json["document"]["children"][1]["label"], "again");
+        assert_eq!(json["remapped"][0]["to"], "t1-n0");
+        let error: serde_json::Value = serde_json::from_str(&merge_pads_json("[1]")).unwrap();
+        assert_eq!(error["type"], "error");
+    }
+
+    #[test]
+    fn get_subtree_works_on_code_and_json() {
+        let code = "fn main() { while a { b(); } }";
+        let from_code: serde_json::Value = serde_json::from_str(&get_subtree(code, "n3")).unwrap();
+        let from_json: serde_json::Value = serde_json::from_str(&get_subtree(&parse_rust_code(code), "n3")).unwrap();
+        assert_eq!(from_code["node"]["type"], "loop");
+        assert_eq!(from_code, from_json);
+        let by_path: serde_json::Value = serde_json::from_str(&get_subtree_by_path(code, "0/0/0")).unwrap();
+        assert_eq!(by_path["node"], from_code["node"]);
+    }
+
+    #[test]
+    fn outline_collapses_function_bodies() {
+        let code = "fn main() { while a { b(); c(); } d(); }\nfn empty() {}";
+        let document = PadDocument::new(code, "{}");
+        let outline: serde_json::Value = serde_json::from_str(&document.outline(1)).unwrap();
+        let main = &outline["children"][0];
+        assert_eq!(main["label"], "fn main()");
+        assert_eq!(main["collapsed"], true);
+        assert_eq!(main["child_count"], 2);
+        assert_eq!(main["children"], serde_json::json!([]));
+        // 中身のない関数は折りたたみません
+        assert!(outline["children"][1].get("collapsed").is_none());
+    }
+
+    #[test]
+    fn expanded_node_matches_the_full_parse() {
+        let code = "fn main() { while a { if b { c(); } } d(); }";
+        let document = PadDocument::new(code, "{}");
+        let outline: serde_json::Value = serde_json::from_str(&document.outline(2)).unwrap();
+        let lp = &outline["children"][0]["children"][0]["children"][0];
+        assert_eq!(lp["collapsed"], true);
+        let id = lp["id"].as_str().unwrap();
+        let expanded: serde_json::Value = serde_json::from_str(&document.expand(id, u8::MAX)).unwrap();
+        let full: serde_json::Value = serde_json::from_str(&get_subtree(code, id)).unwrap();
+        assert_eq!(expanded["node"], full["node"]);
+        assert_eq!(expanded["ancestors"], full["ancestors"]);
+        // 1段だけ展開すると、Ifは折りたたまれたままです
+        let shallow: serde_json::Value = serde_json::from_str(&document.expand(id, 1)).unwrap();
+        assert_eq!(shallow["node"]["body"]["children"][0]["collapsed"], true);
+        let missing: serde_json::Value = serde_json::from_str(&document.expand("n99", 1)).unwrap();
+        assert_eq!(missing["found"], false);
+    }
+
+    #[test]
+    fn measure_estimates_width_from_hints() {
+        let json: serde_json::Value = serde_json::from_str(&measure("fn main() { if ok { run(); } }")).unwrap();
+        assert_eq!(json["max_depth"], 3);
+        assert_eq!(json["bounding_box"]["rows"], 1);
+        // "fn main()"(9) + "ok"(2) + "run ()"(6)
+        assert_eq!(json["bounding_box"]["width"], 17);
+
+        let error: serde_json::Value = serde_json::from_str(&measure("fn main( {")).unwrap();
+        assert_eq!(error["type"], "error");
+        assert!(error.get("max_depth").is_none());
+    }
+
+    #[test]
+    fn html_export_embeds_one_svg_per_function_and_escapes_labels() {
+        let code = "fn main() {\n    if a < b && c > d { run(); }\n}\nfn helper() { x(); }";
+        let html = to_html(code);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert_eq!(html.matches("<svg").count(), 2);
+        assert_eq!(html.matches("<details open>").count(), 2);
+        assert!(html.contains("a &lt; b &amp;&amp; c &gt; d"));
+        assert!(!html.contains("a < b"));
+        assert!(!html.contains("<script"));
+        assert!(html.trim_end().ends_with("</html>"));
+    }
+
+    #[test]
+    fn excalidraw_scene_has_required_fields_and_bound_elements() {
+        let code = "fn main() {\n    let a = 1;\n    if a > 0 { go(); } else { stop(); }\n}";
+        let scene: serde_json::Value = serde_json::from_str(&to_excalidraw(code)).unwrap();
+        assert_eq!(scene["type"], "excalidraw");
+        assert_eq!(scene["version"], 2);
+        let elements = scene["elements"].as_array().unwrap();
+        let count = |kind: &str| elements.iter().filter(|e| e["type"] == kind).count();
+        // 関数・let・go・stop の長方形、if の菱形、それぞれのラベル、入れ子3本と順次1本の矢印
+        assert_eq!((count("rectangle"), count("diamond"), count("text"), count("arrow")), (4, 1, 5, 4));
+        let ids: std::collections::HashSet<&str> = elements.iter().map(|e| e["id"].as_str().unwrap()).collect();
+        assert_eq!(ids.len(), elements.len());
+        for element in elements {
+            if let Some(container) = element["containerId"].as_str() {
+                assert!(ids.contains(container));
+            }
+            for end in ["startBinding", "endBinding"] {
+                if let Some(target) = element[end]["elementId"].as_str() {
+                    let shape = elements.iter().find(|e| e["id"] == target).unwrap();
+                    let bound = shape["boundElements"].as_array().unwrap();
+                    assert!(bound.iter().any(|b| b["id"] == element["id"]));
+                }
+            }
+        }
+        // 同じ入力からは同じシーンになります
+        assert_eq!(to_excalidraw(code), to_excalidraw(code));
+    }
+
+    #[test]
+    fn jsoncanvas_snapshot_of_small_function() {
+        let canvas: serde_json::Value = serde_json::from_str(&to_jsoncanvas("fn main() {\n    let v: Vec<u8> = load();\n    while v.len() > 0 { pop(); }\n}")).unwrap();
+        let expected = serde_json::json!({
+            "nodes": [
+                { "id": "n1-group", "type": "group", "x": 0, "y": 0, "width": 388, "height": 84, "label": "fn main()" },
+                { "id": "n1", "type": "text", "x": 10, "y": 10, "width": 88, "height": 28, "text": "fn main()" },
+                { "id": "n3", "type": "text", "x": 122, "y": 10, "width": 256, "height": 28, "text": "let v : Vec \\< u8 \\> = load () ;" },
+                { "id": "n4", "type": "text", "x": 122, "y": 46, "width": 128, "height": 28, "text": "v . len () \\> 0" },
+                { "id": "n6", "type": "text", "x": 274, "y": 46, "width": 64, "height": 28, "text": "pop ()" },
+            ],
+            "edges": [
+                { "id": "n1-n3", "fromNode": "n1", "fromSide": "right", "toNode": "n3", "toSide": "left" },
+                { "id": "n4-n6", "fromNode": "n4", "fromSide": "right", "toNode": "n6", "toSide": "left" },
+                { "id": "n3-n4", "fromNode": "n3", "fromSide": "bottom", "toNode": "n4", "toSide": "top" },
+            ],
+        });
+        assert_eq!(canvas, expected);
+    }
+
+    /// 開始タグと終了タグの対応が取れているかを確かめ、開始タグの名前を出現順に返します
+    fn xml_elements(xml: &str) -> Vec<String> {
+        let mut stack: Vec<String> = Vec::new();
+        let mut names = Vec::new();
+        for tag in xml.split('<').skip(1).map(|rest| &rest[..rest.find('>').unwrap()]) {
+            assert!(!tag.contains('"') || tag.matches('"').count() % 2 == 0, "{}", tag);
+            if tag.starts_with('?') {
+                continue;
+            }
+            if let Some(name) = tag.strip_prefix('/') {
+                assert_eq!(stack.pop().as_deref(), Some(name));
+                continue;
+            }
+            let name = tag.split_whitespace().next().unwrap().trim_end_matches('/').to_string();
+            names.push(name.clone());
+            if !tag.ends_with('/') {
+                stack.push(name);
+            }
+        }
+        assert!(stack.is_empty(), "{:?}", stack);
+        names
+    }
+
+    #[test]
+    fn graphml_is_well_formed_and_declares_keys() {
+        let code = "fn main() {\n    if a < b && name == \"x\" { go(); } else { stop(); }\n    while n > 0 { n -= 1; }\n}";
+        let xml = to_graphml(code);
+        assert!(xml.starts_with("<?xml"));
+        let elements = xml_elements(&xml);
+        // 関数のBlockは入れ子のグラフになります
+        assert_eq!(elements.iter().filter(|name| *name == "graph").count(), 2);
+        for key in ["label", "type", "span", "function", "kind", "branch"] {
+            assert!(xml.contains(&format!("<key id=\"{}\"", key)), "{}", key);
+        }
+        assert!(xml.contains("a &lt; b &amp;&amp; name == &quot;x&quot;"));
+        assert!(xml.contains("<data key=\"branch\">true</data>"));
+        assert!(xml.contains("<data key=\"branch\">false</data>"));
+        assert!(xml.contains("<data key=\"kind\">back</data>"));
+        assert!(xml.contains("<data key=\"function\">fn main()</data>"));
+    }
+
+    #[test]
+    fn svg_renders_every_function_in_one_document() {
+        let svg = to_svg("fn main() {\n    while n > 0 { step(n); }\n}\nfn other() { if a < b { go(); } }");
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        for label in ["fn main()", "fn other()", "step (n)", "a &lt; b"] {
+            assert!(svg.contains(&format!(">{}</text>", label)), "{}", label);
+        }
+        assert!(to_svg_with_options("fn main() {}", "{\"mode\": 1}").contains("class=\"error\""));
+    }
+
+    #[test]
+    fn dot_snapshot_of_if_and_loop() {
+        let dot = to_dot("fn main() {\n    if name == \"a\\\\b\" { go(); }\n    while n > 0 { n -= 1; }\n}\nfn other() {}");
+        let expected = r#"digraph pad {
+  node [fontname="monospace"];
+  subgraph "cluster_n1" {
+    label="fn main()";
+    "n3" [label="name == \"a\\\\b\"", shape=diamond];
+    "n5" [label="go ()", shape=box];
+    "n6" [label="n > 0", shape=hexagon];
+    "n8" [label="n -= 1", shape=box];
+  }
+  subgraph "cluster_n9" {
+    label="fn other()";
+  }
+  "n3" -> "n6";
+  "n3" -> "n5" [label="true"];
+  "n6" -> "n8";
+  "n8" -> "n6" [style=dashed];
+}
+"#;
+        assert_eq!(dot, expected);
+    }
+
+    #[test]
+    fn mermaid_snapshot_of_if_and_loop() {
+        let mermaid = to_mermaid("fn main() {\n    if x > \"#\" { go(); } else { stop(); }\n    while n > 0 { n -= 1; }\n}");
+        let expected = r##"flowchart TD
+    subgraph n1["fn main()"]
+        n3{"x #gt; #quot;#35;#quot;"}
+        n5["go ()"]
+        n7["stop ()"]
+        n8{{"n #gt; 0"}}
+        n10["n -= 1"]
+    end
+    n3 --> n8
+    n3 -->|true| n5
+    n3 -->|false| n7
+    n8 --> n10
+    n10 -.-> n8
+"##;
+        assert_eq!(mermaid, expected);
+        assert!(to_mermaid_with_options("fn main() {}", "{\"mode\": 1}").starts_with("flowchart TD\n    n0[\"Invalid options"));
+    }
+
+    #[test]
+    fn tikz_snapshot_of_if_and_loop() {
+        let tikz = to_tikz("fn main() {\n    if x > 0 { go(); }\n    for i in 0..n { step(i); }\n}");
+        let expected = r"\begin{tikzpicture}[x=1pt, y=-1pt, font=\ttfamily\small,
+  pad/.style={draw, rectangle, anchor=north west, minimum height=28pt, inner sep=0pt},
+  pad label/.style={anchor=west, inner sep=0pt}]
+  \draw (98,24) -- (122,24);
+  \draw (178,24) -- (202,24);
+  \draw (258,60) -- (282,60);
+  \draw (122,10) -- (122,74);
+  \node[pad, minimum width=88pt, rounded corners=6pt] (n1) at (10,10) {};
+  \node[pad label] at (18,24) {fn main()};
+  \draw (122,10) -- (178,10) -- (168,24) -- (178,38) -- (122,38) -- cycle;
+  \node[pad label] at (130,24) {x > 0};
+  \node[pad, minimum width=56pt] (n5) at (202,10) {};
+  \node[pad label] at (210,24) {go ()};
+  \node[pad, minimum width=136pt] (n6) at (122,46) {};
+  \draw (127,46) -- (127,74);
+  \node[pad label] at (130,60) {for i in 0 .. n};
+  \node[pad, minimum width=80pt] (n8) at (282,46) {};
+  \node[pad label] at (290,60) {step (i)};
+\end{tikzpicture}
+";
+        assert_eq!(tikz, expected);
+    }
+
+    #[test]
+    fn tikz_standalone_document_escapes_labels() {
+        let tikz = to_tikz_with_options("fn main() { rate_limit(合計 % 100); }", r#"{"standalone": true}"#);
+        assert!(tikz.starts_with("\\documentclass[tikz,border=4pt]{standalone}\n\\usepackage{luatexja}\n"));
+        assert!(tikz.contains("{rate\\_limit (合計 \\% 100)}"));
+        assert!(tikz.trim_end().ends_with("\\end{document}"));
+        assert!(!to_tikz("fn main() {}").contains("documentclass"));
+    }
+
+    #[test]
+    fn let_chain_components_are_exposed_on_if_and_while() {
+        let code = "fn main() {\n    if let Some(a) = x && a.enabled && let Ok(b) = fetch(a) {\n        use_it(b);\n    } else {\n        skip();\n    }\n    while let Some(v) = it.next() && v > 0 { f(v); }\n}";
+        let json = parse_json(code);
+        let if_node = &first_body(&json)["children"][0];
+        assert_eq!(if_node["components"], serde_json::json!(["let Some(a) = x", "a.enabled", "let Ok(b) = fetch (a)"]));
+        assert_eq!(if_node["condition"], "let Some(a) = x && a.enabled && let Ok(b) = fetch (a)");
+        assert_eq!(if_node["else_block"]["children"][0]["label"], "skip ()");
+        let while_node = &first_body(&json)["children"][1];
+        assert_eq!(while_node["components"], serde_json::json!(["let Some(v) = it.next ()", "v > 0"]));
+        let plain = parse_json("fn main() { if a && b { c(); } }");
+        assert!(first_body(&plain)["children"][0].get("components").is_none());
+    }
+
+    #[test]
+    fn let_else_becomes_an_if_with_the_else_branch() {
+        let code = "fn main() {\n    let Some(v) = cache.get(key) else {\n        log();\n        return;\n    };\n    use_it(v);\n}";
+        let json = parse_json(code);
+        let if_node = &first_body(&json)["children"][0];
+        assert_eq!(if_node["type"], "if");
+        assert_eq!(if_node["condition"], "let Some(v) = cache.get (key)");
+        assert_eq!(if_node["lowered_from"], "let_else");
+        assert_eq!(if_node["then_block"]["children"].as_array().unwrap().len(), 0);
+        assert_eq!(if_node["else_block"]["children"][1]["type"], "return");
+        assert_eq!(first_body(&json)["children"][1]["label"], "use_it (v)");
+    }
+
+    #[test]
+    fn labelled_block_is_a_break_target() {
+        let code = "fn main() {\n    'done: {\n        if early {\n            break 'done;\n        }\n        heavy_work();\n    }\n}";
+        let json = parse_json(code);
+        let block = &first_body(&json)["children"][0];
+        assert_eq!(block["type"], "block");
+        assert_eq!(block["kind"], "labeled");
+        assert_eq!(block["label"], "'done");
+        let body = &block["children"][0];
+        let brk = &body["children"][0]["then_block"]["children"][0];
+        assert_eq!(brk["target"], "'done");
+        assert_eq!(brk["target_id"], block["id"]);
+        assert_eq!(body["children"][1]["label"], "heavy_work ()");
+    }
+
+    #[test]
+    fn loop_breaks_link_to_their_loops() {
+        let code = "fn main() {\n    'outer: for x in xs {\n        while a {\n            if b { continue 'outer; }\n            break;\n        }\n    }\n}";
+        let json = parse_json(code);
+        let outer = &first_body(&json)["children"][0];
+        assert_eq!(outer["loop_label"], "'outer");
+        let inner = &outer["body"]["children"][0];
+        let cont = &inner["body"]["children"][0]["then_block"]["children"][0];
+        assert_eq!(cont["target_id"], outer["id"]);
+        assert_eq!(inner["body"]["children"][1]["target_id"], inner["id"]);
+    }
+
+    #[test]
+    fn assignments_carry_target_op_and_value() {
+        let code = "fn main() {\n    state = State::Done;\n    total += price * qty;\n    self.items[i].count += 1;\n    sum = items.iter().map(|x| x.price).sum();\n    run();\n}";
+        let json = parse_json(code);
+        let children = &first_body(&json)["children"];
+        let assignment = |i: usize| children[i]["assignment"].clone();
+        assert_eq!(assignment(0), serde_json::json!({"target": "state", "op": "=", "value": "State :: Done"}));
+        assert_eq!(assignment(1), serde_json::json!({"target": "total", "op": "+=", "value": "price * qty"}));
+        assert_eq!(assignment(2)["target"], "self.items[i].count");
+        assert_eq!(assignment(3)["value"], "items.iter ().map (| x | x.price).sum ()");
+        assert_eq!(children[1]["label"], "total += price * qty");
+        assert!(children[4].get("assignment").is_none());
+    }
+
+    const TEST_SAMPLE: &str = "fn helper() { a(); }\n#[test]\nfn adds() { assert_eq!(add(1, 2), 3); }\nimpl Foo { fn bar(&self) {} }\n#[tokio::test(flavor = \"multi_thread\")]\nasync fn fetches() { fetch().await; }\n#[cfg(test)]\nfn not_a_test() {}";
+
+    #[test]
+    fn only_tests_selects_test_functions() {
+        let json = parse_json_with(TEST_SAMPLE, "{\"only_tests\": true}");
+        let labels: Vec<&str> = json["children"].as_array().unwrap().iter().map(|c| c["label"].as_str().unwrap()).collect();
+        assert_eq!(labels, vec!["fn adds()", "fn fetches()"]);
+        assert!(json["children"].as_array().unwrap().iter().all(|c| c["is_test"] == true));
+    }
+
+    #[test]
+    fn skip_tests_omits_test_functions() {
+        let json = parse_json_with(TEST_SAMPLE, "{\"skip_tests\": true}");
+        let labels: Vec<&str> = json["children"].as_array().unwrap().iter().map(|c| c["label"].as_str().unwrap()).collect();
+        assert_eq!(labels, vec!["fn helper()", "impl Foo", "fn not_a_test()"]);
+        let error = parse_json_with(TEST_SAMPLE, "{\"skip_tests\": true, \"only_tests\": true}");
+        assert_eq!(error["type"], "error");
+    }
+
+    const FILTER_SAMPLE: &str = "fn handle_get() {}\nfn handle_post() {}\nfn render_impl() {}\nfn migrate_v1() {}\nimpl Server { fn handle_admin(&self) {} fn shutdown(&self) {} }";
+
+    /// 結果のトップレベルのラベルと、除外された数を返します
+    fn filtered_labels(options: &str) -> (Vec<String>, serde_json::Value) {
+        let json = parse_json_with(FILTER_SAMPLE, options);
+        let labels = json["children"].as_array().unwrap().iter().map(|c| c["label"].as_str().unwrap().to_string()).collect();
+        (labels, json["filtered"].clone())
+    }
+
+    #[test]
+    fn include_patterns_select_functions_and_methods() {
+        let (labels, filtered) = filtered_labels("{\"include\": [\"handle_*\"]}");
+        assert_eq!(labels, vec!["fn handle_get()", "fn handle_post()", "impl Server"]);
+        assert_eq!(filtered, 3);
+        let json = parse_json_with(FILTER_SAMPLE, "{\"include\": [\"handle_*\"]}");
+        assert_eq!(json["children"][2]["children"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn exclude_patterns_remove_functions() {
+        let (labels, filtered) = filtered_labels("{\"exclude\": [\"*_impl\", \"migrate_*\"]}");
+        assert_eq!(labels, vec!["fn handle_get()", "fn handle_post()", "impl Server"]);
+        assert_eq!(filtered, 2);
+        let (_, none) = filtered_labels("{}");
+        assert!(none.is_null());
+    }
+
+    #[test]
+    fn exclude_wins_over_include() {
+        let (labels, _) = filtered_labels("{\"include\": [\"handle_*\", \"render_*\"], \"exclude\": [\"*_post\", \"*_impl\"]}");
+        assert_eq!(labels, vec!["fn handle_get()", "impl Server"]);
+        let (labels, _) = filtered_labels("{\"include\": [\"render_*\"]}");
+        assert_eq!(labels, vec!["fn render_impl()"]);
+    }
+
+    const CFG_SAMPLE: &str = "#[cfg(feature = \"gpu\")]\nfn render_gpu() { draw(); }\n#[cfg(unix)]\nfn open() { unix_open(); }\n#[cfg(not(unix))]\nfn open() { win_open(); }\nfn main() {\n    #[cfg(debug_assertions)]\n    log();\n    run();\n}";
+
+    #[test]
+    fn cfg_attributes_are_annotated() {
+        let json = parse_json(CFG_SAMPLE);
+        let items = json["children"].as_array().unwrap();
+        assert_eq!(items[0]["cfg"], "feature = \"gpu\"");
+        assert_eq!(items[1]["cfg"], "unix");
+        assert_eq!(items[2]["cfg"], "not(unix)");
+        assert!(items[3].get("cfg").is_none());
+        let body = &items[3]["children"][0]["children"];
+        assert_eq!(body[0]["cfg"], "debug_assertions");
+        assert!(body[1].get("cfg").is_none());
+    }
+
+    #[test]
+    fn cfg_as_branches_wraps_nodes_in_if() {
+        let json = parse_json_with(CFG_SAMPLE, "{\"cfg_as_branches\": true}");
+        let items = json["children"].as_array().unwrap();
+        assert_eq!(items[1]["type"], "if");
+        assert_eq!(items[1]["condition"], "cfg(unix)");
+        assert_eq!(items[1]["then_block"]["children"][0]["label"], "fn open()");
+        assert!(items[1]["else_block"].is_null());
+        assert_eq!(items[2]["condition"], "cfg(not(unix))");
+        assert!(items[1]["then_block"]["children"][0].get("cfg").is_none());
+    }
+
+    const PANIC_SAMPLE: &str = "fn load() {\n    let cfg = read(path.as_ref().unwrap()).expect(\"config\");\n    if cfg.get(\"k\").unwrap() > 0 { run(); }\n    let first = items[0];\n}\nfn safe() {\n    let x = compute();\n    if x > 0 { run(); }\n}";
+
+    #[test]
+    fn unwrap_and_expect_are_flagged_as_panic_points() {
+        let json = parse_json(PANIC_SAMPLE);
+        let body = &json["children"][0]["children"][0]["children"];
+        assert_eq!(body[0]["may_panic"], true);
+        assert_eq!(body[0]["panic_reasons"], serde_json::json!(["unwrap", "expect"]));
+        assert_eq!(body[1]["type"], "if");
+        assert_eq!(body[1]["panic_reasons"], serde_json::json!(["unwrap"]));
+        assert!(body[1]["then_block"]["children"][0].get("may_panic").is_none());
+        // 添字アクセスはオプション指定時のみ対象です
+        assert!(body[2].get("may_panic").is_none());
+        let json = parse_json_with(PANIC_SAMPLE, "{\"panic_on_index\": true}");
+        assert_eq!(json["children"][0]["children"][0]["children"][2]["panic_reasons"], serde_json::json!(["index"]));
+    }
+
+    #[test]
+    fn panic_points_are_counted_per_function() {
+        let json: serde_json::Value = serde_json::from_str(&count_nodes(PANIC_SAMPLE)).unwrap();
+        let per_function = json["per_function"].as_array().unwrap();
+        assert_eq!(per_function[0]["function"], "fn load()");
+        assert_eq!(per_function[0]["panics"], 3);
+        assert_eq!(per_function[1]["function"], "fn safe()");
+        assert_eq!(per_function[1]["panics"], 0);
+    }
+
+    #[test]
+    fn yields_in_coroutine_closures_become_yield_nodes() {
+        let code = "fn main() {\n    move |start| {\n        yield start;\n        if ready() { yield start + 1; }\n        done();\n    };\n    async move { fetch().await; };\n}";
+        let json = parse_json(code);
+        let body = &json["children"][0]["children"][0]["children"];
+        assert_eq!(body[0]["type"], "block");
+        assert_eq!(body[0]["kind"], "generator");
+        assert_eq!(body[0]["label"], "move |start|");
+        let inner = &body[0]["children"][0]["children"];
+        assert_eq!(inner[0]["type"], "yield");
+        assert_eq!(inner[0]["label"], "yield start");
+        assert_eq!(inner[0]["value"], "start");
+        assert_eq!(inner[1]["then_block"]["children"][0]["label"], "yield start + 1");
+        assert_eq!(body[1]["kind"], "async");
+        assert_eq!(body[1]["label"], "async move");
+    }
+
+    #[test]
+    fn long_struct_literals_and_calls_are_split_per_line() {
+        let code = "fn main() {\n    let cfg = Config { host, port: 8080, retries: 3, timeout: Duration::from_secs(5), tls: Tls { cert: a, key: b, ca: c, verify: true, sni: None } };\n    connect(a, b, c, d, e, f);\n    let p = Point { x: 1, y: 2 };\n}";
+        let json = parse_json_with(code, "{\"multiline_args\": 4}");
+        let body = &json["children"][0]["children"][0]["children"];
+        assert_eq!(
+            body[0]["label"],
+            "let cfg = Config {\n    host,\n    port: 8080,\n    retries: 3,\n    timeout: Duration :: from_secs (5),\n    tls: Tls {\n        cert: a,\n        key: b,\n        ca: c,\n        verify: true,\n        sni: None,\n    },\n};"
+        );
+        assert_eq!(body[1]["label"], "connect(\n    a,\n    b,\n    c,\n    d,\n    e,\n    f,\n)");
+        assert_eq!(body[2]["label"], "let p = Point { x : 1 , y : 2 } ;");
+        // オプションを省略した場合は折り返しません
+        let json = parse_json(code);
+        assert!(!json["children"][0]["children"][0]["children"][1]["label"].as_str().unwrap().contains('\n'));
+    }
+
+    #[test]
+    fn spawn_calls_become_concurrent_blocks() {
+        let code = "fn main() {\n    let handle = thread::spawn(move || {\n        work();\n        for i in 0..3 { step(i); }\n    });\n    tokio::spawn(async move {\n        fetch().await;\n    });\n    handle.join();\n}";
+        let json = parse_json(code);
+        let body = &json["children"][0]["children"][0]["children"];
+        assert_eq!(body[0]["type"], "block");
+        assert_eq!(body[0]["kind"], "concurrent");
+        assert_eq!(body[0]["label"], "let handle = thread::spawn");
+        let spawned = &body[0]["children"][0]["children"];
+        assert_eq!(spawned[0]["label"], "work ()");
+        assert_eq!(spawned[1]["type"], "loop");
+        assert_eq!(body[1]["kind"], "concurrent");
+        assert_eq!(body[1]["label"], "tokio::spawn");
+        assert_eq!(body[1]["children"][0]["children"][0]["label"], "fetch () . await");
+        assert_eq!(body[2]["type"], "command");
+    }
+
+    #[cfg(feature = "std-docs")]
+    #[test]
+    fn std_calls_get_documentation_links() {
+        let code = "fn main() {\n    let text = std::fs::read_to_string(path).unwrap();\n    my_helper(text);\n    queue.push_back(1);\n    items.push(2);\n}";
+        let json = parse_json(code);
+        let body = &json["children"][0]["children"][0]["children"];
+        assert_eq!(body[0]["doc_url"], "https://doc.rust-lang.org/std/fs/fn.read_to_string.html");
+        assert!(body[1].get("doc_url").is_none());
+        assert_eq!(body[2]["doc_url"], "https://doc.rust-lang.org/std/collections/struct.VecDeque.html#method.push_back");
+        // レシーバーの型がわからず、メソッド名だけでは特定できない場合は付けません
+        assert!(body[3].get("doc_url").is_none());
+    }
+
+    #[test]
+    fn commands_are_classified_into_categories() {
+        let code = "fn main() {\n    let total = 0;\n    println!(\"{}\", total);\n    fs::write(path, data)?;\n    a + b * 2;\n    total += 1;\n    compute(total);\n    process::exit(1);\n}";
+        let json = parse_json(code);
+        let categories: Vec<&str> = json["children"][0]["children"][0]["children"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|node| node["category"].as_str().unwrap())
+            .collect();
+        assert_eq!(categories, vec!["declaration", "io", "io", "arithmetic", "assignment", "call", "exit"]);
+    }
+
+    #[test]
+    fn document_root_carries_metadata() {
+        let code = "fn main() { run(); }";
+        let json = parse_json_with(code, "{\"document\": true, \"source_name\": \"src/main.rs\"}");
+        assert_eq!(json["type"], "document");
+        assert_eq!(json["title"], "main.rs");
+        assert_eq!(json["source_name"], "src/main.rs");
+        assert_eq!(json["generated_by"], "PAD.js backend");
+        assert_eq!(json["backend_version"], env!("CARGO_PKG_VERSION"));
+        assert!(json["created_at"].as_str().unwrap().ends_with('Z'));
+        assert_eq!(json["warnings"], serde_json::json!([]));
+        assert_eq!(json["children"][0]["label"], "fn main()");
+        // 決定的モードでは作成日時を出力しません
+        let json = parse_json_with(code, "{\"document\": true, \"deterministic\": true, \"title\": \"Demo\"}");
+        assert_eq!(json["title"], "Demo");
+        assert!(json.get("created_at").is_none());
+        assert!(json.get("source_name").is_none());
+        let html = to_html_with_options(code, "{\"document\": true, \"title\": \"Demo <1>\"}");
+        assert!(html.contains("<title>Demo &lt;1&gt;</title>"));
+        let mermaid = to_mermaid_with_options(code, "{\"document\": true, \"title\": \"Demo \\\"1\\\"\"}");
+        assert!(mermaid.starts_with("---\ntitle: \"Demo \\\"1\\\"\"\n---\nflowchart TD\n"), "{}", mermaid);
+        assert!(to_mermaid(code).starts_with("flowchart TD\n"));
+    }
+
+    #[test]
+    fn identical_input_is_served_from_the_cache() {
+        clear_cache();
+        let code = "fn main() { run(); }";
+        let first = parse_rust_code_with_options(code, "{\"cache\": true}");
+        let second = parse_rust_code_with_options(code, "{\"cache\": true}");
+        assert_eq!(first, second);
+        let stats: serde_json::Value = serde_json::from_str(&cache_stats()).unwrap();
+        assert_eq!(stats["parses"], 1);
+        assert_eq!(stats["hits"], 1);
+        // オプションが変わると解析し直します
+        parse_rust_code_with_options(code, "{\"cache\": true, \"width_hints\": true}");
+        parse_rust_code_with_options("fn other() {}", "{\"cache\": true}");
+        let stats: serde_json::Value = serde_json::from_str(&cache_stats()).unwrap();
+        assert_eq!(stats["parses"], 3);
+        assert_eq!(stats["entries"], 3);
+        clear_cache();
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&cache_stats()).unwrap()["entries"], 0);
+    }
+
+    #[test]
+    fn parse_errors_include_position_and_code_frame() {
+        let json = parse_json("fn main() {\n    let x = ;\n}\n");
+        assert_eq!(json["type"], "error");
+        assert_eq!(json["line"], 2);
+        assert_eq!(json["column"], 13);
+        assert_eq!(json["frame"], "  1 | fn main() {\n> 2 |     let x = ;\n    |             ^\n  3 | }");
+    }
+
+    #[test]
+    fn recover_mode_reports_every_broken_item() {
+        let code = "fn a() {\n    let = 1;\n}\nfn ok() { run(); }\nfn b() { x + ; }\nfn c() -> { }\n";
+        // 通常は最初の構文エラーだけを返します
+        assert_eq!(parse_json(code)["type"], "error");
+        let json = parse_json_with(code, "{\"recover\": true}");
+        assert_eq!(json["type"], "sequence");
+        let errors = json["errors"].as_array().unwrap();
+        assert_eq!(errors.len(), 3);
+        let positions: Vec<(u64, u64)> =
+            errors.iter().map(|e| (e["line"].as_u64().unwrap(), e["column"].as_u64().unwrap())).collect();
+        assert_eq!(positions, vec![(2, 9), (5, 14), (6, 11)]);
+        assert!(errors[0]["frame"].as_str().unwrap().contains("> 2 |     let = 1;"));
+        let kinds: Vec<&str> = json["children"].as_array().unwrap().iter().map(|n| n["type"].as_str().unwrap()).collect();
+        assert_eq!(kinds, vec!["error", "block", "error", "error"]);
+        assert_eq!(json["children"][1]["label"], "fn ok()");
+    }
+
+    #[test]
+    fn byte_input_is_decoded_as_utf8() {
+        let code = "fn main() { if a { b(); } }";
+        assert_eq!(parse_rust_code_bytes(code.as_bytes()), parse_rust_code(code));
+        let with_bom = [b"\xEF\xBB\xBF".as_slice(), code.as_bytes()].concat();
+        assert_eq!(parse_rust_code_bytes(&with_bom), parse_rust_code(&format!("\u{feff}{}", code)));
+    }
+
+    #[test]
+    fn pasted_wrappers_are_stripped_and_reported() {
+        let code = "\u{feff}```rust\n#!/usr/bin/env run-cargo-script\nfn main() {\n    let x = ;\n}\n```\n";
+        let json = parse_json(code);
+        assert_eq!(json["type"], "error");
+        // 構文エラーの位置は、貼り付けたテキストの行を指します
+        assert_eq!(json["line"], 4);
+
+        let code = code.replace("let x = ;", "run();");
+        let json = parse_json(&code);
+        assert_eq!(json["children"][0]["label"], "fn main()");
+        let codes: Vec<&str> = json["warnings"].as_array().unwrap().iter().map(|w| w["code"].as_str().unwrap()).collect();
+        assert_eq!(codes, ["stripped_bom", "stripped_fence", "stripped_shebang"]);
+        assert_eq!(json["warnings"][2]["span"]["start_line"], 2);
+        assert_eq!(json["children"][0]["children"][0]["children"][0]["label"], "run ()");
+
+        let bytes = parse_rust_code_bytes(code.as_bytes());
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&bytes).unwrap()["warnings"], json["warnings"]);
+    }
+
+    #[test]
+    fn invalid_utf8_reports_the_byte_offset() {
+        let bytes = b"fn main() {\n    s(\"\xE6\x97\xA5\xFFx\");\n}";
+        let json: serde_json::Value = serde_json::from_str(&parse_rust_code_bytes(bytes)).unwrap();
+        assert_eq!(json["type"], "error");
+        assert_eq!(json["byte_offset"], 22);
+        assert_eq!((json["line"].as_u64(), json["column"].as_u64()), (Some(2), Some(9)));
+        assert!(json["frame"].as_str().unwrap().contains("s(\"日\u{fffd}x\");"));
+        let lossy: serde_json::Value =
+            serde_json::from_str(&parse_rust_code_bytes_with_options(bytes, "{\"lossy_utf8\": true}")).unwrap();
+        assert_eq!(lossy["children"][0]["label"], "fn main()");
+    }
+
+    #[test]
+    fn streaming_delivers_items_in_source_order() {
+        let code = "use std::fs;\nfn a() { x(); }\nfn b() { for i in v { y(i); } }\nfn c() { z(); }";
+        let mut calls = Vec::new();
+        let summary = stream_items(code, &ParseOptions::default(), |index, total, json| {
+            calls.push((index, total, serde_json::from_str::<serde_json::Value>(&json).unwrap()));
+            Ok::<(), ()>(())
+        })
+        .unwrap();
+        let labels: Vec<(usize, usize, &str)> =
+            calls.iter().map(|(index, total, node)| (*index, *total, node["label"].as_str().unwrap())).collect();
+        assert_eq!(labels, vec![(0, 3, "fn a()"), (1, 3, "fn b()"), (2, 3, "fn c()")]);
+        // idは一括で変換した場合と同じです
+        let full = parse_json(code);
+        for (i, (_, _, node)) in calls.iter().enumerate() {
+            assert_eq!(node, &full["children"][i]);
+        }
+        let summary: serde_json::Value = serde_json::from_str(&summary).unwrap();
+        assert_eq!(summary["total"], 3);
+        assert_eq!(summary["errors"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn streaming_delivers_broken_items_as_errors() {
+        let code = "fn a() { x(); }\nfn b() { let = 1; }\nfn c() {}";
+        let mut kinds = Vec::new();
+        let summary = stream_items(code, &ParseOptions::default(), |_, _, json| {
+            kinds.push(serde_json::from_str::<serde_json::Value>(&json).unwrap()["type"].as_str().unwrap().to_string());
+            Ok::<(), ()>(())
+        })
+        .unwrap();
+        assert_eq!(kinds, vec!["block", "error", "block"]);
+        let summary: serde_json::Value = serde_json::from_str(&summary).unwrap();
+        assert_eq!(summary["errors"][0]["line"], 2);
+    }
+
+    #[test]
+    fn label_tokens_are_opt_in() {
+        let code = "fn main() { if name == \"root\" { return; } }";
+        assert!(parse_json(code)["children"][0]["children"][0]["children"][0].get("tokens").is_none());
+        let json = parse_json_with(code, "{\"label_tokens\": true}");
+        let node = &json["children"][0]["children"][0]["children"][0];
+        let condition = node["condition"].as_str().unwrap();
+        let string = node["tokens"].as_array().unwrap().iter().find(|t| t["kind"] == "string").unwrap();
+        let (start, end) = (string["start"].as_u64().unwrap() as usize, string["end"].as_u64().unwrap() as usize);
+        assert_eq!(&condition[start..end], "\"root\"");
+        let body = &node["then_block"]["children"][0];
+        assert_eq!(body["tokens"], serde_json::json!([{ "start": 0, "end": 6, "kind": "keyword" }]));
+    }
+
+    #[test]
+    fn profile_is_reported_only_when_requested() {
+        let code = "fn main() { if a { b(); } }";
+        assert!(parse_json(code).get("profile").is_none());
+        let json = parse_json_with(code, "{\"profile\": true}");
+        let profile = &json["profile"];
+        for phase in ["parse_ms", "convert_ms", "serialize_ms"] {
+            assert!(profile[phase].as_f64().unwrap() >= 0.0, "{}", phase);
+        }
+        assert_eq!(profile["input_bytes"], code.len());
+        assert_eq!(profile["node_count"], 6);
+        let document = parse_json_with(code, "{\"profile\": true, \"document\": true, \"field_naming\": \"camel_case\"}");
+        assert!(document["profile"]["serializeMs"].as_f64().unwrap() >= 0.0);
     }
 }
@@ -0,0 +1,106 @@
+//! 構造上の警告（lint）
+//! 解析後のツリーをビジターで走査し、深すぎる入れ子や長すぎる関数などを警告として報告します。
+//! しきい値は `ParseOptions::lint` で変更できます。
+
+use crate::model::{BlockKind, PadNode, Warning};
+use crate::options::LintOptions;
+use crate::visit::{VisitContext, Visitor, walk};
+
+/// 制御構造（入れ子の深さとして数えるノード）かどうか
+fn is_control(node: &PadNode) -> bool {
+    matches!(node, PadNode::If { .. } | PadNode::Loop { .. } | PadNode::Match { .. })
+}
+
+/// 関数ごとの集計中の状態
+struct FunctionScope {
+    node_id: String,
+    label: String,
+    span: Option<crate::model::Span>,
+    statements: usize,
+}
+
+struct Linter<'a> {
+    options: &'a LintOptions,
+    warnings: Vec<Warning>,
+    /// 現在の制御構造の入れ子の深さ
+    nesting: usize,
+    functions: Vec<FunctionScope>,
+}
+
+impl Linter<'_> {
+    fn warn(&mut self, code: &'static str, message: String, node: &PadNode) {
+        let meta = node.meta();
+        self.warnings.push(Warning { code: code.to_string(), message, node_id: meta.id.clone(), span: meta.span });
+    }
+}
+
+impl Visitor for Linter<'_> {
+    fn enter(&mut self, node: &PadNode, _ctx: &VisitContext) -> bool {
+        if let PadNode::Block { kind: BlockKind::Function, label, .. } = node {
+            self.functions.push(FunctionScope {
+                node_id: node.meta().id.clone(),
+                label: label.clone(),
+                span: node.meta().span,
+                statements: 0,
+            });
+            return true;
+        }
+        if !matches!(node, PadNode::Sequence { .. })
+            && let Some(scope) = self.functions.last_mut()
+        {
+            scope.statements += 1;
+        }
+
+        if is_control(node) {
+            self.nesting += 1;
+            // しきい値を超えた最初の制御構造だけを報告し、その内側では重ねて報告しません
+            if self.nesting == self.options.max_nesting + 1 {
+                let message = format!("入れ子が深すぎます（{}段、上限{}段）", self.nesting, self.options.max_nesting);
+                self.warn("deep_nesting", message, node);
+            }
+        }
+        match node {
+            PadNode::If { condition, .. } if condition.chars().count() > self.options.max_condition_length => {
+                let message = format!(
+                    "条件式が長すぎます（{}文字、上限{}文字）",
+                    condition.chars().count(),
+                    self.options.max_condition_length
+                );
+                self.warn("long_condition", message, node);
+            }
+            PadNode::Match { arms, .. } if arms.len() > self.options.max_match_arms => {
+                let message = format!("matchのアームが多すぎます（{}個、上限{}個）", arms.len(), self.options.max_match_arms);
+                self.warn("many_match_arms", message, node);
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn leave(&mut self, node: &PadNode, _ctx: &VisitContext) {
+        if is_control(node) {
+            self.nesting -= 1;
+        }
+        if let PadNode::Block { kind: BlockKind::Function, .. } = node
+            && let Some(scope) = self.functions.pop()
+            && scope.statements > self.options.max_statements
+        {
+            self.warnings.push(Warning {
+                code: "long_function".to_string(),
+                message: format!(
+                    "{} の文が多すぎます（{}個、上限{}個）",
+                    scope.label, scope.statements, self.options.max_statements
+                ),
+                node_id: scope.node_id,
+                span: scope.span,
+            });
+        }
+    }
+}
+
+/// ツリーを検査し、警告の一覧を返します（ノードidは割り当て済みであること）
+pub fn lint(root: &PadNode, options: &LintOptions) -> Vec<Warning> {
+    let mut linter = Linter { options, warnings: Vec::new(), nesting: 0, functions: Vec::new() };
+    walk(root, &mut linter);
+    linter.warnings
+}
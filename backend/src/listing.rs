@@ -0,0 +1,114 @@
+//! 図に表示できるトップレベルのアイテムの一覧
+//! 関数の本体を変換せずに、各アイテムの種類・名前・位置だけを返します。
+//! `index` は `item_offset` / `item_limit` で数えるアイテムの位置と同じで、ページ送りの操作を作るために使います。
+
+use serde::Serialize;
+use syn::{ImplItem, Item};
+
+use crate::convert::{Converter, compact_tokens, default_methods, span_of};
+use crate::options::ParseOptions;
+
+/// アイテム1つ分の情報
+#[derive(Debug, Serialize)]
+pub struct ListedItem {
+    /// 図に表示できるアイテムの中での位置（0始まり）
+    pub index: usize,
+    /// アイテムの種類（`function` / `impl` / `trait` / `extern` / `macro` / `type` / `const` / `static` / `module`）
+    pub kind: &'static str,
+    /// 関数・マクロ・型・モジュールの名前、implブロックの場合は実装対象の型
+    pub name: String,
+    /// トレイト実装の場合のトレイト名
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trait_name: Option<String>,
+    /// implブロックのメソッド名、トレイトの場合は既定の実装を持つメソッド名（`include` / `exclude` で除外したものを除きます）
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub methods: Vec<String>,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+/// 一覧と、図に表示できるアイテムの総数
+#[derive(Debug, Serialize)]
+pub struct Listing {
+    pub total_items: usize,
+    pub items: Vec<ListedItem>,
+}
+
+/// 図に表示できるトップレベルのアイテムを、ソースの順に列挙します
+pub fn list_items(converter: &Converter, items: &[Item], options: &ParseOptions) -> Listing {
+    let items: Vec<ListedItem> = items
+        .iter()
+        .filter(|item| converter.includes_item(item) && converter.draws_item(item))
+        .enumerate()
+        .map(|(index, item)| listed(options, index, item))
+        .collect();
+    Listing { total_items: items.len(), items }
+}
+
+fn listed(options: &ParseOptions, index: usize, item: &Item) -> ListedItem {
+    let span = span_of(item);
+    let mut listed = ListedItem {
+        index,
+        kind: "function",
+        name: String::new(),
+        trait_name: None,
+        methods: Vec::new(),
+        start_line: span.start_line,
+        end_line: span.end_line,
+    };
+    match item {
+        Item::Fn(func) => listed.name = func.sig.ident.to_string(),
+        Item::Impl(item_impl) => {
+            let self_ty = &item_impl.self_ty;
+            listed.kind = "impl";
+            listed.name = compact_tokens(&quote::quote!(#self_ty).to_string());
+            listed.trait_name = item_impl
+                .trait_
+                .as_ref()
+                .map(|(bang, path, _)| compact_tokens(&quote::quote!(#bang #path).to_string()));
+            listed.methods = item_impl
+                .items
+                .iter()
+                .filter_map(|item| match item {
+                    ImplItem::Fn(method) => Some(method.sig.ident.to_string()),
+                    _ => None,
+                })
+                .filter(|name| options.allows_name(name))
+                .collect();
+        }
+        Item::Trait(item_trait) => {
+            listed.kind = "trait";
+            listed.name = item_trait.ident.to_string();
+            listed.methods = default_methods(item_trait)
+                .map(|(method, _)| method.sig.ident.to_string())
+                .filter(|name| options.allows_name(name))
+                .collect();
+        }
+        Item::ForeignMod(foreign_mod) => {
+            listed.kind = "extern";
+            listed.name = foreign_mod.abi.name.as_ref().map_or("C".to_string(), |name| name.value());
+        }
+        Item::Macro(item_macro) => {
+            listed.kind = "macro";
+            listed.name = item_macro.ident.as_ref().map(ToString::to_string).unwrap_or_default();
+        }
+        Item::Type(item_type) => {
+            listed.kind = "type";
+            listed.name = item_type.ident.to_string();
+        }
+        Item::Const(item_const) => {
+            listed.kind = "const";
+            listed.name = item_const.ident.to_string();
+        }
+        Item::Static(item_static) => {
+            listed.kind = "static";
+            listed.name = item_static.ident.to_string();
+        }
+        Item::Mod(item_mod) => {
+            listed.kind = "module";
+            listed.name = item_mod.ident.to_string();
+        }
+        _ => {}
+    }
+    listed
+}
@@ -0,0 +1,131 @@
+//! 標準ライブラリのマクロの展開
+//! マクロ呼び出しはソースのまま1つのCommandになるため、`assert!` や `matches!` のように展開すると分岐になるマクロは、
+//! その構造が図に現れません。`expand_macros` オプション指定時は、表にあるマクロを同じ意味の式（`if` / `match`）に置き換えてから変換します。
+//! `println!` や `vec!` など、展開しても分岐にならないマクロは、これまでどおりCommandにします。
+//! 手続きマクロなども含めてすべて展開したい場合は、`cargo expand` の出力をそのまま入力として渡してください。
+
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, quote_spanned};
+use syn::parse::{ParseStream, Parser};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{Expr, Pat, Token};
+
+/// 展開の表（マクロ名と、引数のトークンから展開後の式を作る関数）
+/// `debug_assert` 系のマクロは、デバッグビルドでの動作として展開します。
+const TABLE: &[(&str, Expansion)] = &[
+    ("assert", assert),
+    ("debug_assert", assert),
+    ("assert_eq", assert_eq),
+    ("debug_assert_eq", assert_eq),
+    ("assert_ne", assert_ne),
+    ("debug_assert_ne", assert_ne),
+    ("matches", matches),
+];
+
+type Expansion = fn(TokenStream, Span) -> syn::Result<Expr>;
+
+/// マクロ呼び出しを展開した式と、マクロ名を返します（表にないマクロや、引数を解釈できない場合は None）
+/// 展開後の式のトークンには、マクロ呼び出しの位置を付けます。
+pub fn expand(mac: &syn::Macro) -> Option<(Expr, String)> {
+    let name = mac.path.segments.last()?.ident.to_string();
+    let (_, expansion) = TABLE.iter().find(|(entry, _)| *entry == name)?;
+    let expr = expansion(mac.tokens.clone(), mac.span()).ok()?;
+    Some((expr, name))
+}
+
+/// カンマ区切りの引数
+fn arguments(tokens: TokenStream) -> syn::Result<Vec<Expr>> {
+    Ok(Punctuated::<Expr, Token![,]>::parse_terminated.parse2(tokens)?.into_iter().collect())
+}
+
+/// 二項演算の項に置いても意味が変わらないよう、単純な式以外を括弧で囲みます
+fn operand(expr: &Expr) -> TokenStream {
+    match expr {
+        Expr::Path(_)
+        | Expr::Lit(_)
+        | Expr::Call(_)
+        | Expr::MethodCall(_)
+        | Expr::Field(_)
+        | Expr::Index(_)
+        | Expr::Macro(_)
+        | Expr::Paren(_)
+        | Expr::Tuple(_) => quote!(#expr),
+        _ => quote!((#expr)),
+    }
+}
+
+/// 失敗した場合の `panic!`（メッセージの指定がない場合は `default` のメッセージ）
+fn failure(message: &[Expr], default: String, span: Span) -> TokenStream {
+    if message.is_empty() {
+        quote_spanned!(span=> panic!(#default))
+    } else {
+        quote_spanned!(span=> panic!(#(#message),*))
+    }
+}
+
+/// `assert!(cond, ...)` → `if !cond { panic!(...) }`
+fn assert(tokens: TokenStream, span: Span) -> syn::Result<Expr> {
+    let args = arguments(tokens)?;
+    let Some((cond, message)) = args.split_first() else { return Err(syn::Error::new(span, "missing condition")) };
+    let panic = failure(message, format!("assertion failed: {}", quote!(#cond)), span);
+    let cond = operand(cond);
+    syn::parse2(quote_spanned!(span=> if !#cond { #panic; }))
+}
+
+/// `assert_eq!(left, right, ...)` → `if left != right { panic!(...) }`
+fn assert_eq(tokens: TokenStream, span: Span) -> syn::Result<Expr> {
+    compare(tokens, span, quote_spanned!(span=> !=), "==")
+}
+
+/// `assert_ne!(left, right, ...)` → `if left == right { panic!(...) }`
+fn assert_ne(tokens: TokenStream, span: Span) -> syn::Result<Expr> {
+    compare(tokens, span, quote_spanned!(span=> ==), "!=")
+}
+
+fn compare(tokens: TokenStream, span: Span, failed_op: TokenStream, asserted_op: &str) -> syn::Result<Expr> {
+    let args = arguments(tokens)?;
+    let [left, right, message @ ..] = args.as_slice() else { return Err(syn::Error::new(span, "expected two operands")) };
+    let panic = failure(message, format!("assertion `left {} right` failed", asserted_op), span);
+    let (left, right) = (operand(left), operand(right));
+    syn::parse2(quote_spanned!(span=> if #left #failed_op #right { #panic; }))
+}
+
+/// `matches!(expr, pattern if guard)` → `match expr { pattern if guard => true, _ => false }`
+fn matches(tokens: TokenStream, span: Span) -> syn::Result<Expr> {
+    let parser = |input: ParseStream| {
+        let scrutinee: Expr = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let pattern = Pat::parse_multi_with_leading_vert(input)?;
+        let guard = match input.parse::<Option<Token![if]>>()? {
+            Some(_) => Some(input.parse::<Expr>()?),
+            None => None,
+        };
+        input.parse::<Option<Token![,]>>()?;
+        Ok((scrutinee, pattern, guard))
+    };
+    let (scrutinee, pattern, guard) = parser.parse2(tokens)?;
+    let guard = guard.map(|guard| quote!(if #guard));
+    syn::parse2(quote_spanned!(span=> match #scrutinee { #pattern #guard => true, _ => false }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expanded(code: &str) -> String {
+        let mac: syn::Macro = syn::parse_str(code).unwrap();
+        let (expr, _) = expand(&mac).unwrap();
+        quote!(#expr).to_string()
+    }
+
+    #[test]
+    fn assertions_become_ifs_that_panic() {
+        assert_eq!(expanded("assert!(v.is_empty())"), "if ! v . is_empty () { panic ! (\"assertion failed: v . is_empty ()\") ; }");
+        assert_eq!(expanded("assert!(a > b, \"{} <= {}\", a, b)"), "if ! (a > b) { panic ! (\"{} <= {}\" , a , b) ; }");
+        assert_eq!(expanded("debug_assert_eq!(x + 1, y)"), "if (x + 1) != y { panic ! (\"assertion `left == right` failed\") ; }");
+        assert_eq!(expanded("matches!(c, 'a'..='z' | '_' if ok)"), "match c { 'a' ..= 'z' | '_' if ok => true , _ => false }");
+        assert!(expand(&syn::parse_str("println!(\"hi\")").unwrap()).is_none());
+        assert!(expand(&syn::parse_str("assert!()").unwrap()).is_none());
+    }
+}
@@ -0,0 +1,155 @@
+//! Markdown中のRustのコードブロックの抽出
+//! ドキュメントに埋め込まれた例ごとに図を作れるよう、`rust` と指定されたフェンス付きコードブロックを取り出します。
+
+use serde::Serialize;
+
+use crate::model::PadNode;
+
+/// `parse_markdown` の結果のうち、コードブロック1つ分
+#[derive(Debug, Serialize)]
+pub struct FenceDiagram {
+    /// 開始のフェンスの行番号（1始まり）
+    pub line: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// コードブロックを変換したツリー（解析できない場合はErrorノード。位置はコードブロック内の行・桁です）
+    pub tree: PadNode,
+}
+
+/// Rustのコードブロック1つ分
+#[derive(Debug)]
+pub struct Fence {
+    /// 開始のフェンス（```` ```rust ````）の行番号（1始まり）
+    pub line: usize,
+    /// コードブロックより前にある最も近い見出しの文字列
+    pub title: Option<String>,
+    /// rustdocの隠し行の記法（行頭の `# `）を取り除いたコード
+    pub code: String,
+}
+
+/// 開いているコードブロックの情報
+struct OpenFence {
+    /// フェンスの文字（`` ` `` または `~`）と長さ
+    marker: char,
+    length: usize,
+    /// Rustのコードブロックの場合、開始行と見出し
+    rust: Option<(usize, Option<String>)>,
+    lines: Vec<String>,
+}
+
+/// Markdownの文字列から、`rust`（`rust,ignore` なども含む）と指定されたコードブロックを出現順に取り出します
+/// 閉じられていないコードブロックは、文書の末尾までをその中身とします。
+pub fn rust_fences(text: &str) -> Vec<Fence> {
+    let mut fences = Vec::new();
+    let mut title = None;
+    let mut open: Option<OpenFence> = None;
+    for (index, line) in text.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if let Some(fence) = &mut open {
+            if is_closing(trimmed, fence.marker, fence.length) {
+                fences.extend(close(open.take().unwrap()));
+            } else {
+                fence.lines.push(line.to_string());
+            }
+            continue;
+        }
+        if let Some((marker, length, info)) = opening(trimmed) {
+            let rust = is_rust(info).then(|| (index + 1, title.clone()));
+            open = Some(OpenFence { marker, length, rust, lines: Vec::new() });
+        } else if let Some(heading) = heading(trimmed) {
+            title = Some(heading);
+        }
+    }
+    fences.extend(open.and_then(close));
+    fences
+}
+
+/// 開始のフェンスであれば、フェンスの文字・長さと情報文字列（言語の指定）を返します
+fn opening(line: &str) -> Option<(char, usize, &str)> {
+    let marker = line.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+    let length = line.chars().take_while(|&c| c == marker).count();
+    if length < 3 {
+        return None;
+    }
+    let info = line[length..].trim();
+    // バッククォートのフェンスの情報文字列にはバッククォートを含められません
+    if marker == '`' && info.contains('`') {
+        return None;
+    }
+    Some((marker, length, info))
+}
+
+fn is_closing(line: &str, marker: char, length: usize) -> bool {
+    let count = line.chars().take_while(|&c| c == marker).count();
+    count >= length && line[count..].trim().is_empty()
+}
+
+/// 情報文字列の最初の属性（`,` や空白で区切ったもの）が `rust` かどうか
+fn is_rust(info: &str) -> bool {
+    info.split([',', ' ', '\t']).next() == Some("rust")
+}
+
+/// ATX形式の見出し（`# 見出し`）であれば、その文字列を返します
+fn heading(line: &str) -> Option<String> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    let rest = &line[level..];
+    if !rest.is_empty() && !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    // 閉じの `#` の並び（`## 見出し ##`）は見出しに含めません
+    let text = rest.trim();
+    let unclosed = text.trim_end_matches('#');
+    let text = if unclosed.is_empty() || unclosed.ends_with([' ', '\t']) { unclosed.trim_end() } else { text };
+    Some(text.to_string())
+}
+
+fn close(fence: OpenFence) -> Option<Fence> {
+    let (line, title) = fence.rust?;
+    let code: Vec<&str> = fence.lines.iter().map(|line| unhide(line)).collect();
+    Some(Fence { line, title, code: code.join("\n") })
+}
+
+/// rustdocの隠し行（`# ` で始まる行、または `#` だけの行）の記法を取り除きます
+/// 隠し行も例のコードの一部なので、行そのものは残します。`##` で始まる行は `#` 1つにします。
+fn unhide(line: &str) -> &str {
+    let trimmed = line.trim_start();
+    let indent = &line[..line.len() - trimmed.len()];
+    if trimmed == "#" {
+        ""
+    } else if let Some(rest) = trimmed.strip_prefix("# ") {
+        rest
+    } else if trimmed.starts_with("##") {
+        &line[indent.len() + 1..]
+    } else {
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_rust_fences_are_extracted_with_heading_and_line() {
+        let text = "# Guide\n\n## Counting\n\n```rust\nfn count() { for i in 0..3 { tick(i); } }\n```\n\n```python\nprint('skip')\n```\n\n## Hidden setup\n\n```rust,ignore\n# fn main() {\nlet x = 1;\n# }\n```\n";
+        let fences = rust_fences(text);
+        assert_eq!(fences.len(), 2);
+        assert_eq!(fences[0].line, 5);
+        assert_eq!(fences[0].title.as_deref(), Some("Counting"));
+        assert_eq!(fences[1].line, 15);
+        assert_eq!(fences[1].title.as_deref(), Some("Hidden setup"));
+        assert_eq!(fences[1].code, "fn main() {\nlet x = 1;\n}");
+    }
+
+    #[test]
+    fn headings_inside_fences_and_attributes_are_not_special() {
+        let text = "```text\n# not a heading\n```\n````rust\n#[derive(Debug)]\n## escaped\n```\n````\n";
+        let fences = rust_fences(text);
+        assert_eq!(fences.len(), 1);
+        assert_eq!(fences[0].title, None);
+        assert_eq!(fences[0].code, "#[derive(Debug)]\n# escaped\n```");
+    }
+}
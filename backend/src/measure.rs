@@ -0,0 +1,128 @@
+//! 図の大きさの見積もり
+//! フロントエンドがキャンバスを確保する前に、図の深さと高さ（行数）を知るための集計です。
+//! 標準的なPADの配置（入れ子が1段深くなるごとに1列右へ、葉のノード1つにつき1行）を前提とします。
+//! Sequenceは箱を持たないため、深さや列には数えません。
+
+use serde::Serialize;
+
+use crate::model::PadNode;
+use crate::visit::{VisitContext, Visitor, walk};
+
+/// 見積もり結果
+#[derive(Debug, Default, Serialize)]
+pub struct Measurement {
+    /// 箱の入れ子の最大の深さ（トップレベルの関数が1）
+    pub max_depth: usize,
+    /// 葉のノード（箱を持つ子孫がいないノード）の数
+    pub leaves: usize,
+    /// 深さごとのノード数（先頭が深さ1）
+    pub per_depth: Vec<usize>,
+    pub bounding_box: BoundingBox,
+}
+
+/// 図全体の外接矩形の見積もり
+#[derive(Debug, Default, Serialize)]
+pub struct BoundingBox {
+    /// 列数（= 最大の深さ）
+    pub columns: usize,
+    /// 行数（= 葉のノードの数）
+    pub rows: usize,
+    /// 各列の幅（その深さのノードの `width_hint` の最大値）の合計。`width_hint` がない場合は出力しません
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<usize>,
+}
+
+struct Measurer {
+    result: Measurement,
+    /// 現在の箱の入れ子の深さ
+    depth: usize,
+    /// 入れ子ごとの「箱を持つ子孫がいたか」のフラグ
+    has_box_child: Vec<bool>,
+    /// 深さごとの `width_hint` の最大値
+    column_widths: Vec<Option<usize>>,
+}
+
+impl Visitor for Measurer {
+    fn enter(&mut self, node: &PadNode, _ctx: &VisitContext) -> bool {
+        if matches!(node, PadNode::Sequence { .. } | PadNode::Document { .. }) {
+            return true;
+        }
+        if let Some(parent) = self.has_box_child.last_mut() {
+            *parent = true;
+        }
+        self.depth += 1;
+        self.has_box_child.push(false);
+        if self.result.per_depth.len() < self.depth {
+            self.result.per_depth.push(0);
+            self.column_widths.push(None);
+        }
+        self.result.per_depth[self.depth - 1] += 1;
+        self.result.max_depth = self.result.max_depth.max(self.depth);
+        if let Some(hint) = node.meta().width_hint {
+            let column = &mut self.column_widths[self.depth - 1];
+            *column = Some(column.unwrap_or(0).max(hint));
+        }
+        true
+    }
+
+    fn leave(&mut self, node: &PadNode, _ctx: &VisitContext) {
+        if matches!(node, PadNode::Sequence { .. } | PadNode::Document { .. }) {
+            return;
+        }
+        if self.has_box_child.pop() == Some(false) {
+            self.result.leaves += 1;
+        }
+        self.depth -= 1;
+    }
+}
+
+/// ツリーの深さ・葉の数・外接矩形を見積もります
+pub fn measure(root: &PadNode) -> Measurement {
+    let mut measurer =
+        Measurer { result: Measurement::default(), depth: 0, has_box_child: Vec::new(), column_widths: Vec::new() };
+    walk(root, &mut measurer);
+    let mut result = measurer.result;
+    result.bounding_box = BoundingBox {
+        columns: result.max_depth,
+        rows: result.leaves,
+        width: measurer.column_widths.iter().copied().sum(),
+    };
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::BlockKind;
+
+    #[test]
+    fn known_tree_has_exact_depth_and_rows() {
+        // fn main()
+        // ├ a
+        // ├ if x ─┬ b
+        // │       └ while y ─ c
+        // └ d
+        let root = PadNode::sequence(vec![PadNode::block(
+            BlockKind::Function,
+            "fn main()",
+            vec![PadNode::sequence(vec![
+                PadNode::command("a"),
+                PadNode::if_node(
+                    "x",
+                    PadNode::sequence(vec![
+                        PadNode::command("b"),
+                        PadNode::loop_node(crate::model::LoopKind::While, "y", PadNode::sequence(vec![PadNode::command("c")])),
+                    ]),
+                    None,
+                ),
+                PadNode::command("d"),
+            ])],
+        )]);
+        let result = measure(&root);
+        assert_eq!(result.max_depth, 4);
+        assert_eq!(result.leaves, 4);
+        assert_eq!(result.per_depth, vec![1, 3, 2, 1]);
+        assert_eq!((result.bounding_box.columns, result.bounding_box.rows), (4, 4));
+        assert_eq!(result.bounding_box.width, None);
+    }
+}
@@ -0,0 +1,212 @@
+//! 複数のPADツリーの結合
+//! 機能ごとに作った図（スニペット）を1つの文書にまとめてエクスポートするために使います。
+//! ノードidが重複する場合は、決まった規則で付け替えて対応表を返します。
+
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::model::{BlockKind, DocumentInfo, PadNode, Warning};
+use crate::validate;
+
+/// 結合する1つ分の入力
+/// JSON文字列そのもの、または `{ "tree": ..., "title": ... }` の形式で指定します。
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum MergeInput {
+    Tree(String),
+    Titled { tree: String, title: Option<String> },
+}
+
+/// 付け替えたノードidの記録
+#[derive(Debug, PartialEq, Serialize)]
+pub struct IdRemap {
+    /// 入力の何番目のツリーか（0始まり）
+    pub tree: usize,
+    pub from: String,
+    pub to: String,
+}
+
+/// 結合の結果
+#[derive(Debug, Serialize)]
+pub struct MergeResult {
+    pub document: PadNode,
+    pub remapped: Vec<IdRemap>,
+}
+
+/// ツリーを順に結合します。いずれかのツリーが不正な場合は、その番号と理由をエラーとして返します
+/// 先に現れたツリーのidは変更せず、すでに使われているidだけを `t<番号>-<元のid>` に付け替えます。
+pub fn merge(inputs: Vec<MergeInput>) -> Result<MergeResult, String> {
+    let mut used = HashSet::new();
+    let mut remapped = Vec::new();
+    let mut warnings = Vec::new();
+    let mut children = Vec::new();
+
+    for (index, input) in inputs.into_iter().enumerate() {
+        let (json, title) = match input {
+            MergeInput::Tree(json) => (json, None),
+            MergeInput::Titled { tree, title } => (tree, title),
+        };
+        let report = validate::validate(&json);
+        if let Some(error) = report.errors.first() {
+            return Err(format!("Tree {} is invalid: {}: {}", index, error.path, error.message));
+        }
+        let mut tree: PadNode = serde_json::from_str(&json).map_err(|e| format!("Tree {} is invalid: {}", index, e))?;
+
+        let mut tree_remaps = Vec::new();
+        remap_ids(&mut tree, index, &mut used, &mut tree_remaps);
+        rename_jump_targets(&mut tree, &tree_remaps);
+        if let PadNode::Sequence { info, .. } | PadNode::Document { info, .. } = &mut tree
+            && let Some(info) = info.take()
+        {
+            warnings.extend(info.warnings.into_iter().map(|warning| Warning {
+                node_id: renamed(&tree_remaps, &warning.node_id),
+                ..warning
+            }));
+        }
+        remapped.extend(tree_remaps);
+
+        children.push(match title {
+            Some(title) => {
+                let mut block = PadNode::block(BlockKind::Group, title, vec![tree]);
+                block.meta_mut().id = unique_id(format!("t{}", index), &mut used);
+                block
+            }
+            None => tree,
+        });
+    }
+
+    let mut document = PadNode::sequence(children);
+    document.meta_mut().id = unique_id("root".to_string(), &mut used);
+    if let PadNode::Sequence { info, .. } = &mut document {
+        *info = Some(Box::new(DocumentInfo { warnings, ..DocumentInfo::default() }));
+    }
+    Ok(MergeResult { document, remapped })
+}
+
+/// ツリー内のidのうち、すでに使われているものを付け替えます（idのないノードはそのままにします）
+fn remap_ids(node: &mut PadNode, index: usize, used: &mut HashSet<String>, remaps: &mut Vec<IdRemap>) {
+    let id = &mut node.meta_mut().id;
+    if !id.is_empty() {
+        if used.contains(id.as_str()) {
+            let to = unique_id(format!("t{}-{}", index, id), used);
+            remaps.push(IdRemap { tree: index, from: std::mem::replace(id, to.clone()), to });
+        } else {
+            used.insert(id.clone());
+        }
+    }
+    for child in node.children_mut() {
+        remap_ids(child, index, used, remaps);
+    }
+}
+
+/// break / continue の脱出先と、関数の呼び出し先（`call_targets`）・戻り値を返すノード（`returned_by`）のidを付け替え後のidに合わせます
+fn rename_jump_targets(node: &mut PadNode, remaps: &[IdRemap]) {
+    if let PadNode::Break { target_id: Some(id), .. } | PadNode::Continue { target_id: Some(id), .. } = node {
+        *id = renamed(remaps, id);
+    }
+    let meta = node.meta_mut();
+    for id in meta.call_targets.iter_mut().chain(&mut meta.returned_by) {
+        *id = renamed(remaps, id);
+    }
+    for child in node.children_mut() {
+        rename_jump_targets(child, remaps);
+    }
+}
+
+/// `base` がまだ使われていなければそのまま、使われていれば `base-2`, `base-3`, ... のうち最初の空きを使います
+fn unique_id(base: String, used: &mut HashSet<String>) -> String {
+    let mut id = base.clone();
+    let mut n = 2;
+    while used.contains(&id) {
+        id = format!("{}-{}", base, n);
+        n += 1;
+    }
+    used.insert(id.clone());
+    id
+}
+
+/// 付け替え後のidを返します（付け替えていなければ元のid）
+fn renamed(remaps: &[IdRemap], id: &str) -> String {
+    remaps.iter().find(|r| r.from == id).map_or_else(|| id.to_string(), |r| r.to.clone())
+}
+
+/// JSON配列（文字列化済み）を結合の入力として読み込みます
+pub fn parse_inputs(json: &str) -> Result<Vec<MergeInput>, String> {
+    let value: Value = serde_json::from_str(json).map_err(|e| format!("Invalid JSON: {}", e))?;
+    serde_json::from_value(value).map_err(|_| "Expected an array of serialized trees".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlapping_ids_are_remapped_deterministically() {
+        let a = crate::parse_rust_code("fn a() { x(); }");
+        let b = crate::parse_rust_code("fn b() { if c { y(); } }");
+        let inputs = vec![MergeInput::Tree(a), MergeInput::Titled { tree: b, title: Some("feature b".into()) }];
+        let result = merge(inputs).unwrap();
+
+        let json = serde_json::to_value(&result.document).unwrap();
+        assert_eq!(json["id"], "root");
+        assert_eq!(json["children"][0]["id"], "n0");
+        let group = &json["children"][1];
+        assert_eq!((group["type"].as_str(), group["label"].as_str()), (Some("block"), Some("feature b")));
+        assert_eq!(group["children"][0]["id"], "t1-n0");
+        assert_eq!(result.remapped[0], IdRemap { tree: 1, from: "n0".into(), to: "t1-n0".into() });
+        // 最初のツリーはn0〜n3、2番目のツリーはn0〜n5なので、重なるn0〜n3だけが付け替えられます
+        assert_eq!(result.remapped.len(), 4);
+        let if_node = &group["children"][0]["children"][0]["children"][0]["children"][0];
+        assert_eq!(if_node["id"], "t1-n3");
+        assert_eq!(if_node["then_block"]["id"], "n4");
+
+        let mut ids = Vec::new();
+        collect_ids(&json, &mut ids);
+        let unique: HashSet<_> = ids.iter().collect();
+        assert_eq!(unique.len(), ids.len());
+    }
+
+    fn collect_ids(value: &Value, ids: &mut Vec<String>) {
+        match value {
+            Value::Object(map) => {
+                if let Some(Value::String(id)) = map.get("id") {
+                    ids.push(id.clone());
+                }
+                map.values().for_each(|v| collect_ids(v, ids));
+            }
+            Value::Array(items) => items.iter().for_each(|v| collect_ids(v, ids)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn jump_targets_follow_remapped_ids() {
+        let code = "fn f() { loop { break; } }";
+        let tree = crate::parse_rust_code(code);
+        let result = merge(vec![MergeInput::Tree(tree.clone()), MergeInput::Tree(tree)]).unwrap();
+        let json = serde_json::to_value(&result.document).unwrap();
+        let lp = &json["children"][1]["children"][0]["children"][0]["children"][0];
+        assert_eq!(lp["id"], "t1-n3");
+        assert_eq!(lp["body"]["children"][0]["target_id"], "t1-n3");
+
+        let code = "fn g() -> u8 { if a { return 0; } 1 }";
+        let tree = crate::parse_rust_code_with_options(code, r#"{"interface_nodes": true}"#);
+        let result = merge(vec![MergeInput::Tree(tree.clone()), MergeInput::Tree(tree)]).unwrap();
+        let json = serde_json::to_value(&result.document).unwrap();
+        for function in [&json["children"][0]["children"][0], &json["children"][1]["children"][0]] {
+            let body = &function["children"][0]["children"];
+            let exits = serde_json::json!([body[0]["then_block"]["children"][0]["id"], body[1]["id"]]);
+            assert_eq!(function["children"][1]["returned_by"], exits);
+        }
+        assert!(json["children"][1]["children"][0]["children"][1]["returned_by"][0].as_str().unwrap().starts_with("t1-"));
+    }
+
+    #[test]
+    fn invalid_tree_is_rejected() {
+        let err = merge(vec![MergeInput::Tree("{\"type\": \"if\"}".into())]).unwrap_err();
+        assert!(err.starts_with("Tree 0 is invalid"));
+        assert!(parse_inputs("{}").is_err());
+    }
+}
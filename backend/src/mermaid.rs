@@ -0,0 +1,106 @@
+//! Mermaidのフローチャートへの書き出し
+//! Mermaidを描画できるドキュメントツール（GitHub、GitLab、mdBookなど）にそのまま埋め込めるよう、
+//! GraphMLと同じく、ノードを頂点、順次・分岐・反復のつながりを辺にします。Blockはサブグラフとして書き出します。
+
+use std::fmt::Write;
+
+use crate::graphml::{Edge, collect_edges};
+use crate::model::PadNode;
+use crate::visit::{VisitContext, Visitor, walk};
+
+/// 頂点になるノードかどうか（Sequenceと文書は順次のつながりとして表します）
+fn is_vertex(node: &PadNode) -> bool {
+    !matches!(node, PadNode::Sequence { .. } | PadNode::Document { .. })
+}
+
+/// 引用符で囲んだラベルの中で特別な意味を持つ文字を、Mermaidの実体参照に置き換えます
+fn escape_label(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("#quot;"),
+            '#' => out.push_str("#35;"),
+            '<' => out.push_str("#lt;"),
+            '>' => out.push_str("#gt;"),
+            '\n' => out.push(' '),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// 頂点とサブグラフを書き出すビジター
+struct NodeWriter {
+    out: String,
+    indent: usize,
+}
+
+impl NodeWriter {
+    fn line(&mut self, text: &str) {
+        let _ = writeln!(self.out, "{:width$}{}", "", text, width = self.indent * 4);
+    }
+}
+
+impl Visitor for NodeWriter {
+    fn enter(&mut self, node: &PadNode, _ctx: &VisitContext) -> bool {
+        if !is_vertex(node) {
+            return true;
+        }
+        let id = &node.meta().id;
+        let label = escape_label(node.display_text().unwrap_or_default());
+        // 分岐はひし形、反復は六角形、それ以外は四角形にします
+        let line = match node {
+            PadNode::Block { .. } => format!("subgraph {}[\"{}\"]", id, label),
+            PadNode::If { .. } | PadNode::Match { .. } => format!("{}{{\"{}\"}}", id, label),
+            PadNode::Loop { .. } => format!("{}{{{{\"{}\"}}}}", id, label),
+            _ => format!("{}[\"{}\"]", id, label),
+        };
+        self.line(&line);
+        if let PadNode::Block { .. } = node {
+            self.indent += 1;
+        }
+        true
+    }
+
+    fn leave(&mut self, node: &PadNode, _ctx: &VisitContext) {
+        if let PadNode::Block { .. } = node {
+            self.indent -= 1;
+            self.line("end");
+        }
+    }
+}
+
+/// 辺1本分の行（Blockから子への辺は、サブグラフの入れ子で表すため書き出しません）
+fn edge_line(edge: &Edge) -> Option<String> {
+    let arrow = match (edge.kind, edge.branch, &edge.pattern) {
+        ("child", ..) => return None,
+        ("back", ..) => "-.->".to_string(),
+        (_, Some(true), _) => "-->|true|".to_string(),
+        (_, Some(false), _) => "-->|false|".to_string(),
+        (_, _, Some(pattern)) => format!("-->|\"{}\"|", escape_label(pattern)),
+        _ => "-->".to_string(),
+    };
+    Some(format!("{} {} {}", edge.source, arrow, edge.target))
+}
+
+/// ツリー全体をMermaidのフローチャート（`flowchart TD`）にします
+/// ルートがDocumentの場合は、そのタイトルを先頭のフロントマター（`---` で囲んだYAML）に書き出します。
+pub fn to_mermaid(root: &PadNode) -> String {
+    let mut writer = NodeWriter { out: String::new(), indent: 1 };
+    walk(root, &mut writer);
+    let mut edges = Vec::new();
+    collect_edges(root, &mut edges);
+
+    let mut out = String::new();
+    if let PadNode::Document { title, .. } = root {
+        // YAMLの二重引用符の文字列として書き出します
+        let title = title.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', " ");
+        let _ = write!(out, "---\ntitle: \"{}\"\n---\n", title);
+    }
+    out.push_str("flowchart TD\n");
+    out.push_str(&writer.out);
+    for line in edges.iter().filter_map(edge_line) {
+        let _ = writeln!(out, "    {}", line);
+    }
+    out
+}
@@ -0,0 +1,86 @@
+//! 関数ごとの複雑さの指標
+//! 構築したツリーから、関数ごとに循環的複雑度・入れ子の最大の深さ・文の数・ループの数を求めます。
+//! 文の数は構造上の警告（`long_function`）と同じく、Sequenceを除くノードの数です。
+
+use serde::Serialize;
+
+use crate::model::{BlockKind, FunctionMetrics, PadNode, Span};
+
+/// `analyze` の結果
+#[derive(Debug, Serialize)]
+pub struct MetricsReport {
+    /// 関数ごとの指標（行きがけ順）
+    pub functions: Vec<FunctionReport>,
+}
+
+/// 1つの関数の指標
+#[derive(Debug, Serialize)]
+pub struct FunctionReport {
+    pub function: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+    #[serde(flatten)]
+    pub metrics: FunctionMetrics,
+}
+
+/// 関数のBlockの指標を求めます
+pub fn measure(function: &PadNode) -> FunctionMetrics {
+    let mut metrics = FunctionMetrics { cyclomatic: 1, ..FunctionMetrics::default() };
+    visit(function, 0, &mut metrics);
+    metrics
+}
+
+fn visit(node: &PadNode, depth: usize, metrics: &mut FunctionMetrics) {
+    for child in node.children() {
+        if let PadNode::Block { kind: BlockKind::Function, .. } = child {
+            continue;
+        }
+        if !matches!(child, PadNode::Sequence { .. }) {
+            metrics.statements += 1;
+        }
+        let decisions = match child {
+            PadNode::If { .. } => 1,
+            PadNode::Loop { .. } => {
+                metrics.loops += 1;
+                1
+            }
+            PadNode::Match { arms, .. } => arms.len().saturating_sub(1),
+            _ => {
+                visit(child, depth, metrics);
+                continue;
+            }
+        };
+        metrics.cyclomatic += decisions;
+        metrics.max_nesting = metrics.max_nesting.max(depth + 1);
+        visit(child, depth + 1, metrics);
+    }
+}
+
+/// ツリーのすべての関数のBlockに指標を付けます
+pub fn apply_metrics(node: &mut PadNode) {
+    if let PadNode::Block { kind: BlockKind::Function, .. } = node {
+        let measured = measure(node);
+        if let PadNode::Block { metrics, .. } = node {
+            *metrics = Some(measured);
+        }
+    }
+    for child in node.children_mut() {
+        apply_metrics(child);
+    }
+}
+
+/// ツリーのすべての関数の指標を集めます
+pub fn report(root: &PadNode) -> MetricsReport {
+    let mut functions = Vec::new();
+    collect(root, &mut functions);
+    MetricsReport { functions }
+}
+
+fn collect(node: &PadNode, functions: &mut Vec<FunctionReport>) {
+    if let PadNode::Block { kind: BlockKind::Function, label, .. } = node {
+        functions.push(FunctionReport { function: label.clone(), span: node.meta().span, metrics: measure(node) });
+    }
+    for child in node.children() {
+        collect(child, functions);
+    }
+}
@@ -1,33 +1,820 @@
-use serde::{Serialize};
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::profile::Profile;
 
 /// PAD（Problem Analysis Diagram）の各ノードを表すデータ構造
 /// Rustのコード解析結果はこの構造体のツリーとして表現され、フロントエンドにJSONとして送信されます。
-#[derive(Serialize)]
+/// フロントエンドで作成・保存されたJSONを読み込めるよう、デシリアライズにも対応しています。
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum PadNode {
     /// 順次処理（Sequence）: 複数の処理が上から順に実行されることを表します
-    Sequence { children: Vec<PadNode> },
+    /// `skipped` は `// pad:skip` で除外された文の数です（除外がない場合は出力しません）
+    /// `is_unsafe` は `unsafe` ブロックや `unsafe fn` の本体の場合に true になります
+    Sequence {
+        children: Vec<PadNode>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        skipped: Option<usize>,
+        #[serde(default, skip_serializing_if = "is_false")]
+        is_unsafe: bool,
+        #[serde(flatten)]
+        meta: NodeMeta,
+        /// ルートのSequenceにだけ付く、文書全体に関する情報
+        #[serde(flatten)]
+        info: Option<Box<DocumentInfo>>,
+    },
+
+    /// 文書（Document）: `document` オプション指定時のルートで、ファイル単位の情報を持ちます
+    /// `source_name` は呼び出し元が指定したファイル名、`created_at` は作成日時（ISO 8601、UTC）です。
+    /// `created_at` は `deterministic` オプション指定時は出力しません。
+    Document {
+        title: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        source_name: Option<String>,
+        generated_by: String,
+        backend_version: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        created_at: Option<String>,
+        children: Vec<PadNode>,
+        #[serde(flatten)]
+        meta: NodeMeta,
+        #[serde(flatten)]
+        info: Option<Box<DocumentInfo>>,
+    },
 
     /// ブロック（Block）: 関数定義など、名前付きの処理の塊を表します
-    Block { label: String, children: Vec<PadNode> },
+    /// implブロック内のメソッドでは `self_type`（実装対象の型）と `trait_name`（トレイト実装の場合）を持ちます
+    Block {
+        kind: BlockKind,
+        label: String,
+        children: Vec<PadNode>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        full_label: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        trait_name: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        self_type: Option<String>,
+        /// 関数・メソッドのBlockの場合、可視性と戻り値の型を含むシグネチャ（`pub fn bar(&mut self, a: u8) -> u8`）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        signature: Option<String>,
+        /// `#[test]`（`#[tokio::test]` なども含む）が付いたテスト関数の場合に true
+        #[serde(default, skip_serializing_if = "is_false")]
+        is_test: bool,
+        /// `async fn` の関数・メソッドの場合に true
+        #[serde(default, skip_serializing_if = "is_false")]
+        is_async: bool,
+        /// `unsafe fn` の関数・メソッドと、`unsafe { ... }` のBlockの場合に true
+        #[serde(default, skip_serializing_if = "is_false")]
+        is_unsafe: bool,
+        /// `group_impls_by_type` オプションで複数のimplブロックをまとめた場合、メソッドが書かれていた元のimplブロックの位置
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        impl_span: Option<Span>,
+        /// 関数の複雑さの指標。`metrics` オプション指定時の関数のBlockのみ
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        metrics: Option<FunctionMetrics>,
+        /// ファイルのBlock（`parse_rust_project`）の場合、そのファイルのモジュールのパス（`crate::net::http` など）
+        /// インラインのモジュールを解いて並べたアイテムのBlockの場合は、そのモジュールのパス（`outer::inner` など）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        module_path: Option<String>,
+        #[serde(flatten)]
+        meta: NodeMeta,
+    },
 
     /// 条件分岐（If/Selection）: 条件によって処理が分岐する構造を表します
     If {
         condition: String,                 // 条件式の内容（"x > 0" など）
         then_block: Box<PadNode>,          // 条件が真の場合の処理（右上に配置）
         else_block: Option<Box<PadNode>>,  // 条件が偽の場合の処理（右下に配置、省略可能）
+        #[serde(skip_serializing_if = "Option::is_none")]
+        full_label: Option<String>,        // `// pad:label:` で条件を置き換えた場合の元の条件式
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        components: Vec<String>,           // let-chain（`if let Some(a) = x && a.ok`）の場合、`&&` で区切った各条件
+        #[serde(default, skip_serializing_if = "is_false")]
+        compile_time: bool,                // 条件が `cfg!(...)`（またはそれを含む `&&` の連鎖）で、コンパイル時に決まる切り替えの場合に true
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cfg_predicate: Option<String>,     // `cfg!(...)` の条件（複数ある場合は `all(...)` にまとめます）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        runtime_condition: Option<String>, // `cfg!(...)` と `&&` で結ばれた、実行時に判定する残りの条件
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        lowered_from: Option<String>,      // 他の構造を変換して作ったIfの場合、元の構造（`match_as_if` オプションでは "match"、let-elseでは "let_else"、`desugar_try` オプションの `?` では "try"、`expand_macros` オプションでは "assert" などのマクロ名）
+        #[serde(flatten)]
+        meta: NodeMeta,
     },
 
     /// 反復処理（Loop/Repetition）: 条件を満たす間、処理を繰り返す構造を表します
-    /// 現在は前判定ループ（while, for）に対応しています
+    /// 前判定ループ（while, for）、無限ループ（loop）、後判定ループ（loop 末尾の `if cond { break }`）に対応しています
     Loop {
+        kind: LoopKind,     // ループの種類
         condition: String, // ループの継続条件
         body: Box<PadNode>, // 繰り返される処理本体
+        #[serde(skip_serializing_if = "Option::is_none")]
+        full_label: Option<String>, // `// pad:label:` で条件を置き換えた場合の元の条件式
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        components: Vec<String>, // let-chain（`while let Some(x) = it.next() && x > 0`）の場合、`&&` で区切った各条件
+        #[serde(skip_serializing_if = "Option::is_none")]
+        break_condition: Option<String>, // 後判定ループの場合、末尾の `if cond { break }` の元の条件式
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        results: Vec<String>, // `break value` でループが返す値の式（breakごとに列挙）
+        #[serde(skip_serializing_if = "Option::is_none")]
+        binding: Option<String>, // `let x = loop { ... };` の場合、ループの値を受け取る変数
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        loop_label: Option<String>, // `'outer: loop { ... }` の場合のループのラベル
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        modifies: Vec<String>, // 各反復で変更される変数（ループの中で宣言された変数とループ変数を除きます）
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        loop_variables: Vec<String>, // forループのパターンが束縛する変数
+        #[serde(flatten)]
+        meta: NodeMeta,
+    },
+
+    /// ループ・ラベル付きブロックの脱出（break）: `target` はラベル付きbreakの対象ラベル（`'outer` など）、`value` はbreakが返す値です
+    /// `target_id` は脱出先のLoopまたはラベル付きBlockのノードidです
+    Break {
+        label: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        target: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        value: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        target_id: Option<String>,
+        #[serde(flatten)]
+        meta: NodeMeta,
+    },
+
+    /// 次の反復へ（continue）: `target` はラベル付きcontinueの対象ラベル、`target_id` は対象のLoopのノードidです
+    Continue {
+        label: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        target: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        target_id: Option<String>,
+        #[serde(flatten)]
+        meta: NodeMeta,
+    },
+
+    /// ジェネレーター・コルーチンの中断（yield）: `value` は `yield` が返す値です
+    Yield {
+        label: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        value: Option<String>,
+        #[serde(flatten)]
+        meta: NodeMeta,
+    },
+
+    /// 関数・クロージャからの戻り（return）: `value` は戻り値の式、`scope` は抜ける範囲です
+    Return {
+        label: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        value: Option<String>,
+        scope: ExitScope,
+        #[serde(flatten)]
+        meta: NodeMeta,
+    },
+
+    /// 多分岐（Match/Selection）: `match` 式の各アームを並列の分岐として表します
+    Match {
+        scrutinee: String,   // 照合対象の式
+        arms: Vec<MatchArm>, // アーム（パターン、ガード、本体）の並び
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        enum_type: Option<String>, // 照合対象が入力の中で定義されたenumで、型が構文から決まる場合のenumの名前
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        covered: Vec<String>, // `enum_type` のバリアントのうち、アームで明示的に扱うもの（宣言順）
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        uncovered_via_wildcard: Vec<String>, // `enum_type` のバリアントのうち、`_` などのワイルドカードで扱われるもの（宣言順）
+        #[serde(flatten)]
+        meta: NodeMeta,
     },
 
     /// 単純な命令（Command/Process）: "let x = 1;" や関数呼び出しなどの単一の処理文
-    Command { label: String },
+    /// `full_label` は `// pad:label:` でラベルを置き換えた場合の元のラベルです
+    /// `foreign` は extern ブロックで宣言された関数（FFI）を呼び出している場合に true になります
+    /// `recursive` は関数のインライン展開で再帰呼び出しを検出し、展開を打ち切った場合に true になります
+    /// `assignment` は代入文（`x = 1`, `total += n`）の場合に、代入先・演算子・値を分けて持ちます
+    /// `category` は文の形から判定した処理の種類で、フロントエンドが箱の色分けに使います
+    /// `receiver` は `interface_nodes` オプションの入力の一覧で、メソッドのレシーバ（`&mut self` など）の場合に true になります
+    /// `scope` は `return` または `?` を含む文の場合に、その早期脱出で抜ける範囲（関数全体か、内側のクロージャか）です
+    /// `fallback` は専用のノードに変換できない構文（`try` ブロックなど）を、ソースのままラベルにした場合に true になります
+    Command {
+        label: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        full_label: Option<String>,
+        #[serde(default, skip_serializing_if = "is_false")]
+        foreign: bool,
+        #[serde(default, skip_serializing_if = "is_false")]
+        recursive: bool,
+        #[serde(default, skip_serializing_if = "is_false")]
+        receiver: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        scope: Option<ExitScope>,
+        #[serde(default, skip_serializing_if = "is_false")]
+        fallback: bool,
+        #[serde(skip)]
+        fallback_kind: Option<&'static str>, // `fallback` の場合の構文のsynの種類（ルートの `fallback_report` の集計用）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        assignment: Option<Assignment>,
+        #[serde(default)]
+        category: CommandCategory,
+        #[serde(flatten)]
+        meta: NodeMeta,
+    },
 
     /// エラー（Error）: 解析不能な構文やエラー発生時用
-    Error { message: String },
+    /// 構文エラーの場合は、位置（`line`・`column`。どちらも1始まり）と、該当箇所の前後の行を抜き出した `frame` を持ちます
+    /// UTF-8として不正な入力の場合は、さらに不正なバイトの位置 `byte_offset` を持ちます
+    Error {
+        message: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        line: Option<usize>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        column: Option<usize>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        frame: Option<String>,
+        /// 入力のバイト列がUTF-8として不正な場合の、最初の不正なバイトの位置（0始まり）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        byte_offset: Option<usize>,
+        /// エラーの種類（構文エラーなど、原因がわかる場合）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        kind: Option<ErrorKind>,
+        #[serde(flatten)]
+        meta: NodeMeta,
+    },
+}
+
+/// すべてのノード種別名（JSONの `type` タグの値）
+/// 新しいバリアントを追加したら、ここと `PadNode::kind_name` の両方に追加してください。
+pub const NODE_KINDS: &[&str] =
+    &["sequence", "document", "block", "if", "loop", "break", "continue", "yield", "return", "match", "command", "error"];
+
+/// すべてのノードに共通する付加情報
+/// いずれも省略可能で、値がない場合はJSONに出力しません。
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct NodeMeta {
+    /// ツリー内で一意なノードのid（警告などからノードを参照するために使います）
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+    /// ノードに対応するソース上の位置（フロントエンドでノードから元のソースの行へ移動するために使います）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+    /// ラベルの表示幅の目安（全角文字を2、複数行の場合は最長の行）。`width_hints` オプション有効時のみ
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width_hint: Option<usize>,
+    /// `#[cfg(...)]` が付いている場合、その条件（`feature = "gpu"` など。複数ある場合は `all(...)` にまとめます）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cfg: Option<String>,
+    /// パニックしうる呼び出し（`unwrap` など）を含む場合に true
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub may_panic: bool,
+    /// パニックしうる箇所の理由（`unwrap`、`expect`、`unwrap_unchecked`、`index`）。出現順に重複を含めて並べます
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub panic_reasons: Vec<String>,
+    /// 標準ライブラリの関数・メソッドを呼び出している場合、そのドキュメントのURL（`std-docs` フィーチャー有効時のみ）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc_url: Option<String>,
+    /// 手順番号（`1`、`1.2` など）。`step_numbers` オプション有効時のみ
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub step: Option<String>,
+    /// ラベル（Commandのラベル、If・Loopの条件、matchの対象）の字句の範囲。`label_tokens` オプション有効時のみ
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tokens: Vec<LabelToken>,
+    /// 定数の条件（`if false`、`while 1 > 2` など）により、決して実行されない分岐・ループ本体の場合に true
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub dead: bool,
+    /// 定数の条件により、必ず実行される分岐（`if true` の真の側など）と、条件が常に真のループの場合に true
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub always_runs: bool,
+    /// 条件が常に偽のため、本体が一度も実行されないループ（`while false`）の場合に true
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub never_runs: bool,
+    /// 同じ構造の部分木のグループに属する部分木の根の場合、そのグループの番号（ルートの `clones` の位置）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clone_group: Option<usize>,
+    /// 関数の出口となるノード（`return` と、戻り値の型を持つ関数の末尾の式＝暗黙の戻り値）の場合に true
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub returns: bool,
+    /// 関数の出力（`output`）のノードの場合、その関数の戻り値を返すノードのid（行きがけ順）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub returned_by: Vec<String>,
+    /// `collapse_depth` オプションで中身を省略した制御構造の場合に true（概略表示の `outline` と同じ形式です）
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub collapsed: bool,
+    /// `collapsed` の場合、省略した直下の子ノード（Sequenceの中身を含む）の数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub child_count: Option<usize>,
+    /// 2つの版の差分（`diff_rust_code`）で付ける、変更前の版と比べたノードの状態
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff: Option<DiffStatus>,
+    /// ノードに付けたコメント（文の前の行コメント・行末コメント、関数のドキュメントコメント）。`comments` オプション有効時のみ
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    /// 同じ入力で定義された関数を呼び出している場合、呼び出し先の関数名（`helper`、`Counter::new` など。出現順）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub calls: Vec<String>,
+    /// `calls` の各関数のBlockのid（`calls` と同じ順）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub call_targets: Vec<String>,
+    /// ノード自身の式（Commandの文、If・Loopの条件、matchの対象）に含まれる `.await`（中断しうる箇所）の数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub awaits: Option<usize>,
+    /// 変換時に集めた、呼び出している関数のパス（`ids::link_calls` で `calls` に絞り込む前のもの）
+    #[serde(skip)]
+    pub call_paths: Vec<String>,
+}
+
+/// 変更前の版と比べたノードの状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffStatus {
+    /// 変更後の版で追加されたノード
+    Added,
+    /// 変更後の版で削除されたノード（変更前の位置に残します）
+    Removed,
+    /// 自身のテキストまたは子孫が変わったノード
+    Modified,
+    /// 子孫を含めて変わっていないノード
+    Unchanged,
+}
+
+/// 関数の複雑さの指標（内側で定義された関数は含みません）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FunctionMetrics {
+    /// 循環的複雑度（1 + if・ループの数 + matchのアーム数から1を引いた数の合計）
+    pub cyclomatic: usize,
+    /// 制御構造（if / ループ / match）の入れ子の最大の深さ
+    pub max_nesting: usize,
+    /// 本体の文の数（Sequenceを除くノードの数）
+    pub statements: usize,
+    /// ループの数
+    pub loops: usize,
+}
+
+/// ラベル中の字句1つ分の範囲（UTF-16のコードユニット単位で、開始を含み終了を含みません）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LabelToken {
+    pub start: usize,
+    pub end: usize,
+    pub kind: TokenKind,
+}
+
+/// 字句の種類（シンタックスハイライト用）
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenKind {
+    Keyword,
+    Ident,
+    /// 数値・文字・真偽値のリテラル
+    Literal,
+    /// 文字列リテラル（バイト列・生文字列を含みます）
+    String,
+    Punct,
+    Comment,
+}
+
+/// 文書（ルートノード）全体に関する情報
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DocumentInfo {
+    /// 構造上の警告の一覧（問題がない場合は空の配列）
+    pub warnings: Vec<Warning>,
+    /// 全ノードの `width_hint` の最大値
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_width_hint: Option<usize>,
+    /// オプション（`include` / `exclude` など）の指定で図から除外した関数・アイテムの数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filtered: Option<usize>,
+    /// 図に表示できるトップレベルのアイテムの総数（`item_offset` / `item_limit` でページに分けた場合）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_items: Option<usize>,
+    /// 構文エラーの一覧（`recover` オプションで解析できたアイテムだけを図にした場合。位置の順）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<Diagnostic>,
+    /// 処理時間などの計測結果（`profile` オプション指定時）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<Profile>,
+    /// 同じ構造の部分木（コピー＆ペーストされた処理）のグループ（`clones` オプション指定時）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub clones: Vec<CloneGroup>,
+    /// 専用のノードに変換できず、Commandとして表示した構文の、synの種類ごとの件数と位置の例
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fallback_report: BTreeMap<String, FallbackEntry>,
+}
+
+/// `fallback_report` の構文の種類1つ分
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FallbackEntry {
+    pub count: usize,
+    /// 出現した位置の例（出現順に最大3件）
+    pub examples: Vec<Span>,
+}
+
+/// 同じ構造を持つ部分木のグループ
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CloneGroup {
+    /// 各部分木の根のノードid（行きがけ順）
+    pub nodes: Vec<String>,
+    /// 部分木1つあたりのノード数
+    pub size: usize,
+}
+
+/// 構文エラー1件分の情報（位置はどちらも1始まりで、`frame` は該当箇所の前後の行の抜粋です）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+    #[serde(default)]
+    pub kind: ErrorKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frame: Option<String>,
+}
+
+/// エラーの種類（メッセージの文言に頼らずにプログラムから扱えるよう、別に持ちます）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// 構文エラー
+    #[default]
+    Syntax,
+    /// 入れ子が `max_depth` より深い
+    Nesting,
+    /// 入力がUTF-8として不正
+    Encoding,
+    /// 解析オプションのJSONが不正
+    Options,
+    /// 図に表示できるアイテムがない
+    Empty,
+    /// 指定した名前の関数がない（`parse_function_by_name`）
+    NotFound,
+    /// 指定した名前に複数の関数が該当する（`parse_function_by_name`）
+    Ambiguous,
+}
+
+/// `match` のアーム1つ分
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MatchArm {
+    pub pattern: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub guard: Option<String>,
+    pub body: PadNode,
+}
+
+/// 代入文の構成要素
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Assignment {
+    /// 代入先（`self.items[i].count` など）
+    pub target: String,
+    /// 代入演算子（`=`, `+=`, `<<=` など）
+    pub op: String,
+    /// 代入する値の式
+    pub value: String,
+}
+
+/// ソース上の位置（行は1始まり、桁は0始まりの文字数）
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Span {
+    pub start_line: usize,
+    pub start_col: usize,
+    pub end_line: usize,
+    pub end_col: usize,
+}
+
+/// 構造上の警告（エラーではないが、フロントエンドで利用者に知らせたい指摘）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Warning {
+    /// 警告の種類を表す固定の識別子（`deep_nesting` など）
+    pub code: String,
+    pub message: String,
+    /// 警告の対象ノードのid
+    pub node_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+}
+
+/// ブロックの種類
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockKind {
+    /// 関数・メソッドの定義
+    Function,
+    /// implブロック（メソッドのまとまり）
+    Impl,
+    /// トレイトの定義（既定の実装を持つメソッドのまとまり）
+    Trait,
+    /// `extern "C" { ... }` の宣言
+    Extern,
+    /// `macro_rules!` の定義
+    Macro,
+    /// `// pad:group:` で作られたグループ
+    Group,
+    /// スクリプトモードでのトップレベルの文の並び
+    Script,
+    /// インライン展開された関数呼び出し（`expand_closures` オプション指定時は、展開したクロージャを引数に取る呼び出しも含みます）
+    Call,
+    /// ラベル付きブロック（`'done: { ... }`）。ラベルは `label` に入ります
+    Labeled,
+    /// `async { ... }` ブロック
+    Async,
+    /// ジェネレーター（`gen { ... }` ブロックや、`yield` を含むコルーチンのクロージャ）
+    Generator,
+    /// 変数に束縛された、または単独の式として書かれたクロージャ（`expand_closures` オプション指定時）
+    Closure,
+    /// `thread::spawn` / `tokio::spawn` などで起動され、呼び出し元と並行して実行される処理
+    Concurrent,
+    /// `use` 宣言の一覧（`imports` オプション指定時）
+    Imports,
+    /// インラインのモジュール（`mod name { ... }`。トップレベルでは `group_by` が `module` の場合）
+    Module,
+    /// 関数の入力（`inputs`）・出力（`output`）の一覧（`interface_nodes` オプション指定時）。どちらかは `label` に入ります
+    Interface,
+    /// 複数ファイルの入力（`parse_rust_project`）の1ファイル。ファイルのパスは `label` に入ります
+    File,
+    /// `unsafe { ... }` ブロック
+    Unsafe,
+}
+
+/// 命令（Command）の処理の種類
+/// フロントエンドが色を割り当てられるよう、値はここに列挙したものに限ります。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CommandCategory {
+    /// 変数の宣言（`let`）
+    Declaration,
+    /// 代入（`x = 1`、`total += n`）
+    Assignment,
+    /// 関数・メソッドの呼び出し
+    Call,
+    /// 入出力（`println!` などのマクロ、`fs::*`、標準入出力）
+    Io,
+    /// 関数・プログラムの終了（`return`、`process::exit`、`panic!` など）
+    Exit,
+    /// 算術演算の式
+    Arithmetic,
+    /// 上記のいずれでもないもの
+    #[default]
+    Other,
+}
+
+/// `return` / `?` で抜ける範囲
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExitScope {
+    /// 関数全体から抜けます
+    Function,
+    /// 展開したクロージャ・asyncブロック（`thread::spawn` に渡したクロージャなど）の本体から抜けるだけで、関数は続きます
+    Closure,
+}
+
+/// ループの種類
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoopKind {
+    /// `while cond { ... }`（前判定）
+    While,
+    /// `for pat in expr { ... }`（前判定）
+    For,
+    /// `loop { ... }`（無限ループ。break で抜ける）
+    Loop,
+    /// `loop { ...; if cond { break } }`（後判定。Rustにはdo-whileがないための慣用句）
+    DoWhile,
+}
+
+impl PadNode {
+    /// 順次処理ノードを作成します
+    pub fn sequence(children: Vec<PadNode>) -> Self {
+        PadNode::Sequence { children, skipped: None, is_unsafe: false, meta: NodeMeta::default(), info: None }
+    }
+
+    /// 条件分岐ノードを作成します
+    pub fn if_node(condition: impl Into<String>, then_block: PadNode, else_block: Option<PadNode>) -> Self {
+        PadNode::If {
+            condition: condition.into(),
+            then_block: Box::new(then_block),
+            else_block: else_block.map(Box::new),
+            full_label: None,
+            components: Vec::new(),
+            compile_time: false,
+            cfg_predicate: None,
+            runtime_condition: None,
+            lowered_from: None,
+            meta: NodeMeta::default(),
+        }
+    }
+
+    /// エラーノードを作成します
+    pub fn error(message: impl Into<String>) -> Self {
+        PadNode::Error {
+            message: message.into(),
+            line: None,
+            column: None,
+            frame: None,
+            byte_offset: None,
+            kind: None,
+            meta: NodeMeta::default(),
+        }
+    }
+
+    /// 種類のわかっているエラーノードを作成します
+    pub fn error_of(kind: ErrorKind, message: impl Into<String>) -> Self {
+        let mut node = PadNode::error(message);
+        if let PadNode::Error { kind: k, .. } = &mut node {
+            *k = Some(kind);
+        }
+        node
+    }
+
+    /// 名前付きのブロックノードを作成します
+    pub fn block(kind: BlockKind, label: impl Into<String>, children: Vec<PadNode>) -> Self {
+        PadNode::Block {
+            kind,
+            label: label.into(),
+            children,
+            full_label: None,
+            trait_name: None,
+            self_type: None,
+            signature: None,
+            is_test: false,
+            is_async: false,
+            is_unsafe: false,
+            impl_span: None,
+            metrics: None,
+            module_path: None,
+            meta: NodeMeta::default(),
+        }
+    }
+
+    /// 反復処理ノードを作成します
+    pub fn loop_node(kind: LoopKind, condition: impl Into<String>, body: PadNode) -> Self {
+        PadNode::Loop {
+            kind,
+            condition: condition.into(),
+            body: Box::new(body),
+            full_label: None,
+            components: Vec::new(),
+            break_condition: None,
+            results: Vec::new(),
+            binding: None,
+            loop_label: None,
+            modifies: Vec::new(),
+            loop_variables: Vec::new(),
+            meta: NodeMeta::default(),
+        }
+    }
+
+    /// 単純な命令ノードを作成します
+    pub fn command(label: impl Into<String>) -> Self {
+        PadNode::Command {
+            label: label.into(),
+            full_label: None,
+            foreign: false,
+            recursive: false,
+            receiver: false,
+            scope: None,
+            fallback: false,
+            fallback_kind: None,
+            assignment: None,
+            category: CommandCategory::Other,
+            meta: NodeMeta::default(),
+        }
+    }
+
+    /// ノード共通の付加情報を返します
+    pub fn meta(&self) -> &NodeMeta {
+        match self {
+            PadNode::Sequence { meta, .. }
+            | PadNode::Document { meta, .. }
+            | PadNode::Block { meta, .. }
+            | PadNode::If { meta, .. }
+            | PadNode::Loop { meta, .. }
+            | PadNode::Break { meta, .. }
+            | PadNode::Continue { meta, .. }
+            | PadNode::Yield { meta, .. }
+            | PadNode::Return { meta, .. }
+            | PadNode::Match { meta, .. }
+            | PadNode::Command { meta, .. }
+            | PadNode::Error { meta, .. } => meta,
+        }
+    }
+
+    /// ノード共通の付加情報を変更可能な参照で返します
+    pub fn meta_mut(&mut self) -> &mut NodeMeta {
+        match self {
+            PadNode::Sequence { meta, .. }
+            | PadNode::Document { meta, .. }
+            | PadNode::Block { meta, .. }
+            | PadNode::If { meta, .. }
+            | PadNode::Loop { meta, .. }
+            | PadNode::Break { meta, .. }
+            | PadNode::Continue { meta, .. }
+            | PadNode::Yield { meta, .. }
+            | PadNode::Return { meta, .. }
+            | PadNode::Match { meta, .. }
+            | PadNode::Command { meta, .. }
+            | PadNode::Error { meta, .. } => meta,
+        }
+    }
+
+    /// 図の箱に表示するテキスト（ラベル、条件式、エラーメッセージ）を返します
+    pub fn display_text(&self) -> Option<&str> {
+        match self {
+            PadNode::Sequence { .. } => None,
+            PadNode::Document { title, .. } => Some(title),
+            PadNode::Block { label, .. }
+            | PadNode::Break { label, .. }
+            | PadNode::Continue { label, .. }
+            | PadNode::Yield { label, .. }
+            | PadNode::Return { label, .. }
+            | PadNode::Command { label, .. } => Some(label),
+            PadNode::If { condition, .. } | PadNode::Loop { condition, .. } => Some(condition),
+            PadNode::Match { scrutinee, .. } => Some(scrutinee),
+            PadNode::Error { message, .. } => Some(message),
+        }
+    }
+
+    /// JSONの `type` タグと同じノード種別名を返します
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            PadNode::Sequence { .. } => "sequence",
+            PadNode::Document { .. } => "document",
+            PadNode::Block { .. } => "block",
+            PadNode::If { .. } => "if",
+            PadNode::Loop { .. } => "loop",
+            PadNode::Break { .. } => "break",
+            PadNode::Continue { .. } => "continue",
+            PadNode::Yield { .. } => "yield",
+            PadNode::Return { .. } => "return",
+            PadNode::Match { .. } => "match",
+            PadNode::Command { .. } => "command",
+            PadNode::Error { .. } => "error",
+        }
+    }
+
+    /// 直下の子ノードを返します（then/else節やループ本体を含む）
+    pub fn children(&self) -> Vec<&PadNode> {
+        match self {
+            PadNode::Sequence { children, .. } | PadNode::Document { children, .. } | PadNode::Block { children, .. } => {
+                children.iter().collect()
+            }
+            PadNode::If { then_block, else_block, .. } => {
+                let mut children = vec![&**then_block];
+                children.extend(else_block.as_deref());
+                children
+            }
+            PadNode::Loop { body, .. } => vec![&**body],
+            PadNode::Match { arms, .. } => arms.iter().map(|arm| &arm.body).collect(),
+            PadNode::Break { .. }
+            | PadNode::Continue { .. }
+            | PadNode::Yield { .. }
+            | PadNode::Return { .. }
+            | PadNode::Command { .. }
+            | PadNode::Error { .. } => Vec::new(),
+        }
+    }
+
+    /// 直下の子ノードを変更可能な参照で返します（then/else節やループ本体を含む）
+    pub fn children_mut(&mut self) -> Vec<&mut PadNode> {
+        match self {
+            PadNode::Sequence { children, .. } | PadNode::Document { children, .. } | PadNode::Block { children, .. } => {
+                children.iter_mut().collect()
+            }
+            PadNode::If { then_block, else_block, .. } => {
+                let mut children = vec![&mut **then_block];
+                children.extend(else_block.as_deref_mut());
+                children
+            }
+            PadNode::Loop { body, .. } => vec![&mut **body],
+            PadNode::Match { arms, .. } => arms.iter_mut().map(|arm| &mut arm.body).collect(),
+            PadNode::Break { .. }
+            | PadNode::Continue { .. }
+            | PadNode::Yield { .. }
+            | PadNode::Return { .. }
+            | PadNode::Command { .. }
+            | PadNode::Error { .. } => Vec::new(),
+        }
+    }
+
+    /// `// pad:label:` の指定でラベル（条件式）を置き換え、元のテキストを `full_label` に残します
+    /// ラベルを持たないノード（Sequenceなど）は変更しません
+    pub fn override_label(&mut self, text: &str) {
+        match self {
+            PadNode::Command { label, full_label, .. }
+            | PadNode::If { condition: label, full_label, .. }
+            | PadNode::Loop { condition: label, full_label, .. }
+            | PadNode::Block { label, full_label, .. } => {
+                *full_label = Some(std::mem::replace(label, text.to_string()));
+            }
+            PadNode::Sequence { .. }
+            | PadNode::Document { .. }
+            | PadNode::Break { .. }
+            | PadNode::Continue { .. }
+            | PadNode::Yield { .. }
+            | PadNode::Return { .. }
+            | PadNode::Match { .. }
+            | PadNode::Error { .. } => {}
+        }
+    }
+}
+
+/// serdeの `skip_serializing_if` 用: false のフィールドは出力しません
+fn is_false(value: &bool) -> bool {
+    !*value
 }
@@ -0,0 +1,52 @@
+//! 出力JSONのキーの命名規則の変換
+//! シリアライズ済みの `serde_json::Value` を後処理し、オブジェクトのキーだけを書き換えます。
+
+use serde_json::{Map, Value};
+
+/// `then_block` を `thenBlock` のようにsnake_caseの識別子をcamelCaseに変換します
+pub fn to_camel_case(key: &str) -> String {
+    let mut result = String::with_capacity(key.len());
+    let mut upper = false;
+    for c in key.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            result.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+/// 値に含まれるすべてのオブジェクトのキーを再帰的にcamelCaseに変換します
+/// 文字列の値（`type` や `kind` の値など）はそのまま残します。
+pub fn camel_case_keys(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            let renamed: Map<String, Value> = std::mem::take(map)
+                .into_iter()
+                .map(|(key, mut child)| {
+                    camel_case_keys(&mut child);
+                    (to_camel_case(&key), child)
+                })
+                .collect();
+            *map = renamed;
+        }
+        Value::Array(items) => items.iter_mut().for_each(camel_case_keys),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_snake_case_identifiers() {
+        assert_eq!(to_camel_case("then_block"), "thenBlock");
+        assert_eq!(to_camel_case("max_width_hint"), "maxWidthHint");
+        assert_eq!(to_camel_case("type"), "type");
+    }
+}
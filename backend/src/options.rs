@@ -0,0 +1,382 @@
+use serde::Deserialize;
+
+/// JavaScript側から渡される解析オプション
+/// JSONで受け取り、省略されたフィールドは既定値になります。
+/// 名前の誤り（`max_label_lenght` など）に気付けるよう、未知のフィールドは誤りとします。
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ParseOptions {
+    /// 入力の解釈方法（ファイル全体 / 文の並び）
+    pub mode: ParseMode,
+    /// `macro_rules!` の定義をルールごとに展開せず、1つの宣言Commandにまとめます
+    pub collapse_macros: bool,
+    /// 同じ入力内で定義された関数の呼び出しを、呼び出し先の本体で置き換える（インライン展開する）深さ
+    /// 0 の場合は展開しません。
+    pub inline_depth: u8,
+    /// `loop { ...; if cond { break } }` を後判定ループ（do_while）として表示します
+    pub detect_do_while: bool,
+    /// 各ノードにラベルの表示幅の目安（`width_hint`）を付け、ルートに最大値を記録します
+    pub width_hints: bool,
+    /// 各ノードに階層的な手順番号（`step`。`1`、`1.1`、`1.2`、`2` ...）を付けます
+    pub step_numbers: bool,
+    /// Commandのラベル・条件などに、シンタックスハイライト用の字句の範囲（`tokens`）を付けます
+    pub label_tokens: bool,
+    /// 関数のBlockに複雑さの指標（`metrics`: 循環的複雑度・入れ子の最大の深さ・文の数・ループの数）を付けます
+    pub metrics: bool,
+    /// ルートをSequenceではなく、タイトルや作成日時などを持つDocumentノードにします
+    pub document: bool,
+    /// Documentのタイトル（省略した場合は `source_name` のファイル名部分）
+    pub title: Option<String>,
+    /// 入力のファイル名（Documentに記録します）
+    pub source_name: Option<String>,
+    /// 作成日時など、実行のたびに変わる値を出力しません（スナップショットテスト用）
+    pub deterministic: bool,
+    /// TikZの書き出しで、`tikzpicture` だけでなく、そのままコンパイルできるLaTeX文書を出力します
+    pub standalone: bool,
+    /// 入力とオプションが直近の呼び出しと同じ場合に、解析せずに前回の結果を返します
+    pub cache: bool,
+    /// 構文エラーがあっても、解析できたアイテムだけを図にします（ファイルモードのみ）
+    /// 壊れたアイテムはその位置のErrorノードになり、ルートの `errors` にすべての構文エラーを位置の順に記録します。
+    pub recover: bool,
+    /// 構文解析・変換・シリアライズの時間と、入力の大きさ・ノード数をルートの `profile` に記録します
+    pub profile: bool,
+    /// バイト列で受け取った入力のうちUTF-8として不正な部分を、エラーにせず U+FFFD に置き換えて解析します
+    pub lossy_utf8: bool,
+    /// 括弧の入れ子（と `else if` の連鎖）の深さの上限（既定値は 48）
+    /// 超える入力は、スタックを使い切って異常終了しないよう、解析せずに超えた位置を示すErrorノードを返します。
+    pub max_depth: usize,
+    /// 構造上の警告（`warnings`）のしきい値
+    pub lint: LintOptions,
+    /// 型エイリアス（`type Result<T> = ...;`）・定数・staticと、implブロック・トレイトの関連型を、宣言のCommandとして図に含めます（既定値は true）
+    pub declarations: bool,
+    /// ファイルの `use` 宣言を、図の先頭の "imports" というBlockにまとめて表示します
+    /// 関数の中の `use` 宣言は、その関数の中の位置に表示します。
+    pub imports: bool,
+    /// `imports` の表示で、`use std::{fs, io::Read};` のようにまとめた宣言をパスごとのCommandに展開します
+    pub expand_imports: bool,
+    /// 同じ型に対する固有実装（`impl Foo`）のブロックを、最初のブロックの位置に1つのBlockとしてまとめます
+    /// トレイト実装はまとめません。まとめた各メソッドには、元のimplブロックの位置（`impl_span`）を記録します。
+    pub group_impls_by_type: bool,
+    /// トップレベルの関数の並び順（既定値はソースの順）
+    pub order: ItemOrder,
+    /// トップレベルの関数のまとめ方（既定値はimplブロックごと。インラインのモジュールの中のアイテムはトップレベルに並べます）
+    pub group_by: GroupBy,
+    /// 同じ構造を持つ部分木（コピー＆ペーストされた処理）を検出し、ルートの `clones` に記録します
+    pub clones: bool,
+    /// `clones` で検出する部分木の最小のノード数（既定値は 5）
+    pub clone_min_size: usize,
+    /// `clones` の検出で識別子の名前の違いを無視します（変数名だけを変えた複製も検出します）
+    pub clone_ignore_names: bool,
+    /// 関数のBlockの先頭に引数の一覧（"inputs"）、末尾に戻り値の型（"output"）のBlockを置きます
+    /// "output" には、戻り値を返すノード（`return` と末尾の式。`returns` が true）のidを `returned_by` に記録します。
+    pub interface_nodes: bool,
+    /// matchを、各アームを順に判定する入れ子のIf（`lowered_from: "match"`）で表します（Matchに対応していない描画系との互換用）
+    pub match_as_if: bool,
+    /// `#[test]` の付いた関数を図に含めません
+    pub skip_tests: bool,
+    /// `#[test]` の付いた関数だけを図にします（`skip_tests` と同時には指定できません）
+    pub only_tests: bool,
+    /// 添字アクセス（`v[i]`）もパニックしうる箇所（`may_panic`）として扱います
+    pub panic_on_index: bool,
+    /// 構造体リテラルのフィールド数・呼び出しの引数の数がこの値を超える場合、1行に1つずつ並べた複数行のラベルにします
+    /// 省略した場合は折り返しません。
+    pub multiline_args: Option<usize>,
+    /// 文・条件・式のラベルを、quote!のトークン区切り（`x . len ()`）ではなくprettypleaseで整形した書き方（`x.len()`）にします
+    pub pretty_labels: bool,
+    /// Commandのラベル・If / Loopの条件・Blockのラベルの最大の文字数。超える部分は `truncation_marker` に置き換え、元のテキストを `full_label` に残します
+    /// 省略した場合は切り詰めません。
+    pub max_label_length: Option<usize>,
+    /// `max_label_length` で切り詰めたラベルの省略した位置に付ける文字列（既定値は `…`）
+    pub truncation_marker: String,
+    /// `max_label_length` で切り詰める位置（既定値は末尾）
+    pub truncation_position: TruncationPosition,
+    /// 制御構造（if / ループ / match）の入れ子の深さの上限。関数ごとに数え、上限の深さにある制御構造は中身を省略して `collapsed: true` と `child_count` を付けます
+    /// 0 の場合はすべての制御構造を畳みます。省略した場合は畳みません。
+    pub collapse_depth: Option<usize>,
+    /// `pub` の付いていないトップレベルの関数と固有実装のメソッドを図に含めます（既定値は true）
+    /// false の場合、除外したアイテムは `filtered` に数えます。トレイト実装のメソッドは常に含めます。
+    pub include_private: bool,
+    /// 変数に束縛したクロージャ（`let f = |x| { ... };`）と単独の式のクロージャを、1行のCommandではなく本体を展開したBlockにします
+    /// `v.iter().map(|x| { ... })` のようにブロックを本体とするクロージャを引数に取る式は、各クロージャのBlockを子に持つ呼び出しのBlock（`call`）にします。
+    pub expand_closures: bool,
+    /// `?` 演算子を、失敗した場合（`Err` / `None`）に早期に戻るIf（`lowered_from: "try"`）として、その文の前に表示します
+    pub desugar_try: bool,
+    /// `assert!` / `assert_eq!` / `matches!` など、展開すると分岐になる標準ライブラリのマクロを、展開した `if` / `match` として表示します
+    /// 展開したIfの `lowered_from` にはマクロ名が入ります。
+    pub expand_macros: bool,
+    /// 文の直前の行コメント・行末コメントと、関数・メソッドのドキュメントコメント（`///`）を、ノードの `comment` に付けます
+    /// `// pad:...` のディレクティブは含めません。
+    pub comments: bool,
+    /// `#[cfg(...)]` の付いたアイテム・文を、`cfg` の注記ではなく条件 `cfg(...)` のIf（else節なし）で包んで表示します
+    pub cfg_as_branches: bool,
+    /// 図に含める関数名のパターン（`*` と `?` のglob）。空の場合はすべての関数を含めます
+    pub include: Vec<String>,
+    /// 図から除外する関数名のパターン。`include` と両方に一致する場合は除外が優先されます
+    pub exclude: Vec<String>,
+    /// 図にするトップレベルのアイテムの先頭からの読み飛ばし数（大きなファイルをページに分けて表示する場合）
+    /// `include` / `exclude` などで除外した後の、図に表示されるアイテムの位置で数えます。読み飛ばしたアイテムは変換しません。
+    pub item_offset: usize,
+    /// 図にするトップレベルのアイテムの最大数（省略した場合はすべて）
+    pub item_limit: Option<usize>,
+    /// 出力するJSONのキーの命名規則（`type` などの値は変わりません）
+    pub field_naming: FieldNaming,
+    /// ノードidの付け方（既定値は行きがけ順の連番）
+    pub id_scheme: IdScheme,
+}
+
+/// 構造上の警告のしきい値
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LintOptions {
+    /// 制御構造（if / ループ / match）の入れ子の深さの上限
+    pub max_nesting: usize,
+    /// 1つの関数に含まれる文の数の上限
+    pub max_statements: usize,
+    /// if の条件式の文字数の上限
+    pub max_condition_length: usize,
+    /// match のアーム数の上限
+    pub max_match_arms: usize,
+}
+
+impl Default for LintOptions {
+    fn default() -> Self {
+        LintOptions { max_nesting: 5, max_statements: 50, max_condition_length: 80, max_match_arms: 12 }
+    }
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            mode: ParseMode::File,
+            collapse_macros: false,
+            inline_depth: 0,
+            detect_do_while: true,
+            width_hints: false,
+            step_numbers: false,
+            label_tokens: false,
+            metrics: false,
+            document: false,
+            title: None,
+            source_name: None,
+            deterministic: false,
+            standalone: false,
+            cache: false,
+            recover: false,
+            profile: false,
+            lossy_utf8: false,
+            max_depth: 48,
+            lint: LintOptions::default(),
+            field_naming: FieldNaming::SnakeCase,
+            id_scheme: IdScheme::Sequential,
+            declarations: true,
+            imports: false,
+            expand_imports: false,
+            group_impls_by_type: false,
+            order: ItemOrder::Source,
+            group_by: GroupBy::Impl,
+            clones: false,
+            clone_min_size: 5,
+            clone_ignore_names: false,
+            interface_nodes: false,
+            match_as_if: false,
+            skip_tests: false,
+            only_tests: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            item_offset: 0,
+            item_limit: None,
+            cfg_as_branches: false,
+            panic_on_index: false,
+            multiline_args: None,
+            pretty_labels: false,
+            max_label_length: None,
+            truncation_marker: "…".to_string(),
+            truncation_position: TruncationPosition::End,
+            collapse_depth: None,
+            include_private: true,
+            expand_closures: false,
+            desugar_try: false,
+            expand_macros: false,
+            comments: false,
+        }
+    }
+}
+
+/// 入力コードの解釈方法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParseMode {
+    /// アイテム（関数、implなど）が並んだ通常のファイルとして解析します
+    #[default]
+    File,
+    /// 関数で囲まれていないトップレベルの文の並び（Playgroundやスクリプト風のコード）として解析します
+    Script,
+}
+
+/// トップレベルの関数の並び順
+/// implブロックなどのまとまりの中のメソッドも同じ規則で並べ替えます。同じ順位の場合はソースの順を保ちます。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemOrder {
+    /// ソースに書かれた順
+    #[default]
+    Source,
+    /// 関数名の順
+    Name,
+    /// 呼び出しの順（`main` から呼び出し先を幅優先でたどり、たどれない関数はソースの順で後ろに並べます）
+    CallOrder,
+}
+
+/// トップレベルの関数のまとめ方
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupBy {
+    /// まとめません（implブロック・トレイトのメソッドもトップレベルに並べます）
+    None,
+    /// implブロックごとにまとめます
+    #[default]
+    Impl,
+    /// implブロックに加え、インラインのモジュール（`mod name { ... }`）ごとにまとめます
+    Module,
+}
+
+/// 出力するJSONのキーの命名規則
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldNaming {
+    /// `then_block` のようなsnake_case（既定値。安定した出力形式です）
+    #[default]
+    SnakeCase,
+    /// `thenBlock` のようなcamelCase（JavaScript側の慣習に合わせる場合）
+    CamelCase,
+}
+
+/// ノードidの付け方
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdScheme {
+    /// 行きがけ順の連番（`n0`, `n1`, ...）。ノードを1つ追加すると、それより後のノードのidはすべて変わります
+    #[default]
+    Sequential,
+    /// 囲むBlockのラベル・種別・テキスト・Block内の位置から求めたハッシュ（`s` + 16桁の16進数）
+    /// 再解析してもidが変わりにくいため、フロントエンドが選択・折り畳みの状態を保存する場合に使います。
+    Stable,
+}
+
+/// 長いラベルを切り詰める位置
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TruncationPosition {
+    /// 先頭を残し、末尾を省略します（`let total = compute…`）
+    #[default]
+    End,
+    /// 先頭と末尾を残し、中ほどを省略します（`items.iter()…collect()` など）。長いメソッドチェーン向けです
+    Middle,
+    /// 末尾を残し、先頭を省略します（`…unwrap_or_default()`）
+    Start,
+}
+
+impl ParseOptions {
+    /// 関数名が `include` / `exclude` の指定で図に含まれるかどうかを判定します
+    pub fn allows_name(&self, name: &str) -> bool {
+        let included = self.include.is_empty() || self.include.iter().any(|pattern| glob_match(pattern, name));
+        included && !self.exclude.iter().any(|pattern| glob_match(pattern, name))
+    }
+
+    /// `item_offset` / `item_limit` でトップレベルのアイテムをページに分けるかどうか
+    pub fn paginates(&self) -> bool {
+        self.item_offset > 0 || self.item_limit.is_some()
+    }
+
+    /// 図に表示できるアイテムの並びから、`item_offset` / `item_limit` で指定されたページの分だけを取り出します
+    pub fn page<T>(&self, items: Vec<T>) -> Vec<T> {
+        items.into_iter().skip(self.item_offset).take(self.item_limit.unwrap_or(usize::MAX)).collect()
+    }
+
+    /// JSON文字列からオプションを読み込みます（空文字列の場合は既定値）
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        if json.trim().is_empty() {
+            return Ok(ParseOptions::default());
+        }
+        let options: ParseOptions = serde_json::from_str(json).map_err(|e| format!("Invalid options: {}", e))?;
+        if options.skip_tests && options.only_tests {
+            return Err("Invalid options: skip_tests and only_tests cannot both be set".to_string());
+        }
+        Ok(options)
+    }
+}
+
+/// `*`（0文字以上の任意の文字列）と `?`（任意の1文字）だけを扱う簡易的なglobで照合します
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // 直前の `*` の位置と、そこから照合を再開する名前側の位置（バックトラック用）
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let options = ParseOptions::from_json("{}").unwrap();
+        assert!(!options.collapse_macros);
+        assert_eq!(options.mode, ParseMode::File);
+        assert!(options.detect_do_while);
+        assert_eq!(options.field_naming, FieldNaming::SnakeCase);
+        assert!(ParseOptions::from_json("").is_ok());
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let err = ParseOptions::from_json("{\"max_label_lenght\": 5}").unwrap_err();
+        assert!(err.starts_with("Invalid options: unknown field `max_label_lenght`"), "{}", err);
+        assert!(ParseOptions::from_json("{\"lint\": {\"max_nesting\": 3, \"max_depth\": 1}}").is_err());
+    }
+
+    #[test]
+    fn skip_tests_and_only_tests_are_exclusive() {
+        assert!(ParseOptions::from_json("{\"only_tests\": true}").is_ok());
+        let err = ParseOptions::from_json("{\"only_tests\": true, \"skip_tests\": true}").unwrap_err();
+        assert!(err.contains("cannot both be set"));
+    }
+
+    #[test]
+    fn glob_supports_star_and_question_mark() {
+        assert!(glob_match("handle_*", "handle_request"));
+        assert!(glob_match("*_impl", "render_impl"));
+        assert!(glob_match("get_?", "get_x"));
+        assert!(!glob_match("get_?", "get_xy"));
+        assert!(glob_match("*a*b*", "xxaxxbxx"));
+        assert!(!glob_match("handle_*", "unhandled"));
+    }
+
+    #[test]
+    fn invalid_json_is_reported() {
+        let err = ParseOptions::from_json("{\"collapse_macros\": 1}").unwrap_err();
+        assert!(err.starts_with("Invalid options"));
+    }
+}
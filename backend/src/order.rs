@@ -0,0 +1,219 @@
+//! トップレベルの関数の並べ替えとまとめ方
+//! `order` オプションに従って関数（implブロックなどのまとまりの中のメソッドを含みます）を並べ替えます。
+//! `group_by` が `module` でない場合はインラインのモジュールを解いて中のアイテムをトップレベルに並べ（中のBlockには `module_path` を付けます）、
+//! `none` の場合はさらにimplブロック・トレイトを解いてメソッドをトップレベルに並べます。
+//! 関数以外のアイテム（use宣言の一覧、externブロックなど）は元の位置に残し、関数を含むノードだけをその間で並べ替えます。
+
+use std::collections::{HashMap, VecDeque};
+
+use syn::visit::Visit;
+use syn::{ImplItem, Item};
+
+use crate::convert::{default_methods, span_of};
+use crate::model::{BlockKind, PadNode};
+use crate::options::{GroupBy, ItemOrder, ParseOptions};
+
+/// 呼び出しの順を決めるための関数1つ分の情報（ソースの順）
+struct FnInfo {
+    name: String,
+    /// 関数の定義の開始位置（変換後のBlockの位置と対応付けるために使います）
+    start: (usize, usize),
+    /// 呼び出している関数・メソッドの名前（最初に呼び出した順、重複なし）
+    calls: Vec<String>,
+}
+
+/// 呼び出している関数・メソッドの名前を集めるビジター
+#[derive(Default)]
+struct Calls(Vec<String>);
+
+impl Calls {
+    fn push(&mut self, name: String) {
+        if !self.0.contains(&name) {
+            self.0.push(name);
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for Calls {
+    fn visit_expr_call(&mut self, call: &'ast syn::ExprCall) {
+        if let syn::Expr::Path(path) = &*call.func
+            && let Some(segment) = path.path.segments.last()
+        {
+            self.push(segment.ident.to_string());
+        }
+        syn::visit::visit_expr_call(self, call);
+    }
+
+    fn visit_expr_method_call(&mut self, call: &'ast syn::ExprMethodCall) {
+        self.push(call.method.to_string());
+        syn::visit::visit_expr_method_call(self, call);
+    }
+
+    fn visit_item(&mut self, _: &'ast Item) {}
+}
+
+fn fn_info(name: &syn::Ident, block: &syn::Block, spanned: &impl syn::spanned::Spanned) -> FnInfo {
+    let mut calls = Calls::default();
+    calls.visit_block(block);
+    let span = span_of(spanned);
+    FnInfo { name: name.to_string(), start: (span.start_line, span.start_col), calls: calls.0 }
+}
+
+/// アイテムの並びから、関数・メソッドの情報をソースの順に集めます（インラインのモジュールの中も含みます）
+fn functions(items: &[Item], out: &mut Vec<FnInfo>) {
+    for item in items {
+        match item {
+            Item::Fn(func) => out.push(fn_info(&func.sig.ident, &func.block, func)),
+            Item::Impl(item_impl) => {
+                for impl_item in &item_impl.items {
+                    if let ImplItem::Fn(method) = impl_item {
+                        out.push(fn_info(&method.sig.ident, &method.block, method));
+                    }
+                }
+            }
+            Item::Trait(item_trait) => {
+                for (method, block) in default_methods(item_trait) {
+                    out.push(fn_info(&method.sig.ident, block, method));
+                }
+            }
+            Item::Mod(item_mod) => {
+                if let Some((_, items)) = &item_mod.content {
+                    functions(items, out);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 関数の順位（定義の開始位置から順位への対応）を求めます
+fn ranks(functions: &[FnInfo], order: ItemOrder) -> HashMap<(usize, usize), usize> {
+    let sequence: Vec<usize> = match order {
+        ItemOrder::Source => (0..functions.len()).collect(),
+        ItemOrder::Name => {
+            let mut indices: Vec<usize> = (0..functions.len()).collect();
+            indices.sort_by(|&a, &b| functions[a].name.cmp(&functions[b].name));
+            indices
+        }
+        ItemOrder::CallOrder => call_order(functions),
+    };
+    sequence.into_iter().enumerate().map(|(rank, index)| (functions[index].start, rank)).collect()
+}
+
+/// `main`（ない場合は他の関数から呼び出されていない関数）から呼び出し先を幅優先でたどった順
+/// 同名の関数・メソッドが複数ある場合は、そのすべてを呼び出し先とみなします。たどれない関数はソースの順で後ろに並べます。
+fn call_order(functions: &[FnInfo]) -> Vec<usize> {
+    let mut by_name: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (index, function) in functions.iter().enumerate() {
+        by_name.entry(&function.name).or_default().push(index);
+    }
+    let called = |index: usize| {
+        functions
+            .iter()
+            .enumerate()
+            .any(|(caller, function)| caller != index && function.calls.contains(&functions[index].name))
+    };
+    let mut roots: Vec<usize> = by_name.get("main").cloned().unwrap_or_default();
+    if roots.is_empty() {
+        roots = (0..functions.len()).filter(|&index| !called(index)).collect();
+    }
+
+    let mut visited = vec![false; functions.len()];
+    let mut sequence = Vec::new();
+    let mut queue: VecDeque<usize> = VecDeque::new();
+    for root in roots {
+        visited[root] = true;
+        queue.push_back(root);
+    }
+    while let Some(index) = queue.pop_front() {
+        sequence.push(index);
+        for name in &functions[index].calls {
+            for &callee in by_name.get(name.as_str()).map(Vec::as_slice).unwrap_or_default() {
+                if !visited[callee] {
+                    visited[callee] = true;
+                    queue.push_back(callee);
+                }
+            }
+        }
+    }
+    sequence.extend((0..functions.len()).filter(|&index| !visited[index]));
+    sequence
+}
+
+/// ノードに含まれる関数の最も高い順位（関数を含まないノードは None）
+fn rank_of(node: &PadNode, ranks: &HashMap<(usize, usize), usize>) -> Option<usize> {
+    match node {
+        PadNode::Block { kind: BlockKind::Function, meta, .. } => {
+            meta.span.and_then(|span| ranks.get(&(span.start_line, span.start_col)).copied())
+        }
+        PadNode::Block { kind: BlockKind::Impl | BlockKind::Trait | BlockKind::Module, children, .. } => {
+            children.iter().filter_map(|child| rank_of(child, ranks)).min()
+        }
+        _ => None,
+    }
+}
+
+/// 関数を含むノードだけを、それらが占めていた位置の中で順位の順に並べ替えます（同じ順位の場合は元の順）
+fn sort_nodes(nodes: &mut Vec<PadNode>, ranks: &HashMap<(usize, usize), usize>) {
+    for node in nodes.iter_mut() {
+        if let PadNode::Block { kind: BlockKind::Impl | BlockKind::Trait | BlockKind::Module, children, .. } = node {
+            sort_nodes(children, ranks);
+        }
+    }
+    let keys: Vec<Option<usize>> = nodes.iter().map(|node| rank_of(node, ranks)).collect();
+    let slots: Vec<usize> = (0..nodes.len()).filter(|&index| keys[index].is_some()).collect();
+    let mut sorted = slots.clone();
+    sorted.sort_by_key(|&index| keys[index]);
+    let mut taken: Vec<Option<PadNode>> = std::mem::take(nodes).into_iter().map(Some).collect();
+    let mut placed: Vec<Option<PadNode>> = (0..taken.len()).map(|_| None).collect();
+    for (slot, from) in slots.iter().zip(&sorted) {
+        placed[*slot] = taken[*from].take();
+    }
+    *nodes = placed.into_iter().zip(taken).map(|(placed, rest)| placed.or(rest).unwrap()).collect();
+}
+
+/// `group_by` でまとめないBlockを解き、中のノードをその位置に並べます
+/// モジュールに付いていた `#[cfg(...)]` の条件は、中のノードの条件に引き継ぎます。
+/// 解いたモジュールの中の関数・implブロックなどのBlockには、どのモジュールのものか分かるよう `module_path`（`outer::inner`）を付けます。
+fn ungroup(nodes: Vec<PadNode>, group_by: GroupBy) -> Vec<PadNode> {
+    nodes
+        .into_iter()
+        .flat_map(|node| match node {
+            PadNode::Block { kind: BlockKind::Module, label, children, meta, .. } if group_by != GroupBy::Module => {
+                let mut children = ungroup(children, group_by);
+                let name = label.strip_prefix("mod ").unwrap_or(&label);
+                for child in &mut children {
+                    if let PadNode::Block { module_path, .. } = child {
+                        *module_path = Some(match module_path.take() {
+                            Some(inner) => format!("{}::{}", name, inner),
+                            None => name.to_string(),
+                        });
+                    }
+                }
+                if let Some(outer) = meta.cfg {
+                    for child in &mut children {
+                        let cfg = &mut child.meta_mut().cfg;
+                        *cfg = Some(match cfg.take() {
+                            Some(inner) => format!("all({}, {})", outer, inner),
+                            None => outer.clone(),
+                        });
+                    }
+                }
+                children
+            }
+            PadNode::Block { kind: BlockKind::Impl | BlockKind::Trait, children, .. } if group_by == GroupBy::None => children,
+            other => vec![other],
+        })
+        .collect()
+}
+
+/// `order` と `group_by` のオプションに従って、トップレベルのノードを並べ替え・まとめ直します
+pub fn arrange(nodes: Vec<PadNode>, items: &[Item], options: &ParseOptions) -> Vec<PadNode> {
+    let mut nodes = ungroup(nodes, options.group_by);
+    if options.order != ItemOrder::Source {
+        let mut infos = Vec::new();
+        functions(items, &mut infos);
+        sort_nodes(&mut nodes, &ranks(&infos, options.order));
+    }
+    nodes
+}
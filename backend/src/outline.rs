@@ -0,0 +1,80 @@
+//! 概略表示（アウトライン）
+//! 大きなファイルでは、まず関数やトップレベルの制御構造だけの骨組みを返し、利用者が展開したノードの中身を後から返します。
+//! 指定した深さより深いノードは中身を空にし、`collapsed: true` と直下の子ノードの数（`child_count`）を付けます。
+//! 深さは箱の入れ子の深さで、Sequenceは数えません（`find_nodes` の `min_depth` などと同じです）。
+
+use serde_json::Value;
+
+use crate::model::PadNode;
+
+/// ノードの直下に、`max_depth` 段までの子孫を残したJSON値を返します
+/// ノード自身は折りたたまず、その子ノードを深さ1として数えます。
+pub fn truncated(node: &PadNode, max_depth: usize) -> Value {
+    let mut value = serde_json::to_value(node).unwrap();
+    for child in child_nodes(&mut value) {
+        truncate(child, 0, max_depth);
+    }
+    value
+}
+
+fn is_box(value: &Value) -> bool {
+    !matches!(value["type"].as_str(), Some("sequence" | "document"))
+}
+
+fn truncate(value: &mut Value, depth: usize, max_depth: usize) {
+    let depth = if is_box(value) { depth + 1 } else { depth };
+    if is_box(value) && depth >= max_depth {
+        collapse(value);
+        return;
+    }
+    for child in child_nodes(value) {
+        truncate(child, depth, max_depth);
+    }
+}
+
+/// 子孫を取り除き、直下の子ノード（Sequenceの中身を含む）の数を記録します
+/// then節などのSequenceは形を保つため、中身だけを空にします。
+fn collapse(value: &mut Value) {
+    let count: usize = child_nodes(value).into_iter().map(box_count).sum();
+    if count == 0 {
+        return;
+    }
+    if let Some(children) = value.get_mut("children").and_then(Value::as_array_mut) {
+        children.clear();
+    }
+    for child in child_nodes(value) {
+        if is_box(child) {
+            *child = serde_json::json!({ "type": "sequence", "children": [] });
+        } else {
+            child["children"] = Value::Array(Vec::new());
+        }
+    }
+    value["collapsed"] = Value::Bool(true);
+    value["child_count"] = count.into();
+}
+
+/// Sequenceをたどって箱の数を数えます
+fn box_count(value: &mut Value) -> usize {
+    if is_box(value) {
+        1
+    } else {
+        child_nodes(value).into_iter().map(box_count).sum()
+    }
+}
+
+/// 直下の子ノードのJSON値（`children`、`then_block`、`else_block`、`body`、各アームの `body`）
+fn child_nodes(value: &mut Value) -> Vec<&mut Value> {
+    let Some(object) = value.as_object_mut() else { return Vec::new() };
+    let mut nodes = Vec::new();
+    for (key, field) in object.iter_mut() {
+        match (key.as_str(), field) {
+            ("children", Value::Array(children)) => {
+                nodes.extend(children.iter_mut());
+            }
+            ("then_block" | "else_block" | "body", field @ Value::Object(_)) => nodes.push(field),
+            ("arms", Value::Array(arms)) => nodes.extend(arms.iter_mut().map(|arm| &mut arm["body"])),
+            _ => {}
+        }
+    }
+    nodes
+}
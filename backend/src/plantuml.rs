@@ -0,0 +1,114 @@
+//! PlantUMLのアクティビティ図への書き出し
+//! PlantUMLで設計書を書いているチームが解析結果をそのまま使えるよう、新しい構文（`start` / `if` / `while` / `repeat` / `stop`）で書き出します。
+//! 関数ごとに `start` 〜 `stop` の流れを作り、関数の名前のパーティションで囲みます。
+//! 関数の中のBlock（クロージャ・ラベル付きブロックなど）もパーティションにします。
+
+use std::fmt::Write;
+
+use crate::model::{BlockKind, ExitScope, LoopKind, PadNode};
+
+/// ツリー全体をPlantUMLのアクティビティ図（`@startuml` 〜 `@enduml`）にします
+pub fn to_plantuml(root: &PadNode) -> String {
+    let mut writer = ActivityWriter { out: String::from("@startuml\n"), indent: 0 };
+    writer.node(root, false);
+    writer.out.push_str("@enduml\n");
+    writer.out
+}
+
+/// ラベル中の改行を、PlantUMLで改行を表す `\n` に置き換えます
+fn escape(text: &str) -> String {
+    text.replace('\n', "\\n")
+}
+
+/// `start` 〜 `stop` の流れになるBlock（関数と、スクリプトモードの文の並び）
+fn has_flow(kind: BlockKind) -> bool {
+    matches!(kind, BlockKind::Function | BlockKind::Script)
+}
+
+struct ActivityWriter {
+    out: String,
+    indent: usize,
+}
+
+impl ActivityWriter {
+    fn line(&mut self, text: &str) {
+        let _ = writeln!(self.out, "{:width$}{}", "", text, width = self.indent * 2);
+    }
+
+    fn nested(&mut self, node: &PadNode, in_function: bool) {
+        self.indent += 1;
+        self.node(node, in_function);
+        self.indent -= 1;
+    }
+
+    /// ノードを書き出します（`in_function` は関数の本体の中かどうか）
+    fn node(&mut self, node: &PadNode, in_function: bool) {
+        match node {
+            PadNode::Sequence { children, .. } | PadNode::Document { children, .. } => {
+                for child in children {
+                    self.node(child, in_function);
+                }
+            }
+            // 関数の外のBlock（implやモジュールなど）は流れを持たないため、中の関数だけを書き出します
+            PadNode::Block { kind, children, .. } if !in_function && !has_flow(*kind) => {
+                for child in children {
+                    self.node(child, false);
+                }
+            }
+            PadNode::Block { kind, label, children, .. } => {
+                let top_level = has_flow(*kind) && !in_function;
+                if top_level {
+                    self.line("start");
+                }
+                self.line(&format!("partition \"{}\" {{", escape(label).replace('"', "'")));
+                for child in children {
+                    self.nested(child, true);
+                }
+                self.line("}");
+                if top_level {
+                    self.line("stop");
+                }
+            }
+            PadNode::If { condition, then_block, else_block, .. } => {
+                self.line(&format!("if ({}) then (true)", escape(condition)));
+                self.nested(then_block, in_function);
+                if let Some(else_block) = else_block {
+                    self.line("else (false)");
+                    self.nested(else_block, in_function);
+                }
+                self.line("endif");
+            }
+            PadNode::Loop { kind: LoopKind::DoWhile, condition, body, .. } => {
+                self.line("repeat");
+                self.nested(body, in_function);
+                self.line(&format!("repeat while ({})", escape(condition)));
+            }
+            PadNode::Loop { condition, body, .. } => {
+                self.line(&format!("while ({})", escape(condition)));
+                self.nested(body, in_function);
+                self.line("endwhile");
+            }
+            PadNode::Match { scrutinee, arms, .. } => {
+                self.line(&format!("switch ({})", escape(scrutinee)));
+                for arm in arms {
+                    let pattern = match &arm.guard {
+                        Some(guard) => format!("{} if {}", arm.pattern, guard),
+                        None => arm.pattern.clone(),
+                    };
+                    self.line(&format!("case ({})", escape(&pattern)));
+                    self.nested(&arm.body, in_function);
+                }
+                self.line("endswitch");
+            }
+            // 関数から戻る場合は、その場で流れを終えます
+            PadNode::Return { label, scope, .. } => {
+                self.line(&format!(":{};", escape(label)));
+                if *scope == ExitScope::Function {
+                    self.line("stop");
+                }
+            }
+            PadNode::Error { message, .. } => self.line(&format!("#pink:{};", escape(message))),
+            _ => self.line(&format!(":{};", escape(node.display_text().unwrap_or_default()))),
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! 解析前の入力の整形
+//! スクリプトやチャットから貼り付けたコードに含まれる、先頭のBOM、1行目のshebang（`#!/usr/bin/env ...`）、
+//! 入力全体を囲むMarkdownのコードフェンスを取り除きます。
+//! 取り除いた部分は空白・空行に置き換えるため、残りのコードの行と桁は変わらず、位置は貼り付けたテキストをそのまま指します。
+
+use std::borrow::Cow;
+
+use crate::model::{Span, Warning};
+
+/// 取り除いた部分の警告（`node_id` は空のため、idの割り当て後に設定してください）
+fn warning(code: &str, message: String, line: usize, width: usize) -> Warning {
+    let span = Span { start_line: line, start_col: 0, end_line: line, end_col: width };
+    Warning { code: code.to_string(), message, node_id: String::new(), span: Some(span) }
+}
+
+/// コードフェンスの記号（3文字以上の ``` または ~~~）とその長さ
+fn fence_marker(line: &str) -> Option<(char, usize)> {
+    let line = line.trim();
+    let c = line.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+    let len = line.chars().take_while(|&x| x == c).count();
+    (len >= 3).then_some((c, len))
+}
+
+/// BOM・shebang・入力全体を囲むコードフェンスを取り除き、取り除いたことを示す警告とともに返します
+/// 何も取り除かない場合は、入力をそのまま返します。
+pub fn preprocess(code: &str) -> (Cow<'_, str>, Vec<Warning>) {
+    let mut warnings = Vec::new();
+    let mut lines: Vec<String> = code.split('\n').map(str::to_string).collect();
+
+    if let Some(rest) = lines[0].strip_prefix('\u{feff}') {
+        lines[0] = format!(" {}", rest);
+        warnings.push(warning("stripped_bom", "Removed the UTF-8 byte order mark (BOM)".to_string(), 1, 1));
+    }
+
+    // 空行を除いた最初と最後の行がフェンスで、間に別のフェンスがない場合だけ、1つのコードブロックとみなします
+    let mut first_code_line = 0;
+    let first = lines.iter().position(|line| !line.trim().is_empty());
+    let last = lines.iter().rposition(|line| !line.trim().is_empty());
+    if let (Some(first), Some(last)) = (first, last)
+        && first < last
+        && let Some((c, len)) = fence_marker(&lines[first])
+        && fence_marker(&lines[last]).is_some_and(|(d, n)| d == c && n >= len && lines[last].trim().chars().all(|x| x == c))
+        && lines[first + 1..last].iter().all(|line| fence_marker(line).is_none_or(|(d, _)| d != c))
+    {
+        let message = format!("Unwrapped the Markdown code fence on lines {} and {}", first + 1, last + 1);
+        warnings.push(warning("stripped_fence", message, first + 1, lines[first].chars().count()));
+        lines[first].clear();
+        lines[last].clear();
+        first_code_line = first + 1;
+    }
+
+    // `#![allow(...)]` のような内部属性はshebangではありません
+    if let Some(line) = lines.get(first_code_line)
+        && let Some(rest) = line.trim_start().strip_prefix("#!")
+        && !rest.trim_start().starts_with('[')
+    {
+        let message = format!("Removed the shebang line `{}`", line.trim());
+        warnings.push(warning("stripped_shebang", message, first_code_line + 1, line.chars().count()));
+        lines[first_code_line].clear();
+    }
+
+    if warnings.is_empty() {
+        return (Cow::Borrowed(code), warnings);
+    }
+    (Cow::Owned(lines.join("\n")), warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(warnings: &[Warning]) -> Vec<&str> {
+        warnings.iter().map(|warning| warning.code.as_str()).collect()
+    }
+
+    #[test]
+    fn stripped_parts_keep_line_and_column_positions() {
+        let (code, warnings) = preprocess("\u{feff}fn main() {}");
+        assert_eq!(code, " fn main() {}");
+        assert_eq!(codes(&warnings), ["stripped_bom"]);
+
+        let (code, warnings) = preprocess("#!/usr/bin/env run-cargo-script\nfn main() {}\n");
+        assert_eq!(code, "\nfn main() {}\n");
+        assert_eq!(codes(&warnings), ["stripped_shebang"]);
+
+        let (code, warnings) = preprocess("\n```rust\nfn main() {}\n```\n");
+        assert_eq!(code, "\n\nfn main() {}\n\n");
+        assert_eq!(warnings[0].message, "Unwrapped the Markdown code fence on lines 2 and 4");
+    }
+
+    #[test]
+    fn code_that_only_looks_similar_is_left_alone() {
+        for code in ["#![allow(unused)]\nfn main() {}", "```\na\n```\ntext\n```\nb\n```", "fn main() {}"] {
+            let (stripped, warnings) = preprocess(code);
+            assert_eq!(stripped, code);
+            assert!(warnings.is_empty());
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! prettypleaseによるラベルの整形（`pretty_labels` オプション）
+//! prettypleaseはアイテム単位で整形するため、式・パターン・文を仮の関数の本体に入れて整形し、本体の部分だけを取り出します。
+//! 対応していない構文（`Verbatim` のトークン列）を含む場合はprettypleaseがパニックするため、quote!の出力のままにします。
+//! 仮の関数として解釈し直せない場合も、quote!の出力のままにします。
+
+use quote::ToTokens;
+use syn::visit::Visit;
+use syn::{Expr, Pat, Stmt};
+
+/// `Verbatim`（synが構文木にできなかったトークン列）を含むかどうかを調べるビジター
+#[derive(Default)]
+struct Verbatim(bool);
+
+impl<'ast> Visit<'ast> for Verbatim {
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        self.0 |= matches!(expr, Expr::Verbatim(_));
+        syn::visit::visit_expr(self, expr);
+    }
+
+    fn visit_pat(&mut self, pat: &'ast Pat) {
+        self.0 |= matches!(pat, Pat::Verbatim(_));
+        syn::visit::visit_pat(self, pat);
+    }
+
+    fn visit_type(&mut self, ty: &'ast syn::Type) {
+        self.0 |= matches!(ty, syn::Type::Verbatim(_));
+        syn::visit::visit_type(self, ty);
+    }
+
+    fn visit_item(&mut self, item: &'ast syn::Item) {
+        self.0 |= matches!(item, syn::Item::Verbatim(_));
+        syn::visit::visit_item(self, item);
+    }
+}
+
+/// 文を整形します（関数の本体の字下げを除き、複数行になった場合は改行を残します）
+pub fn stmt(stmt: &Stmt) -> String {
+    let mut verbatim = Verbatim::default();
+    verbatim.visit_stmt(stmt);
+    if verbatim.0 {
+        return stmt.to_token_stream().to_string();
+    }
+    let Ok(file) = syn::parse2::<syn::File>(quote::quote!(fn __pad_label() { #stmt })) else {
+        return stmt.to_token_stream().to_string();
+    };
+    let text = prettyplease::unparse(&file);
+    let body = text.strip_prefix("fn __pad_label() {\n").and_then(|text| text.strip_suffix("}\n")).unwrap_or(&text);
+    body.lines()
+        .map(|line| line.strip_prefix("    ").unwrap_or(line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 式を整形します（文にするために付けた `;` は除きます）
+pub fn expr(expr: &Expr) -> String {
+    let text = stmt(&Stmt::Expr(expr.clone(), Some(Default::default())));
+    match text.strip_suffix(';') {
+        Some(text) => text.to_string(),
+        None => text,
+    }
+}
+
+/// パターンを整形します（`let パターン;` の文として整形し、パターンの部分を取り出します）
+/// `let` に直接は書けない or-パターン（`'a' | 'b'`）は、括弧で囲んで整形した後に括弧を外します。
+pub fn pat(pat: &Pat) -> String {
+    let is_or = matches!(pat, Pat::Or(_));
+    let pat = if is_or {
+        Pat::Paren(syn::PatParen { attrs: Vec::new(), paren_token: Default::default(), pat: Box::new(pat.clone()) })
+    } else {
+        pat.clone()
+    };
+    let local = syn::Local { attrs: Vec::new(), let_token: Default::default(), pat, init: None, semi_token: Default::default() };
+    let text = stmt(&Stmt::Local(local));
+    let text = text.strip_prefix("let ").and_then(|text| text.strip_suffix(';')).map(str::to_string).unwrap_or(text);
+    match text.strip_prefix('(').and_then(|text| text.strip_suffix(')')) {
+        Some(inner) if is_or => inner.to_string(),
+        _ => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse::Parser;
+
+    #[test]
+    fn expressions_and_patterns_read_like_rust() {
+        let call: Expr = syn::parse_str("items . iter () . map (| i | i * 2) . collect :: < Vec < _ > > ()").unwrap();
+        assert_eq!(expr(&call), "items.iter().map(|i| i * 2).collect::<Vec<_>>()");
+        let pattern: Pat = Pat::parse_single.parse_str("Some (& v)").unwrap();
+        assert_eq!(pat(&pattern), "Some(&v)");
+        let local: Stmt = syn::parse_str("let x : Vec < u8 > = Vec :: new () ;").unwrap();
+        assert_eq!(stmt(&local), "let x: Vec<u8> = Vec::new();");
+    }
+
+    #[test]
+    fn or_patterns_do_not_panic() {
+        let pattern: Pat = Pat::parse_multi_with_leading_vert.parse_str("'a' | 'b'").unwrap();
+        assert_eq!(pat(&pattern), "'a' | 'b'");
+        let code = "fn f(c: char) -> u8 {\n    match c { 'a' | 'b' => 1, Some(1 | 2) => 2, _ => 0 }\n}";
+        let json: serde_json::Value =
+            serde_json::from_str(&crate::parse_rust_code_with_options(code, r#"{"pretty_labels": true}"#)).unwrap();
+        let arms = &json["children"][0]["children"][0]["children"][0]["arms"];
+        assert_eq!(arms[0]["pattern"], "'a' | 'b'");
+        assert_eq!(arms[1]["pattern"], "Some(1 | 2)");
+    }
+}
@@ -0,0 +1,88 @@
+//! 処理時間の計測
+//! `profile` オプション指定時に、構文解析・PADノードへの変換・シリアライズのそれぞれにかかった時間（ミリ秒）と、
+//! 入力の大きさ・ノード数をルートの `profile` に記録します。遅い入力の原因を調べるためのものです。
+//! オプションを指定しない場合は時計を読まず、出力にも含めません。
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::PadNode;
+
+/// 計測結果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Profile {
+    /// synによる構文解析の時間
+    pub parse_ms: f64,
+    /// 構文木からPADノードのツリーを作る時間（id付与や警告の検出などを含みます）
+    pub convert_ms: f64,
+    /// ツリーをJSONにする時間
+    pub serialize_ms: f64,
+    /// 入力のバイト数
+    pub input_bytes: usize,
+    /// ツリーのノード数
+    pub node_count: usize,
+}
+
+/// ツリーの構築中の計測
+pub struct Phases {
+    enabled: bool,
+    start: f64,
+    parse_ms: f64,
+}
+
+impl Phases {
+    /// 計測を始めます（`enabled` が false の場合は何もしません）
+    pub fn start(enabled: bool) -> Self {
+        Phases { enabled, start: if enabled { now_ms() } else { 0.0 }, parse_ms: 0.0 }
+    }
+
+    /// 構文解析の処理を実行し、その時間を加算します
+    pub fn parse<T>(&mut self, parse: impl FnOnce() -> T) -> T {
+        if !self.enabled {
+            return parse();
+        }
+        let start = now_ms();
+        let result = parse();
+        self.parse_ms += elapsed_ms(start);
+        result
+    }
+
+    /// 計測を終え、構築したツリーの計測結果を返します（無効な場合は None）
+    /// 変換の時間は、開始からの時間のうち構文解析以外の部分です。
+    pub fn finish(&self, code: &str, tree: &PadNode) -> Option<Profile> {
+        self.enabled.then(|| Profile {
+            parse_ms: self.parse_ms,
+            convert_ms: (elapsed_ms(self.start) - self.parse_ms).max(0.0),
+            serialize_ms: 0.0,
+            input_bytes: code.len(),
+            node_count: node_count(tree),
+        })
+    }
+}
+
+fn node_count(node: &PadNode) -> usize {
+    1 + node.children().into_iter().map(node_count).sum::<usize>()
+}
+
+/// `start`（`now_ms` の値）からの経過時間（ミリ秒）
+pub fn elapsed_ms(start: f64) -> f64 {
+    (now_ms() - start).max(0.0)
+}
+
+/// 経過時間を測るための現在時刻（ミリ秒。wasmでは `Date.now()` のUNIX時刻です）
+#[cfg(target_arch = "wasm32")]
+pub fn now_ms() -> f64 {
+    #[wasm_bindgen::prelude::wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_namespace = Date, js_name = now)]
+        fn date_now() -> f64;
+    }
+    date_now()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now_ms() -> f64 {
+    use std::sync::OnceLock;
+    use std::time::Instant;
+    static ORIGIN: OnceLock<Instant> = OnceLock::new();
+    ORIGIN.get_or_init(Instant::now).elapsed().as_secs_f64() * 1000.0
+}
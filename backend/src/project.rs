@@ -0,0 +1,102 @@
+//! 複数ファイルの入力（プロジェクト全体）
+//! `{ パス: ソース }` のファイルの集合から、`mod foo;` の宣言をたどってモジュールの木を組み立て、
+//! ファイルごとのBlock（`file`）をモジュールの親子の順に入れ子にした1つのツリーにします。
+//! どのファイルからも `mod` で参照されていないファイル（`lib.rs`・`main.rs` など）を、モジュールのパス `crate` の根とします。
+
+use std::collections::{BTreeMap, HashSet};
+
+use syn::{Item, parse_str};
+
+use crate::model::{BlockKind, PadNode};
+use crate::options::ParseOptions;
+
+/// JSONのオブジェクト（文字列化済み）をファイルの集合として読み込みます
+/// パスの区切りの `\` は `/` に揃えます。
+pub fn parse_files(json: &str) -> Result<BTreeMap<String, String>, String> {
+    let files: BTreeMap<String, String> = serde_json::from_str(json)
+        .map_err(|_| "Expected an object mapping file paths to source code".to_string())?;
+    Ok(files.into_iter().map(|(path, source)| (path.replace('\\', "/"), source)).collect())
+}
+
+/// ファイルの集合を、モジュールの木の順に並べたファイルのBlockのツリー（ルートはSequence）に変換します
+/// 各ファイルは `options` で変換し、構文エラーのあるファイルはそのファイルのBlockの中のErrorノードになります。
+/// ノードidなどは呼び出し側でツリー全体に割り当て直してください。
+pub fn build(files: &BTreeMap<String, String>, options: &ParseOptions) -> PadNode {
+    let children: HashSet<String> =
+        files.iter().flat_map(|(path, source)| submodules(files, path, source)).map(|(_, child)| child).collect();
+    let mut visited = HashSet::new();
+    let roots = files
+        .keys()
+        .filter(|path| !children.contains(*path))
+        .map(|path| file_block(files, path, "crate".to_string(), options, &mut visited))
+        .collect();
+    PadNode::sequence(roots)
+}
+
+fn file_block(
+    files: &BTreeMap<String, String>,
+    path: &str,
+    module_path: String,
+    options: &ParseOptions,
+    visited: &mut HashSet<String>,
+) -> PadNode {
+    visited.insert(path.to_string());
+    let source = &files[path];
+    let mut children = match crate::build_tree(source, options) {
+        PadNode::Sequence { children, .. } => children,
+        tree => vec![tree],
+    };
+    for (name, child) in submodules(files, path, source) {
+        // `#[path]` で循環している場合は、2回目以降をたどりません
+        if !visited.contains(&child) {
+            children.push(file_block(files, &child, format!("{}::{}", module_path, name), options, visited));
+        }
+    }
+    let mut node = PadNode::block(BlockKind::File, path, children);
+    if let PadNode::Block { module_path: m, .. } = &mut node {
+        *m = Some(module_path);
+    }
+    node
+}
+
+/// ファイル中の `mod foo;` の宣言を、(モジュール名, 対応するファイルのパス) の宣言順の一覧にします
+/// 対応するファイルが集合にない宣言は除きます。
+fn submodules(files: &BTreeMap<String, String>, path: &str, source: &str) -> Vec<(String, String)> {
+    let Ok(file) = parse_str::<syn::File>(source) else { return Vec::new() };
+    let (parent, stem) = match path.rsplit_once('/') {
+        Some((parent, name)) => (format!("{}/", parent), name.trim_end_matches(".rs")),
+        None => (String::new(), path.trim_end_matches(".rs")),
+    };
+    // `lib.rs`・`main.rs`・`mod.rs` の子モジュールは同じディレクトリに、それ以外は `foo/` に置きます
+    let dir = match stem {
+        "lib" | "main" | "mod" => parent.clone(),
+        _ => format!("{}{}/", parent, stem),
+    };
+    file.items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Mod(item_mod) if item_mod.content.is_none() => Some(item_mod),
+            _ => None,
+        })
+        .filter_map(|item_mod| {
+            let name = item_mod.ident.to_string();
+            let candidates = match path_attr(&item_mod.attrs) {
+                Some(target) => vec![format!("{}{}", parent, target)],
+                None => vec![format!("{}{}.rs", dir, name), format!("{}{}/mod.rs", dir, name)],
+            };
+            let child = candidates.into_iter().find(|candidate| files.contains_key(candidate))?;
+            Some((name, child))
+        })
+        .collect()
+}
+
+/// `#[path = "..."]` の値
+fn path_attr(attrs: &[syn::Attribute]) -> Option<String> {
+    attrs.iter().filter(|attr| attr.path().is_ident("path")).find_map(|attr| match &attr.meta {
+        syn::Meta::NameValue(syn::MetaNameValue {
+            value: syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(text), .. }),
+            ..
+        }) => Some(text.value()),
+        _ => None,
+    })
+}
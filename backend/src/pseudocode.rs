@@ -0,0 +1,377 @@
+//! 擬似コード（インデントで構造を表す簡易言語）の解析
+//! 正しいRustを書かずにPADを描けるよう、次の行を解釈してRustの場合と同じPadNodeのツリーを作ります。
+//!
+//! - `function <名前>:` … 関数のBlock
+//! - `if <条件>:` / `else:` … If
+//! - `while <条件>:` / `for <変数> in <対象>:` … Loop
+//! - それ以外の行 … Command（空行と `#` で始まる行は無視します）
+//!
+//! 構造の本体は、見出しの行より深くインデントした行の並びです。
+//! インデントにはタブと空白のどちらも使えますが、1つの入力の中で混在させることはできません。
+
+use crate::diagnostics;
+use crate::model::{BlockKind, ErrorKind, LoopKind, PadNode, Span};
+
+/// 擬似コードの1行分
+struct Line<'a> {
+    /// 行番号（1始まり）
+    number: usize,
+    /// インデントの文字数
+    indent: usize,
+    /// インデントを除いた内容（末尾の空白も除きます）
+    text: &'a str,
+}
+
+/// 解析エラー（行は1始まり、桁は0始まりの文字数）
+struct SyntaxError {
+    message: String,
+    line: usize,
+    column: usize,
+}
+
+impl SyntaxError {
+    fn indentation(line: &Line, message: &str) -> Self {
+        SyntaxError {
+            message: format!("Indentation error on line {}: {}", line.number, message),
+            line: line.number,
+            column: line.indent,
+        }
+    }
+
+    fn syntax(line: &Line, message: &str) -> Self {
+        SyntaxError {
+            message: format!("Syntax error on line {}: {}", line.number, message),
+            line: line.number,
+            column: line.indent,
+        }
+    }
+}
+
+/// 擬似コードを解析し、関数ごとのBlockを並べたSequenceを返します
+/// 関数の外に書かれた行は、Rustのスクリプトモードと同じく `(script)` というBlockにまとめます。
+/// 解析できない場合は、位置とコードフレームを持つErrorノードを返します。
+pub fn parse(text: &str) -> PadNode {
+    let result = split_lines(text).and_then(|lines| {
+        let mut parser = Parser { lines, pos: 0 };
+        parser.parse_top_level()
+    });
+    match result {
+        Ok(nodes) if nodes.is_empty() => PadNode::error_of(ErrorKind::Empty, "No statement found"),
+        Ok(nodes) => PadNode::sequence(nodes),
+        Err(error) => PadNode::Error {
+            frame: diagnostics::code_frame(text, error.line, error.column),
+            message: error.message,
+            line: Some(error.line),
+            column: Some(error.column + 1),
+            byte_offset: None,
+            kind: Some(ErrorKind::Syntax),
+            meta: Default::default(),
+        },
+    }
+}
+
+/// 空行とコメント行を除いて行に分け、インデントの幅を求めます
+/// タブと空白が混在している場合（1行の中でも、行をまたいでも）はエラーにします。
+fn split_lines(text: &str) -> Result<Vec<Line<'_>>, SyntaxError> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut lines = Vec::new();
+    // 最初にインデントに使われた文字と、その行番号
+    let mut indent_char: Option<(char, usize)> = None;
+    for (index, raw) in text.lines().enumerate() {
+        let content = raw.trim_start_matches([' ', '\t']);
+        let text = content.trim_end();
+        if text.is_empty() || text.starts_with('#') {
+            continue;
+        }
+        let indentation = &raw[..raw.len() - content.len()];
+        let line = Line { number: index + 1, indent: indentation.chars().count(), text };
+        if indentation.contains(' ') && indentation.contains('\t') {
+            return Err(SyntaxError::indentation(&line, "mixed tabs and spaces in indentation"));
+        }
+        if let Some(c) = indentation.chars().next() {
+            match indent_char {
+                None => indent_char = Some((c, line.number)),
+                Some((first, first_line)) if first != c => {
+                    let message = format!(
+                        "inconsistent use of tabs and spaces (line {} is indented with {})",
+                        first_line,
+                        if first == '\t' { "tabs" } else { "spaces" },
+                    );
+                    return Err(SyntaxError::indentation(&line, &message));
+                }
+                Some(_) => {}
+            }
+        }
+        lines.push(line);
+    }
+    Ok(lines)
+}
+
+/// 見出しの行（`:` で終わり、本体を持つ行）の種類
+enum Header<'a> {
+    Function(&'a str),
+    If(&'a str),
+    Else,
+    While(&'a str),
+    For { pattern: &'a str, iter: &'a str },
+}
+
+/// 行が見出しであれば、その種類を返します
+/// キーワードで始まるのに `:` で終わらない行や、条件・名前が空の行はエラーにします。
+fn parse_header<'a>(line: &Line<'a>) -> Result<Option<Header<'a>>, SyntaxError> {
+    let text = line.text;
+    let keyword = text.split(|c: char| c.is_whitespace() || c == ':').next().unwrap_or_default();
+    if !matches!(keyword, "function" | "if" | "else" | "while" | "for") {
+        return Ok(None);
+    }
+    let Some(rest) = text.strip_suffix(':') else {
+        return Err(SyntaxError::syntax(line, &format!("expected ':' at the end of '{}'", keyword)));
+    };
+    let rest = rest[keyword.len()..].trim();
+    let require = |what: &str| {
+        if rest.is_empty() {
+            Err(SyntaxError::syntax(line, &format!("missing {} after '{}'", what, keyword)))
+        } else {
+            Ok(rest)
+        }
+    };
+    let header = match keyword {
+        "function" => Header::Function(require("function name")?),
+        "if" => Header::If(require("condition")?),
+        "while" => Header::While(require("condition")?),
+        "else" if rest.is_empty() => Header::Else,
+        "else" => return Err(SyntaxError::syntax(line, "unexpected text after 'else'")),
+        _ => {
+            let Some((pattern, iter)) = require("loop variable")?.split_once(" in ") else {
+                return Err(SyntaxError::syntax(line, "expected 'for <variable> in <iterable>:'"));
+            };
+            Header::For { pattern: pattern.trim(), iter: iter.trim() }
+        }
+    };
+    Ok(Some(header))
+}
+
+struct Parser<'a> {
+    lines: Vec<Line<'a>>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    /// トップレベルの行を解析します（関数の外の行は連続するものごとに `(script)` にまとめます）
+    fn parse_top_level(&mut self) -> Result<Vec<PadNode>, SyntaxError> {
+        let indent = self.lines.first().map_or(0, |line| line.indent);
+        let mut nodes = Vec::new();
+        let mut script = Vec::new();
+        while self.pos < self.lines.len() {
+            let line = &self.lines[self.pos];
+            if line.indent != indent {
+                return Err(self.misplaced_indent(indent));
+            }
+            if let Some(Header::Function(_)) = parse_header(line)? {
+                flush_script(&mut script, &mut nodes);
+                nodes.push(self.parse_statement()?);
+            } else {
+                script.push(self.parse_statement()?);
+            }
+        }
+        flush_script(&mut script, &mut nodes);
+        Ok(nodes)
+    }
+
+    /// インデントが `indent` の行の並びを解析し、Sequenceにします
+    fn parse_body(&mut self, indent: usize) -> Result<PadNode, SyntaxError> {
+        let mut children = Vec::new();
+        while let Some(line) = self.lines.get(self.pos) {
+            if line.indent < indent {
+                break;
+            }
+            if line.indent > indent {
+                return Err(SyntaxError::indentation(line, "unexpected indent"));
+            }
+            children.push(self.parse_statement()?);
+        }
+        Ok(PadNode::sequence(children))
+    }
+
+    /// 現在の行から文を1つ解析します（見出しの場合は本体まで）
+    fn parse_statement(&mut self) -> Result<PadNode, SyntaxError> {
+        let line = &self.lines[self.pos];
+        let (indent, text, span) = (line.indent, line.text, line_span(line));
+        let Some(header) = parse_header(line)? else {
+            self.pos += 1;
+            return Ok(with_span(PadNode::command(text), span));
+        };
+        let node = match header {
+            Header::Function(name) => {
+                let body = self.parse_block()?;
+                PadNode::block(BlockKind::Function, format!("function {}", name), vec![body])
+            }
+            Header::If(condition) => {
+                let then_block = self.parse_block()?;
+                let else_block = match self.lines.get(self.pos) {
+                    Some(next) if next.indent == indent && matches!(parse_header(next)?, Some(Header::Else)) => {
+                        Some(self.parse_block()?)
+                    }
+                    _ => None,
+                };
+                PadNode::if_node(condition, then_block, else_block)
+            }
+            Header::Else => return Err(SyntaxError::syntax(line, "'else:' without a matching 'if'")),
+            Header::While(condition) => PadNode::loop_node(LoopKind::While, condition, self.parse_block()?),
+            Header::For { pattern, iter } => {
+                PadNode::loop_node(LoopKind::For, format!("for {} in {}", pattern, iter), self.parse_block()?)
+            }
+        };
+        Ok(with_span(node, span))
+    }
+
+    /// 見出しの行を読み進め、続くインデントされた本体を解析します
+    /// 本体がない（次の行が深くインデントされていない）場合は、構造が閉じていないものとしてエラーにします。
+    fn parse_block(&mut self) -> Result<PadNode, SyntaxError> {
+        let line = &self.lines[self.pos];
+        self.pos += 1;
+        let body_indent = match self.lines.get(self.pos) {
+            Some(next) if next.indent > line.indent => next.indent,
+            _ => {
+                let message = format!("expected an indented block after '{}'", line.text);
+                return Err(SyntaxError::indentation(line, &message));
+            }
+        };
+        let indent = line.indent;
+        let body = self.parse_body(body_indent)?;
+        // 本体の後の行は、見出しと同じかそれより外側の段に戻っている必要があります
+        if let Some(next) = self.lines.get(self.pos)
+            && next.indent > indent
+        {
+            return Err(SyntaxError::indentation(next, "unindent does not match any outer indentation level"));
+        }
+        Ok(body)
+    }
+
+    /// トップレベルでインデントが揃っていない行のエラー
+    fn misplaced_indent(&self, indent: usize) -> SyntaxError {
+        let line = &self.lines[self.pos];
+        if line.indent > indent {
+            SyntaxError::indentation(line, "unexpected indent")
+        } else {
+            SyntaxError::indentation(line, "unindent does not match any outer indentation level")
+        }
+    }
+}
+
+/// 関数の外の行がたまっていれば、`(script)` のBlockにまとめて追加します
+fn flush_script(script: &mut Vec<PadNode>, nodes: &mut Vec<PadNode>) {
+    if !script.is_empty() {
+        let body = PadNode::sequence(std::mem::take(script));
+        nodes.push(PadNode::block(BlockKind::Script, "(script)", vec![body]));
+    }
+}
+
+/// 行の（インデントを除いた）範囲
+/// 構造のノードでは、見出しの行の範囲をソース上の位置とします。
+fn line_span(line: &Line) -> Span {
+    Span {
+        start_line: line.number,
+        start_col: line.indent,
+        end_line: line.number,
+        end_col: line.indent + line.text.chars().count(),
+    }
+}
+
+fn with_span(mut node: PadNode, span: Span) -> PadNode {
+    node.meta_mut().span = Some(span);
+    node
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_json(text: &str) -> serde_json::Value {
+        serde_json::to_value(parse(text)).unwrap()
+    }
+
+    #[test]
+    fn nesting_follows_indentation() {
+        let json = parse_json(
+            "function gcd:\n    while b != 0:\n        if a > b:\n            a = a - b\n        else:\n            b = b - a\n    return a\n",
+        );
+        let function = &json["children"][0];
+        assert_eq!(function["kind"], "function");
+        assert_eq!(function["label"], "function gcd");
+        let body = &function["children"][0]["children"];
+        assert_eq!(body[0]["type"], "loop");
+        assert_eq!(body[0]["kind"], "while");
+        assert_eq!(body[0]["condition"], "b != 0");
+        let branch = &body[0]["body"]["children"][0];
+        assert_eq!(branch["condition"], "a > b");
+        assert_eq!(branch["then_block"]["children"][0]["label"], "a = a - b");
+        assert_eq!(branch["else_block"]["children"][0]["label"], "b = b - a");
+        assert_eq!(body[1]["label"], "return a");
+    }
+
+    #[test]
+    fn lines_outside_functions_form_a_script_block() {
+        let json = parse_json("# totals\nsum = 0\nfor x in items:\n  sum = sum + x\nprint sum\n");
+        let script = &json["children"][0];
+        assert_eq!(script["kind"], "script");
+        let body = &script["children"][0]["children"];
+        assert_eq!(body[1]["kind"], "for");
+        assert_eq!(body[1]["condition"], "for x in items");
+        assert_eq!(body[2]["label"], "print sum");
+    }
+
+    #[test]
+    fn tabs_are_accepted_when_used_consistently() {
+        let json = parse_json("function f:\n\tif ok:\n\t\tgo\n");
+        let branch = &json["children"][0]["children"][0]["children"][0];
+        assert_eq!(branch["then_block"]["children"][0]["label"], "go");
+    }
+
+    #[test]
+    fn mixing_tabs_and_spaces_reports_the_line() {
+        let json = parse_json("function f:\n\tif ok:\n        go\n");
+        assert_eq!(json["type"], "error");
+        assert_eq!(json["line"], 3);
+        let message = json["message"].as_str().unwrap();
+        assert!(message.starts_with("Indentation error on line 3"), "{}", message);
+        assert!(message.contains("tabs and spaces"), "{}", message);
+
+        let json = parse_json("function f:\n \tgo\n");
+        assert_eq!(json["line"], 2);
+        assert!(json["message"].as_str().unwrap().contains("mixed tabs and spaces"));
+    }
+
+    #[test]
+    fn unclosed_structure_reports_the_header_line() {
+        let json = parse_json("function f:\n    while running:\n");
+        assert_eq!(json["type"], "error");
+        assert_eq!(json["line"], 2);
+        assert_eq!(json["column"], 5);
+        assert_eq!(
+            json["message"],
+            "Indentation error on line 2: expected an indented block after 'while running:'"
+        );
+        assert!(json["frame"].as_str().unwrap().contains("> 2 |     while running:"));
+    }
+
+    #[test]
+    fn inconsistent_dedent_is_reported() {
+        let json = parse_json("function f:\n    if a:\n        x\n      y\n");
+        assert_eq!(json["line"], 4);
+        assert!(json["message"].as_str().unwrap().contains("unindent does not match"));
+
+        let json = parse_json("function f:\n    x\n        y\n");
+        assert_eq!(json["message"], "Indentation error on line 3: unexpected indent");
+    }
+
+    #[test]
+    fn malformed_headers_are_syntax_errors() {
+        let json = parse_json("if ready\n    go\n");
+        assert_eq!(json["message"], "Syntax error on line 1: expected ':' at the end of 'if'");
+        let json = parse_json("x\nelse:\n    y\n");
+        assert_eq!(json["message"], "Syntax error on line 2: 'else:' without a matching 'if'");
+        let json = parse_json("for x:\n    y\n");
+        assert_eq!(json["line"], 1);
+    }
+}
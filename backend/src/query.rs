@@ -0,0 +1,183 @@
+//! ノードの検索
+//! 「条件式に `retries` を含むループ」「`unwrap` を含む命令」のような問い合わせに答えるための検索です。
+//! 問い合わせはJSONオブジェクトで受け取り、指定されたすべての条件を満たすノードを返します。
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::{NODE_KINDS, PadNode, Span};
+use crate::regex::Regex;
+use crate::visit::{VisitContext, Visitor, walk};
+
+/// 検索条件（省略した条件は絞り込みに使いません）
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Query {
+    /// ノード種別（JSONの `type` の値）
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    /// ラベル（条件式などの表示テキスト）に含まれる文字列
+    pub label_contains: Option<String>,
+    /// ラベルが一致する正規表現
+    pub label_regex: Option<String>,
+    /// 箱の入れ子の深さ（トップレベルの関数が1。Sequenceは数えません）の下限と上限
+    pub min_depth: Option<usize>,
+    pub max_depth: Option<usize>,
+    /// 大文字・小文字を区別するかどうか（既定値は true）
+    #[serde(default = "default_case_sensitive")]
+    pub case_sensitive: bool,
+}
+
+fn default_case_sensitive() -> bool {
+    true
+}
+
+/// 検索に一致したノード
+#[derive(Debug, Serialize)]
+pub struct NodeMatch {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    /// ルートからの子ノードの位置（`get_subtree_by_path` に渡せる形式）
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+}
+
+struct Finder<'a> {
+    query: &'a Query,
+    regex: Option<Regex>,
+    /// 現在の箱の入れ子の深さ
+    depth: usize,
+    matches: Vec<NodeMatch>,
+}
+
+impl Finder<'_> {
+    fn is_match(&self, node: &PadNode) -> bool {
+        let query = self.query;
+        if query.kind.as_deref().is_some_and(|kind| kind != node.kind_name()) {
+            return false;
+        }
+        if query.min_depth.is_some_and(|min| self.depth < min) || query.max_depth.is_some_and(|max| self.depth > max) {
+            return false;
+        }
+        if query.label_contains.is_none() && self.regex.is_none() {
+            return true;
+        }
+        let Some(label) = node.display_text() else { return false };
+        if let Some(needle) = &query.label_contains {
+            let found = if query.case_sensitive {
+                label.contains(needle.as_str())
+            } else {
+                label.to_lowercase().contains(&needle.to_lowercase())
+            };
+            if !found {
+                return false;
+            }
+        }
+        self.regex.as_ref().is_none_or(|regex| regex.is_match(label))
+    }
+}
+
+impl Visitor for Finder<'_> {
+    fn enter(&mut self, node: &PadNode, ctx: &VisitContext) -> bool {
+        if !matches!(node, PadNode::Sequence { .. } | PadNode::Document { .. }) {
+            self.depth += 1;
+        }
+        if self.is_match(node) {
+            self.matches.push(NodeMatch {
+                id: node.meta().id.clone(),
+                kind: node.kind_name(),
+                path: ctx.path.iter().map(|i| i.to_string()).collect::<Vec<_>>().join("/"),
+                label: node.display_text().map(str::to_string),
+                span: node.meta().span,
+            });
+        }
+        true
+    }
+
+    fn leave(&mut self, node: &PadNode, _ctx: &VisitContext) {
+        if !matches!(node, PadNode::Sequence { .. } | PadNode::Document { .. }) {
+            self.depth -= 1;
+        }
+    }
+}
+
+/// 問い合わせ（JSON文字列）を読み込みます
+pub fn parse_query(json: &str) -> Result<Query, String> {
+    serde_json::from_str(json).map_err(|e| format!("Invalid query: {}", e))
+}
+
+/// ツリーから条件に一致するノードを行きがけ順に探します
+/// 存在しないノード種別の指定は、何にも一致しない検索ではなく誤りとします。
+pub fn find_nodes(root: &PadNode, query: &Query) -> Result<Vec<NodeMatch>, String> {
+    if let Some(kind) = &query.kind
+        && !NODE_KINDS.contains(&kind.as_str())
+    {
+        return Err(format!("Invalid type: unknown node type `{}`", kind));
+    }
+    let regex = match &query.label_regex {
+        Some(pattern) => Some(Regex::new(pattern, !query.case_sensitive).map_err(|e| format!("Invalid label_regex: {}", e))?),
+        None => None,
+    };
+    let mut finder = Finder { query, regex, depth: 0, matches: Vec::new() };
+    walk(root, &mut finder);
+    Ok(finder.matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "fn main() {\n    let v = load().unwrap();\n    while retries < 3 {\n        if Retries_left() { x.unwrap(); }\n    }\n}";
+
+    fn find(query: &str) -> Vec<NodeMatch> {
+        let root = crate::build_tree(SAMPLE, &crate::options::ParseOptions::default());
+        find_nodes(&root, &parse_query(query).unwrap()).unwrap()
+    }
+
+    fn labels(matches: &[NodeMatch]) -> Vec<&str> {
+        matches.iter().map(|m| m.label.as_deref().unwrap()).collect()
+    }
+
+    #[test]
+    fn filters_by_type() {
+        let matches = find(r#"{"type": "loop"}"#);
+        assert_eq!(labels(&matches), vec!["retries < 3"]);
+        assert_eq!(matches[0].path, "0/0/1");
+        assert_eq!(matches[0].span.unwrap().start_line, 3);
+    }
+
+    #[test]
+    fn filters_by_label_with_and_without_case() {
+        assert_eq!(find(r#"{"label_contains": "retries"}"#).len(), 1);
+        assert_eq!(find(r#"{"label_contains": "retries", "case_sensitive": false}"#).len(), 2);
+    }
+
+    #[test]
+    fn filters_by_regex() {
+        let matches = find(r#"{"label_regex": "unwrap \\(\\)"}"#);
+        assert_eq!(labels(&matches), vec!["let v = load () . unwrap () ;", "x . unwrap ()"]);
+        assert_eq!(labels(&find(r#"{"label_regex": "^x .* \\(\\)$"}"#)), vec!["x . unwrap ()"]);
+    }
+
+    #[test]
+    fn filters_by_depth() {
+        assert_eq!(find(r#"{"min_depth": 4}"#).len(), 1);
+        let kinds: Vec<&str> = find(r#"{"max_depth": 1}"#).iter().map(|m| m.kind).collect();
+        assert_eq!(kinds, vec!["sequence", "block", "sequence"]);
+    }
+
+    #[test]
+    fn combines_filters() {
+        let matches = find(r#"{"type": "command", "label_contains": "unwrap", "min_depth": 3}"#);
+        assert_eq!(labels(&matches), vec!["x . unwrap ()"]);
+        assert!(parse_query(r#"{"kind": "loop"}"#).is_err());
+        let root = PadNode::sequence(Vec::new());
+        assert!(find_nodes(&root, &parse_query(r#"{"label_regex": "("}"#).unwrap()).is_err());
+        assert!(find_nodes(&root, &parse_query(r#"{"type": "loops"}"#).unwrap()).is_err());
+        let nested = serde_json::json!({ "label_regex": "(".repeat(6000) }).to_string();
+        assert!(find_nodes(&root, &parse_query(&nested).unwrap()).unwrap_err().starts_with("Invalid label_regex"));
+    }
+}
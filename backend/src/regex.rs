@@ -0,0 +1,393 @@
+//! ラベル検索用の小さな正規表現エンジン
+//! 外部クレートに依存しないよう、よく使う構文だけを実装しています。
+//! パターンは命令列にコンパイルし、すべての候補を同時に進めるNFAのシミュレーション（Pike VM）で照合します。
+//! バックトラッキングを使わないため、`(a*)*b` のような入れ子の繰り返しでも、照合の時間はテキストの長さに比例します。
+//! 対応している構文: リテラル、`.`、`[...]`（範囲・否定）、`\d` `\w` `\s` と各否定、
+//! `*` `+` `?`（貪欲）、`^` `$`、`(...)`、`|`
+
+/// 文字クラスの要素
+#[derive(Debug, Clone)]
+enum ClassItem {
+    Char(char),
+    Range(char, char),
+    Digit(bool),
+    Word(bool),
+    Space(bool),
+}
+
+impl ClassItem {
+    fn matches(&self, c: char) -> bool {
+        match *self {
+            ClassItem::Char(x) => c == x,
+            ClassItem::Range(a, b) => a <= c && c <= b,
+            ClassItem::Digit(negated) => c.is_ascii_digit() != negated,
+            ClassItem::Word(negated) => (c.is_alphanumeric() || c == '_') != negated,
+            ClassItem::Space(negated) => c.is_whitespace() != negated,
+        }
+    }
+}
+
+/// 正規表現の構文木
+#[derive(Debug, Clone)]
+enum Node {
+    /// 任意の1文字
+    Any,
+    Class { items: Vec<ClassItem>, negated: bool },
+    Start,
+    End,
+    /// 連接
+    Concat(Vec<Node>),
+    /// 選択
+    Alt(Vec<Node>),
+    /// 繰り返し（`max` が None の場合は上限なし）
+    Repeat { node: Box<Node>, min: usize, max: Option<usize> },
+}
+
+/// コンパイル済みの正規表現
+#[derive(Debug, Clone)]
+pub struct Regex {
+    program: Vec<Inst>,
+}
+
+/// 照合用の命令
+#[derive(Debug, Clone)]
+enum Inst {
+    /// 任意の1文字を読み進めます
+    Any,
+    /// 文字クラスに一致する1文字を読み進めます
+    Class { items: Vec<ClassItem>, negated: bool },
+    Start,
+    End,
+    /// 次の命令へ文字を読まずに進みます
+    Jump(usize),
+    /// 複数の命令へ同時に進みます
+    Split(Vec<usize>),
+    Match,
+}
+
+/// グループ `(...)` の入れ子の深さの上限（構文解析とコンパイルは再帰するため、スタックを使い切らないよう制限します）
+const MAX_GROUP_DEPTH: usize = 64;
+
+struct Parser<'a> {
+    chars: &'a [char],
+    pos: usize,
+    case_insensitive: bool,
+    /// 現在のグループの入れ子の深さ
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn alternation(&mut self) -> Result<Node, String> {
+        let mut branches = vec![self.concat()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            branches.push(self.concat()?);
+        }
+        Ok(if branches.len() == 1 { branches.pop().unwrap() } else { Node::Alt(branches) })
+    }
+
+    fn concat(&mut self) -> Result<Node, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.quantifier(atom)?);
+        }
+        Ok(Node::Concat(nodes))
+    }
+
+    fn quantifier(&mut self, atom: Node) -> Result<Node, String> {
+        let (min, max) = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            _ => return Ok(atom),
+        };
+        if matches!(atom, Node::Start | Node::End) {
+            return Err("nothing to repeat".to_string());
+        }
+        self.pos += 1;
+        Ok(Node::Repeat { node: Box::new(atom), min, max })
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        let c = self.peek().ok_or("unexpected end of pattern")?;
+        self.pos += 1;
+        Ok(match c {
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '(' => {
+                if self.depth == MAX_GROUP_DEPTH {
+                    return Err(format!("groups nested deeper than {}", MAX_GROUP_DEPTH));
+                }
+                self.depth += 1;
+                let inner = self.alternation()?;
+                self.depth -= 1;
+                if self.peek() != Some(')') {
+                    return Err("missing `)`".to_string());
+                }
+                self.pos += 1;
+                inner
+            }
+            '[' => self.class()?,
+            '*' | '+' | '?' => return Err(format!("nothing to repeat before `{}`", c)),
+            '\\' => {
+                let item = self.escape()?;
+                Node::Class { items: vec![item], negated: false }
+            }
+            c => self.literal(c),
+        })
+    }
+
+    fn literal(&self, c: char) -> Node {
+        let mut items = vec![ClassItem::Char(c)];
+        if self.case_insensitive {
+            items.extend(c.to_lowercase().chain(c.to_uppercase()).filter(|&x| x != c).map(ClassItem::Char));
+        }
+        Node::Class { items, negated: false }
+    }
+
+    fn escape(&mut self) -> Result<ClassItem, String> {
+        let c = self.peek().ok_or("trailing `\\`")?;
+        self.pos += 1;
+        Ok(match c {
+            'd' => ClassItem::Digit(false),
+            'D' => ClassItem::Digit(true),
+            'w' => ClassItem::Word(false),
+            'W' => ClassItem::Word(true),
+            's' => ClassItem::Space(false),
+            'S' => ClassItem::Space(true),
+            'n' => ClassItem::Char('\n'),
+            't' => ClassItem::Char('\t'),
+            c => ClassItem::Char(c),
+        })
+    }
+
+    fn class(&mut self) -> Result<Node, String> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+        let mut items = Vec::new();
+        loop {
+            let c = self.peek().ok_or("missing `]`")?;
+            self.pos += 1;
+            let item = match c {
+                ']' if !items.is_empty() => break,
+                '\\' => self.escape()?,
+                c if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&n| n != ']') => {
+                    let end = self.chars[self.pos + 1];
+                    self.pos += 2;
+                    if end < c {
+                        return Err(format!("invalid range `{}-{}`", c, end));
+                    }
+                    ClassItem::Range(c, end)
+                }
+                c => ClassItem::Char(c),
+            };
+            if self.case_insensitive
+                && let ClassItem::Char(c) = item
+            {
+                items.extend(c.to_lowercase().chain(c.to_uppercase()).map(ClassItem::Char));
+            }
+            items.push(item);
+        }
+        Ok(Node::Class { items, negated })
+    }
+}
+
+impl Regex {
+    /// パターンをコンパイルします。`case_insensitive` の場合は大文字・小文字を区別しません
+    pub fn new(pattern: &str, case_insensitive: bool) -> Result<Self, String> {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut parser = Parser { chars: &chars, pos: 0, case_insensitive, depth: 0 };
+        let node = parser.alternation()?;
+        if parser.pos < chars.len() {
+            return Err("unmatched `)`".to_string());
+        }
+        let mut program = Vec::new();
+        compile(&node, &mut program);
+        program.push(Inst::Match);
+        Ok(Regex { program })
+    }
+
+    /// テキストのどこかにパターンに一致する部分があるかどうかを返します
+    /// 各位置で先頭から始まる候補を加えながら、1文字ずつすべての候補を進めます。
+    pub fn is_match(&self, text: &str) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        let program = &self.program;
+        let mut current = Threads::new(program.len());
+        let mut next = Threads::new(program.len());
+        for pos in 0..=chars.len() {
+            current.add(program, 0, pos, chars.len());
+            for &pc in &current.pcs {
+                let consumed = match &program[pc] {
+                    Inst::Match => return true,
+                    Inst::Any => pos < chars.len(),
+                    Inst::Class { items, negated } => {
+                        pos < chars.len() && items.iter().any(|item| item.matches(chars[pos])) != *negated
+                    }
+                    _ => false,
+                };
+                if consumed {
+                    next.add(program, pc + 1, pos + 1, chars.len());
+                }
+            }
+            std::mem::swap(&mut current, &mut next);
+            next.pcs.clear();
+        }
+        false
+    }
+}
+
+/// 構文木を命令列に変換します
+fn compile(node: &Node, program: &mut Vec<Inst>) {
+    match node {
+        Node::Any => program.push(Inst::Any),
+        Node::Class { items, negated } => program.push(Inst::Class { items: items.clone(), negated: *negated }),
+        Node::Start => program.push(Inst::Start),
+        Node::End => program.push(Inst::End),
+        Node::Concat(nodes) => nodes.iter().for_each(|node| compile(node, program)),
+        Node::Alt(branches) => {
+            let split = program.len();
+            program.push(Inst::Split(Vec::new()));
+            let mut targets = Vec::new();
+            let mut jumps = Vec::new();
+            for branch in branches {
+                targets.push(program.len());
+                compile(branch, program);
+                jumps.push(program.len());
+                program.push(Inst::Jump(0));
+            }
+            program[split] = Inst::Split(targets);
+            let end = program.len();
+            for jump in jumps {
+                program[jump] = Inst::Jump(end);
+            }
+        }
+        Node::Repeat { node, min, max } => {
+            for _ in 0..*min {
+                compile(node, program);
+            }
+            match max {
+                // 上限のない繰り返しは、本体の後に分岐へ戻るループにします
+                None => {
+                    let split = program.len();
+                    program.push(Inst::Split(Vec::new()));
+                    compile(node, program);
+                    program.push(Inst::Jump(split));
+                    program[split] = Inst::Split(vec![split + 1, program.len()]);
+                }
+                // 上限のある繰り返しは、省略できる本体を上限との差の数だけ並べます
+                Some(max) => {
+                    let mut splits = Vec::new();
+                    for _ in *min..*max {
+                        splits.push(program.len());
+                        program.push(Inst::Split(Vec::new()));
+                        compile(node, program);
+                    }
+                    let end = program.len();
+                    for split in splits {
+                        program[split] = Inst::Split(vec![split + 1, end]);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// 照合中の候補（命令の位置）の集合
+/// 同じ位置の候補は1つにまとめるため、各時点の候補の数は命令の数を超えません。
+struct Threads {
+    pcs: Vec<usize>,
+    /// 命令ごとに、最後に追加した時点の番号
+    added: Vec<usize>,
+}
+
+impl Threads {
+    fn new(size: usize) -> Self {
+        Threads { pcs: Vec::new(), added: vec![usize::MAX; size] }
+    }
+
+    /// 文字を読まずに進める命令をたどり、文字を読む命令と `Match` を候補に加えます
+    fn add(&mut self, program: &[Inst], pc: usize, pos: usize, len: usize) {
+        if self.added[pc] == pos {
+            return;
+        }
+        self.added[pc] = pos;
+        match &program[pc] {
+            Inst::Jump(target) => self.add(program, *target, pos, len),
+            Inst::Split(targets) => targets.iter().for_each(|&target| self.add(program, target, pos, len)),
+            Inst::Start => {
+                if pos == 0 {
+                    self.add(program, pc + 1, pos, len);
+                }
+            }
+            Inst::End => {
+                if pos == len {
+                    self.add(program, pc + 1, pos, len);
+                }
+            }
+            Inst::Any | Inst::Class { .. } | Inst::Match => self.pcs.push(pc),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_match(pattern: &str, text: &str) -> bool {
+        Regex::new(pattern, false).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn supports_common_syntax() {
+        assert!(is_match("retr(y|ies)", "while retries < 3"));
+        assert!(is_match(r"^\w+ \(\)$", "run ()"));
+        assert!(!is_match("^run$", "run ()"));
+        assert!(is_match("a[0-9]+b", "xa123b"));
+        assert!(!is_match("a[^0-9]b", "a1b"));
+        assert!(is_match("colou?r", "color"));
+        assert!(is_match(r"\. unwrap", "x . unwrap ()"));
+        assert!(is_match("(a*)*b", "aaab"));
+        assert!(is_match("^$", ""));
+        assert!(!is_match("a$", "ab"));
+    }
+
+    #[test]
+    fn nested_quantifiers_run_in_linear_time() {
+        let text = "a".repeat(10_000);
+        assert!(!is_match("(a*)*b", &text));
+        assert!(!is_match("(a|a)*(a|aa)+$b", &text));
+        assert!(is_match("^(a+)+$", &text));
+    }
+
+    #[test]
+    fn deeply_nested_groups_are_rejected() {
+        let nested = format!("{}a{}", "(".repeat(6000), ")".repeat(6000));
+        assert!(Regex::new(&nested, false).unwrap_err().contains("nested deeper"));
+        let shallow = format!("{}a{}", "(".repeat(MAX_GROUP_DEPTH), ")".repeat(MAX_GROUP_DEPTH));
+        assert!(is_match(&shallow, "a"));
+    }
+
+    #[test]
+    fn case_insensitive_matching() {
+        assert!(Regex::new("UNWRAP", true).unwrap().is_match("x.unwrap()"));
+        assert!(!Regex::new("UNWRAP", false).unwrap().is_match("x.unwrap()"));
+    }
+
+    #[test]
+    fn invalid_patterns_are_errors() {
+        for pattern in ["(a", "a)", "*a", "[a", "[z-a]", "\\"] {
+            assert!(Regex::new(pattern, false).is_err(), "{}", pattern);
+        }
+    }
+}
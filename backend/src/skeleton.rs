@@ -0,0 +1,294 @@
+//! PADのツリーからRustのコードの骨組みへの変換
+//! フロントエンドで編集した図をコードに戻せるよう、ツリーの構造（関数・分岐・反復・match）をRustの構文で書き出します。
+//! 処理（Command）は内容を説明の文字列にした `todo!()` にします。
+//! 条件・パターン・ジャンプのラベルは、Rustとして解釈できる場合はそのまま使い、解釈できない場合は `todo!()` や `_` に置き換えます。
+//! 関数・メソッドの見出しは、可視性と戻り値の型を含むシグネチャ（`signature`）から作り、解いたモジュールは `mod` で囲み直します。
+//! 関数の外のCommandのうち、定数・型エイリアス・関連型の宣言はそのまま書き出し、その他（構造体の宣言など）とErrorノードはコメントとして残します。
+//! 関数を1つも含まないツリー（処理の並びだけの図）は、全体を `fn main()` の本体とします。
+
+use std::fmt::Write;
+
+use syn::parse_str;
+
+use crate::model::{BlockKind, CommandCategory, LoopKind, PadNode};
+
+/// ツリー全体をRustのコードにします
+pub fn to_rust(root: &PadNode) -> String {
+    let mut writer = RustWriter { out: String::new(), indent: 0 };
+    if has_function(root) {
+        writer.node(root, false);
+    } else {
+        writer.line("fn main() {");
+        writer.nested(root, true);
+        writer.line("}");
+    }
+    writer.out
+}
+
+fn has_function(node: &PadNode) -> bool {
+    matches!(node, PadNode::Block { kind: BlockKind::Function | BlockKind::Script, .. }) || node.children().into_iter().any(has_function)
+}
+
+/// Rustの式として解釈できるかどうか
+fn is_expr(text: &str) -> bool {
+    parse_str::<syn::Expr>(text).is_ok()
+}
+
+/// モジュール・implブロック・トレイトのアイテムとして解釈できるかどうか
+fn is_item(text: &str) -> bool {
+    parse_str::<syn::Item>(text).is_ok() || parse_str::<syn::ImplItem>(text).is_ok() || parse_str::<syn::TraitItem>(text).is_ok()
+}
+
+/// 式として解釈できる場合はそのまま、できない場合は説明付きの `todo!()` にします
+fn expr_or_todo(text: &str) -> String {
+    if is_expr(text) { text.to_string() } else { todo(text) }
+}
+
+/// 説明の文字列を持つ `todo!()`（`{` `}` は書式の指定にならないよう重ねます）
+fn todo(text: &str) -> String {
+    format!("todo!({:?})", text.replace('{', "{{").replace('}', "}}"))
+}
+
+/// 関数のBlockの見出し（`pub fn name(params) -> T {`）
+/// シグネチャ（`signature`）を持つ場合はそのまま使います。持たない場合（フロントエンドで描いた図など）はラベルから作り、
+/// ラベルの `Type::` の修飾は外します。シグネチャとして解釈できないラベルは、識別子に使えない文字を `_` にした名前にします。
+fn function_header(signature: Option<&str>, label: &str, is_async: bool, is_unsafe: bool) -> String {
+    if let Some(signature) = signature
+        && parse_str::<syn::ItemFn>(&format!("{} {{}}", signature)).is_ok()
+    {
+        return signature.to_string();
+    }
+    let signature = label.strip_prefix("fn ").unwrap_or(label).trim();
+    let (path, params) = match signature.find('(') {
+        Some(index) => signature.split_at(index),
+        None => (signature, "()"),
+    };
+    let name = path.rsplit("::").next().unwrap_or(path).trim();
+    let qualifiers = format!("{}{}", if is_async { "async " } else { "" }, if is_unsafe { "unsafe " } else { "" });
+    let header = format!("{}fn {}{}", qualifiers, name, params);
+    if parse_str::<syn::ItemFn>(&format!("{} {{}}", header)).is_ok() {
+        return header;
+    }
+    let mut name: String = name.chars().map(|c| if c.is_alphanumeric() || c == '_' { c } else { '_' }).collect();
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+    let header = format!("{}fn {}()", qualifiers, name);
+    if parse_str::<syn::ItemFn>(&format!("{} {{}}", header)).is_ok() { header } else { format!("{}fn function()", qualifiers) }
+}
+
+/// 関数の外のノードを、モジュールごとにまとめた並び
+enum Entry<'a> {
+    Node(&'a PadNode),
+    Module(&'a str, Vec<Entry<'a>>),
+}
+
+/// モジュールのパス（`outer::inner` を区切ったもの）の位置にノードを加えます（同じモジュールは最初に現れた位置にまとめます）
+fn insert_entry<'a>(entries: &mut Vec<Entry<'a>>, path: &[&'a str], node: &'a PadNode) {
+    let Some((name, rest)) = path.split_first() else {
+        entries.push(Entry::Node(node));
+        return;
+    };
+    let index = match entries.iter().position(|entry| matches!(entry, Entry::Module(module, _) if module == name)) {
+        Some(index) => index,
+        None => {
+            entries.push(Entry::Module(name, Vec::new()));
+            entries.len() - 1
+        }
+    };
+    if let Entry::Module(_, inner) = &mut entries[index] {
+        insert_entry(inner, rest, node);
+    }
+}
+
+struct RustWriter {
+    out: String,
+    indent: usize,
+}
+
+impl RustWriter {
+    fn line(&mut self, text: &str) {
+        let _ = writeln!(self.out, "{:width$}{}", "", text, width = self.indent * 4);
+    }
+
+    fn nested(&mut self, node: &PadNode, in_function: bool) {
+        self.indent += 1;
+        self.node(node, in_function);
+        self.indent -= 1;
+    }
+
+    fn children(&mut self, children: &[PadNode], in_function: bool) {
+        self.indent += 1;
+        for child in children {
+            self.node(child, in_function);
+        }
+        self.indent -= 1;
+    }
+
+    /// 関数の外のノードの並びを書き出します
+    /// モジュールを解いて並べたBlock（`module_path` を持つもの）は、モジュールごとにまとめて `mod` で囲み直します。
+    /// Blockでないノード（コメントにするもの）は、直前のBlockと同じモジュールに置きます。
+    fn items(&mut self, children: &[PadNode]) {
+        let mut entries = Vec::new();
+        let mut path = Vec::new();
+        for child in children {
+            if let PadNode::Block { kind, module_path, .. } = child
+                && *kind != BlockKind::File
+            {
+                path = module_path.as_deref().map(|path| path.split("::").collect()).unwrap_or_default();
+            }
+            insert_entry(&mut entries, &path, child);
+        }
+        self.entries(&entries);
+    }
+
+    fn entries(&mut self, entries: &[Entry]) {
+        for entry in entries {
+            match entry {
+                Entry::Node(node) => self.node(node, false),
+                Entry::Module(name, inner) => {
+                    self.line(&format!("mod {} {{", name));
+                    self.indent += 1;
+                    self.entries(inner);
+                    self.indent -= 1;
+                    self.line("}");
+                }
+            }
+        }
+    }
+
+    /// ノードを書き出します（`in_function` は関数の本体の中かどうか）
+    fn node(&mut self, node: &PadNode, in_function: bool) {
+        match node {
+            PadNode::Sequence { children, .. } | PadNode::Document { children, .. } if !in_function => self.items(children),
+            PadNode::Sequence { children, .. } | PadNode::Document { children, .. } => {
+                for child in children {
+                    self.node(child, in_function);
+                }
+            }
+            PadNode::Block { kind: BlockKind::Interface, .. } => {}
+            PadNode::Block { kind: BlockKind::Function, label, full_label, signature, is_async, is_unsafe, children, .. } => {
+                let label = full_label.as_deref().unwrap_or(label);
+                self.line(&format!("{} {{", function_header(signature.as_deref(), label, *is_async, *is_unsafe)));
+                self.children(children, true);
+                self.line("}");
+            }
+            PadNode::Block { kind: BlockKind::Script, children, .. } if !in_function => {
+                self.line("fn main() {");
+                self.children(children, true);
+                self.line("}");
+            }
+            // implブロック・トレイト・モジュールは、ラベルが見出しとして解釈できる場合に囲みます
+            PadNode::Block { kind: BlockKind::Impl | BlockKind::Trait | BlockKind::Module, label, children, .. }
+                if !in_function && parse_str::<syn::Item>(&format!("{} {{}}", label)).is_ok() =>
+            {
+                self.line(&format!("{} {{", label));
+                self.children(children, false);
+                self.line("}");
+            }
+            // その他の関数の外のBlock（ファイル・グループなど）は、ラベルのコメントの後に中身を並べます
+            // クレートのルート以外のファイルは、モジュールのパスの最後の名前の `mod` で囲みます
+            PadNode::Block { kind, label, children, module_path, .. } if !in_function => {
+                self.line(&format!("// {}", label));
+                match module_path.as_deref().and_then(|path| path.rsplit_once("::")) {
+                    Some((_, name)) if *kind == BlockKind::File => {
+                        self.line(&format!("mod {} {{", name));
+                        self.indent += 1;
+                        self.items(children);
+                        self.indent -= 1;
+                        self.line("}");
+                    }
+                    _ => self.items(children),
+                }
+            }
+            PadNode::Block { kind, label, children, .. } => {
+                match kind {
+                    BlockKind::Unsafe => self.line("unsafe {"),
+                    BlockKind::Labeled => self.line(&format!("{}: {{", label)),
+                    _ => self.line(&format!("{{ // {}", label)),
+                }
+                self.children(children, true);
+                self.line("}");
+            }
+            // 宣言（定数・型エイリアス・関連型）は、アイテムとして解釈できる場合はそのまま書き出します
+            PadNode::Command { label, full_label, category: CommandCategory::Declaration, .. }
+                if !in_function && is_item(&format!("{};", full_label.as_deref().unwrap_or(label))) =>
+            {
+                self.line(&format!("{};", full_label.as_deref().unwrap_or(label)));
+            }
+            _ if !in_function => {
+                let text = match node {
+                    PadNode::Error { message, .. } => format!("error: {}", message),
+                    _ => node.display_text().unwrap_or_default().to_string(),
+                };
+                for line in text.lines() {
+                    self.line(&format!("// {}", line));
+                }
+            }
+            PadNode::If { condition, then_block, else_block, .. } => {
+                self.line(&format!("if {} {{", expr_or_todo(condition)));
+                self.nested(then_block, true);
+                if let Some(else_block) = else_block {
+                    self.line("} else {");
+                    self.nested(else_block, true);
+                }
+                self.line("}");
+            }
+            PadNode::Loop { kind, condition, body, break_condition, loop_label, .. } => {
+                let label = loop_label.as_ref().map(|label| format!("{}: ", label)).unwrap_or_default();
+                let header = match kind {
+                    LoopKind::Loop | LoopKind::DoWhile => "loop".to_string(),
+                    LoopKind::While => format!("while {}", expr_or_todo(condition)),
+                    LoopKind::For => {
+                        let header = if condition.starts_with("for ") { condition.clone() } else { format!("for {}", condition) };
+                        if is_expr(&format!("{} {{}}", header)) { header } else { format!("while {}", todo(condition)) }
+                    }
+                };
+                self.line(&format!("{}{} {{", label, header));
+                self.nested(body, true);
+                // 後判定ループは、本体の後で終了条件を判定します
+                if *kind == LoopKind::DoWhile {
+                    let exit = match break_condition {
+                        Some(condition) => expr_or_todo(condition),
+                        None if is_expr(condition) => format!("!({})", condition),
+                        None => todo(condition),
+                    };
+                    self.indent += 1;
+                    self.line(&format!("if {} {{", exit));
+                    self.line("    break;");
+                    self.line("}");
+                    self.indent -= 1;
+                }
+                self.line("}");
+            }
+            PadNode::Match { scrutinee, arms, .. } => {
+                self.line(&format!("match {} {{", expr_or_todo(scrutinee)));
+                self.indent += 1;
+                for arm in arms {
+                    let head = match &arm.guard {
+                        Some(guard) => format!("{} if {}", arm.pattern, guard),
+                        None => arm.pattern.clone(),
+                    };
+                    let head = if parse_str::<syn::Arm>(&format!("{} => {{}}", head)).is_ok() { head } else { "_".to_string() };
+                    self.line(&format!("{} => {{", head));
+                    self.nested(&arm.body, true);
+                    self.line("}");
+                }
+                self.indent -= 1;
+                self.line("}");
+            }
+            PadNode::Break { label, .. } => self.line(&format!("{};", if is_expr(label) { label } else { "break" })),
+            PadNode::Continue { label, .. } => self.line(&format!("{};", if is_expr(label) { label } else { "continue" })),
+            PadNode::Return { label, .. } => {
+                let text = if is_expr(label) { label.clone() } else { format!("return {}", todo(label)) };
+                self.line(&format!("{};", text));
+            }
+            PadNode::Error { message, .. } => {
+                for line in message.lines() {
+                    self.line(&format!("// error: {}", line));
+                }
+            }
+            _ => self.line(&format!("{};", todo(node.display_text().unwrap_or_default()))),
+        }
+    }
+}
@@ -0,0 +1,296 @@
+//! ソース上の位置とノードの対応付け
+//! エディタ連携のため、カーソル位置（オフセット）から最も内側のノードを求め、逆にノードからハイライトする範囲を求めます。
+//! ノードの位置は行・桁（桁は文字数）で記録されているため、バイトオフセットやUTF-16のオフセットとの変換をここで行います。
+
+use serde::Serialize;
+
+use crate::model::{LoopKind, PadNode, Span};
+use crate::subtree::{Breadcrumb, breadcrumb};
+
+/// オフセットの単位
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OffsetUnit {
+    /// UTF-8のバイト数（Rust側の文字列の添字）
+    Byte,
+    /// UTF-16のコードユニット数（JavaScriptの文字列の添字）
+    Utf16,
+}
+
+/// ソース文字列の行の開始位置の表
+pub struct SourceMap<'a> {
+    code: &'a str,
+    /// 各行の先頭のバイトオフセット
+    line_starts: Vec<usize>,
+}
+
+/// ソース上の範囲（開始を含み、終了を含まない）
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Range {
+    pub start: usize,
+    pub end: usize,
+    pub utf16_start: usize,
+    pub utf16_end: usize,
+}
+
+impl<'a> SourceMap<'a> {
+    pub fn new(code: &'a str) -> Self {
+        let line_starts = std::iter::once(0).chain(code.match_indices('\n').map(|(i, _)| i + 1)).collect();
+        SourceMap { code, line_starts }
+    }
+
+    /// 行（1始まり）・桁（0始まりの文字数）をバイトオフセットに変換します
+    pub fn to_byte(&self, line: usize, col: usize) -> usize {
+        let Some(&start) = self.line_starts.get(line.saturating_sub(1)) else { return self.code.len() };
+        let rest = &self.code[start..];
+        start + rest.char_indices().nth(col).map_or(rest.len(), |(i, _)| i)
+    }
+
+    /// バイトオフセットを行・桁に変換します（文字の途中を指す場合はその文字の先頭とみなします）
+    pub fn to_line_col(&self, byte: usize) -> (usize, usize) {
+        let byte = floor_char_boundary(self.code, byte.min(self.code.len()));
+        let line = self.line_starts.partition_point(|&start| start <= byte);
+        let start = self.line_starts[line - 1];
+        (line, self.code[start..byte].chars().count())
+    }
+
+    /// UTF-16のオフセットをバイトオフセットに変換します
+    pub fn utf16_to_byte(&self, offset: usize) -> usize {
+        let mut units = 0;
+        for (i, c) in self.code.char_indices() {
+            if units >= offset {
+                return i;
+            }
+            units += c.len_utf16();
+        }
+        self.code.len()
+    }
+
+    /// バイトオフセットをUTF-16のオフセットに変換します
+    pub fn byte_to_utf16(&self, byte: usize) -> usize {
+        let byte = floor_char_boundary(self.code, byte.min(self.code.len()));
+        self.code[..byte].encode_utf16().count()
+    }
+
+    /// ノードの位置をバイト・UTF-16の両方の範囲に変換します
+    pub fn range(&self, span: &Span) -> Range {
+        let start = self.to_byte(span.start_line, span.start_col);
+        let end = self.to_byte(span.end_line, span.end_col);
+        Range { start, end, utf16_start: self.byte_to_utf16(start), utf16_end: self.byte_to_utf16(end) }
+    }
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+/// 位置がノードの範囲に含まれるか（行・桁の組で比較します）
+fn contains(span: &Span, pos: (usize, usize)) -> bool {
+    (span.start_line, span.start_col) <= pos && pos < (span.end_line, span.end_col)
+}
+
+/// 位置を含む最も内側のノードを探し、ルートからそのノードまでの経路を返します
+fn innermost<'a>(node: &'a PadNode, pos: (usize, usize), chain: &mut Vec<&'a PadNode>) {
+    chain.push(node);
+    for child in node.children() {
+        if child.meta().span.as_ref().is_some_and(|span| contains(span, pos)) {
+            innermost(child, pos, chain);
+            return;
+        }
+    }
+}
+
+/// オフセットを含むノードの検索結果
+#[derive(Debug, Serialize)]
+pub struct NodeAtOffset {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub range: Option<Range>,
+    /// ルートから親までの祖先
+    pub ancestors: Vec<Breadcrumb>,
+}
+
+/// オフセットを含む最も内側のノードを求めます
+/// 文と文の間の空白などはそれらを囲むブロックのノードになり、どのノードにも含まれない場合はルートになります。
+pub fn node_at_offset(root: &PadNode, code: &str, offset: usize, unit: OffsetUnit) -> Result<NodeAtOffset, String> {
+    let map = SourceMap::new(code);
+    let byte = match unit {
+        OffsetUnit::Byte => offset,
+        OffsetUnit::Utf16 => map.utf16_to_byte(offset),
+    };
+    if byte > code.len() {
+        return Err(format!("Offset {} is out of range", offset));
+    }
+    let mut chain = Vec::new();
+    innermost(root, map.to_line_col(byte), &mut chain);
+    let node = chain.pop().unwrap();
+    Ok(NodeAtOffset {
+        id: node.meta().id.clone(),
+        kind: node.kind_name(),
+        range: node.meta().span.as_ref().map(|span| map.range(span)),
+        ancestors: chain.into_iter().map(breadcrumb).collect(),
+    })
+}
+
+/// カーソル位置を囲む構造（パンくずリストの1項目）
+#[derive(Debug, Serialize)]
+pub struct Enclosing {
+    pub id: String,
+    /// `block` / `if` / `loop` / `match` / `arm`（matchのアーム。idはアームの本体のもの）
+    #[serde(rename = "type")]
+    pub node_type: &'static str,
+    /// ブロック・ループの種類（`function`、`for` など）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    /// 表示用の文字列（`fn process()`、`for order in orders`、`if order.urgent` など）
+    pub label: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub range: Option<Range>,
+}
+
+/// バイトオフセットを囲む構造（関数などのブロック・ループ・if・match・matchのアーム）を外側から順に返します
+/// 条件式の中の位置はそのif・ループに含まれます。どの構造にも含まれない位置（トップレベルの空白など）では空になります。
+pub fn breadcrumb_at(root: &PadNode, code: &str, offset: usize) -> Result<Vec<Enclosing>, String> {
+    if offset > code.len() {
+        return Err(format!("Offset {} is out of range", offset));
+    }
+    let map = SourceMap::new(code);
+    let mut chain = Vec::new();
+    innermost(root, map.to_line_col(offset), &mut chain);
+    let range = |node: &PadNode| node.meta().span.as_ref().map(|span| map.range(span));
+    let mut path = Vec::new();
+    for (i, node) in chain.iter().enumerate() {
+        let (kind, label) = match node {
+            PadNode::Block { kind, label, .. } => (Some(kind_name(kind)), label.clone()),
+            PadNode::If { condition, .. } => (None, format!("if {}", condition)),
+            PadNode::Loop { kind, condition, .. } => {
+                let label = match kind {
+                    LoopKind::While => format!("while {}", condition),
+                    LoopKind::DoWhile => format!("do while {}", condition),
+                    LoopKind::For | LoopKind::Loop => condition.clone(),
+                };
+                (Some(kind_name(kind)), label)
+            }
+            PadNode::Match { scrutinee, .. } => (None, format!("match {}", scrutinee)),
+            _ => continue,
+        };
+        path.push(Enclosing { id: node.meta().id.clone(), node_type: node.kind_name(), kind, label, range: range(node) });
+        // カーソルがアームの本体の中にある場合は、そのアームも加えます
+        if let PadNode::Match { arms, .. } = node
+            && let Some(next) = chain.get(i + 1)
+            && let Some(arm) = arms.iter().find(|arm| std::ptr::eq(&arm.body, *next))
+        {
+            let label = match &arm.guard {
+                Some(guard) => format!("{} if {}", arm.pattern, guard),
+                None => arm.pattern.clone(),
+            };
+            path.push(Enclosing { id: next.meta().id.clone(), node_type: "arm", kind: None, label, range: range(next) });
+        }
+    }
+    Ok(path)
+}
+
+/// ブロック・ループの種類の名前（JSONの `kind` の値）
+fn kind_name(kind: &impl Serialize) -> String {
+    serde_json::to_value(kind).ok().and_then(|value| value.as_str().map(str::to_string)).unwrap_or_default()
+}
+
+/// idが一致するノードのソース上の範囲を返します
+pub fn span_of_node(root: &PadNode, code: &str, id: &str) -> Result<Range, String> {
+    fn find<'a>(node: &'a PadNode, id: &str) -> Option<&'a PadNode> {
+        if node.meta().id == id {
+            return Some(node);
+        }
+        node.children().into_iter().find_map(|child| find(child, id))
+    }
+    let node = find(root, id).ok_or_else(|| format!("No node with id `{}`", id))?;
+    let span = node.meta().span.as_ref().ok_or_else(|| format!("Node `{}` has no source position", id))?;
+    Ok(SourceMap::new(code).range(span))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 日本語のコメントを含むソース（マルチバイト文字の前後で位置がずれないことを確かめます）
+    const SAMPLE: &str = "// 設定を読み込む 🎌\nfn main() {\n    let 値 = load(); // 読み込み\n\n    if 値 > 0 {\n        run(\"実行\");\n    }\n}\n";
+
+    fn tree() -> PadNode {
+        crate::build_tree(SAMPLE, &crate::options::ParseOptions::default())
+    }
+
+    #[test]
+    fn byte_and_utf16_offsets_resolve_to_the_same_node() {
+        let root = tree();
+        let byte = SAMPLE.find("run").unwrap();
+        let utf16 = SAMPLE[..byte].encode_utf16().count();
+        assert_ne!(byte, utf16);
+        let by_byte = node_at_offset(&root, SAMPLE, byte, OffsetUnit::Byte).unwrap();
+        let by_utf16 = node_at_offset(&root, SAMPLE, utf16, OffsetUnit::Utf16).unwrap();
+        assert_eq!(by_byte.kind, "command");
+        assert_eq!(by_byte.id, by_utf16.id);
+        let range = by_byte.range.unwrap();
+        assert_eq!(&SAMPLE[range.start..range.end], "run(\"実行\")");
+        assert_eq!(range.utf16_start, utf16);
+        let kinds: Vec<&str> = by_byte.ancestors.iter().map(|a| a.kind).collect();
+        assert_eq!(kinds, vec!["sequence", "block", "sequence", "if", "sequence"]);
+    }
+
+    #[test]
+    fn whitespace_between_statements_resolves_to_enclosing_block() {
+        let root = tree();
+        let blank_line = SAMPLE.find("\n\n").unwrap() + 1;
+        let node = node_at_offset(&root, SAMPLE, blank_line, OffsetUnit::Byte).unwrap();
+        assert_eq!(node.kind, "sequence");
+        assert_eq!(node.ancestors.last().unwrap().label.as_deref(), Some("fn main()"));
+        // 文字の途中を指すバイトオフセットもその文字を含むノードになります
+        let inside_char = SAMPLE.find("値 >").unwrap() + 1;
+        assert_eq!(node_at_offset(&root, SAMPLE, inside_char, OffsetUnit::Byte).unwrap().kind, "if");
+        assert!(node_at_offset(&root, SAMPLE, SAMPLE.len() + 1, OffsetUnit::Byte).is_err());
+    }
+
+    const ORDERS: &str = "fn process(orders: Vec<Order>) {\n    for order in orders {\n        if order.urgent {\n            ship(order);\n        }\n        match order.kind {\n            Kind::A => { a(); }\n            _ => b(),\n        }\n    }\n}\n\nfn other() {}\n";
+
+    fn labels_at(offset: usize) -> Vec<String> {
+        let root = crate::build_tree(ORDERS, &crate::options::ParseOptions::default());
+        breadcrumb_at(&root, ORDERS, offset).unwrap().into_iter().map(|e| e.label).collect()
+    }
+
+    #[test]
+    fn breadcrumb_inside_a_nested_if() {
+        let labels = labels_at(ORDERS.find("ship").unwrap());
+        assert_eq!(labels, vec!["fn process()", "for order in orders", "if order.urgent"]);
+        let root = crate::build_tree(ORDERS, &crate::options::ParseOptions::default());
+        let path = breadcrumb_at(&root, ORDERS, ORDERS.find("a()").unwrap()).unwrap();
+        let kinds: Vec<(&str, Option<&str>)> = path.iter().map(|e| (e.node_type, e.kind.as_deref())).collect();
+        assert_eq!(kinds, vec![("block", Some("function")), ("loop", Some("for")), ("match", None), ("arm", None)]);
+        assert_eq!(path[3].label, "Kind :: A");
+    }
+
+    #[test]
+    fn breadcrumb_inside_a_condition_expression() {
+        let labels = labels_at(ORDERS.find("urgent").unwrap());
+        assert_eq!(labels.last().unwrap(), "if order.urgent");
+    }
+
+    #[test]
+    fn breadcrumb_in_top_level_whitespace_is_empty() {
+        assert!(labels_at(ORDERS.find("\n\nfn other").unwrap() + 1).is_empty());
+        let root = crate::build_tree(ORDERS, &crate::options::ParseOptions::default());
+        assert!(breadcrumb_at(&root, ORDERS, ORDERS.len() + 1).is_err());
+    }
+
+    #[test]
+    fn span_of_node_round_trips() {
+        let root = tree();
+        let offset = SAMPLE.find("let 値").unwrap();
+        let node = node_at_offset(&root, SAMPLE, offset, OffsetUnit::Byte).unwrap();
+        let range = span_of_node(&root, SAMPLE, &node.id).unwrap();
+        assert_eq!(&SAMPLE[range.start..range.end], "let 値 = load();");
+        assert!(span_of_node(&root, SAMPLE, "missing").is_err());
+    }
+}
@@ -0,0 +1,115 @@
+//! ノード数の集計
+//! UIが描画前に「関数4、文37、分岐9、ループ5」のような概要を表示するための集計です。
+//! JSONへのシリアライズは行わず、構築したツリーをビジターで数えるだけです。
+
+use crate::model::{BlockKind, NODE_KINDS, PadNode};
+use crate::visit::{VisitContext, Visitor, walk};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// ノード種別ごとの件数（すべての種別を0件でも含みます）
+pub type KindCounts = BTreeMap<&'static str, usize>;
+
+/// 集計結果
+#[derive(Debug, Serialize)]
+pub struct NodeStats {
+    /// 全ノード数
+    pub total: usize,
+    /// 関数（メソッドを含む）の数
+    pub functions: usize,
+    /// ツリー全体の種別ごとの件数
+    pub counts: KindCounts,
+    /// 関数ごとの内訳（ソース順）
+    pub per_function: Vec<FunctionStats>,
+}
+
+/// 1つの関数の内訳
+#[derive(Debug, Serialize)]
+pub struct FunctionStats {
+    pub function: String,
+    /// 関数Block自身を除く、本体内のノードの種別ごとの件数
+    pub counts: KindCounts,
+    /// 本体内のパニックしうる箇所（`panic_reasons`）の数
+    pub panics: usize,
+}
+
+fn empty_counts() -> KindCounts {
+    NODE_KINDS.iter().map(|kind| (*kind, 0)).collect()
+}
+
+struct Counter {
+    stats: NodeStats,
+}
+
+impl Visitor for Counter {
+    fn enter(&mut self, node: &PadNode, ctx: &VisitContext) -> bool {
+        let kind = node.kind_name();
+        self.stats.total += 1;
+        *self.stats.counts.entry(kind).or_default() += 1;
+        if let PadNode::Block { kind: BlockKind::Function, label, .. } = node {
+            self.stats.functions += 1;
+            self.stats.per_function.push(FunctionStats { function: label.clone(), counts: empty_counts(), panics: 0 });
+        } else if let Some(function) = ctx.function
+            && let Some(entry) = self.stats.per_function.iter_mut().rev().find(|f| f.function == function)
+        {
+            // ノードは最も内側の関数に計上します
+            *entry.counts.entry(kind).or_default() += 1;
+            entry.panics += node.meta().panic_reasons.len();
+        }
+        true
+    }
+}
+
+/// ツリーのノード数を集計します
+pub fn count_nodes(root: &PadNode) -> NodeStats {
+    let mut counter = Counter {
+        stats: NodeStats { total: 0, functions: 0, counts: empty_counts(), per_function: Vec::new() },
+    };
+    walk(root, &mut counter);
+    counter.stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{ExitScope, LoopKind, NodeMeta};
+
+    /// すべてのバリアントを1つずつ含むツリー
+    /// 新しいバリアントを追加したらここにも追加してください（下のテストが種別の漏れを検出します）
+    fn one_of_each() -> Vec<PadNode> {
+        vec![
+            PadNode::sequence(Vec::new()),
+            crate::document::into_document(PadNode::sequence(Vec::new()), &crate::options::ParseOptions::default()),
+            PadNode::block(BlockKind::Group, "g", Vec::new()),
+            PadNode::if_node("c", PadNode::sequence(Vec::new()), None),
+            PadNode::loop_node(LoopKind::Loop, "loop", PadNode::sequence(Vec::new())),
+            PadNode::Break { label: "break".into(), target: None, value: None, target_id: None, meta: NodeMeta::default() },
+            PadNode::Continue { label: "continue".into(), target: None, target_id: None, meta: NodeMeta::default() },
+            PadNode::Yield { label: "yield".into(), value: None, meta: NodeMeta::default() },
+            PadNode::Return { label: "return".into(), value: None, scope: ExitScope::Function, meta: NodeMeta::default() },
+            PadNode::Match {
+                scrutinee: "m".into(),
+                arms: Vec::new(),
+                enum_type: None,
+                covered: Vec::new(),
+                uncovered_via_wildcard: Vec::new(),
+                meta: NodeMeta::default(),
+            },
+            PadNode::command("x"),
+            PadNode::error("e"),
+        ]
+    }
+
+    #[test]
+    fn every_variant_is_known_to_the_counter() {
+        let samples = one_of_each();
+        let kinds: Vec<&str> = samples.iter().map(|n| n.kind_name()).collect();
+        assert_eq!(kinds, NODE_KINDS);
+        for sample in &samples {
+            let json = serde_json::to_value(sample).unwrap();
+            assert_eq!(json["type"], sample.kind_name());
+        }
+        let stats = count_nodes(&PadNode::sequence(samples));
+        assert!(stats.counts.values().all(|&n| n > 0), "{:?}", stats.counts);
+    }
+}
@@ -0,0 +1,92 @@
+//! 標準ライブラリのドキュメントへのリンク
+//! `fs::read_to_string(...)` や `Vec::push` のような呼び出しを、doc.rust-lang.org の該当ページに対応付けます。
+//! 表はよく使うパスだけを収録した静的な配列で、wasmのサイズを抑えたい場合は `std-docs` フィーチャーを無効にして除けます。
+//! 型推論は行わないため、表にない名前や、メソッド名だけでは型を特定できない呼び出しにはリンクを付けません。
+
+const BASE_URL: &str = "https://doc.rust-lang.org/std/";
+
+/// パス（`型::関数` または `モジュール::関数`）とドキュメントの相対URLの表（パスの昇順）
+const ENTRIES: &[(&str, &str)] = &[
+    ("BTreeMap::entry", "collections/struct.BTreeMap.html#method.entry"),
+    ("BTreeMap::insert", "collections/struct.BTreeMap.html#method.insert"),
+    ("BTreeMap::new", "collections/struct.BTreeMap.html#method.new"),
+    ("Box::new", "boxed/struct.Box.html#method.new"),
+    ("File::create", "fs/struct.File.html#method.create"),
+    ("File::open", "fs/struct.File.html#method.open"),
+    ("HashMap::contains_key", "collections/struct.HashMap.html#method.contains_key"),
+    ("HashMap::entry", "collections/struct.HashMap.html#method.entry"),
+    ("HashMap::insert", "collections/struct.HashMap.html#method.insert"),
+    ("HashMap::new", "collections/struct.HashMap.html#method.new"),
+    ("HashMap::with_capacity", "collections/struct.HashMap.html#method.with_capacity"),
+    ("HashSet::new", "collections/struct.HashSet.html#method.new"),
+    ("Instant::now", "time/struct.Instant.html#method.now"),
+    ("Option::unwrap_or_default", "option/enum.Option.html#method.unwrap_or_default"),
+    ("Rc::new", "rc/struct.Rc.html#method.new"),
+    ("String::from", "string/struct.String.html#impl-From%3C%26str%3E-for-String"),
+    ("String::new", "string/struct.String.html#method.new"),
+    ("String::push", "string/struct.String.html#method.push"),
+    ("String::push_str", "string/struct.String.html#method.push_str"),
+    ("String::with_capacity", "string/struct.String.html#method.with_capacity"),
+    ("Vec::new", "vec/struct.Vec.html#method.new"),
+    ("Vec::pop", "vec/struct.Vec.html#method.pop"),
+    ("Vec::push", "vec/struct.Vec.html#method.push"),
+    ("Vec::retain", "vec/struct.Vec.html#method.retain"),
+    ("Vec::with_capacity", "vec/struct.Vec.html#method.with_capacity"),
+    ("VecDeque::new", "collections/struct.VecDeque.html#method.new"),
+    ("VecDeque::pop_front", "collections/struct.VecDeque.html#method.pop_front"),
+    ("VecDeque::push_back", "collections/struct.VecDeque.html#method.push_back"),
+    ("env::args", "env/fn.args.html"),
+    ("env::var", "env/fn.var.html"),
+    ("fs::create_dir_all", "fs/fn.create_dir_all.html"),
+    ("fs::read", "fs/fn.read.html"),
+    ("fs::read_dir", "fs/fn.read_dir.html"),
+    ("fs::read_to_string", "fs/fn.read_to_string.html"),
+    ("fs::remove_file", "fs/fn.remove_file.html"),
+    ("fs::write", "fs/fn.write.html"),
+    ("io::stdin", "io/fn.stdin.html"),
+    ("io::stdout", "io/fn.stdout.html"),
+    ("mem::replace", "mem/fn.replace.html"),
+    ("mem::swap", "mem/fn.swap.html"),
+    ("mem::take", "mem/fn.take.html"),
+    ("process::exit", "process/fn.exit.html"),
+    ("thread::sleep", "thread/fn.sleep.html"),
+    ("thread::spawn", "thread/fn.spawn.html"),
+];
+
+/// 呼び出しのパスに対応するドキュメントのURLを返します
+/// `std::fs::read_to_string` のように先頭に `std::` などが付いている場合は、末尾の2つのセグメントで照合します。
+pub fn lookup_path(segments: &[String]) -> Option<String> {
+    let [.., module, name] = segments else { return None };
+    let key = format!("{}::{}", module, name);
+    ENTRIES.binary_search_by(|(path, _)| path.cmp(&key.as_str())).ok().map(|i| format!("{}{}", BASE_URL, ENTRIES[i].1))
+}
+
+/// メソッド名に対応するドキュメントのURLを返します
+/// レシーバーの型はわからないため、表の中でメソッド名が1つの型にしか現れない場合だけ対応付けます。
+pub fn lookup_method(name: &str) -> Option<String> {
+    let mut candidates = ENTRIES.iter().filter(|(path, _)| {
+        path.rsplit_once("::").is_some_and(|(owner, method)| method == name && owner.starts_with(char::is_uppercase))
+    });
+    match (candidates.next(), candidates.next()) {
+        (Some((_, url)), None) => Some(format!("{}{}", BASE_URL, url)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_are_sorted_for_binary_search() {
+        assert!(ENTRIES.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn methods_are_linked_only_when_unambiguous() {
+        assert!(lookup_method("push_back").unwrap().ends_with("VecDeque.html#method.push_back"));
+        // `push` は Vec と String の両方にあるため対応付けません
+        assert_eq!(lookup_method("push"), None);
+        assert_eq!(lookup_method("frobnicate"), None);
+    }
+}
@@ -0,0 +1,129 @@
+//! 部分木の取り出し
+//! UIで選択したノード（ループなど）に「フォーカス」するために、ノードidまたは位置のパスで部分木を取り出します。
+
+use serde::Serialize;
+
+use crate::model::PadNode;
+
+/// 祖先ノード1つ分の情報（パンくずリスト用）
+#[derive(Debug, Serialize)]
+pub struct Breadcrumb {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// 部分木の検索結果
+/// 見つかった場合は部分木とルートから親までの祖先を、見つからない場合はエラーの種類と説明を持ちます。
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum SubtreeResult<'a> {
+    Found { found: bool, node: &'a PadNode, ancestors: Vec<Breadcrumb> },
+    NotFound { found: bool, error: SubtreeError },
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubtreeError {
+    /// `not_found`（該当するノードがない）または `invalid_path`（パスの書式が不正）
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl SubtreeResult<'_> {
+    fn not_found(code: &'static str, message: String) -> Self {
+        SubtreeResult::NotFound { found: false, error: SubtreeError { code, message } }
+    }
+}
+
+pub fn breadcrumb(node: &PadNode) -> Breadcrumb {
+    Breadcrumb {
+        id: node.meta().id.clone(),
+        kind: node.kind_name(),
+        label: node.display_text().map(str::to_string),
+    }
+}
+
+/// idが一致するノードを探し、そのノードに至るまでの祖先を `ancestors` に積みます（行きがけ順で最初に一致したもの）
+fn find_by_id<'a>(node: &'a PadNode, id: &str, ancestors: &mut Vec<&'a PadNode>) -> Option<&'a PadNode> {
+    if node.meta().id == id {
+        return Some(node);
+    }
+    ancestors.push(node);
+    for child in node.children() {
+        if let Some(found) = find_by_id(child, id, ancestors) {
+            return Some(found);
+        }
+    }
+    ancestors.pop();
+    None
+}
+
+/// ノードidで部分木を取り出します
+pub fn by_id<'a>(root: &'a PadNode, id: &str) -> SubtreeResult<'a> {
+    let mut ancestors = Vec::new();
+    match find_by_id(root, id, &mut ancestors) {
+        Some(node) => SubtreeResult::Found {
+            found: true,
+            node,
+            ancestors: ancestors.into_iter().map(breadcrumb).collect(),
+        },
+        None => SubtreeResult::not_found("not_found", format!("No node with id `{}`", id)),
+    }
+}
+
+/// `"0/2/1"` のような子ノードの位置（`PadNode::children` の添字）の並びで部分木を取り出します
+/// 空文字列はルート自身を表します。
+pub fn by_path<'a>(root: &'a PadNode, path: &str) -> SubtreeResult<'a> {
+    let mut node = root;
+    let mut ancestors = Vec::new();
+    for segment in path.split('/').filter(|s| !s.is_empty()) {
+        let Ok(index) = segment.parse::<usize>() else {
+            return SubtreeResult::not_found("invalid_path", format!("Invalid path segment `{}`", segment));
+        };
+        let Some(child) = node.children().get(index).copied() else {
+            return SubtreeResult::not_found("not_found", format!("No node at path `{}`", path));
+        };
+        ancestors.push(breadcrumb(node));
+        node = child;
+    }
+    SubtreeResult::Found { found: true, node, ancestors }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "fn main() {\n    for x in xs {\n        if x > 0 {\n            go(x);\n        }\n    }\n}";
+
+    fn tree() -> PadNode {
+        serde_json::from_str(&crate::parse_rust_code(SAMPLE)).unwrap()
+    }
+
+    #[test]
+    fn nested_node_is_found_by_id_and_path() {
+        let root = tree();
+        // n0:ルート n1:fn main n2:本体 n3:for n4:ループ本体 n5:if
+        let json = serde_json::to_value(by_id(&root, "n5")).unwrap();
+        assert_eq!(json["found"], true);
+        assert_eq!(json["node"]["type"], "if");
+        let labels: Vec<_> = json["ancestors"].as_array().unwrap().iter().map(|a| a["id"].as_str().unwrap()).collect();
+        assert_eq!(labels, vec!["n0", "n1", "n2", "n3", "n4"]);
+        assert_eq!(json["ancestors"][3]["label"], "for x in xs");
+
+        let by_path = serde_json::to_value(by_path(&root, "0/0/0/0/0")).unwrap();
+        assert_eq!(by_path["node"], json["node"]);
+        assert_eq!(by_path["ancestors"], json["ancestors"]);
+    }
+
+    #[test]
+    fn bogus_id_and_path_are_not_found() {
+        let root = tree();
+        let json = serde_json::to_value(by_id(&root, "nope")).unwrap();
+        assert_eq!(json["found"], false);
+        assert_eq!(json["error"]["code"], "not_found");
+        assert_eq!(serde_json::to_value(by_path(&root, "0/9")).unwrap()["error"]["code"], "not_found");
+        assert_eq!(serde_json::to_value(by_path(&root, "0/x")).unwrap()["error"]["code"], "invalid_path");
+    }
+}
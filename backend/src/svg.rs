@@ -0,0 +1,103 @@
+//! PAD図のSVG描画
+//! ブラウザやD3.jsを使わずに図を出力するための、簡易的なSVG生成です（配置は `layout` モジュールで求めます）。
+
+use std::fmt::Write;
+
+use crate::layout::{BOX_HEIGHT, PADDING_X, Shape, ShapeKind, layout};
+use crate::model::PadNode;
+
+/// XMLの特殊文字をエスケープします
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// 配置済みの図形をSVG要素にします
+fn shape(out: &mut String, shape: &Shape) {
+    let Shape { x, y, width: w, height: h, .. } = *shape;
+    match shape.kind {
+        ShapeKind::Box | ShapeKind::Loop { .. } => {
+            let rx = if shape.class == "block" { 6 } else { 0 };
+            let fill = if shape.class == "error" { "#fdd" } else { "white" };
+            let _ = writeln!(
+                out,
+                r##"<rect class="{}" x="{}" y="{}" width="{}" height="{}" rx="{}" fill="{}" stroke="#333"/>"##,
+                shape.class, x, y, w, h, rx, fill
+            );
+            // 反復の箱は縦の二重線で表します（後判定ループは右側）
+            if let ShapeKind::Loop { do_while } = shape.kind {
+                let line_x = if do_while { x + w - 5 } else { x + 5 };
+                line(out, line_x, y, line_x, y + h);
+            }
+        }
+        // 選択の箱は右辺に切り欠きのある多角形です
+        ShapeKind::Selection => {
+            let _ = writeln!(
+                out,
+                r##"<polygon class="{}" points="{},{} {},{} {},{} {},{} {},{}" fill="white" stroke="#333"/>"##,
+                shape.class, x, y, x + w, y, x + w - 10, y + h / 2, x + w, y + h, x, y + h
+            );
+        }
+        ShapeKind::Title => {}
+    }
+    let _ = writeln!(
+        out,
+        r#"<text x="{}" y="{}" font-family="monospace" font-size="13" dominant-baseline="middle">{}</text>"#,
+        x + PADDING_X,
+        y + BOX_HEIGHT / 2,
+        escape_xml(&shape.label)
+    );
+}
+
+fn line(out: &mut String, x1: usize, y1: usize, x2: usize, y2: usize) {
+    let _ = writeln!(out, r##"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="#333"/>"##, x1, y1, x2, y2);
+}
+
+/// ノード（通常は関数1つ分）を1つのSVG文書として描画します
+/// 線を先に描き、その上に箱を重ねます。
+pub fn render_svg(node: &PadNode) -> String {
+    let layout = layout(node);
+    let mut out = String::new();
+    for connector in &layout.connectors {
+        line(&mut out, connector.x1, connector.y1, connector.x2, connector.y2);
+    }
+    for s in &layout.shapes {
+        shape(&mut out, s);
+    }
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" class=\"pad\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n{}</svg>\n",
+        out,
+        w = layout.width,
+        h = layout.height
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::{COLUMN_GAP, MARGIN, ROW, ROW_GAP, box_width};
+
+    #[test]
+    fn nested_boxes_are_placed_in_columns() {
+        let node = PadNode::if_node("a < b", PadNode::command("x"), Some(PadNode::command("y")));
+        let svg = render_svg(&node);
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("a &lt; b"));
+        // 条件の箱の右に then / else が縦に並びます
+        let if_width = box_width("a < b");
+        let child_x = MARGIN + if_width + COLUMN_GAP;
+        assert!(svg.contains(&format!(r#"<rect class="command" x="{}" y="{}""#, child_x, MARGIN)));
+        assert!(svg.contains(&format!(r#"<rect class="command" x="{}" y="{}""#, child_x, MARGIN + ROW)));
+        assert!(svg.contains(&format!(r#"height="{}""#, ROW * 2 - ROW_GAP + MARGIN * 2)));
+    }
+}
@@ -0,0 +1,105 @@
+//! TikZ（LaTeX）への書き出し
+//! 配布資料などにPADを載せられるよう、SVGと同じ配置の座標で箱と線を描く `tikzpicture` を生成します。
+//! 座標はSVGのpxをそのままptとして使い、y軸は下向きにします。
+
+use std::fmt::Write;
+
+use crate::layout::{BOX_HEIGHT, PADDING_X, Shape, ShapeKind, layout};
+use crate::model::PadNode;
+
+/// `tikzpicture` の共通のスタイル
+const STYLES: &str = "x=1pt, y=-1pt, font=\\ttfamily\\small,
+  pad/.style={draw, rectangle, anchor=north west, minimum height=28pt, inner sep=0pt},
+  pad label/.style={anchor=west, inner sep=0pt}";
+
+/// LaTeXの特殊文字をエスケープします
+/// 日本語などのASCII以外の文字はそのまま残します（LuaLaTeXでのコンパイルを想定しています）。
+pub fn escape_latex(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '_' | '#' | '%' | '&' | '$' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// 配置済みの図形を描画する命令
+fn shape(out: &mut String, shape: &Shape) {
+    let Shape { x, y, width: w, height: h, .. } = *shape;
+    let label = escape_latex(&shape.label);
+    match shape.kind {
+        ShapeKind::Box | ShapeKind::Loop { .. } => {
+            let mut style = format!("pad, minimum width={}pt", w);
+            if shape.class == "block" {
+                style += ", rounded corners=6pt";
+            }
+            if shape.class == "error" {
+                style += ", fill=red!15";
+            }
+            let name = if shape.id.is_empty() { String::new() } else { format!(" ({})", shape.id) };
+            let _ = writeln!(out, "  \\node[{}]{} at ({},{}) {{}};", style, name, x, y);
+            // 反復の箱は縦の二重線で表します（後判定ループは右側）
+            if let ShapeKind::Loop { do_while } = shape.kind {
+                let line_x = if do_while { x + w - 5 } else { x + 5 };
+                let _ = writeln!(out, "  \\draw ({},{}) -- ({},{});", line_x, y, line_x, y + h);
+            }
+        }
+        // 選択の箱は右辺に切り欠きのある多角形です
+        ShapeKind::Selection => {
+            let _ = writeln!(
+                out,
+                "  \\draw ({},{}) -- ({},{}) -- ({},{}) -- ({},{}) -- ({},{}) -- cycle;",
+                x, y, x + w, y, x + w - 10, y + h / 2, x + w, y + h, x, y + h
+            );
+        }
+        ShapeKind::Title => {}
+    }
+    let _ = writeln!(out, "  \\node[pad label] at ({},{}) {{{}}};", x + PADDING_X, y + BOX_HEIGHT / 2, label);
+}
+
+/// ノード（通常はツリー全体）を描画する `tikzpicture` を生成します
+/// `standalone` を指定すると、そのままコンパイルできる `standalone` クラスの文書にします。
+pub fn to_tikz(node: &PadNode, standalone: bool) -> String {
+    let layout = layout(node);
+    let mut picture = format!("\\begin{{tikzpicture}}[{}]\n", STYLES);
+    for connector in &layout.connectors {
+        let _ = writeln!(
+            picture,
+            "  \\draw ({},{}) -- ({},{});",
+            connector.x1, connector.y1, connector.x2, connector.y2
+        );
+    }
+    for s in &layout.shapes {
+        shape(&mut picture, s);
+    }
+    picture.push_str("\\end{tikzpicture}\n");
+    if !standalone {
+        return picture;
+    }
+    // 日本語のラベルを含む場合は、LuaLaTeXで組版するためのパッケージを読み込みます
+    let japanese = if layout.shapes.iter().any(|s| !s.label.is_ascii()) { "\\usepackage{luatexja}\n" } else { "" };
+    format!(
+        "\\documentclass[tikz,border=4pt]{{standalone}}\n{}\\begin{{document}}\n{}\\end{{document}}\n",
+        japanese, picture
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn special_characters_are_escaped_for_latex() {
+        assert_eq!(escape_latex("rate_limit % 100"), "rate\\_limit \\% 100");
+        assert_eq!(escape_latex("a & b # c\\d"), "a \\& b \\# c\\textbackslash{}d");
+        assert_eq!(escape_latex("合計_値"), "合計\\_値");
+    }
+}
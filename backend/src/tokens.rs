@@ -0,0 +1,198 @@
+//! ラベルの字句の分類
+//! フロントエンドがRustの字句解析をせずにラベルを色分けできるよう、表示するラベルの文字列を字句解析し、
+//! キーワード・識別子・リテラル・文字列・記号・コメントの範囲を求めます。
+//! 範囲は出力するラベルそのものに対する位置のため、複数行への整形などをすべて終えた後に計算します。
+
+use proc_macro2::{Delimiter, Spacing, TokenStream, TokenTree};
+
+use crate::model::{LabelToken, PadNode, TokenKind};
+
+/// Rustのキーワード（予約語を含みます）
+pub const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate", "do", "dyn", "else",
+    "enum", "extern", "final", "fn", "for", "gen", "if", "impl", "in", "let", "loop", "macro", "match", "mod", "move",
+    "mut", "override", "priv", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "try",
+    "type", "typeof", "union", "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// ツリーの全ノードのラベルに字句の範囲を付けます
+pub fn apply_label_tokens(node: &mut PadNode) {
+    let text = match node {
+        PadNode::Command { label, .. } | PadNode::Return { label, .. } => Some(label.clone()),
+        PadNode::If { condition, .. } | PadNode::Loop { condition, .. } => Some(condition.clone()),
+        PadNode::Match { scrutinee, .. } => Some(scrutinee.clone()),
+        _ => None,
+    };
+    if let Some(tokens) = text.as_deref().and_then(classify) {
+        node.meta_mut().tokens = tokens;
+    }
+    for child in node.children_mut() {
+        apply_label_tokens(child);
+    }
+}
+
+/// ラベルを字句解析し、字句の範囲を出現順に返します
+/// 省略記号（`…`）で切り詰めたラベルなど、字句解析できない場合は None を返します。
+pub fn classify(text: &str) -> Option<Vec<LabelToken>> {
+    let stream: TokenStream = text.parse().ok()?;
+    let lines = LineStarts::new(text);
+    let mut tokens = Vec::new();
+    collect(stream, &lines, &mut tokens);
+    // 字句の間にあるコメントは字句解析で取り除かれるため、隙間から探します
+    let mut with_comments = Vec::new();
+    let mut last = 0;
+    for token in tokens {
+        with_comments.extend(comment_in(text, last, token.start));
+        last = token.end;
+        with_comments.push(token);
+    }
+    with_comments.extend(comment_in(text, last, utf16_len(text)));
+    Some(with_comments)
+}
+
+fn collect(stream: TokenStream, lines: &LineStarts, tokens: &mut Vec<LabelToken>) {
+    let mut joint = false;
+    for tree in stream {
+        let (range, kind) = match &tree {
+            TokenTree::Group(group) => {
+                if group.delimiter() != Delimiter::None {
+                    tokens.push(lines.token(group.span_open(), TokenKind::Punct));
+                }
+                collect(group.stream(), lines, tokens);
+                if group.delimiter() != Delimiter::None {
+                    tokens.push(lines.token(group.span_close(), TokenKind::Punct));
+                }
+                joint = false;
+                continue;
+            }
+            TokenTree::Ident(ident) => {
+                let name = ident.to_string();
+                let kind = match name.as_str() {
+                    "true" | "false" => TokenKind::Literal,
+                    name if KEYWORDS.contains(&name) => TokenKind::Keyword,
+                    _ => TokenKind::Ident,
+                };
+                (lines.token(ident.span(), kind), kind)
+            }
+            TokenTree::Literal(literal) => {
+                let text = literal.to_string();
+                let is_string = ["\"", "r\"", "r#", "b\"", "br", "c\"", "cr"].iter().any(|prefix| text.starts_with(prefix));
+                let kind = if is_string { TokenKind::String } else { TokenKind::Literal };
+                (lines.token(literal.span(), kind), kind)
+            }
+            TokenTree::Punct(punct) => (lines.token(punct.span(), TokenKind::Punct), TokenKind::Punct),
+        };
+        // `::` や `+=` のように連続する記号は1つの字句にまとめます
+        match tokens.last_mut() {
+            Some(previous) if joint && kind == TokenKind::Punct && previous.end == range.start => previous.end = range.end,
+            _ => tokens.push(range),
+        }
+        joint = matches!(&tree, TokenTree::Punct(punct) if punct.spacing() == Spacing::Joint);
+    }
+}
+
+/// `start` から `end` まで（UTF-16の位置）の隙間にあるコメントの範囲を返します
+fn comment_in(text: &str, start: usize, end: usize) -> Option<LabelToken> {
+    let gap = utf16_slice(text, start, end);
+    let trimmed = gap.trim_start();
+    if !(trimmed.starts_with("//") || trimmed.starts_with("/*")) {
+        return None;
+    }
+    let leading = utf16_len(&gap[..gap.len() - trimmed.len()]);
+    Some(LabelToken { start: start + leading, end: start + leading + utf16_len(trimmed.trim_end()), kind: TokenKind::Comment })
+}
+
+fn utf16_len(text: &str) -> usize {
+    text.encode_utf16().count()
+}
+
+fn utf16_slice(text: &str, start: usize, end: usize) -> &str {
+    let mut units = 0;
+    let mut from = text.len();
+    let mut to = text.len();
+    for (i, c) in text.char_indices() {
+        if units == start {
+            from = i;
+        }
+        if units == end {
+            to = i;
+            break;
+        }
+        units += c.len_utf16();
+    }
+    &text[from.min(to)..to]
+}
+
+/// ラベルの各行の先頭の位置（UTF-16）と、行の文字列
+struct LineStarts<'a> {
+    lines: Vec<(usize, &'a str)>,
+}
+
+impl<'a> LineStarts<'a> {
+    fn new(text: &'a str) -> Self {
+        let mut start = 0;
+        let lines = text
+            .split('\n')
+            .map(|line| {
+                let entry = (start, line);
+                start += utf16_len(line) + 1;
+                entry
+            })
+            .collect();
+        LineStarts { lines }
+    }
+
+    /// 行（1始まり）・桁（0始まりの文字数）をラベル先頭からのUTF-16の位置にします
+    fn offset(&self, line: usize, column: usize) -> usize {
+        let Some(&(start, text)) = self.lines.get(line.saturating_sub(1)) else { return 0 };
+        start + text.chars().take(column).map(char::len_utf16).sum::<usize>()
+    }
+
+    fn token(&self, span: proc_macro2::Span, kind: TokenKind) -> LabelToken {
+        let (start, end) = (span.start(), span.end());
+        LabelToken { start: self.offset(start.line, start.column), end: self.offset(end.line, end.column), kind }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(text: &str) -> Vec<(&str, TokenKind)> {
+        let tokens = classify(text).unwrap();
+        tokens.iter().map(|t| (utf16_slice(text, t.start, t.end), t.kind)).collect()
+    }
+
+    #[test]
+    fn string_literals_and_keywords_are_classified() {
+        assert_eq!(
+            kinds("let msg = format! (\"値: {}\" , x)"),
+            vec![
+                ("let", TokenKind::Keyword),
+                ("msg", TokenKind::Ident),
+                ("=", TokenKind::Punct),
+                ("format", TokenKind::Ident),
+                ("!", TokenKind::Punct),
+                ("(", TokenKind::Punct),
+                ("\"値: {}\"", TokenKind::String),
+                (",", TokenKind::Punct),
+                ("x", TokenKind::Ident),
+                (")", TokenKind::Punct),
+            ]
+        );
+    }
+
+    #[test]
+    fn joint_punctuation_numbers_and_comments() {
+        assert_eq!(
+            kinds("n += 1 // 加算"),
+            vec![
+                ("n", TokenKind::Ident),
+                ("+=", TokenKind::Punct),
+                ("1", TokenKind::Literal),
+                ("// 加算", TokenKind::Comment),
+            ]
+        );
+        assert!(classify("call (a, …").is_none());
+    }
+}
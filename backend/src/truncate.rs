@@ -0,0 +1,67 @@
+//! ラベルの切り詰め
+//! `max_label_length` オプション指定時に、長いラベル・条件式を指定文字数に切り詰め、省略した位置に `truncation_marker` を付けます。
+//! 省略する位置（末尾・中ほど・先頭）は `truncation_position` で選べます。
+//! 元のテキストは `// pad:label:` での置き換えと同じく `full_label` に残します（すでにある場合はそのままにします）。
+
+use crate::model::PadNode;
+use crate::options::TruncationPosition;
+
+/// ツリー全体の長いラベルを切り詰めます
+pub fn truncate_labels(node: &mut PadNode, max: usize, marker: &str, position: TruncationPosition) {
+    match node {
+        PadNode::Command { label, full_label, .. }
+        | PadNode::If { condition: label, full_label, .. }
+        | PadNode::Loop { condition: label, full_label, .. }
+        | PadNode::Block { label, full_label, .. } => {
+            if let Some(short) = shorten(label, max, marker, position) {
+                let original = std::mem::replace(label, short);
+                full_label.get_or_insert(original);
+            }
+        }
+        _ => {}
+    }
+    for child in node.children_mut() {
+        truncate_labels(child, max, marker, position);
+    }
+}
+
+/// `max` 文字を超える場合に、`max` 文字を残して切り詰めたテキストを返します
+fn shorten(text: &str, max: usize, marker: &str, position: TruncationPosition) -> Option<String> {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() <= max {
+        return None;
+    }
+    let (head, tail) = match position {
+        TruncationPosition::End => (max, 0),
+        TruncationPosition::Middle => (max - max / 2, max / 2),
+        TruncationPosition::Start => (0, max),
+    };
+    let head: String = chars[..head].iter().collect();
+    let tail: String = chars[chars.len() - tail..].iter().collect();
+    Some(format!("{}{}{}", head, marker, tail))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_labels_keep_the_original_text() {
+        let mut node = PadNode::sequence(vec![PadNode::command("let 値 = compute();"), PadNode::command("ok()")]);
+        truncate_labels(&mut node, 5, "…", TruncationPosition::End);
+        let PadNode::Sequence { children, .. } = &node else { unreachable!() };
+        let PadNode::Command { label, full_label, .. } = &children[0] else { unreachable!() };
+        assert_eq!(label, "let 値…");
+        assert_eq!(full_label.as_deref(), Some("let 値 = compute();"));
+        let PadNode::Command { label, full_label, .. } = &children[1] else { unreachable!() };
+        assert_eq!((label.as_str(), full_label), ("ok()", &None));
+    }
+
+    #[test]
+    fn marker_can_be_placed_in_the_middle_or_at_the_start() {
+        let chain = "items.iter().map(f).collect()";
+        assert_eq!(shorten(chain, 10, "…", TruncationPosition::Middle).as_deref(), Some("items…ect()"));
+        assert_eq!(shorten(chain, 9, "…", TruncationPosition::Start).as_deref(), Some("…collect()"));
+        assert_eq!(shorten("short", 5, "…", TruncationPosition::Middle), None);
+    }
+}
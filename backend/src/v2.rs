@@ -0,0 +1,121 @@
+//! スキーマv2の出力形式
+//! v1ではIfの `then_block` / `else_block` とLoopの `body` が（ほぼ常にSequenceの）単一ノードですが、
+//! v2ではそれらを子ノードの配列 `then` / `else` / `body` として直接持たせます。
+//! v1のJSON値とv2のJSON値を相互に変換します。v1の出力自体は変更しません。
+
+use serde_json::{Map, Value, json};
+
+/// v1のJSON値をv2の形式に変換します
+/// 包んでいたSequenceの `skipped`・`dead`・`always_runs` は `then_skipped` `else_dead` のように親ノードへ移します（idや位置などは失われます）。
+pub fn to_v2(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for child in map.values_mut() {
+                to_v2(child);
+            }
+            match map.get("type").and_then(Value::as_str) {
+                Some("if") => {
+                    flatten_field(map, "then_block", "then");
+                    if map.get("else_block").is_some_and(Value::is_null) {
+                        map.remove("else_block");
+                    }
+                    flatten_field(map, "else_block", "else");
+                    map.entry("else").or_insert_with(|| Value::Array(Vec::new()));
+                }
+                Some("loop") => flatten_field(map, "body", "body"),
+                _ => {}
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(to_v2),
+        _ => {}
+    }
+}
+
+/// v2のJSON値をv1の形式に戻します
+/// 子ノードの配列はSequenceで包み直します。空の `else` は `else_block: null` になります。
+pub fn to_v1(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for child in map.values_mut() {
+                to_v1(child);
+            }
+            match map.get("type").and_then(Value::as_str) {
+                Some("if") => {
+                    wrap_field(map, "then", "then_block");
+                    let is_empty = map.get("else").and_then(Value::as_array).is_some_and(Vec::is_empty);
+                    if is_empty && !WRAPPER_FIELDS.iter().any(|field| map.contains_key(&format!("else_{}", field))) {
+                        map.remove("else");
+                        map.insert("else_block".to_string(), Value::Null);
+                    } else {
+                        wrap_field(map, "else", "else_block");
+                    }
+                }
+                Some("loop") => wrap_field(map, "body", "body"),
+                _ => {}
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(to_v1),
+        _ => {}
+    }
+}
+
+/// スキーマv2のJSON Schemaを、v1の形式（PadNodeをそのまま書き出した形式）のスキーマに書き換えます
+/// Ifの `then` / `else` とLoopの `body` を単一ノードの `then_block` / `else_block` / `body` に戻し、親ノードへ移した `*_skipped` などを除きます。
+pub fn schema_to_v1(schema: &mut Value) {
+    let node = json!({ "$ref": "#/$defs/node" });
+    schema["$id"] = json!("https://github.com/yanqirenshi/PAD.js/schema/pad-v1.schema.json");
+    schema["title"] = json!("PadNode (v1)");
+    schema["description"] = json!("If の then_block / else_block と Loop の body が単一のノードになった、既定の出力形式");
+    let defs = &mut schema["$defs"];
+    defs["if"]["required"] = json!(["condition", "then_block"]);
+    if let Some(properties) = defs["if"]["properties"].as_object_mut() {
+        properties.remove("then");
+        properties.remove("else");
+        for field in WRAPPER_FIELDS {
+            properties.remove(&format!("then_{}", field));
+            properties.remove(&format!("else_{}", field));
+        }
+        properties.insert("then_block".to_string(), node.clone());
+        properties.insert("else_block".to_string(), json!({ "anyOf": [node, { "type": "null" }] }));
+    }
+    if let Some(properties) = defs["loop"]["properties"].as_object_mut() {
+        for field in WRAPPER_FIELDS {
+            properties.remove(&format!("body_{}", field));
+        }
+        properties.insert("body".to_string(), node);
+    }
+}
+
+/// 包んでいたSequenceから親ノードへ（`then_skipped` のように節の名前を付けて）移す項目
+const WRAPPER_FIELDS: [&str; 3] = ["skipped", "dead", "always_runs"];
+
+/// `from` の単一ノードを `to` の配列に置き換えます（Sequenceならその子ノード、それ以外は1要素の配列）
+fn flatten_field(map: &mut Map<String, Value>, from: &str, to: &str) {
+    let Some(node) = map.remove(from) else { return };
+    let children = match node {
+        Value::Object(mut inner) if inner.get("type").and_then(Value::as_str) == Some("sequence") => {
+            for field in WRAPPER_FIELDS {
+                if let Some(value) = inner.remove(field) {
+                    map.insert(format!("{}_{}", to, field), value);
+                }
+            }
+            inner.remove("children").unwrap_or_else(|| Value::Array(Vec::new()))
+        }
+        other => Value::Array(vec![other]),
+    };
+    map.insert(to.to_string(), children);
+}
+
+/// `from` の配列をSequenceで包み、`to` の単一ノードとして置き換えます
+fn wrap_field(map: &mut Map<String, Value>, from: &str, to: &str) {
+    let Some(children) = map.remove(from) else { return };
+    let mut sequence = Map::new();
+    sequence.insert("type".to_string(), Value::String("sequence".to_string()));
+    sequence.insert("children".to_string(), children);
+    for field in WRAPPER_FIELDS {
+        if let Some(value) = map.remove(&format!("{}_{}", from, field)) {
+            sequence.insert(field.to_string(), value);
+        }
+    }
+    map.insert(to.to_string(), Value::Object(sequence));
+}
@@ -0,0 +1,238 @@
+//! フロントエンドで作成されたPADツリー（JSON）の検証
+//! 手で組み立てたツリーや古い保存ファイルを描画・コード生成に渡す前に、構造と内容を検査します。
+//! どのような入力に対してもパニックせず、問題の一覧をJSONパス付きで返します。
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::model::{NODE_KINDS, PadNode};
+
+/// 検証で見つかった問題1つ分
+#[derive(Debug, Serialize)]
+pub struct ValidationIssue {
+    /// 問題のある値の位置（`$.children[0].then_block` のようなJSONパス）
+    pub path: String,
+    pub message: String,
+}
+
+/// 検証結果
+/// `errors` が空であれば `valid` は true になります。`warnings` は描画には支障のない指摘です。
+#[derive(Debug, Default, Serialize)]
+pub struct ValidationReport {
+    pub valid: bool,
+    pub errors: Vec<ValidationIssue>,
+    pub warnings: Vec<ValidationIssue>,
+}
+
+/// ノード種別ごとの必須フィールド
+fn required_fields(kind: &str) -> &'static [&'static str] {
+    match kind {
+        "sequence" => &["children"],
+        "document" => &["title", "children"],
+        "block" => &["kind", "label", "children"],
+        "if" => &["condition", "then_block"],
+        "loop" => &["kind", "condition", "body"],
+        "break" | "continue" | "yield" | "command" => &["label"],
+        "return" => &["label", "scope"],
+        "match" => &["scrutinee", "arms"],
+        "error" => &["message"],
+        _ => &[],
+    }
+}
+
+struct Validator {
+    report: ValidationReport,
+    /// 出現済みのノードidと、その位置
+    ids: HashMap<String, String>,
+}
+
+impl Validator {
+    fn error(&mut self, path: &str, message: impl Into<String>) {
+        self.report.errors.push(ValidationIssue { path: path.to_string(), message: message.into() });
+    }
+
+    fn warning(&mut self, path: &str, message: impl Into<String>) {
+        self.report.warnings.push(ValidationIssue { path: path.to_string(), message: message.into() });
+    }
+
+    fn check_node(&mut self, value: &Value, path: &str) {
+        let Some(map) = value.as_object() else {
+            self.error(path, "node must be an object");
+            return;
+        };
+        let kind = match map.get("type") {
+            Some(Value::String(kind)) => kind.as_str(),
+            Some(_) => return self.error(&format!("{}.type", path), "`type` must be a string"),
+            None => return self.error(path, "missing field `type`"),
+        };
+        if !NODE_KINDS.contains(&kind) {
+            self.error(&format!("{}.type", path), format!("unknown node type `{}`", kind));
+            return;
+        }
+        for field in required_fields(kind) {
+            if !map.contains_key(*field) {
+                self.error(path, format!("missing field `{}` on {} node", field, kind));
+            }
+        }
+
+        if let Some(id) = map.get("id").and_then(Value::as_str) {
+            match self.ids.get(id) {
+                Some(first) => {
+                    let message = format!("duplicate id `{}` (first used at {})", id, first);
+                    self.error(&format!("{}.id", path), message);
+                }
+                None => {
+                    self.ids.insert(id.to_string(), path.to_string());
+                }
+            }
+        }
+
+        self.check_text(map, kind, path);
+        self.check_children(map, path);
+    }
+
+    /// ラベル・条件式などの文字列フィールドを検査します
+    fn check_text(&mut self, map: &Map<String, Value>, kind: &str, path: &str) {
+        let field = match kind {
+            "block" | "break" | "continue" | "yield" | "return" | "command" => "label",
+            "if" | "loop" => "condition",
+            "match" => "scrutinee",
+            "error" => "message",
+            _ => return,
+        };
+        let field_path = format!("{}.{}", path, field);
+        let Some(value) = map.get(field) else { return };
+        let Some(text) = value.as_str() else {
+            self.error(&field_path, format!("`{}` must be a string", field));
+            return;
+        };
+        // 無限ループ（loop）は条件を持たないため空でも構いません
+        let may_be_empty = kind == "loop" && map.get("kind").and_then(Value::as_str) == Some("loop");
+        if text.trim().is_empty() && !may_be_empty {
+            self.error(&field_path, format!("`{}` must not be empty", field));
+            return;
+        }
+        // `// pad:label:` で置き換えた条件（full_labelあり）やfor文の `pat in expr` は式として解釈できなくてもよい
+        let is_expression = match kind {
+            "if" => true,
+            "loop" => matches!(map.get("kind").and_then(Value::as_str), Some("while" | "do_while")),
+            _ => false,
+        };
+        if is_expression && !map.contains_key("full_label") && syn::parse_str::<syn::Expr>(text).is_err() {
+            self.warning(&field_path, format!("condition `{}` is not a valid Rust expression", text));
+        }
+    }
+
+    /// 子ノードを持つフィールドを再帰的に検査します
+    fn check_children(&mut self, map: &Map<String, Value>, path: &str) {
+        if let Some(children) = map.get("children") {
+            self.check_node_array(children, &format!("{}.children", path));
+        }
+        for field in ["then_block", "body"] {
+            if let Some(child) = map.get(field) {
+                self.check_node(child, &format!("{}.{}", path, field));
+            }
+        }
+        match map.get("else_block") {
+            None | Some(Value::Null) => {}
+            Some(child) => self.check_node(child, &format!("{}.else_block", path)),
+        }
+        if let Some(arms) = map.get("arms") {
+            let arms_path = format!("{}.arms", path);
+            let Some(arms) = arms.as_array() else {
+                return self.error(&arms_path, "`arms` must be an array");
+            };
+            for (i, arm) in arms.iter().enumerate() {
+                let arm_path = format!("{}[{}]", arms_path, i);
+                if !arm.get("pattern").is_some_and(Value::is_string) {
+                    self.error(&arm_path, "match arm requires a string `pattern`");
+                }
+                match arm.get("body") {
+                    Some(body) => self.check_node(body, &format!("{}.body", arm_path)),
+                    None => self.error(&arm_path, "missing field `body` on match arm"),
+                }
+            }
+        }
+    }
+
+    fn check_node_array(&mut self, value: &Value, path: &str) {
+        match value.as_array() {
+            Some(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    self.check_node(item, &format!("{}[{}]", path, i));
+                }
+            }
+            None => self.error(path, "expected an array of nodes"),
+        }
+    }
+}
+
+/// JSON文字列のPADツリーを検証します
+pub fn validate(json: &str) -> ValidationReport {
+    let mut validator = Validator { report: ValidationReport::default(), ids: HashMap::new() };
+    match serde_json::from_str::<Value>(json) {
+        Ok(value) => {
+            validator.check_node(&value, "$");
+            // 個別の検査で問題がなければ、最終的にPadNodeとして読み込めることを確認します
+            // （`kind` の値など、上の検査で見ていない型の不一致をここで拾います）
+            if validator.report.errors.is_empty()
+                && let Err(e) = serde_json::from_value::<PadNode>(value)
+            {
+                validator.error("$", e.to_string());
+            }
+        }
+        Err(e) => validator.error("$", format!("Invalid JSON: {}", e)),
+    }
+    validator.report.valid = validator.report.errors.is_empty();
+    validator.report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_tree_is_valid() {
+        let json = crate::parse_rust_code("fn main() { if a > 0 { b(); } else { c(); } for x in xs { d(x); } }");
+        let report = validate(&json);
+        assert!(report.valid, "{:?}", report.errors);
+        assert!(report.warnings.is_empty());
+    }
+
+    #[test]
+    fn missing_then_block_is_reported_with_path() {
+        let json = r#"{"type": "sequence", "children": [{"type": "if", "condition": "x"}]}"#;
+        let report = validate(json);
+        assert!(!report.valid);
+        assert_eq!(report.errors[0].path, "$.children[0]");
+        assert!(report.errors[0].message.contains("then_block"));
+    }
+
+    #[test]
+    fn garbage_condition_is_a_warning() {
+        let json = r#"{"type": "if", "condition": "x >>> (", "then_block": {"type": "sequence", "children": []}}"#;
+        let report = validate(json);
+        assert!(report.valid);
+        assert_eq!(report.warnings[0].path, "$.condition");
+    }
+
+    #[test]
+    fn unknown_types_empty_labels_and_duplicate_ids_are_errors() {
+        let json = r#"{"type": "sequence", "id": "a", "children": [
+            {"type": "switch"},
+            {"type": "command", "label": " ", "id": "a"}
+        ]}"#;
+        let report = validate(json);
+        let paths: Vec<&str> = report.errors.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec!["$.children[0].type", "$.children[1].id", "$.children[1].label"]);
+    }
+
+    #[test]
+    fn malformed_input_does_not_panic() {
+        for json in ["", "[", "42", "null", r#"{"type": 1}"#, r#"{"type": "block", "kind": "nope", "label": "f", "children": []}"#] {
+            assert!(!validate(json).valid, "{}", json);
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! PADノードのツリーを走査するためのビジターAPI
+//! 集計や検査などの後処理はこのAPIの上に実装し、ノード種別の追加に追従しやすくします。
+
+use crate::model::{BlockKind, PadNode};
+
+/// 走査中のノードの位置情報
+pub struct VisitContext<'a> {
+    /// ルートからの深さ（ルートは0）
+    pub depth: usize,
+    /// ルートからの子インデックスの列（`PadNode::children` の順序）
+    pub path: &'a [usize],
+    /// ノードを囲む最も内側の関数Blockのラベル
+    pub function: Option<&'a str>,
+}
+
+/// ツリーの各ノードで呼び出される処理
+pub trait Visitor {
+    /// ノードに入るときに呼ばれます。false を返すとそのノードの子孫は走査しません
+    fn enter(&mut self, _node: &PadNode, _ctx: &VisitContext) -> bool {
+        true
+    }
+
+    /// ノードの子孫の走査を終えたときに呼ばれます
+    fn leave(&mut self, _node: &PadNode, _ctx: &VisitContext) {}
+}
+
+/// ルートから深さ優先（行きがけ順）でツリーを走査します
+pub fn walk(root: &PadNode, visitor: &mut impl Visitor) {
+    let mut path = Vec::new();
+    walk_node(root, visitor, &mut path, None);
+}
+
+fn walk_node<'a>(node: &'a PadNode, visitor: &mut impl Visitor, path: &mut Vec<usize>, function: Option<&'a str>) {
+    let ctx = VisitContext { depth: path.len(), path, function };
+    if visitor.enter(node, &ctx) {
+        let inner_function = match node {
+            PadNode::Block { kind: BlockKind::Function, label, .. } => Some(label.as_str()),
+            _ => function,
+        };
+        for (index, child) in node.children().into_iter().enumerate() {
+            path.push(index);
+            walk_node(child, visitor, path, inner_function);
+            path.pop();
+        }
+    }
+    let ctx = VisitContext { depth: path.len(), path, function };
+    visitor.leave(node, &ctx);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Recorder(Vec<(String, usize, Option<String>)>);
+
+    impl Visitor for Recorder {
+        fn enter(&mut self, node: &PadNode, ctx: &VisitContext) -> bool {
+            let path = ctx.path.iter().map(|i| i.to_string()).collect::<Vec<_>>().join("/");
+            self.0.push((format!("{}:{}", node.kind_name(), path), ctx.depth, ctx.function.map(String::from)));
+            true
+        }
+    }
+
+    #[test]
+    fn walks_in_pre_order_with_paths_and_function_context() {
+        let root = PadNode::sequence(vec![PadNode::block(
+            BlockKind::Function,
+            "fn main()",
+            vec![PadNode::if_node("x", PadNode::command("a"), Some(PadNode::command("b")))],
+        )]);
+        let mut recorder = Recorder(Vec::new());
+        walk(&root, &mut recorder);
+        let visited: Vec<&str> = recorder.0.iter().map(|(k, _, _)| k.as_str()).collect();
+        assert_eq!(visited, vec!["sequence:", "block:0", "if:0/0", "command:0/0/0", "command:0/0/1"]);
+        assert_eq!(recorder.0[1].2, None);
+        assert_eq!(recorder.0[3].1, 3);
+        assert_eq!(recorder.0[3].2.as_deref(), Some("fn main()"));
+    }
+}
@@ -0,0 +1,206 @@
+//! ラベルの表示幅の見積もり
+//! フォント読み込み前にフロントエンドが箱の幅を決められるよう、等幅フォントでの桁数を計算します。
+//! East Asian Width が全角（W/F）の文字は2桁、結合文字やゼロ幅文字は0桁として数えます。
+//! 絵文字のZWJシーケンス・肌の色の修飾・国旗（地域指示子の組）は1つの書記素として2桁、
+//! 異体字セレクタ16（U+FE0F）の付いた文字は絵文字の表示として2桁に数えます。
+
+use crate::model::PadNode;
+
+/// テキストの表示幅（桁数）を返します。複数行の場合は最も長い行の幅です
+pub fn display_width(text: &str) -> usize {
+    text.lines().map(line_width).max().unwrap_or(0)
+}
+
+/// 1行分の表示幅（書記素ごとに、先頭の文字の幅で数えます）
+fn line_width(line: &str) -> usize {
+    let mut total = 0;
+    // 現在の書記素の幅と、ZWJの直後か・対になっていない地域指示子の直後か
+    let mut current = 0;
+    let mut after_zwj = false;
+    let mut open_flag = false;
+    for c in line.chars() {
+        let cp = c as u32;
+        if after_zwj {
+            after_zwj = false;
+            continue;
+        }
+        match cp {
+            0x200D => after_zwj = current > 0,
+            0xFE0F if current == 1 => {
+                total += 1;
+                current = 2;
+            }
+            0x1F3FB..=0x1F3FF if current == 2 => {}
+            0x1F1E6..=0x1F1FF if open_flag => open_flag = false,
+            _ if is_zero_width(cp) => {}
+            _ => {
+                open_flag = (0x1F1E6..=0x1F1FF).contains(&cp);
+                current = char_width(c);
+                total += current;
+            }
+        }
+    }
+    total
+}
+
+/// 1文字の表示幅（0, 1, 2）
+pub fn char_width(c: char) -> usize {
+    let cp = c as u32;
+    if is_zero_width(cp) {
+        0
+    } else if is_wide(cp) {
+        2
+    } else {
+        1
+    }
+}
+
+/// 結合文字・ゼロ幅文字・異体字セレクタなど、直前の文字と1つの書記素をなす文字
+fn is_zero_width(cp: u32) -> bool {
+    matches!(cp,
+        0x0300..=0x036F   // 結合用ダイアクリティカルマーク
+        | 0x1AB0..=0x1AFF
+        | 0x1DC0..=0x1DFF
+        | 0x200B..=0x200F // ゼロ幅スペース、ZWJ など
+        | 0x20D0..=0x20FF
+        | 0x3099..=0x309A // 結合用濁点・半濁点
+        | 0xFE00..=0xFE0F // 異体字セレクタ
+        | 0xFE20..=0xFE2F
+        | 0xE0020..=0xE007F // タグ文字（地域の旗）
+        | 0xE0100..=0xE01EF)
+}
+
+/// East Asian Width が Wide / Fullwidth の文字
+fn is_wide(cp: u32) -> bool {
+    matches!(cp,
+        0x1100..=0x115F     // ハングル字母
+        | 0x231A..=0x231B   // 絵文字として表示する記号
+        | 0x23E9..=0x23EC
+        | 0x23F0
+        | 0x23F3
+        | 0x25FD..=0x25FE
+        | 0x2614..=0x2615
+        | 0x2648..=0x2653
+        | 0x267F
+        | 0x2693
+        | 0x26A1
+        | 0x26AA..=0x26AB
+        | 0x26BD..=0x26BE
+        | 0x26C4..=0x26C5
+        | 0x26CE
+        | 0x26D4
+        | 0x26EA
+        | 0x26F2..=0x26F3
+        | 0x26F5
+        | 0x26FA
+        | 0x26FD
+        | 0x2705
+        | 0x270A..=0x270B
+        | 0x2728
+        | 0x274C
+        | 0x274E
+        | 0x2753..=0x2755
+        | 0x2757
+        | 0x2795..=0x2797
+        | 0x27B0
+        | 0x27BF
+        | 0x2B1B..=0x2B1C
+        | 0x2B50
+        | 0x2B55
+        | 0x2E80..=0x303E   // CJK部首、記号と句読点
+        | 0x3041..=0x33FF   // ひらがな、カタカナ、CJK互換
+        | 0x3400..=0x4DBF   // CJK統合漢字拡張A
+        | 0x4E00..=0x9FFF   // CJK統合漢字
+        | 0xA000..=0xA4CF   // イ文字
+        | 0xAC00..=0xD7A3   // ハングル音節
+        | 0xF900..=0xFAFF   // CJK互換漢字
+        | 0xFE30..=0xFE4F   // CJK互換形
+        | 0xFF00..=0xFF60   // 全角英数・記号
+        | 0xFFE0..=0xFFE6
+        | 0x1F004
+        | 0x1F0CF
+        | 0x1F18E
+        | 0x1F191..=0x1F19A
+        | 0x1F1E6..=0x1F1FF // 地域指示子
+        | 0x1F200..=0x1F2FF
+        | 0x1F300..=0x1F64F // 絵文字
+        | 0x1F680..=0x1F6FF // 乗り物と地図記号
+        | 0x1F7E0..=0x1F7EB
+        | 0x1F90C..=0x1F9FF
+        | 0x1FA70..=0x1FAFF
+        | 0x20000..=0x2FFFD // CJK統合漢字拡張B以降
+        | 0x30000..=0x3FFFD)
+}
+
+/// ツリー全体のノードに `width_hint` を設定し、ルートに最大値を記録します
+pub fn apply_width_hints(root: &mut PadNode) {
+    let max = annotate(root);
+    if let PadNode::Sequence { info, .. } = root {
+        info.get_or_insert_with(Box::default).max_width_hint = Some(max);
+    }
+}
+
+/// ノードとその子孫に幅を設定し、部分木内の最大値を返します
+fn annotate(node: &mut PadNode) -> usize {
+    let own = node.display_text().map(display_width);
+    node.meta_mut().width_hint = own;
+    let mut max = own.unwrap_or(0);
+    for child in node.children_mut() {
+        max = max.max(annotate(child));
+    }
+    max
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::BlockKind;
+
+    #[test]
+    fn counts_wide_characters_as_two_columns() {
+        assert_eq!(display_width("let x = 1;"), 10);
+        assert_eq!(display_width("入力を検証"), 10);
+        assert_eq!(display_width("値 = 3"), 6);
+        assert_eq!(display_width("ＡＢ"), 4);
+    }
+
+    #[test]
+    fn combining_marks_do_not_add_width() {
+        assert_eq!(display_width("e\u{301}"), 1);
+        assert_eq!(display_width("か\u{3099}"), 2);
+    }
+
+    #[test]
+    fn emoji_sequences_are_one_grapheme() {
+        assert_eq!(display_width("🚀"), 2);
+        assert_eq!(display_width("🪐 ok"), 5);
+        // ZWJでつないだ家族の絵文字、肌の色の修飾、国旗
+        assert_eq!(display_width("\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}"), 2);
+        assert_eq!(display_width("\u{1F44D}\u{1F3FD}"), 2);
+        assert_eq!(display_width("\u{1F1EF}\u{1F1F5}"), 2);
+        assert_eq!(display_width("\u{1F1EF}\u{1F1F5}\u{1F1FA}\u{1F1F8}"), 4);
+        // 異体字セレクタ16で絵文字として表示する文字
+        assert_eq!(display_width("\u{2764}\u{FE0F}"), 2);
+        assert_eq!(display_width("\u{2764}"), 1);
+        assert_eq!(display_width("\u{2764}\u{FE0F}\u{200D}\u{1F525}"), 2);
+    }
+
+    #[test]
+    fn multi_line_labels_use_longest_line() {
+        assert_eq!(display_width("short\nずっと長い行"), 12);
+    }
+
+    #[test]
+    fn hints_are_applied_to_every_labelled_node() {
+        let mut root = PadNode::sequence(vec![PadNode::block(
+            BlockKind::Function,
+            "fn 処理()",
+            vec![PadNode::sequence(vec![PadNode::if_node("x > 0", PadNode::command("表示する"), None)])],
+        )]);
+        apply_width_hints(&mut root);
+        let PadNode::Sequence { children, meta, info, .. } = &mut root else { unreachable!() };
+        assert_eq!(meta.width_hint, None);
+        assert_eq!(info.as_ref().unwrap().max_width_hint, Some(9));
+        assert_eq!(children[0].meta_mut().width_hint, Some(9));
+    }
+}
@@ -0,0 +1,43 @@
+import { test } from 'node:test';
+import assert from 'node:assert/strict';
+import { readFileSync } from 'node:fs';
+import { createRequire } from 'node:module';
+
+const require = createRequire(import.meta.url);
+const pad = require('../index.js');
+const sample = readFileSync(new URL('./sample.rs', import.meta.url), 'utf8');
+
+test('parseRustCode returns the tree synchronously', () => {
+    const tree = pad.parseRustCode(sample);
+    assert.equal(tree.type, 'sequence');
+    assert.equal(tree.children[0].label, 'fn main()');
+});
+
+test('listFunctions lists every top-level item like the wasm build', () => {
+    const listing = pad.listFunctions(sample);
+    assert.equal(listing.total_items, 2);
+    assert.deepEqual(listing.items.map((item) => item.name), ['main', 'load']);
+    assert.ok(listing.items.every((item) => item.kind === 'function'));
+});
+
+test('toHtml exports a standalone page', () => {
+    const html = pad.toHtml(sample, { document: true, source_name: 'sample.rs' });
+    assert.match(html, /<title>sample\.rs<\/title>/);
+});
+
+test('diagram exporters are available', () => {
+    assert.match(pad.toSvg(sample), /^<svg/);
+    assert.match(pad.toMermaid(sample), /flowchart/);
+    assert.match(pad.toDot(sample), /digraph/);
+    assert.match(pad.toPlantuml(sample), /^@startuml/);
+});
+
+test('errors are thrown with their position', () => {
+    assert.throws(() => pad.parseRustCode('fn main( {'), (error) => {
+        assert.match(error.message, /^Parse error/);
+        assert.equal(error.line, 1);
+        assert.equal(typeof error.column, 'number');
+        assert.equal(typeof error.frame, 'string');
+        return true;
+    });
+});
@@ -0,0 +1,12 @@
+fn main() {
+    let items = load();
+    for item in items {
+        if item.ok {
+            println!("{}", item.name);
+        }
+    }
+}
+
+fn load() -> Vec<Item> {
+    Vec::new()
+}
@@ -0,0 +1,93 @@
+//! Node.js向けのネイティブアドオン（N-API）
+//! サーバー上のドキュメント生成などで、wasmの読み込みと非同期の初期化を避けて同期的に呼び出すためのものです。
+//! 解析・変換はwasm版と同じ `backend` クレートの関数をそのまま使います（wasm版の既定のビルドには影響しません）。
+//! 解析の結果がErrorノードの場合は、`line`・`column`・`frame`・`byte_offset` を持つJavaScriptの例外にします。
+
+use napi::{Env, Error, JsUnknown, Result, Status};
+use napi_derive::napi;
+use serde_json::Value;
+
+/// Rustコードを解析し、PADのツリー（オブジェクト）を返します
+/// オプションはwasm版の `parse_rust_code_with_options` と同じです。
+#[napi(js_name = "parseRustCode")]
+pub fn parse_rust_code(env: Env, code: String, options: Option<Value>) -> Result<JsUnknown> {
+    let tree = parse(&env, &code, options)?;
+    env.to_js_value(&tree)
+}
+
+/// 図に表示できるトップレベルのアイテムの一覧を、wasm版の `list_functions_with_options` と同じ `{ total_items, items }` の形式で返します
+#[napi(js_name = "listFunctions")]
+pub fn list_functions(env: Env, code: String, options: Option<Value>) -> Result<JsUnknown> {
+    let listing = checked(&env, backend::list_functions_with_options(&code, &options_json(options)))?;
+    env.to_js_value(&listing)
+}
+
+/// Rustコードを、図を埋め込んだ単独のHTMLページにします
+#[napi(js_name = "toHtml")]
+pub fn to_html(env: Env, code: String, options: Option<Value>) -> Result<String> {
+    export(&env, &code, options, backend::to_html_with_options)
+}
+
+/// Rustコードを、ツリー全体を描いた単体のSVG文書にします
+#[napi(js_name = "toSvg")]
+pub fn to_svg(env: Env, code: String, options: Option<Value>) -> Result<String> {
+    export(&env, &code, options, backend::to_svg_with_options)
+}
+
+/// RustコードをMermaidのフローチャートにします
+#[napi(js_name = "toMermaid")]
+pub fn to_mermaid(env: Env, code: String, options: Option<Value>) -> Result<String> {
+    export(&env, &code, options, backend::to_mermaid_with_options)
+}
+
+/// RustコードをGraphvizのDOTの有向グラフにします
+#[napi(js_name = "toDot")]
+pub fn to_dot(env: Env, code: String, options: Option<Value>) -> Result<String> {
+    export(&env, &code, options, backend::to_dot_with_options)
+}
+
+/// RustコードをPlantUMLのアクティビティ図にします
+#[napi(js_name = "toPlantuml")]
+pub fn to_plantuml(env: Env, code: String, options: Option<Value>) -> Result<String> {
+    export(&env, &code, options, backend::to_plantuml_with_options)
+}
+
+/// ノード種別ごとの件数（関数ごとの内訳付き）を返します
+#[napi(js_name = "countNodes")]
+pub fn count_nodes(env: Env, code: String) -> Result<JsUnknown> {
+    let counts = checked(&env, backend::count_nodes(&code))?;
+    env.to_js_value(&counts)
+}
+
+fn options_json(options: Option<Value>) -> String {
+    options.map_or_else(|| "{}".to_string(), |options| options.to_string())
+}
+
+/// 書き出しの関数を呼び出します
+/// オプションやコードの誤りを例外として報告するため、先に解析します。
+fn export(env: &Env, code: &str, options: Option<Value>, render: fn(&str, &str) -> String) -> Result<String> {
+    let options = options_json(options);
+    checked(env, backend::parse_rust_code_with_options(code, &options))?;
+    Ok(render(code, &options))
+}
+
+fn parse(env: &Env, code: &str, options: Option<Value>) -> Result<Value> {
+    checked(env, backend::parse_rust_code_with_options(code, &options_json(options)))
+}
+
+/// JSON文字列を読み込み、ルートがErrorノードであれば例外を投げます
+fn checked(env: &Env, json: String) -> Result<Value> {
+    let value: Value = serde_json::from_str(&json).map_err(|e| Error::from_reason(e.to_string()))?;
+    if value["type"] != "error" {
+        return Ok(value);
+    }
+    let message = value["message"].as_str().unwrap_or_default().to_string();
+    let mut error = env.create_error(Error::from_reason(message))?;
+    for key in ["line", "column", "frame", "byte_offset"] {
+        if let Some(field) = value.get(key) {
+            error.set_named_property(key, env.to_js_value(field)?)?;
+        }
+    }
+    env.throw(error)?;
+    Err(Error::from_status(Status::PendingException))
+}